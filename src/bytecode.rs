@@ -0,0 +1,792 @@
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::Value;
+use crate::ir::{BINARY_OPERATORS, BinaryOperator, UnaryOperator};
+use crate::syntax_tree::Type;
+
+const MAGIC: &[u8; 4] = b"MTBC";
+
+/// Bumped whenever the file layout or the meaning of an instruction changes.
+pub const VERSION: u16 = 1;
+
+/// The name of the function holding global variable initializers, which runs before `main`. It
+/// can't clash with a Metor function since it isn't a valid name.
+pub const GLOBALS_FUNCTION: &str = "<globals>";
+
+/// The types memory can be allocated for, in the order their operands are encoded.
+const ELEMENT_TYPES: &[Type] = &[Type::I32, Type::U32, Type::F32, Type::Boolean];
+
+/// The lines and characters an instruction was compiled from. They're resolved when compiling,
+/// so a bytecode file can report runtime errors without its source.
+pub type Position = ((usize, usize), (usize, usize));
+
+/// An instruction for the stack machine. Every expression leaves exactly one value on the stack,
+/// `void` included, so statements always pop what they evaluate.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Pushes a value from the constant pool.
+    Constant(u32),
+    LoadLocal(u32),
+    StoreLocal(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    Binary(BinaryOperator),
+    Unary(UnaryOperator),
+    /// Builds an array from the given number of values on top of the stack.
+    Array(u32),
+    /// Prints the given number of values on top of the stack, separated by spaces, and a newline.
+    Print(u32),
+    /// Like `Print`, but without the newline.
+    Write(u32),
+    Swap,
+    Pop,
+    Jump(u32),
+    /// Pops a boolean and jumps when it's false.
+    JumpIfFalse(u32),
+    /// Calls a function by index, taking its arguments from the top of the stack.
+    Call(u32),
+    Return,
+    ReturnVoid,
+    /// Reached when a function with a return type runs off its end.
+    MissingReturn,
+    /// Pops a count and pushes a pointer to that many zeroed values of the type.
+    Alloc(Type),
+    /// Pops a count and a pointer, and pushes a pointer to the values moved to an allocation of
+    /// that size. The old pointer is freed.
+    Resize,
+    /// Pops a pointer and frees what it points to.
+    Free,
+    /// Pops an index and a pointer, and pushes the value at that index.
+    Load,
+    /// Pops a value, an index and a pointer, and stores the value at that index.
+    Store,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Constant(index) => write!(f, "constant #{}", index),
+            Instruction::LoadLocal(slot) => write!(f, "load_local {}", slot),
+            Instruction::StoreLocal(slot) => write!(f, "store_local {}", slot),
+            Instruction::LoadGlobal(index) => write!(f, "load_global @{}", index),
+            Instruction::StoreGlobal(index) => write!(f, "store_global @{}", index),
+            Instruction::Binary(operator) => write!(f, "{}", operator),
+            Instruction::Unary(operator) => write!(f, "{}", operator),
+            Instruction::Array(count) => write!(f, "array {}", count),
+            Instruction::Print(count) => write!(f, "print {}", count),
+            Instruction::Write(count) => write!(f, "write {}", count),
+            Instruction::Swap => write!(f, "swap"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Jump(target) => write!(f, "jump {:04}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {:04}", target),
+            Instruction::Call(function) => write!(f, "call ${}", function),
+            Instruction::Return => write!(f, "return"),
+            Instruction::ReturnVoid => write!(f, "return_void"),
+            Instruction::MissingReturn => write!(f, "missing_return"),
+            Instruction::Alloc(element_type) => write!(f, "alloc {}", element_type),
+            Instruction::Resize => write!(f, "resize"),
+            Instruction::Free => write!(f, "free"),
+            Instruction::Load => write!(f, "load"),
+            Instruction::Store => write!(f, "store"),
+        }
+    }
+}
+
+pub struct Function {
+    pub name: String,
+    pub parameter_count: u32,
+    /// The number of local slots, parameters included.
+    pub local_count: u32,
+    pub code: Vec<Instruction>,
+    /// The source span of each instruction, for runtime errors.
+    pub spans: Vec<Position>,
+    pub lines: (usize, usize),
+    pub characters: (usize, usize),
+}
+
+pub struct Program {
+    pub constants: Vec<Value>,
+    pub globals: Vec<String>,
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    pub fn function_index(&self, name: &str) -> Option<usize> {
+        return self
+            .functions
+            .iter()
+            .position(|function| function.name == name);
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "constants:")?;
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            let constant_type = match constant.value_type() {
+                Some(constant_type) => constant_type.to_string(),
+                None => String::from("array"),
+            };
+
+            writeln!(f, "    #{} = {} {}", index, constant_type, constant)?;
+        }
+
+        writeln!(f, "globals:")?;
+
+        for (index, global) in self.globals.iter().enumerate() {
+            writeln!(f, "    @{} = {}", index, global)?;
+        }
+
+        for (index, function) in self.functions.iter().enumerate() {
+            writeln!(
+                f,
+                "\nfunction ${} {} ({} parameters, {} locals):",
+                index, function.name, function.parameter_count, function.local_count
+            )?;
+
+            for (offset, (instruction, (lines, characters))) in
+                function.code.iter().zip(&function.spans).enumerate()
+            {
+                let text = instruction.to_string();
+
+                write!(f, "    {:04}  {:<24}", offset, text)?;
+
+                match instruction {
+                    Instruction::Constant(constant) => {
+                        write!(f, "// {}", self.constants[*constant as usize])?
+                    }
+                    Instruction::LoadGlobal(global) | Instruction::StoreGlobal(global) => {
+                        write!(f, "// {}", self.globals[*global as usize])?
+                    }
+                    Instruction::Call(function) => {
+                        write!(f, "// {}", self.functions[*function as usize].name)?
+                    }
+                    _ => write!(
+                        f,
+                        "// {}, {} -> {}, {}",
+                        lines.0, characters.0, lines.1, characters.1
+                    )?,
+                }
+
+                writeln!(f)?;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    write_u32(bytes, text.len() as u32);
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn write_span(bytes: &mut Vec<u8>, lines: (usize, usize), characters: (usize, usize)) {
+    for value in [lines.0, characters.0, lines.1, characters.1] {
+        write_u32(bytes, value as u32);
+    }
+}
+
+fn write_instruction(bytes: &mut Vec<u8>, instruction: &Instruction) {
+    let (opcode, operand) = match instruction {
+        Instruction::Constant(index) => (0, Some(*index)),
+        Instruction::LoadLocal(slot) => (1, Some(*slot)),
+        Instruction::StoreLocal(slot) => (2, Some(*slot)),
+        Instruction::LoadGlobal(index) => (3, Some(*index)),
+        Instruction::StoreGlobal(index) => (4, Some(*index)),
+        Instruction::Binary(operator) => {
+            let index = BINARY_OPERATORS.iter().position(|o| o == operator).unwrap();
+
+            (5, Some(index as u32))
+        }
+        Instruction::Unary(UnaryOperator::Negate) => (6, Some(0)),
+        Instruction::Unary(UnaryOperator::Not) => (6, Some(1)),
+        Instruction::Array(count) => (7, Some(*count)),
+        Instruction::Print(count) => (8, Some(*count)),
+        Instruction::Swap => (9, None),
+        Instruction::Pop => (10, None),
+        Instruction::Jump(target) => (11, Some(*target)),
+        Instruction::JumpIfFalse(target) => (12, Some(*target)),
+        Instruction::Call(function) => (13, Some(*function)),
+        Instruction::Return => (14, None),
+        Instruction::ReturnVoid => (15, None),
+        Instruction::MissingReturn => (16, None),
+        Instruction::Write(count) => (17, Some(*count)),
+        Instruction::Alloc(element_type) => {
+            let index = ELEMENT_TYPES
+                .iter()
+                .position(|t| t == element_type)
+                .unwrap();
+
+            (18, Some(index as u32))
+        }
+        Instruction::Resize => (19, None),
+        Instruction::Free => (20, None),
+        Instruction::Load => (21, None),
+        Instruction::Store => (22, None),
+    };
+
+    bytes.push(opcode);
+
+    if let Some(operand) = operand {
+        write_u32(bytes, operand);
+    }
+}
+
+/// Reads a `.mtbc` file front to back, failing on anything truncated or out of range.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+fn format_error(message: &str) -> Diagnostic {
+    return Diagnostic::resolved(
+        format!("Invalid bytecode file: {}", message),
+        (0, 0),
+        (0, 0),
+    );
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Diagnostic> {
+        if self.bytes.len() - self.position < count {
+            return Err(format_error("unexpected end of file"));
+        }
+
+        let bytes = &self.bytes[self.position..self.position + count];
+
+        self.position += count;
+
+        return Ok(bytes);
+    }
+
+    fn u8(&mut self) -> Result<u8, Diagnostic> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u32(&mut self) -> Result<u32, Diagnostic> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn string(&mut self) -> Result<String, Diagnostic> {
+        let length = self.u32()? as usize;
+
+        return String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| format_error("a name is not valid UTF-8"));
+    }
+
+    fn span(&mut self) -> Result<Position, Diagnostic> {
+        let line_start = self.u32()? as usize;
+        let character_start = self.u32()? as usize;
+        let line_end = self.u32()? as usize;
+        let character_end = self.u32()? as usize;
+
+        return Ok(((line_start, line_end), (character_start, character_end)));
+    }
+
+    fn constant(&mut self) -> Result<Value, Diagnostic> {
+        match self.u8()? {
+            0 => return Ok(Value::I32(self.u32()? as i32)),
+            1 => return Ok(Value::U32(self.u32()?)),
+            2 => return Ok(Value::F32(f32::from_bits(self.u32()?))),
+            3 => return Ok(Value::Boolean(self.u8()? != 0)),
+            4 => return Ok(Value::Void),
+            tag => return Err(format_error(&format!("unknown constant tag {}", tag))),
+        }
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, Diagnostic> {
+        let opcode = self.u8()?;
+
+        let instruction = match opcode {
+            0 => Instruction::Constant(self.u32()?),
+            1 => Instruction::LoadLocal(self.u32()?),
+            2 => Instruction::StoreLocal(self.u32()?),
+            3 => Instruction::LoadGlobal(self.u32()?),
+            4 => Instruction::StoreGlobal(self.u32()?),
+            5 => match BINARY_OPERATORS.get(self.u32()? as usize) {
+                Some(operator) => Instruction::Binary(*operator),
+                None => return Err(format_error("unknown binary operator")),
+            },
+            6 => match self.u32()? {
+                0 => Instruction::Unary(UnaryOperator::Negate),
+                1 => Instruction::Unary(UnaryOperator::Not),
+                _ => return Err(format_error("unknown unary operator")),
+            },
+            7 => Instruction::Array(self.u32()?),
+            8 => Instruction::Print(self.u32()?),
+            9 => Instruction::Swap,
+            10 => Instruction::Pop,
+            11 => Instruction::Jump(self.u32()?),
+            12 => Instruction::JumpIfFalse(self.u32()?),
+            13 => Instruction::Call(self.u32()?),
+            14 => Instruction::Return,
+            15 => Instruction::ReturnVoid,
+            16 => Instruction::MissingReturn,
+            17 => Instruction::Write(self.u32()?),
+            18 => match ELEMENT_TYPES.get(self.u32()? as usize) {
+                Some(element_type) => Instruction::Alloc(*element_type),
+                None => return Err(format_error("unknown element type")),
+            },
+            19 => Instruction::Resize,
+            20 => Instruction::Free,
+            21 => Instruction::Load,
+            22 => Instruction::Store,
+            _ => return Err(format_error(&format!("unknown opcode {}", opcode))),
+        };
+
+        return Ok(instruction);
+    }
+}
+
+impl Program {
+    /// Serializes the program in the `.mtbc` format: the `MTBC` magic and a little endian version,
+    /// then the constant pool, the global names and the functions with their code and spans.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+        write_u32(&mut bytes, self.constants.len() as u32);
+
+        for constant in &self.constants {
+            match constant {
+                Value::I32(value) => {
+                    bytes.push(0);
+                    write_u32(&mut bytes, *value as u32);
+                }
+                Value::U32(value) => {
+                    bytes.push(1);
+                    write_u32(&mut bytes, *value);
+                }
+                Value::F32(value) => {
+                    bytes.push(2);
+                    write_u32(&mut bytes, value.to_bits());
+                }
+                Value::Boolean(value) => bytes.extend_from_slice(&[3, *value as u8]),
+                Value::Void => bytes.push(4),
+                Value::Array(_) => panic!("Arrays can't be stored in the constant pool"),
+                Value::Pointer(_, _) => panic!("Pointers can't be stored in the constant pool"),
+            }
+        }
+
+        write_u32(&mut bytes, self.globals.len() as u32);
+
+        for global in &self.globals {
+            write_string(&mut bytes, global);
+        }
+
+        write_u32(&mut bytes, self.functions.len() as u32);
+
+        for function in &self.functions {
+            write_string(&mut bytes, &function.name);
+            write_u32(&mut bytes, function.parameter_count);
+            write_u32(&mut bytes, function.local_count);
+            write_span(&mut bytes, function.lines, function.characters);
+            write_u32(&mut bytes, function.code.len() as u32);
+
+            for (instruction, (lines, characters)) in function.code.iter().zip(&function.spans) {
+                write_instruction(&mut bytes, instruction);
+                write_span(&mut bytes, *lines, *characters);
+            }
+        }
+
+        return bytes;
+    }
+
+    /// Reads a program written by `to_bytes`. Files from a different format version are rejected
+    /// rather than guessed at, and every index is checked before the VM gets to use it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, Diagnostic> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4).ok() != Some(MAGIC.as_slice()) {
+            return Err(format_error("missing MTBC header"));
+        }
+
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+
+        if version != VERSION {
+            return Err(format_error(&format!(
+                "version {} is not supported, expected version {}",
+                version, VERSION
+            )));
+        }
+
+        let mut program = Program {
+            constants: Vec::new(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+        };
+
+        for _ in 0..reader.u32()? {
+            program.constants.push(reader.constant()?);
+        }
+
+        for _ in 0..reader.u32()? {
+            program.globals.push(reader.string()?);
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let parameter_count = reader.u32()?;
+            let local_count = reader.u32()?;
+            let (lines, characters) = reader.span()?;
+
+            let mut code: Vec<Instruction> = Vec::new();
+            let mut spans: Vec<Position> = Vec::new();
+
+            for _ in 0..reader.u32()? {
+                code.push(reader.instruction()?);
+                spans.push(reader.span()?);
+            }
+
+            program.functions.push(Function {
+                name,
+                parameter_count,
+                local_count,
+                code,
+                spans,
+                lines,
+                characters,
+            });
+        }
+
+        if reader.position != bytes.len() {
+            return Err(format_error("unexpected data after the last function"));
+        }
+
+        program.validate()?;
+
+        return Ok(program);
+    }
+
+    /// How many values an instruction takes off the stack, and how many it puts back.
+    fn stack_effect(&self, instruction: &Instruction) -> (u32, u32) {
+        match instruction {
+            Instruction::Constant(_) | Instruction::LoadLocal(_) | Instruction::LoadGlobal(_) => {
+                (0, 1)
+            }
+            Instruction::StoreLocal(_) | Instruction::StoreGlobal(_) => (1, 0),
+            Instruction::Binary(_) => (2, 1),
+            Instruction::Unary(_) => (1, 1),
+            Instruction::Array(count) | Instruction::Print(count) | Instruction::Write(count) => {
+                (*count, 1)
+            }
+            Instruction::Swap => (2, 2),
+            Instruction::Pop => (1, 0),
+            Instruction::Jump(_) => (0, 0),
+            Instruction::JumpIfFalse(_) => (1, 0),
+            Instruction::Call(index) => (self.functions[*index as usize].parameter_count, 1),
+            Instruction::Return => (1, 0),
+            Instruction::ReturnVoid | Instruction::MissingReturn => (0, 0),
+            Instruction::Alloc(_) => (1, 1),
+            Instruction::Resize => (2, 1),
+            Instruction::Free => (1, 1),
+            Instruction::Load => (2, 1),
+            Instruction::Store => (3, 0),
+        }
+    }
+
+    /// Follows every path through a function to check that no instruction takes more values than
+    /// the frame has on its stack, and that the paths agree on how many there are where they meet.
+    fn validate_stack(&self, function: &Function) -> Result<(), Diagnostic> {
+        let mut depths: Vec<Option<u32>> = vec![None; function.code.len()];
+        let mut pending: Vec<(usize, u32)> = vec![(0, 0)];
+
+        while let Some((offset, depth)) = pending.pop() {
+            match depths[offset] {
+                Some(known) if known == depth => continue,
+                Some(_) => {
+                    return Err(format_error(&format!(
+                        "paths to instruction {:04} in function {} leave different stack depths",
+                        offset, function.name
+                    )));
+                }
+                None => depths[offset] = Some(depth),
+            }
+
+            let instruction = &function.code[offset];
+            let (taken, pushed) = self.stack_effect(instruction);
+
+            if taken > depth {
+                return Err(format_error(&format!(
+                    "{} at {:04} in function {} takes more values than are on the stack",
+                    instruction, offset, function.name
+                )));
+            }
+
+            let depth = depth - taken + pushed;
+
+            match instruction {
+                Instruction::Jump(target) => pending.push((*target as usize, depth)),
+                Instruction::JumpIfFalse(target) => {
+                    pending.push((*target as usize, depth));
+                    pending.push((offset + 1, depth));
+                }
+                Instruction::Return | Instruction::ReturnVoid | Instruction::MissingReturn => {}
+                _ => pending.push((offset + 1, depth)),
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn validate(&self) -> Result<(), Diagnostic> {
+        for function in &self.functions {
+            if function.parameter_count > function.local_count {
+                return Err(format_error(&format!(
+                    "function {} has more parameters than locals",
+                    function.name
+                )));
+            }
+
+            // Every local past the parameters is stored to by some instruction, which keeps a
+            // corrupt count from making the VM reserve more memory than the file could ever use.
+            if function.local_count - function.parameter_count > function.code.len() as u32 {
+                return Err(format_error(&format!(
+                    "function {} has more locals than instructions",
+                    function.name
+                )));
+            }
+
+            let entry_point = function.name == "main" || function.name == GLOBALS_FUNCTION;
+
+            if entry_point && function.parameter_count != 0 {
+                return Err(format_error(&format!(
+                    "function {} can't take parameters",
+                    function.name
+                )));
+            }
+
+            if !matches!(
+                function.code.last(),
+                Some(
+                    Instruction::Jump(_)
+                        | Instruction::Return
+                        | Instruction::ReturnVoid
+                        | Instruction::MissingReturn
+                )
+            ) {
+                return Err(format_error(&format!(
+                    "function {} can run past its end",
+                    function.name
+                )));
+            }
+
+            for instruction in &function.code {
+                let valid = match instruction {
+                    Instruction::Constant(index) => (*index as usize) < self.constants.len(),
+                    Instruction::LoadLocal(slot) | Instruction::StoreLocal(slot) => {
+                        *slot < function.local_count
+                    }
+                    Instruction::LoadGlobal(index) | Instruction::StoreGlobal(index) => {
+                        (*index as usize) < self.globals.len()
+                    }
+                    Instruction::Jump(target) | Instruction::JumpIfFalse(target) => {
+                        (*target as usize) < function.code.len()
+                    }
+                    Instruction::Call(index) => (*index as usize) < self.functions.len(),
+                    _ => true,
+                };
+
+                if !valid {
+                    return Err(format_error(&format!(
+                        "{} in function {} is out of range",
+                        instruction, function.name
+                    )));
+                }
+            }
+        }
+
+        // Calls need every function's parameter count, so the stacks are checked once all the
+        // indices are known to be in range.
+        for function in &self.functions {
+            self.validate_stack(function)?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(
+        name: &str,
+        parameter_count: u32,
+        local_count: u32,
+        code: &[Instruction],
+    ) -> Function {
+        return Function {
+            name: String::from(name),
+            parameter_count,
+            local_count,
+            code: code.to_vec(),
+            spans: vec![((0, 0), (0, 0)); code.len()],
+            lines: (0, 0),
+            characters: (0, 0),
+        };
+    }
+
+    fn load(functions: Vec<Function>) -> Result<Program, String> {
+        let program = Program {
+            constants: vec![Value::I32(1)],
+            globals: vec![String::from("g")],
+            functions,
+        };
+
+        return Program::from_bytes(&program.to_bytes()).map_err(|diagnostic| diagnostic.message);
+    }
+
+    fn assert_invalid(functions: Vec<Function>, message: &str) {
+        match load(functions) {
+            Ok(_) => panic!("expected the program to be rejected with {:?}", message),
+            Err(found) => assert!(found.contains(message), "{:?}", found),
+        }
+    }
+
+    #[test]
+    fn loads_what_it_writes() {
+        let program = load(vec![
+            function(
+                "add",
+                2,
+                2,
+                &[
+                    Instruction::LoadLocal(0),
+                    Instruction::LoadLocal(1),
+                    Instruction::Binary(BinaryOperator::Add),
+                    Instruction::Return,
+                ],
+            ),
+            function(
+                "main",
+                0,
+                1,
+                &[
+                    Instruction::Constant(0),
+                    Instruction::StoreLocal(0),
+                    Instruction::LoadLocal(0),
+                    Instruction::JumpIfFalse(9),
+                    Instruction::LoadLocal(0),
+                    Instruction::Constant(0),
+                    Instruction::Call(0),
+                    Instruction::Print(1),
+                    Instruction::Pop,
+                    Instruction::ReturnVoid,
+                ],
+            ),
+        ]);
+
+        let program = program.unwrap();
+
+        assert_eq!(program.functions.len(), 2);
+        assert_eq!(program.functions[1].code.len(), 10);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        for instruction in [
+            Instruction::Constant(1),
+            Instruction::LoadLocal(1),
+            Instruction::LoadGlobal(1),
+            Instruction::Call(1),
+            Instruction::Jump(5),
+        ] {
+            assert_invalid(
+                vec![function(
+                    "main",
+                    0,
+                    1,
+                    &[instruction, Instruction::Pop, Instruction::ReturnVoid],
+                )],
+                "is out of range",
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_taking_more_values_than_the_stack_has() {
+        assert_invalid(
+            vec![function(
+                "main",
+                0,
+                0,
+                &[
+                    Instruction::Constant(0),
+                    Instruction::Array(u32::MAX),
+                    Instruction::Pop,
+                    Instruction::ReturnVoid,
+                ],
+            )],
+            "takes more values than are on the stack",
+        );
+        assert_invalid(
+            vec![function(
+                "main",
+                0,
+                0,
+                &[Instruction::Print(2), Instruction::ReturnVoid],
+            )],
+            "takes more values than are on the stack",
+        );
+        assert_invalid(
+            vec![function("main", 0, 0, &[Instruction::Return])],
+            "takes more values than are on the stack",
+        );
+    }
+
+    #[test]
+    fn rejects_paths_that_disagree_on_the_stack() {
+        assert_invalid(
+            vec![function(
+                "main",
+                0,
+                0,
+                &[
+                    Instruction::Constant(0),
+                    Instruction::Constant(0),
+                    Instruction::Binary(BinaryOperator::Equal),
+                    Instruction::JumpIfFalse(5),
+                    Instruction::Constant(0),
+                    Instruction::ReturnVoid,
+                ],
+            )],
+            "leave different stack depths",
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_local_counts() {
+        assert_invalid(
+            vec![function("main", 0, u32::MAX, &[Instruction::ReturnVoid])],
+            "more locals than instructions",
+        );
+        assert_invalid(
+            vec![function("main", 1, 1, &[Instruction::ReturnVoid])],
+            "can't take parameters",
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let program = Program {
+            constants: vec![Value::I32(1)],
+            globals: Vec::new(),
+            functions: vec![function("main", 0, 0, &[Instruction::ReturnVoid])],
+        };
+
+        let bytes = program.to_bytes();
+
+        for length in 0..bytes.len() {
+            assert!(Program::from_bytes(&bytes[..length]).is_err());
+        }
+    }
+}
@@ -0,0 +1,734 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::bytecode::{Function, GLOBALS_FUNCTION, Instruction, Position, Program};
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::Value;
+use crate::ir::{BinaryOperator, UnaryOperator};
+use crate::source_map::SourceMap;
+use crate::syntax_tree::{
+    CallNode, FunctionDefinitionNode, IndexNode, Keyword, Node, OperationNode, Operator, Type,
+};
+use crate::type_checker::{CheckedType, FunctionSignature};
+
+fn error(message: String, node: &Node) -> Diagnostic {
+    return Diagnostic::error(message, node.get_span());
+}
+
+fn element_type(checked_type: CheckedType) -> Type {
+    match checked_type {
+        CheckedType::Scalar(node_type) => node_type,
+        CheckedType::Array(node_type, _) => node_type,
+        CheckedType::Pointer(node_type) => node_type,
+    }
+}
+
+struct Variable {
+    slot: u32,
+    variable_type: CheckedType,
+}
+
+/// Compiles one function at a time into the shared program. Locals get a slot each, reused once
+/// their scope ends, and globals are addressed by index.
+struct Compiler<'a> {
+    source_map: &'a SourceMap,
+    program: Program,
+    functions: &'a HashMap<String, (u32, FunctionSignature)>,
+    /// Functions declared with `extern "C"`, which the VM has no way to call.
+    externs: HashSet<&'a str>,
+    globals: HashMap<String, (u32, CheckedType)>,
+    code: Vec<Instruction>,
+    spans: Vec<Position>,
+    scopes: Vec<HashMap<String, Variable>>,
+    /// The statements deferred in each scope, which are compiled again wherever the scope is left.
+    deferred: Vec<Vec<&'a Node>>,
+    next_slot: u32,
+    local_count: u32,
+    return_type: CheckedType,
+    /// The `break` jumps of each enclosing loop, patched once the loop's end is known.
+    loop_exits: Vec<Vec<usize>>,
+    /// The number of scopes around each enclosing loop, which a `break` doesn't leave.
+    loop_scopes: Vec<usize>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, instruction: Instruction, node: &Node) -> usize {
+        self.code.push(instruction);
+        self.spans
+            .push(self.source_map.lines_and_characters(node.get_span()));
+
+        return self.code.len() - 1;
+    }
+
+    fn patch(&mut self, jump: usize) {
+        let target = self.code.len() as u32;
+
+        match &mut self.code[jump] {
+            Instruction::Jump(old_target) | Instruction::JumpIfFalse(old_target) => {
+                *old_target = target
+            }
+            _ => panic!("Patched an instruction that isn't a jump"),
+        }
+    }
+
+    fn constant(&mut self, value: Value, node: &Node) {
+        let index = match self.program.constants.iter().position(|c| *c == value) {
+            Some(index) => index,
+            None => {
+                self.program.constants.push(value);
+
+                self.program.constants.len() - 1
+            }
+        };
+
+        self.emit(Instruction::Constant(index as u32), node);
+    }
+
+    fn define(&mut self, name: &str, variable_type: CheckedType) -> u32 {
+        let slot = self.next_slot;
+
+        self.next_slot += 1;
+        self.local_count = self.local_count.max(self.next_slot);
+        self.scopes.last_mut().unwrap().insert(
+            String::from(name),
+            Variable {
+                slot,
+                variable_type,
+            },
+        );
+
+        return slot;
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.deferred.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+
+        self.deferred.pop();
+        self.next_slot -= scope.len() as u32;
+    }
+
+    /// Compiles the statements deferred in the scopes from `depth` inward, innermost first, for
+    /// code that leaves them. Each statement only sees the scopes that were around when it was
+    /// deferred.
+    fn leave_scopes(&mut self, depth: usize) -> Result<(), Diagnostic> {
+        for scope in (depth..self.scopes.len()).rev() {
+            for statement in self.deferred[scope].clone().into_iter().rev() {
+                let hidden_scopes = self.scopes.split_off(scope + 1);
+                let hidden_deferred = self.deferred.split_off(scope + 1);
+
+                let result = self.block(std::slice::from_ref(statement));
+
+                self.scopes.extend(hidden_scopes);
+                self.deferred.extend(hidden_deferred);
+
+                result?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Finds a variable, returning the instructions to load and store it along with its type.
+    fn lookup(&self, name: &str) -> Option<(Instruction, Instruction, CheckedType)> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(variable) = scope.get(name) {
+                return Some((
+                    Instruction::LoadLocal(variable.slot),
+                    Instruction::StoreLocal(variable.slot),
+                    variable.variable_type,
+                ));
+            }
+        }
+
+        return self.globals.get(name).map(|(index, global_type)| {
+            (
+                Instruction::LoadGlobal(*index),
+                Instruction::StoreGlobal(*index),
+                *global_type,
+            )
+        });
+    }
+
+    /// Compiles an expression, leaving its value on the stack. Integer literals take on the
+    /// `expected` type when there is one, and `i32` otherwise.
+    fn expression(
+        &mut self,
+        node: &'a Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        match node {
+            Node::Number(number_node) => {
+                let value = number_node.value;
+
+                let result = match expected.map(element_type) {
+                    Some(Type::U32) => u32::try_from(value).ok().map(Value::U32),
+                    Some(Type::F32) => Some(Value::F32(value as f32)),
+                    _ => i32::try_from(value).ok().map(Value::I32),
+                };
+
+                let value = result.ok_or_else(|| {
+                    error(format!("Integer literal {} is out of range", value), node)
+                })?;
+
+                let value_type = value.value_type().unwrap();
+
+                self.constant(value, node);
+
+                return Ok(CheckedType::Scalar(value_type));
+            }
+            Node::Boolean(boolean_node) => {
+                self.constant(Value::Boolean(boolean_node.value), node);
+
+                return Ok(CheckedType::Scalar(Type::Boolean));
+            }
+            Node::Name(name_node) => {
+                return match self.lookup(&name_node.value) {
+                    Some((load, _, variable_type)) => {
+                        self.emit(load, node);
+
+                        Ok(variable_type)
+                    }
+                    None => Err(error(format!("Unknown variable {}", name_node.value), node)),
+                };
+            }
+            Node::Array(array_node) => {
+                let element_expected =
+                    expected.map(|expected| CheckedType::Scalar(element_type(expected)));
+                let mut element = Type::I32;
+
+                for value_node in &array_node.values {
+                    element = element_type(self.expression(value_node, element_expected)?);
+                }
+
+                let count = array_node.values.len();
+
+                self.emit(Instruction::Array(count as u32), node);
+
+                return Ok(CheckedType::Array(element, count));
+            }
+            Node::Group(group_node) if group_node.content.len() == 1 => {
+                return self.expression(&group_node.content[0], expected);
+            }
+            Node::Comptime(comptime_node) => {
+                return self.expression(&comptime_node.value, expected);
+            }
+            Node::Operation(operation_node) => {
+                return self.operation(operation_node, node, expected);
+            }
+            Node::Call(call_node) => return self.call(call_node, node, expected),
+            Node::Index(index_node) => {
+                let element_type = self.index(index_node)?;
+
+                self.emit(Instruction::Load, node);
+
+                return Ok(CheckedType::Scalar(element_type));
+            }
+            _ => {}
+        }
+
+        return Err(error(String::from("Expected an expression"), node));
+    }
+
+    /// Compiles the pointer and the index of `pointer[index]`, returning the type it points to.
+    fn index(&mut self, index_node: &'a IndexNode) -> Result<Type, Diagnostic> {
+        let name = &index_node.name;
+
+        let element_type = match self.lookup(&name.value) {
+            Some((load, _, CheckedType::Pointer(element_type))) => {
+                self.emit(load, &index_node.index);
+
+                element_type
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    format!("{} is not a pointer", name.value),
+                    name.span,
+                ));
+            }
+        };
+
+        self.expression(&index_node.index, None)?;
+
+        return Ok(element_type);
+    }
+
+    fn operation(
+        &mut self,
+        operation_node: &'a OperationNode,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        let operator = &operation_node.operator.operator;
+
+        let operand_expected = match operator {
+            Operator::Add
+            | Operator::Subtract
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Modulo
+            | Operator::BitwiseAnd
+            | Operator::BitwiseOr
+            | Operator::Negate => expected,
+            _ => None,
+        };
+
+        if operator.one_sided() {
+            let value_type = self.expression(&operation_node.values[0], operand_expected)?;
+
+            let unary_operator = match operator {
+                Operator::Not => UnaryOperator::Not,
+                _ => UnaryOperator::Negate,
+            };
+
+            self.emit(Instruction::Unary(unary_operator), node);
+
+            return Ok(value_type);
+        }
+
+        let value_a_node = &operation_node.values[0];
+        let value_b_node = &operation_node.values[1];
+
+        // `a && b` is `if a { b } else { false }`, and `a || b` is `if a { true } else { b }`.
+        if let Operator::And | Operator::Or = operator {
+            let boolean = Some(CheckedType::Scalar(Type::Boolean));
+
+            self.expression(value_a_node, boolean)?;
+
+            let skip = self.emit(Instruction::JumpIfFalse(0), node);
+
+            if let Operator::And = operator {
+                self.expression(value_b_node, boolean)?;
+            } else {
+                self.constant(Value::Boolean(true), node);
+            }
+
+            let end = self.emit(Instruction::Jump(0), node);
+
+            self.patch(skip);
+
+            if let Operator::And = operator {
+                self.constant(Value::Boolean(false), node);
+            } else {
+                self.expression(value_b_node, boolean)?;
+            }
+
+            self.patch(end);
+
+            return Ok(CheckedType::Scalar(Type::Boolean));
+        }
+
+        // A literal operand takes the type of the other side, matching the type checker. Literals
+        // have no side effects, so evaluating the other side first and swapping is safe.
+        let value_type = if let Node::Number(_) = value_a_node
+            && !matches!(value_b_node, Node::Number(_))
+        {
+            let value_b_type = self.expression(value_b_node, operand_expected)?;
+
+            self.expression(value_a_node, Some(value_b_type))?;
+            self.emit(Instruction::Swap, node);
+
+            value_b_type
+        } else {
+            let value_a_type = self.expression(value_a_node, operand_expected)?;
+
+            self.expression(value_b_node, Some(value_a_type))?;
+
+            value_a_type
+        };
+
+        let binary_operator = match operator {
+            Operator::Add => BinaryOperator::Add,
+            Operator::Subtract => BinaryOperator::Subtract,
+            Operator::Multiply => BinaryOperator::Multiply,
+            Operator::Divide => BinaryOperator::Divide,
+            Operator::Modulo => BinaryOperator::Remainder,
+            Operator::BitwiseAnd => BinaryOperator::And,
+            Operator::BitwiseOr => BinaryOperator::Or,
+            Operator::Equal => BinaryOperator::Equal,
+            Operator::NotEqual => BinaryOperator::NotEqual,
+            Operator::LessThan => BinaryOperator::LessThan,
+            Operator::LessThanOrEqual => BinaryOperator::LessThanOrEqual,
+            Operator::GreaterThan => BinaryOperator::GreaterThan,
+            Operator::GreaterThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+            _ => return Err(error(format!("Unsupported operator {}", operator), node)),
+        };
+
+        self.emit(Instruction::Binary(binary_operator), node);
+
+        if binary_operator.is_comparison() {
+            return Ok(CheckedType::Scalar(Type::Boolean));
+        }
+
+        return Ok(value_type);
+    }
+
+    fn call(
+        &mut self,
+        call_node: &'a CallNode,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        let name = &call_node.name.value;
+
+        if self.externs.contains(name.as_str()) {
+            return Err(error(
+                format!(
+                    "{} is an extern \"C\" function, which only compiled programs can call",
+                    name
+                ),
+                node,
+            ));
+        }
+
+        let (index, signature) = match self.functions.get(name) {
+            Some(function) => function,
+            None if name == "alloc" || name == "resize" || name == "free" => {
+                return self.memory_call(call_node, node, expected);
+            }
+            None if name == "print" || name == "println" => {
+                for argument in &call_node.arguments {
+                    self.expression(argument, None)?;
+                }
+
+                let count = call_node.arguments.len() as u32;
+
+                if name == "println" {
+                    self.emit(Instruction::Print(count), node);
+                } else {
+                    self.emit(Instruction::Write(count), node);
+                }
+
+                return Ok(CheckedType::Scalar(Type::Void));
+            }
+            None => return Err(error(format!("Unknown function {}", name), node)),
+        };
+
+        if signature.parameters.len() != call_node.arguments.len() {
+            return Err(error(
+                format!(
+                    "Function {} expects {} arguments but got {}",
+                    name,
+                    signature.parameters.len(),
+                    call_node.arguments.len()
+                ),
+                node,
+            ));
+        }
+
+        for (parameter, argument) in signature.parameters.iter().zip(&call_node.arguments) {
+            self.expression(argument, Some(*parameter))?;
+        }
+
+        self.emit(Instruction::Call(*index), node);
+
+        return Ok(signature.return_type);
+    }
+
+    /// Compiles a call to `alloc`, `resize` or `free`. What `alloc` allocates comes from the
+    /// pointer type expected of it.
+    fn memory_call(
+        &mut self,
+        call_node: &'a CallNode,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        let arguments = &call_node.arguments;
+        let count = Some(CheckedType::Scalar(Type::I32));
+
+        match (call_node.name.value.as_str(), arguments.len(), expected) {
+            ("alloc", 1, Some(CheckedType::Pointer(element_type))) => {
+                self.expression(&arguments[0], count)?;
+                self.emit(Instruction::Alloc(element_type), node);
+
+                return Ok(CheckedType::Pointer(element_type));
+            }
+            ("resize", 2, _) => {
+                let pointer_type = self.expression(&arguments[0], None)?;
+
+                self.expression(&arguments[1], count)?;
+                self.emit(Instruction::Resize, node);
+
+                return Ok(pointer_type);
+            }
+            ("free", 1, _) => {
+                self.expression(&arguments[0], None)?;
+                self.emit(Instruction::Free, node);
+
+                return Ok(CheckedType::Scalar(Type::Void));
+            }
+            (name, _, _) => return Err(error(format!("Invalid call to {}", name), node)),
+        }
+    }
+
+    fn block(&mut self, nodes: &'a [Node]) -> Result<(), Diagnostic> {
+        self.push_scope();
+
+        for node in nodes {
+            self.statement(node)?;
+        }
+
+        self.leave_scopes(self.scopes.len() - 1)?;
+        self.pop_scope();
+
+        return Ok(());
+    }
+
+    fn statement(&mut self, node: &'a Node) -> Result<(), Diagnostic> {
+        match node {
+            Node::Block(block_node) => return self.block(&block_node.content),
+            Node::VariableDefinition(definition_node) => {
+                let variable_type = CheckedType::from_type_node(&definition_node.node_type);
+
+                self.expression(&definition_node.assignment.value, Some(variable_type))?;
+
+                let slot = self.define(&definition_node.assignment.name.value, variable_type);
+
+                self.emit(Instruction::StoreLocal(slot), node);
+            }
+            Node::Assignment(assignment_node) => {
+                let name = &assignment_node.name.value;
+
+                let (_, store, variable_type) = match self.lookup(name) {
+                    Some(variable) => variable,
+                    None => return Err(error(format!("Unknown variable {}", name), node)),
+                };
+
+                self.expression(&assignment_node.value, Some(variable_type))?;
+                self.emit(store, node);
+            }
+            Node::IndexAssignment(assignment_node) => {
+                let element_type = self.index(&assignment_node.target)?;
+
+                self.expression(
+                    &assignment_node.value,
+                    Some(CheckedType::Scalar(element_type)),
+                )?;
+                self.emit(Instruction::Store, node);
+            }
+            Node::Defer(defer_node) => {
+                self.deferred
+                    .last_mut()
+                    .unwrap()
+                    .push(&defer_node.statement);
+            }
+            Node::If(if_node) => {
+                self.expression(&if_node.condition, None)?;
+
+                let skip = self.emit(Instruction::JumpIfFalse(0), &if_node.condition);
+
+                self.block(&if_node.block.content)?;
+
+                match &if_node.else_node {
+                    Some(else_node) => {
+                        let end = self.emit(Instruction::Jump(0), node);
+
+                        self.patch(skip);
+                        self.statement(else_node)?;
+                        self.patch(end);
+                    }
+                    None => self.patch(skip),
+                }
+            }
+            Node::Forever(forever_node) => {
+                let start = self.code.len() as u32;
+
+                self.loop_exits.push(Vec::new());
+                self.loop_scopes.push(self.scopes.len());
+                self.block(&forever_node.block.content)?;
+                self.emit(Instruction::Jump(start), node);
+                self.loop_scopes.pop();
+
+                for exit in self.loop_exits.pop().unwrap() {
+                    self.patch(exit);
+                }
+            }
+            Node::Return(return_node) => match &return_node.value {
+                Some(value_node) => {
+                    self.expression(value_node, Some(self.return_type))?;
+                    self.leave_scopes(0)?;
+                    self.emit(Instruction::Return, node);
+                }
+                None => {
+                    self.leave_scopes(0)?;
+                    self.emit(Instruction::ReturnVoid, node);
+                }
+            },
+            Node::Keyword(keyword_node) if keyword_node.keyword == Keyword::Break => {
+                if let Some(depth) = self.loop_scopes.last() {
+                    self.leave_scopes(*depth)?;
+                }
+
+                let jump = self.emit(Instruction::Jump(0), node);
+
+                match self.loop_exits.last_mut() {
+                    Some(exits) => exits.push(jump),
+                    None => return Err(error(String::from("Break outside of a loop"), node)),
+                }
+            }
+            Node::FunctionDefinition(function_node) => {
+                return Err(error(
+                    format!(
+                        "Function {} must be defined at the top level",
+                        function_node.name
+                    ),
+                    node,
+                ));
+            }
+            Node::ExternFunction(function_node) => {
+                return Err(error(
+                    format!(
+                        "Function {} must be defined at the top level",
+                        function_node.name
+                    ),
+                    node,
+                ));
+            }
+            _ => {
+                self.expression(node, None)?;
+                self.emit(Instruction::Pop, node);
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Moves the finished code into a new function and resets the per function state.
+    fn finish(&mut self, name: &str, parameter_count: u32, (lines, characters): Position) {
+        self.program.functions.push(Function {
+            name: String::from(name),
+            parameter_count,
+            local_count: self.local_count,
+            code: std::mem::take(&mut self.code),
+            spans: std::mem::take(&mut self.spans),
+            lines,
+            characters,
+        });
+
+        self.scopes.clear();
+        self.deferred.clear();
+        self.next_slot = 0;
+        self.local_count = 0;
+    }
+
+    fn function(
+        &mut self,
+        function_node: &'a FunctionDefinitionNode,
+        node: &Node,
+    ) -> Result<(), Diagnostic> {
+        self.push_scope();
+
+        for parameter in &function_node.parameters {
+            let parameter_type = CheckedType::from_type_node(&parameter.node_type);
+
+            self.define(&parameter.name.value, parameter_type);
+        }
+
+        self.return_type = CheckedType::from_type_node(&function_node.node_type);
+        self.block(&function_node.block.content)?;
+
+        if self.return_type == CheckedType::Scalar(Type::Void) {
+            self.emit(Instruction::ReturnVoid, node);
+        } else {
+            self.emit(Instruction::MissingReturn, node);
+        }
+
+        self.finish(
+            &function_node.name.value,
+            function_node.parameters.len() as u32,
+            self.source_map.lines_and_characters(node.get_span()),
+        );
+
+        return Ok(());
+    }
+}
+
+/// Compiles a type checked syntax tree to bytecode. Function 0 initializes the globals in order,
+/// and the rest follow in definition order so calls can refer to them by index.
+pub fn compile<'a>(nodes: &'a [Node], source_map: &'a SourceMap) -> Result<Program, Diagnostic> {
+    let mut functions: HashMap<String, (u32, FunctionSignature)> = HashMap::new();
+
+    for node in nodes {
+        if let Node::FunctionDefinition(function_node) = node {
+            let index = functions.len() as u32 + 1;
+
+            functions.insert(
+                function_node.name.value.clone(),
+                (
+                    index,
+                    FunctionSignature {
+                        parameters: function_node
+                            .parameters
+                            .iter()
+                            .map(|parameter| CheckedType::from_type_node(&parameter.node_type))
+                            .collect(),
+                        return_type: CheckedType::from_type_node(&function_node.node_type),
+                    },
+                ),
+            );
+        }
+    }
+
+    let mut compiler = Compiler {
+        source_map,
+        program: Program {
+            constants: Vec::new(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+        },
+        functions: &functions,
+        externs: HashSet::new(),
+        globals: HashMap::new(),
+        code: Vec::new(),
+        spans: Vec::new(),
+        scopes: Vec::new(),
+        deferred: Vec::new(),
+        next_slot: 0,
+        local_count: 0,
+        return_type: CheckedType::Scalar(Type::Void),
+        loop_exits: Vec::new(),
+        loop_scopes: Vec::new(),
+    };
+
+    let mut function_nodes: Vec<(&FunctionDefinitionNode, &Node)> = Vec::new();
+
+    for node in nodes {
+        match node {
+            Node::FunctionDefinition(function_node) => function_nodes.push((function_node, node)),
+            Node::ExternFunction(function_node) => {
+                compiler.externs.insert(&function_node.name.value);
+            }
+            Node::VariableDefinition(definition_node) => {
+                let global_type = CheckedType::from_type_node(&definition_node.node_type);
+                let name = &definition_node.assignment.name.value;
+
+                compiler.expression(&definition_node.assignment.value, Some(global_type))?;
+
+                let index = compiler.program.globals.len() as u32;
+
+                compiler.program.globals.push(name.clone());
+                compiler.globals.insert(name.clone(), (index, global_type));
+                compiler.emit(Instruction::StoreGlobal(index), node);
+            }
+            _ => return Err(error(String::from("Expected a definition"), node)),
+        }
+    }
+
+    let position = match nodes.first() {
+        Some(first) => source_map.lines_and_characters(first.get_span()),
+        None => ((0, 0), (0, 0)),
+    };
+
+    compiler.code.push(Instruction::ReturnVoid);
+    compiler.spans.push(position);
+    compiler.finish(GLOBALS_FUNCTION, 0, position);
+
+    for (function_node, node) in function_nodes {
+        compiler.function(function_node, node)?;
+    }
+
+    return Ok(compiler.program);
+}
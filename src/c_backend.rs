@@ -0,0 +1,581 @@
+use crate::ir::{
+    BinaryOperator, BlockCall, BlockId, Constant, Function, Instruction, InstructionKind, IrType,
+    Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::source_map::{SourceMap, Span};
+
+const PRELUDE: &str = "#include <inttypes.h>
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static void mt_division_by_zero(int line_start, int character_start, int line_end, int character_end) {
+    fprintf(stderr, \"Error: Division by zero %d, %d -> %d, %d\\n\", line_start, character_start, line_end, character_end);
+    exit(1);
+}
+
+static int32_t mt_div_i32(int32_t a, int32_t b, int line_start, int character_start, int line_end, int character_end) {
+    if (b == 0) mt_division_by_zero(line_start, character_start, line_end, character_end);
+    if (a == INT32_MIN && b == -1) return INT32_MIN;
+    return a / b;
+}
+
+static int32_t mt_rem_i32(int32_t a, int32_t b, int line_start, int character_start, int line_end, int character_end) {
+    if (b == 0) mt_division_by_zero(line_start, character_start, line_end, character_end);
+    if (b == -1) return 0;
+    return a % b;
+}
+
+static uint32_t mt_div_u32(uint32_t a, uint32_t b, int line_start, int character_start, int line_end, int character_end) {
+    if (b == 0) mt_division_by_zero(line_start, character_start, line_end, character_end);
+    return a / b;
+}
+
+static uint32_t mt_rem_u32(uint32_t a, uint32_t b, int line_start, int character_start, int line_end, int character_end) {
+    if (b == 0) mt_division_by_zero(line_start, character_start, line_end, character_end);
+    return a % b;
+}
+
+static void mt_allocation_failed(int64_t count) {
+    if (count < 0) fprintf(stderr, \"Error: Can't allocate %\" PRId64 \" values\\n\", count);
+    else fprintf(stderr, \"Error: Out of memory\\n\");
+    exit(1);
+}
+
+/* Allocations keep their size in bytes just before their values, so resize knows what to zero. */
+static void *mt_heap_alloc(int64_t count, int64_t size) {
+    char *allocation = count < 0 ? NULL : calloc(1, (size_t)(count * size) + 8);
+    if (allocation == NULL) mt_allocation_failed(count);
+    *(int64_t *)allocation = count * size;
+    return allocation + 8;
+}
+
+static void *mt_heap_resize(void *pointer, int64_t count, int64_t size) {
+    char *allocation = (char *)pointer - 8;
+    int64_t old_size = *(int64_t *)allocation;
+    allocation = count < 0 ? NULL : realloc(allocation, (size_t)(count * size) + 8);
+    if (allocation == NULL) mt_allocation_failed(count);
+    if (count * size > old_size) memset(allocation + 8 + old_size, 0, (size_t)(count * size - old_size));
+    *(int64_t *)allocation = count * size;
+    return allocation + 8;
+}
+
+static void mt_heap_free(void *pointer) {
+    free((char *)pointer - 8);
+}
+
+/* Floats print as %g with the fewest significant digits that read back as the same float. */
+static const char *mt_f32_text(float value, char *text) {
+    if (value != value) return \"nan\";
+    for (int precision = 1; precision <= 9; precision++) {
+        snprintf(text, 32, \"%.*g\", precision, (double)value);
+        if (strtof(text, NULL) == value) break;
+    }
+    return text;
+}
+";
+
+fn c_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::I32 => "int32_t",
+        IrType::U32 => "uint32_t",
+        IrType::F32 => "float",
+        IrType::Bool => "bool",
+        IrType::Void => "void",
+        IrType::Pointer => "void *",
+    }
+}
+
+fn function_name(name: &str) -> String {
+    return format!("mt_{}", name);
+}
+
+fn global_name(name: &str) -> String {
+    return format!("mt_global_{}", name);
+}
+
+fn value_name(value: ValueId) -> String {
+    return format!("v{}", value.0);
+}
+
+fn constant(constant: &Constant, constant_type: IrType) -> String {
+    match constant {
+        Constant::Integer(value) if constant_type == IrType::U32 => format!("{}u", value),
+        Constant::Integer(value) if *value == i32::MIN as i64 => String::from("INT32_MIN"),
+        Constant::Integer(value) => format!("{}", value),
+        Constant::Float(value) => format!("{:?}f", value),
+        Constant::Boolean(value) => format!("{}", value),
+    }
+}
+
+/// The value at `pointer[index]`, as an lvalue.
+fn element(element_type: IrType, pointer: ValueId, index: ValueId) -> String {
+    return format!(
+        "(({} *){})[{}]",
+        c_type(element_type),
+        value_name(pointer),
+        value_name(index)
+    );
+}
+
+fn escape(path: &str) -> String {
+    return path.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+struct FunctionGenerator<'a> {
+    function: &'a Function,
+    source_map: &'a SourceMap,
+    output: String,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    fn line(&mut self, depth: usize, line: &str) {
+        self.output.push_str(&"    ".repeat(depth));
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    /// Points the C compiler at the Metor source the next line came from. C only tracks lines, so
+    /// the full span is kept alongside in a comment.
+    fn line_directive(&mut self, span: Span) {
+        let (lines, characters) = self.source_map.lines_and_characters(span);
+
+        let directive = format!(
+            "#line {} \"{}\" /* {}, {} -> {}, {} */",
+            lines.0 + 1,
+            escape(self.source_map.name(span.file)),
+            lines.0,
+            characters.0,
+            lines.1,
+            characters.1
+        );
+
+        self.line(0, &directive);
+    }
+
+    /// Prints the arguments separated by spaces, followed by a newline for `println`.
+    fn print(&self, arguments: &[ValueId], newline: bool) -> String {
+        let mut format: Vec<&str> = Vec::new();
+        let mut values: Vec<String> = Vec::new();
+
+        for argument in arguments {
+            let name = value_name(*argument);
+
+            match self.function.value_type(*argument) {
+                IrType::I32 => {
+                    format.push("\"%\" PRId32");
+                    values.push(name);
+                }
+                IrType::U32 => {
+                    format.push("\"%\" PRIu32");
+                    values.push(name);
+                }
+                IrType::F32 => {
+                    format.push("\"%s\"");
+                    values.push(format!("mt_f32_text({}, (char[32]){{0}})", name));
+                }
+                IrType::Bool => {
+                    format.push("\"%s\"");
+                    values.push(format!("{} ? \"true\" : \"false\"", name));
+                }
+                IrType::Void | IrType::Pointer => {}
+            }
+        }
+
+        let mut call = format!("printf({}", format.join(" \" \" "));
+
+        if format.is_empty() {
+            call = String::from("printf(\"\"");
+        }
+
+        if newline {
+            call.push_str(" \"\\n\"");
+        }
+
+        for value in values {
+            call.push_str(", ");
+            call.push_str(&value);
+        }
+
+        call.push_str(");");
+
+        return call;
+    }
+
+    fn binary(
+        &self,
+        operator: BinaryOperator,
+        value_a: ValueId,
+        value_b: ValueId,
+        instruction: &Instruction,
+    ) -> String {
+        let value_type = self.function.value_type(value_a);
+        let a = value_name(value_a);
+        let b = value_name(value_b);
+
+        let symbol = match operator {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::And => "&",
+            BinaryOperator::Or => "|",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+        };
+
+        if operator.is_comparison() {
+            return format!("{} {} {}", a, symbol, b);
+        }
+
+        match (operator, value_type) {
+            (BinaryOperator::Divide | BinaryOperator::Remainder, IrType::I32 | IrType::U32) => {
+                let helper = if operator == BinaryOperator::Divide {
+                    "div"
+                } else {
+                    "rem"
+                };
+
+                let (lines, characters) = self.source_map.lines_and_characters(instruction.span);
+
+                return format!(
+                    "mt_{}_{}({}, {}, {}, {}, {}, {})",
+                    helper, value_type, a, b, lines.0, characters.0, lines.1, characters.1
+                );
+            }
+            (BinaryOperator::Remainder, IrType::F32) => return format!("fmodf({}, {})", a, b),
+            // Signed overflow is undefined in C, so i32 arithmetic wraps through uint32_t instead.
+            (
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply,
+                IrType::I32,
+            ) => {
+                return format!("(int32_t)((uint32_t){} {} (uint32_t){})", a, symbol, b);
+            }
+            (_, IrType::U32) => return format!("(uint32_t)({} {} {})", a, symbol, b),
+            (_, IrType::Bool) => return format!("(bool)({} {} {})", a, symbol, b),
+            _ => return format!("{} {} {}", a, symbol, b),
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        self.line_directive(instruction.span);
+
+        let expression = match &instruction.kind {
+            InstructionKind::Constant(value) => {
+                let constant_type = self.function.value_type(instruction.result.unwrap());
+
+                constant(value, constant_type)
+            }
+            InstructionKind::Binary(operator, value_a, value_b) => {
+                self.binary(*operator, *value_a, *value_b, instruction)
+            }
+            InstructionKind::Unary(operator, value) => {
+                let value_type = self.function.value_type(*value);
+                let name = value_name(*value);
+
+                match (operator, value_type) {
+                    (UnaryOperator::Negate, IrType::I32) => {
+                        format!("(int32_t)(0u - (uint32_t){})", name)
+                    }
+                    (UnaryOperator::Negate, _) => format!("-{}", name),
+                    (UnaryOperator::Not, IrType::Bool) => format!("!{}", name),
+                    (UnaryOperator::Not, _) => format!("~{}", name),
+                }
+            }
+            InstructionKind::Call(name, arguments) if name == "print" || name == "println" => {
+                let call = self.print(arguments, name == "println");
+
+                self.line(1, &call);
+
+                return;
+            }
+            InstructionKind::Call(name, arguments) => {
+                let arguments: Vec<String> =
+                    arguments.iter().map(|value| value_name(*value)).collect();
+
+                format!("{}({})", function_name(name), arguments.join(", "))
+            }
+            InstructionKind::LoadGlobal(name) => global_name(name),
+            InstructionKind::StoreGlobal(name, value) => {
+                let statement = format!("{} = {};", global_name(name), value_name(*value));
+
+                self.line(1, &statement);
+
+                return;
+            }
+            InstructionKind::Alloc(element_type, count) => format!(
+                "mt_heap_alloc((int64_t){}, {})",
+                value_name(*count),
+                element_type.size()
+            ),
+            InstructionKind::Resize(element_type, pointer, count) => format!(
+                "mt_heap_resize({}, (int64_t){}, {})",
+                value_name(*pointer),
+                value_name(*count),
+                element_type.size()
+            ),
+            InstructionKind::Free(pointer) => format!("mt_heap_free({})", value_name(*pointer)),
+            InstructionKind::Load(pointer, index) => {
+                let element_type = self.function.value_type(instruction.result.unwrap());
+
+                element(element_type, *pointer, *index)
+            }
+            InstructionKind::Store(pointer, index, value) => {
+                let element_type = self.function.value_type(*value);
+
+                let statement = format!(
+                    "{} = {};",
+                    element(element_type, *pointer, *index),
+                    value_name(*value)
+                );
+
+                self.line(1, &statement);
+
+                return;
+            }
+        };
+
+        let statement = match instruction.result {
+            Some(result) => format!("{} = {};", value_name(result), expression),
+            None => format!("{};", expression),
+        };
+
+        self.line(1, &statement);
+    }
+
+    /// Passes block arguments by assigning the target's parameters before jumping. Arguments go
+    /// through temporaries when there's more than one, since they can read the parameters being
+    /// assigned.
+    fn jump(&mut self, depth: usize, target: &BlockCall) {
+        let parameters = &self.function.blocks[target.block.0].parameters;
+
+        if target.arguments.len() == 1 {
+            let assignment = format!(
+                "{} = {};",
+                value_name(parameters[0]),
+                value_name(target.arguments[0])
+            );
+
+            self.line(depth, &assignment);
+        } else if !target.arguments.is_empty() {
+            let mut lines: Vec<String> = Vec::new();
+
+            for (index, (parameter, argument)) in
+                parameters.iter().zip(&target.arguments).enumerate()
+            {
+                lines.push(format!(
+                    "{} t{} = {};",
+                    c_type(self.function.value_type(*parameter)),
+                    index,
+                    value_name(*argument)
+                ));
+            }
+
+            for (index, parameter) in parameters.iter().enumerate() {
+                lines.push(format!("{} = t{};", value_name(*parameter), index));
+            }
+
+            self.line(depth, "{");
+
+            for line in lines {
+                self.line(depth + 1, &line);
+            }
+
+            self.line(depth, "}");
+        }
+
+        let goto = format!("goto {};", target.block);
+
+        self.line(depth, &goto);
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Jump(target) => self.jump(1, target),
+            Terminator::Branch(condition, then_target, else_target) => {
+                let condition = format!("if ({}) {{", value_name(*condition));
+
+                self.line(1, &condition);
+                self.jump(2, then_target);
+                self.line(1, "} else {");
+                self.jump(2, else_target);
+                self.line(1, "}");
+            }
+            Terminator::Return(Some(value)) => {
+                let statement = format!("return {};", value_name(*value));
+
+                self.line(1, &statement);
+            }
+            Terminator::Return(None) => self.line(1, "return;"),
+            Terminator::Unreachable => self.line(1, "abort();"),
+        }
+    }
+
+    fn generate(&mut self) {
+        let function = self.function;
+
+        self.line_directive(function.span);
+
+        let header = format!("{} {{", signature(function));
+
+        self.line(0, &header);
+
+        let entry_parameters = &function.blocks[0].parameters;
+
+        for (index, value_type) in function.value_types.iter().enumerate() {
+            if *value_type == IrType::Void || entry_parameters.contains(&ValueId(index)) {
+                continue;
+            }
+
+            let declaration = format!("{} {};", c_type(*value_type), value_name(ValueId(index)));
+
+            self.line(1, &declaration);
+        }
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            if index > 0 {
+                let label = format!("{}:;", BlockId(index));
+
+                self.line(0, &label);
+            }
+
+            for instruction in &block.instructions {
+                self.instruction(instruction);
+            }
+
+            self.terminator(&block.terminator);
+        }
+
+        self.line(0, "}");
+    }
+}
+
+/// Links a declaration to the plain C symbol `name`. The Metor name keeps its prefix in the C
+/// source, so it can't clash with whatever the headers declare under the same name.
+fn label(name: &str) -> String {
+    return format!(" __asm__(MT_LABEL(__USER_LABEL_PREFIX__) \"{}\")", name);
+}
+
+fn signature(function: &Function) -> String {
+    let parameters: Vec<String> = if function.parameters.is_empty() {
+        vec![String::from("void")]
+    } else {
+        function.blocks[0]
+            .parameters
+            .iter()
+            .map(|parameter| {
+                format!(
+                    "{} {}",
+                    c_type(function.value_type(*parameter)),
+                    value_name(*parameter)
+                )
+            })
+            .collect()
+    };
+
+    let linkage = if function.external { "" } else { "static " };
+
+    return format!(
+        "{}{} {}({})",
+        linkage,
+        c_type(function.return_type),
+        function_name(&function.name),
+        parameters.join(", ")
+    );
+}
+
+/// Translates a verified module to C99. Every instruction is preceded by a `#line` directive
+/// pointing at the file it came from, so compiler errors, debuggers and sanitizers report Metor
+/// locations.
+pub fn generate(module: &Module, source_map: &SourceMap) -> String {
+    let mut output = String::from(PRELUDE);
+
+    output.push('\n');
+
+    for global in &module.globals {
+        output.push_str(&format!(
+            "static {} {} = {};\n",
+            c_type(global.global_type),
+            global_name(&global.name),
+            constant(&global.value, global.global_type)
+        ));
+    }
+
+    if !module.globals.is_empty() {
+        output.push('\n');
+    }
+
+    if !module.externs.is_empty() || module.functions.iter().any(|function| function.external) {
+        output.push_str("#define MT_STRINGIFY(x) #x\n#define MT_LABEL(x) MT_STRINGIFY(x)\n\n");
+    }
+
+    for extern_function in &module.externs {
+        let parameters: Vec<&str> = if extern_function.parameters.is_empty() {
+            vec!["void"]
+        } else {
+            extern_function
+                .parameters
+                .iter()
+                .map(|parameter| c_type(*parameter))
+                .collect()
+        };
+
+        output.push_str(&format!(
+            "extern {} {}({}){};\n",
+            c_type(extern_function.return_type),
+            function_name(&extern_function.name),
+            parameters.join(", "),
+            label(&extern_function.name)
+        ));
+    }
+
+    for function in &module.functions {
+        let symbol = if function.external {
+            label(&function.name)
+        } else {
+            String::new()
+        };
+
+        output.push_str(&format!("{}{};\n", signature(function), symbol));
+    }
+
+    for function in &module.functions {
+        let mut generator = FunctionGenerator {
+            function,
+            source_map,
+            output: String::new(),
+        };
+
+        generator.generate();
+
+        output.push('\n');
+        output.push_str(&generator.output);
+    }
+
+    let main = module
+        .functions
+        .iter()
+        .find(|function| function.name == "main" && function.parameters.is_empty());
+
+    if let Some(main) = main {
+        output.push_str("\nint main(void) {\n");
+
+        if main.return_type == IrType::I32 {
+            output.push_str("    return (int)mt_main();\n");
+        } else {
+            output.push_str("    mt_main();\n    return 0;\n");
+        }
+
+        output.push_str("}\n");
+    }
+
+    return output;
+}
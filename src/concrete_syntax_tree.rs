@@ -0,0 +1,316 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::json::{self, Json};
+use crate::source_map::{FileId, SourceMap, Span};
+use crate::syntax_tree::Node;
+use crate::tokenizer::{Token, TokenType};
+
+/// A token the parser reads, with the white space, newlines and comments around it. Trivia on the
+/// same line after a token, like a trailing comment, belongs to that token and everything else
+/// leads the next one.
+pub struct ConcreteToken<'a> {
+    pub leading_trivia: Vec<Token<'a>>,
+    pub token: Token<'a>,
+    pub trailing_trivia: Vec<Token<'a>>,
+}
+
+pub enum Element<'a> {
+    Node(ConcreteNode<'a>),
+    Token(ConcreteToken<'a>),
+}
+
+/// A syntax tree node, with the same `kind` as its JSON form, holding its child nodes and its own
+/// tokens in source order.
+pub struct ConcreteNode<'a> {
+    pub kind: String,
+    pub span: Span,
+    pub children: Vec<Element<'a>>,
+}
+
+/// A syntax tree that keeps every token of the source, so printing it gives back the exact input.
+pub struct ConcreteSyntaxTree<'a> {
+    pub root: ConcreteNode<'a>,
+    /// Trivia after the last token, which has no token to lead.
+    pub trailing_trivia: Vec<Token<'a>>,
+}
+
+fn is_trivia(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::WhiteSpace | TokenType::Seperator | TokenType::Comment
+    )
+}
+
+type Tokens<'a> = Peekable<IntoIter<ConcreteToken<'a>>>;
+
+/// Reads the start or end byte offset of a JSON node's span.
+fn offset(json: &Json, end: bool) -> u32 {
+    match json.get("span") {
+        Some(Json::Array(values)) => match values.get(end as usize) {
+            Some(Json::Number(value)) => return *value as u32,
+            _ => return 0,
+        },
+        _ => return 0,
+    }
+}
+
+/// Takes every token up to `end`, grouping the ones inside a child's span into that child.
+fn build_children<'a>(
+    children: &[Json],
+    tokens: &mut Tokens<'a>,
+    end: Option<u32>,
+    file: FileId,
+) -> Vec<Element<'a>> {
+    let mut children: Vec<&Json> = children.iter().collect();
+
+    // Operations list their operator first, so order children by where they start.
+    children.sort_by_key(|child| offset(child, false));
+
+    let mut children = children.into_iter().peekable();
+    let mut elements: Vec<Element> = Vec::new();
+
+    while let Some(token) = tokens.peek() {
+        let start = token.token.span.lo;
+
+        if let Some(end) = end
+            && start >= end
+        {
+            break;
+        }
+
+        while let Some(child) = children.peek()
+            && offset(child, true) <= start
+        {
+            children.next();
+        }
+
+        if let Some(child) = children.next_if(|child| offset(child, false) <= start) {
+            elements.push(Element::Node(build_node(child, tokens, file)));
+        } else if let Some(token) = tokens.next() {
+            elements.push(Element::Token(token));
+        }
+    }
+
+    return elements;
+}
+
+fn build_node<'a>(json: &Json, tokens: &mut Tokens<'a>, file: FileId) -> ConcreteNode<'a> {
+    let kind = match json.get("kind") {
+        Some(Json::String(kind)) => kind.clone(),
+        _ => String::new(),
+    };
+
+    let span = Span::new(
+        file,
+        offset(json, false) as usize,
+        offset(json, true) as usize,
+    );
+
+    let children = match json.get("children") {
+        Some(Json::Array(children)) => build_children(children, tokens, Some(span.hi), file),
+        _ => build_children(&[], tokens, Some(span.hi), file),
+    };
+
+    return ConcreteNode {
+        kind,
+        span,
+        children,
+    };
+}
+
+/// Attaches every token of the source to the syntax tree built from it.
+pub fn build<'a>(
+    tokens: Vec<Token<'a>>,
+    nodes: &[Node],
+    source_map: &SourceMap,
+) -> ConcreteSyntaxTree<'a> {
+    let mut concrete_tokens: Vec<ConcreteToken<'a>> = Vec::new();
+    let mut trivia: Vec<Token<'a>> = Vec::new();
+
+    let mut line_ended = true;
+
+    for token in tokens {
+        if !is_trivia(&token) {
+            concrete_tokens.push(ConcreteToken {
+                leading_trivia: std::mem::take(&mut trivia),
+                token,
+                trailing_trivia: Vec::new(),
+            });
+
+            line_ended = false;
+        } else if let TokenType::Seperator = token.token_type {
+            trivia.push(token);
+
+            line_ended = true;
+        } else if !line_ended && let Some(previous) = concrete_tokens.last_mut() {
+            previous.trailing_trivia.push(token);
+        } else {
+            trivia.push(token);
+        }
+    }
+
+    let span = match (concrete_tokens.first(), concrete_tokens.last()) {
+        (Some(first), Some(last)) => first.token.span.to(last.token.span),
+        _ => Span::default(),
+    };
+
+    let nodes: Vec<Json> = nodes
+        .iter()
+        .map(|node| json::node(node, source_map))
+        .collect();
+
+    let children = build_children(
+        &nodes,
+        &mut concrete_tokens.into_iter().peekable(),
+        None,
+        span.file,
+    );
+
+    return ConcreteSyntaxTree {
+        root: ConcreteNode {
+            kind: String::from("Source"),
+            span,
+            children,
+        },
+        trailing_trivia: trivia,
+    };
+}
+
+impl ConcreteNode<'_> {
+    pub fn display(&self, depth: usize) -> String {
+        let mut display = format!("{}{} {}", " | ".repeat(depth), self.kind, self.span);
+
+        for child in &self.children {
+            match child {
+                Element::Node(node) => display += &format!("\n{}", node.display(depth + 1)),
+                Element::Token(token) => {
+                    for trivia in &token.leading_trivia {
+                        display += &format!(
+                            "\n{}Trivia '{}'",
+                            " | ".repeat(depth + 1),
+                            trivia.content.escape_default()
+                        );
+                    }
+
+                    display += &format!(
+                        "\n{}Token '{}'",
+                        " | ".repeat(depth + 1),
+                        token.token.content
+                    );
+
+                    for trivia in &token.trailing_trivia {
+                        display += &format!(
+                            "\n{}Trailing Trivia '{}'",
+                            " | ".repeat(depth + 1),
+                            trivia.content.escape_default()
+                        );
+                    }
+                }
+            }
+        }
+
+        return display;
+    }
+}
+
+impl ConcreteSyntaxTree<'_> {
+    pub fn display(&self) -> String {
+        let mut display = self.root.display(0);
+
+        for trivia in &self.trailing_trivia {
+            display += &format!("\n | Trivia '{}'", trivia.content.escape_default());
+        }
+
+        return display;
+    }
+}
+
+impl fmt::Display for ConcreteNode<'_> {
+    /// Prints the node's source text exactly as it was written.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                Element::Node(node) => write!(f, "{}", node)?,
+                Element::Token(token) => {
+                    for trivia in &token.leading_trivia {
+                        write!(f, "{}", trivia.content)?;
+                    }
+
+                    write!(f, "{}", token.token.content)?;
+
+                    for trivia in &token.trailing_trivia {
+                        write!(f, "{}", trivia.content)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConcreteSyntaxTree<'_> {
+    /// Prints the source the tree was built from, byte for byte.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.root)?;
+
+        for trivia in &self.trailing_trivia {
+            write!(f, "{}", trivia.content)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax_tree;
+    use crate::tokenizer;
+
+    fn assert_round_trip(code: &str) {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+
+        let tokens = tokenizer::tokenize(code, file);
+        let tree = syntax_tree::build_syntax_tree(tokens.iter().copied());
+        let concrete_tree = build(tokens, &tree, &source_map);
+
+        assert_eq!(concrete_tree.to_string(), code);
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        assert_round_trip("i32 main {\r\n    i32 a = 1\r\n    return a\r\n}\r\n");
+        assert_round_trip("i32 main {\n    return 0\n}");
+    }
+
+    #[test]
+    fn keeps_white_space() {
+        assert_round_trip("i32 main {\n\ti32 a\t=\t1\n\treturn  a \n}\n");
+        assert_round_trip("\n\n\ni32 main {\n\n\n    return 0\n\n}\n\n\n");
+        assert_round_trip("");
+        assert_round_trip("   \n\t\n");
+    }
+
+    #[test]
+    fn keeps_comments() {
+        assert_round_trip(
+            "// Leading\ni32 main { // Trailing\n    // Own line\n    return 0 // After\n}\n// Last",
+        );
+        assert_round_trip("// Only a comment");
+    }
+
+    #[test]
+    fn keeps_error_tokens() {
+        assert_round_trip("i32 main {\n    i32 1x = $2\n    return @\n}\n");
+        assert_round_trip("i32 a = ((1\n}");
+    }
+
+    #[test]
+    fn keeps_emoji() {
+        assert_round_trip("i32 main {\n    // 👋 Hi 👨‍👩‍👧\n    i32 a = 1 🚀\n    return a\n}\n");
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::source_map::Span;
+use crate::syntax_tree::{BooleanNode, Node, NumberNode, OperationNode, Operator, Type, TypeNode};
+
+// Every intermediate integer has to fit in at least one of Metor's integer types.
+const INTEGER_MIN: i64 = i32::MIN as i64;
+const INTEGER_MAX: i64 = u32::MAX as i64;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Constant {
+    pub fn to_node(self, span: Span) -> Node {
+        match self {
+            Constant::Integer(value) => Node::Number(NumberNode { value, span }),
+            Constant::Boolean(value) => Node::Boolean(BooleanNode { value, span }),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+struct Binding {
+    value: Option<Constant>,
+    node_type: Type,
+    /// Whether the variable holds a single value, rather than an array or a pointer.
+    scalar: bool,
+}
+
+pub struct ConstantScope {
+    scopes: Vec<HashMap<String, Binding>>,
+    return_type: Option<Type>,
+}
+
+impl ConstantScope {
+    pub fn new() -> ConstantScope {
+        return ConstantScope {
+            scopes: vec![HashMap::new()],
+            return_type: None,
+        };
+    }
+
+    pub fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Binds a name in the innermost scope. Runtime variables are bound to `None` so they shadow
+    /// constants of the same name from outer scopes.
+    pub fn define(&mut self, name: &str, value: Option<Constant>, type_node: &TypeNode) {
+        self.scopes.last_mut().unwrap().insert(
+            String::from(name),
+            Binding {
+                value,
+                node_type: type_node.node_type,
+                scalar: type_node.array_size.is_none() && !type_node.pointer,
+            },
+        );
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Constant> {
+        return self.binding(name).and_then(|binding| binding.value);
+    }
+
+    /// The type of a variable's values: the variable itself if it's a single value, and its
+    /// elements otherwise.
+    fn value_type(&self, name: &str, element: bool) -> Option<Type> {
+        return self
+            .binding(name)
+            .filter(|binding| binding.scalar != element)
+            .map(|binding| binding.node_type);
+    }
+}
+
+/// The type an expression has regardless of where it's used, if the folder can tell. Integer
+/// literals have no type of their own and take on the type of whatever they're combined with.
+fn expression_type(node: &Node, scope: &ConstantScope) -> Option<Type> {
+    match node {
+        Node::Boolean(_) => Some(Type::Boolean),
+        Node::Name(node) => scope.value_type(&node.value, false),
+        Node::Index(node) => scope.value_type(&node.name.value, true),
+        Node::Comptime(node) => expression_type(&node.value, scope),
+        Node::Group(node) => match node.content.as_slice() {
+            [value] => expression_type(value, scope),
+            _ => None,
+        },
+        Node::Operation(node) if node.operator.operator.comparison() => Some(Type::Boolean),
+        Node::Operation(node) => node
+            .values
+            .iter()
+            .find_map(|value| expression_type(value, scope)),
+        _ => None,
+    }
+}
+
+/// The type the operands of an operation are computed in, given the type `expected` of the
+/// operation's result.
+fn operand_type(
+    operation: &OperationNode,
+    expected: Option<Type>,
+    scope: &ConstantScope,
+) -> Option<Type> {
+    let operand_type = operation
+        .values
+        .iter()
+        .find_map(|value| expression_type(value, scope));
+
+    if operation.operator.operator.comparison() {
+        return operand_type;
+    }
+
+    return operand_type.or(expected);
+}
+
+/// Checks the result of an integer operation against the range of the type it's computed in, or
+/// against every integer type when that isn't known.
+fn check_integer(
+    value: Option<i64>,
+    operation: &OperationNode,
+    node_type: Option<Type>,
+) -> Result<Constant, Diagnostic> {
+    let (min, max) = node_type
+        .and_then(|node_type| integer_range(&node_type))
+        .unwrap_or((INTEGER_MIN, INTEGER_MAX));
+
+    if let Some(value) = value
+        && (min..=max).contains(&value)
+    {
+        return Ok(Constant::Integer(value));
+    }
+
+    let message = match (value, node_type) {
+        (Some(value), Some(node_type)) if integer_range(&node_type).is_some() => {
+            format!("Integer overflow: {} does not fit in {}", value, node_type)
+        }
+        _ => format!(
+            "Integer overflow in constant expression {}",
+            operation.operator.operator
+        ),
+    };
+
+    return Err(Diagnostic::error(message, operation.span));
+}
+
+fn evaluate_operation(
+    operation: &OperationNode,
+    expected: Option<Type>,
+    scope: &ConstantScope,
+) -> Result<Option<Constant>, Diagnostic> {
+    let operator = &operation.operator.operator;
+    let operand_type = operand_type(operation, expected, scope);
+
+    // Operations the parser couldn't complete are reported as errors elsewhere.
+    if operator.one_sided() {
+        let [value] = operation.values.as_slice() else {
+            return Ok(None);
+        };
+
+        let value = match evaluate_typed(value, operand_type, scope)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        return match (operator, value) {
+            (Operator::Not, Constant::Boolean(value)) => Ok(Some(Constant::Boolean(!value))),
+            (Operator::Negate, Constant::Integer(value)) => {
+                check_integer(value.checked_neg(), operation, operand_type).map(Some)
+            }
+            _ => Ok(None),
+        };
+    }
+
+    let [value_a, value_b] = operation.values.as_slice() else {
+        return Ok(None);
+    };
+
+    let value_a = evaluate_typed(value_a, operand_type, scope)?;
+    let value_b = evaluate_typed(value_b, operand_type, scope)?;
+
+    let (value_a, value_b) = match (value_a, value_b) {
+        (Some(value_a), Some(value_b)) => (value_a, value_b),
+        _ => return Ok(None),
+    };
+
+    if let Constant::Integer(a) = value_a
+        && let Constant::Integer(b) = value_b
+    {
+        let result = match operator {
+            Operator::Add => check_integer(a.checked_add(b), operation, operand_type)?,
+            Operator::Subtract => check_integer(a.checked_sub(b), operation, operand_type)?,
+            Operator::Multiply => check_integer(a.checked_mul(b), operation, operand_type)?,
+            Operator::Divide | Operator::Modulo if b == 0 => {
+                return Err(Diagnostic::error(
+                    String::from("Division by zero in constant expression"),
+                    operation.span,
+                ));
+            }
+            Operator::Divide => check_integer(a.checked_div(b), operation, operand_type)?,
+            Operator::Modulo => check_integer(a.checked_rem(b), operation, operand_type)?,
+            Operator::BitwiseAnd => Constant::Integer(a & b),
+            Operator::BitwiseOr => Constant::Integer(a | b),
+            Operator::LessThan => Constant::Boolean(a < b),
+            Operator::LessThanOrEqual => Constant::Boolean(a <= b),
+            Operator::GreaterThan => Constant::Boolean(a > b),
+            Operator::GreaterThanOrEqual => Constant::Boolean(a >= b),
+            Operator::Equal => Constant::Boolean(a == b),
+            Operator::NotEqual => Constant::Boolean(a != b),
+            _ => return Ok(None),
+        };
+
+        return Ok(Some(result));
+    }
+
+    if let Constant::Boolean(a) = value_a
+        && let Constant::Boolean(b) = value_b
+    {
+        let result = match operator {
+            Operator::And | Operator::BitwiseAnd => a && b,
+            Operator::Or | Operator::BitwiseOr => a || b,
+            Operator::Equal => a == b,
+            Operator::NotEqual => a != b,
+            _ => return Ok(None),
+        };
+
+        return Ok(Some(Constant::Boolean(result)));
+    }
+
+    return Ok(None);
+}
+
+/// Evaluates an expression at compile time. Returns `Ok(None)` when the expression depends on
+/// something that is only known at runtime, and an error when evaluating it is known to fail.
+pub fn evaluate(node: &Node, scope: &ConstantScope) -> Result<Option<Constant>, Diagnostic> {
+    return evaluate_typed(node, None, scope);
+}
+
+/// Like `evaluate`, where `expected` is the type the expression's value is used as, so integer
+/// arithmetic overflows when it leaves that type's range.
+fn evaluate_typed(
+    node: &Node,
+    expected: Option<Type>,
+    scope: &ConstantScope,
+) -> Result<Option<Constant>, Diagnostic> {
+    match node {
+        Node::Number(node) => Ok(Some(Constant::Integer(node.value))),
+        Node::Boolean(node) => Ok(Some(Constant::Boolean(node.value))),
+        Node::Name(node) => Ok(scope.get(&node.value)),
+        Node::Comptime(node) => evaluate_typed(&node.value, expected, scope),
+        Node::Operation(node) => evaluate_operation(node, expected, scope),
+        _ => Ok(None),
+    }
+}
+
+fn integer_range(node_type: &Type) -> Option<(i64, i64)> {
+    match node_type {
+        Type::I32 => Some((i32::MIN as i64, i32::MAX as i64)),
+        Type::U32 => Some((0, u32::MAX as i64)),
+        _ => None,
+    }
+}
+
+fn fold_type(type_node: &mut TypeNode, scope: &ConstantScope, diagnostics: &mut Vec<Diagnostic>) {
+    let array_size = match &mut type_node.array_size {
+        Some(array_size) => array_size,
+        None => return,
+    };
+
+    let span = array_size.get_span();
+
+    match evaluate(array_size, scope) {
+        Ok(Some(Constant::Integer(size))) if size >= 0 => {
+            **array_size = Constant::Integer(size).to_node(span);
+        }
+        Ok(Some(_)) => diagnostics.push(Diagnostic::error(
+            String::from("Array size must be a non-negative integer"),
+            span,
+        )),
+        Ok(None) => diagnostics.push(Diagnostic::error(
+            String::from("Array size must be a compile time constant"),
+            span,
+        )),
+        Err(diagnostic) => diagnostics.push(diagnostic),
+    }
+}
+
+/// Folds an expression whose value is used as the type `expected`, when that's known.
+fn fold_expression(
+    node: &mut Node,
+    expected: Option<Type>,
+    scope: &ConstantScope,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let span = node.get_span();
+
+    match node {
+        Node::Comptime(comptime_node) => {
+            match evaluate_typed(&comptime_node.value, expected, scope) {
+                Ok(Some(value)) => *node = value.to_node(span),
+                Ok(None) => diagnostics.push(Diagnostic::error(
+                    String::from("Expression is not a compile time constant"),
+                    span,
+                )),
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+        Node::Name(_) | Node::Operation(_) => match evaluate_typed(node, expected, scope) {
+            Ok(Some(value)) => *node = value.to_node(span),
+            Ok(None) => {
+                if let Node::Operation(operation_node) = node {
+                    let operand_type = operand_type(operation_node, expected, scope);
+
+                    for value in &mut operation_node.values {
+                        fold_expression(value, operand_type, scope, diagnostics);
+                    }
+                }
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        },
+        Node::Array(array_node) => {
+            for value in &mut array_node.values {
+                fold_expression(value, expected, scope, diagnostics);
+            }
+        }
+        Node::Group(group_node) => {
+            for value in &mut group_node.content {
+                fold_expression(value, expected, scope, diagnostics);
+            }
+        }
+        Node::Call(call_node) => {
+            for value in &mut call_node.arguments {
+                fold_expression(value, None, scope, diagnostics);
+            }
+        }
+        Node::Index(index_node) => fold_expression(&mut index_node.index, None, scope, diagnostics),
+        _ => {}
+    }
+}
+
+fn fold_nodes(nodes: &mut [Node], scope: &mut ConstantScope, diagnostics: &mut Vec<Diagnostic>) {
+    for node in nodes {
+        match node {
+            Node::Block(block_node) => {
+                scope.push();
+                fold_nodes(&mut block_node.content, scope, diagnostics);
+                scope.pop();
+            }
+            Node::FunctionDefinition(function_node) => {
+                scope.push();
+
+                for parameter in &mut function_node.parameters {
+                    fold_type(&mut parameter.node_type, scope, diagnostics);
+
+                    scope.define(&parameter.name.value, None, &parameter.node_type);
+                }
+
+                let return_type = scope.return_type.replace(function_node.node_type.node_type);
+
+                fold_nodes(&mut function_node.block.content, scope, diagnostics);
+
+                scope.return_type = return_type;
+                scope.pop();
+            }
+            Node::ExternFunction(function_node) => {
+                for parameter in &mut function_node.parameters {
+                    fold_type(&mut parameter.node_type, scope, diagnostics);
+                }
+            }
+            Node::If(if_node) => {
+                fold_expression(&mut if_node.condition, None, scope, diagnostics);
+
+                scope.push();
+                fold_nodes(&mut if_node.block.content, scope, diagnostics);
+                scope.pop();
+
+                if let Some(else_node) = &mut if_node.else_node {
+                    fold_nodes(std::slice::from_mut(else_node.as_mut()), scope, diagnostics);
+                }
+            }
+            Node::Forever(forever_node) => {
+                scope.push();
+                fold_nodes(&mut forever_node.block.content, scope, diagnostics);
+                scope.pop();
+            }
+            Node::Return(return_node) => {
+                if let Some(value) = &mut return_node.value {
+                    fold_expression(value, scope.return_type, scope, diagnostics);
+                }
+            }
+            Node::VariableDefinition(definition_node) => {
+                fold_type(&mut definition_node.node_type, scope, diagnostics);
+
+                let value = &mut definition_node.assignment.value;
+
+                let node_type = &definition_node.node_type;
+                let expected = (!node_type.pointer).then_some(node_type.node_type);
+
+                fold_expression(value, expected, scope, diagnostics);
+
+                let constant = evaluate_typed(value, expected, scope).unwrap_or_default();
+
+                if let Some(Constant::Integer(integer)) = constant
+                    && definition_node.node_type.array_size.is_none()
+                    && !definition_node.node_type.pointer
+                    && let Some((min, max)) = integer_range(&definition_node.node_type.node_type)
+                    && !(min..=max).contains(&integer)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        format!(
+                            "Integer overflow: {} does not fit in {}",
+                            integer, definition_node.node_type.node_type
+                        ),
+                        value.get_span(),
+                    ));
+                }
+
+                scope.define(
+                    &definition_node.assignment.name.value,
+                    if definition_node.constant {
+                        constant
+                    } else {
+                        None
+                    },
+                    &definition_node.node_type,
+                );
+            }
+            Node::Assignment(assignment_node) => {
+                let expected = scope.value_type(&assignment_node.name.value, false);
+
+                fold_expression(&mut assignment_node.value, expected, scope, diagnostics);
+            }
+            Node::IndexAssignment(assignment_node) => {
+                let expected = scope.value_type(&assignment_node.target.name.value, true);
+
+                fold_expression(&mut assignment_node.target.index, None, scope, diagnostics);
+                fold_expression(&mut assignment_node.value, expected, scope, diagnostics);
+            }
+            Node::Defer(defer_node) => {
+                scope.push();
+                fold_nodes(
+                    std::slice::from_mut(defer_node.statement.as_mut()),
+                    scope,
+                    diagnostics,
+                );
+                scope.pop();
+            }
+            _ => fold_expression(node, None, scope, diagnostics),
+        }
+    }
+}
+
+/// Replaces every expression that can be evaluated at compile time with its value. Constant
+/// contexts (`comptime` expressions and array sizes) report an error if they can't be folded.
+pub fn fold_constants(nodes: &mut [Node]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut scope = ConstantScope::new();
+
+    fold_nodes(nodes, &mut scope, &mut diagnostics);
+
+    return diagnostics;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_map::FileId;
+    use crate::syntax_tree;
+    use crate::tokenizer;
+
+    fn fold(code: &str) -> Result<String, Vec<String>> {
+        let mut tree = syntax_tree::build_syntax_tree(tokenizer::Lexer::new(code, FileId(0)));
+
+        assert!(
+            syntax_tree::errors(&tree).is_empty(),
+            "{} didn't parse",
+            code
+        );
+
+        let diagnostics = fold_constants(&mut tree);
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect());
+        }
+
+        return Ok(tree
+            .iter()
+            .map(|node| node.display(0))
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    #[test]
+    fn folds_constant_bindings() {
+        let tree =
+            fold("const i32 a = 2 * 3\ni32 main {\n    i32 b = a + 1\n    return b\n}").unwrap();
+
+        assert!(tree.contains("Name a\n |  | Number 6"));
+        assert!(tree.contains("Name b\n |  |  |  | Number 7"));
+    }
+
+    #[test]
+    fn keeps_variables_out_of_constants() {
+        let tree = fold("i32 main {\n    i32 a = 2\n    i32 b = a + 1\n    return b\n}").unwrap();
+
+        assert!(tree.contains("Operator +\n |  |  |  |  | Name a"));
+    }
+
+    #[test]
+    fn reports_overflow() {
+        assert_eq!(
+            fold("i32 main {\n    i32 a = 2147483647 + 1\n    return a\n}"),
+            Err(vec![String::from(
+                "Integer overflow: 2147483648 does not fit in I32"
+            )])
+        );
+        assert_eq!(
+            fold("i32 main {\n    u32 a = 0 - 1\n    return 0\n}"),
+            Err(vec![String::from(
+                "Integer overflow: -1 does not fit in U32"
+            )])
+        );
+        assert_eq!(
+            fold("i32 main {\n    i32 a = 4294967296\n    return 0\n}"),
+            Err(vec![String::from(
+                "Integer overflow: 4294967296 does not fit in I32"
+            )])
+        );
+    }
+
+    #[test]
+    fn reports_division_by_zero() {
+        assert_eq!(
+            fold("i32 main {\n    i32 a = 1 / (2 - 2)\n    return a\n}"),
+            Err(vec![String::from(
+                "Division by zero in constant expression"
+            )])
+        );
+    }
+
+    #[test]
+    fn folds_comptime() {
+        let tree = fold("i32 main {\n    i32 a = 1 + comptime 2\n    return a\n}").unwrap();
+
+        assert!(tree.contains("Name a\n |  |  |  | Number 3"));
+        assert_eq!(
+            fold("i32 main {\n    i32 a = 1\n    i32 b = comptime a + 1\n    return b\n}"),
+            Err(vec![String::from(
+                "Expression is not a compile time constant"
+            )])
+        );
+    }
+
+    #[test]
+    fn folds_array_sizes() {
+        let tree = fold(
+            "const i32 size = 2\ni32 main {\n    i32[size * 2] a = [1, 2, 3, 4]\n    return 0\n}",
+        )
+        .unwrap();
+
+        assert!(tree.contains("Array Type I32\n |  |  |  | Number 4"));
+        assert_eq!(
+            fold("i32 main {\n    i32 size = 2\n    i32[size] a = [1, 2]\n    return 0\n}"),
+            Err(vec![String::from(
+                "Array size must be a compile time constant"
+            )])
+        );
+        assert_eq!(
+            fold("i32 main {\n    i32[0 - 1] a = [1]\n    return 0\n}"),
+            Err(vec![String::from(
+                "Array size must be a non-negative integer"
+            )])
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::source_map::{SourceMap, Span};
+
+/// Where a diagnostic points.
+#[derive(Clone, Copy)]
+pub enum Location {
+    Span(Span),
+    /// Lines and characters that were resolved before the source went away, like the positions
+    /// kept in a bytecode file.
+    Resolved((usize, usize), (usize, usize)),
+}
+
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Location,
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Span) -> Diagnostic {
+        return Diagnostic {
+            message,
+            location: Location::Span(span),
+        };
+    }
+
+    pub fn resolved(
+        message: String,
+        lines: (usize, usize),
+        characters: (usize, usize),
+    ) -> Diagnostic {
+        return Diagnostic {
+            message,
+            location: Location::Resolved(lines, characters),
+        };
+    }
+
+    pub fn lines_and_characters(&self, source_map: &SourceMap) -> ((usize, usize), (usize, usize)) {
+        match self.location {
+            Location::Span(span) => return source_map.lines_and_characters(span),
+            Location::Resolved(lines, characters) => return (lines, characters),
+        }
+    }
+
+    /// The diagnostic as the command line prints it, naming the file when the location has one.
+    pub fn display(&self, source_map: &SourceMap) -> String {
+        let (lines, characters) = self.lines_and_characters(source_map);
+
+        let file = match self.location {
+            Location::Span(span) => format!(" in {}", source_map.name(span.file)),
+            Location::Resolved(_, _) => String::new(),
+        };
+
+        return format!(
+            "Error: {}{} {}, {} -> {}, {}",
+            self.message, file, lines.0, characters.0, lines.1, characters.1
+        );
+    }
+}
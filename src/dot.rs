@@ -0,0 +1,177 @@
+use crate::ir::{BlockId, Function, Module, Terminator};
+use crate::json::{self, Json};
+use crate::source_map::{SourceMap, Span};
+use crate::syntax_tree::Node;
+
+/// Escapes text for a quoted Graphviz label. Newlines become left justified line breaks.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\l"),
+            character => escaped.push(character),
+        }
+    }
+
+    return escaped;
+}
+
+fn range(json: Option<&Json>) -> (i64, i64) {
+    if let Some(Json::Array(values)) = json
+        && let [Json::Number(start), Json::Number(end)] = values.as_slice()
+    {
+        return (*start, *end);
+    }
+
+    return (0, 0);
+}
+
+/// Adds a node and its children to the graph, returning the node's id. This walks the JSON form
+/// of the tree so both dumps agree on node kinds and children.
+fn syntax_tree_node(node: &Json, graph: &mut String, next_id: &mut usize) -> usize {
+    let id = *next_id;
+
+    *next_id += 1;
+
+    let mut label = match node.get("kind") {
+        Some(Json::String(kind)) => kind.clone(),
+        _ => String::new(),
+    };
+
+    match node.get("value") {
+        Some(Json::String(value)) => label += &format!(" {}", value),
+        Some(value @ (Json::Number(_) | Json::Boolean(_))) => label += &format!(" {}", value),
+        _ => {}
+    }
+
+    if let Some(Json::Boolean(true)) = node.get("constant") {
+        label += " const";
+    }
+
+    let lines = range(node.get("lines"));
+    let characters = range(node.get("characters"));
+
+    label += &format!(
+        "\n{}, {} -> {}, {}\n",
+        lines.0, characters.0, lines.1, characters.1
+    );
+
+    graph.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(&label)));
+
+    if let Some(Json::Array(children)) = node.get("children") {
+        for child in children {
+            let child_id = syntax_tree_node(child, graph, next_id);
+
+            graph.push_str(&format!("    n{} -> n{};\n", id, child_id));
+        }
+    }
+
+    return id;
+}
+
+pub fn syntax_tree(nodes: &[Node], source_map: &SourceMap) -> String {
+    let mut graph = String::from("digraph ast {\n    node [shape=box, fontname=monospace];\n");
+    let mut next_id = 0;
+
+    for node in nodes {
+        syntax_tree_node(&json::node(node, source_map), &mut graph, &mut next_id);
+    }
+
+    graph.push_str("}\n");
+
+    return graph;
+}
+
+/// The lines of a function's source that a block's instructions came from.
+fn block_lines(
+    function: &Function,
+    block: BlockId,
+    source_map: &SourceMap,
+) -> Option<(usize, usize)> {
+    let span = function.blocks[block.0]
+        .instructions
+        .iter()
+        .map(|instruction| instruction.span)
+        .reduce(Span::to)?;
+
+    return Some(source_map.lines_and_characters(span).0);
+}
+
+fn control_flow_graph(function: &Function, graph: &mut String, source_map: &SourceMap) {
+    let prefix = format!("{}_", function.name);
+    let (lines, characters) = source_map.lines_and_characters(function.span);
+
+    graph.push_str(&format!(
+        "    subgraph \"cluster_{}\" {{\n        label=\"@{} {}, {} -> {}, {}\";\n",
+        escape(&function.name),
+        escape(&function.name),
+        lines.0,
+        characters.0,
+        lines.1,
+        characters.1
+    ));
+
+    for (index, block) in function.blocks.iter().enumerate() {
+        let mut label = format!("{}:\n", function.display_block_header(BlockId(index)));
+
+        if let Some((start, end)) = block_lines(function, BlockId(index), source_map) {
+            label = format!("{}lines {} -> {}\n", label, start, end);
+        }
+
+        for instruction in &block.instructions {
+            label += &format!("    {}\n", function.display_instruction(instruction));
+        }
+
+        label += &format!("    {}\n", block.terminator);
+
+        graph.push_str(&format!(
+            "        \"{}{}\" [label=\"{}\"];\n",
+            escape(&prefix),
+            BlockId(index),
+            escape(&label)
+        ));
+    }
+
+    for (index, block) in function.blocks.iter().enumerate() {
+        let edges = match &block.terminator {
+            Terminator::Branch(_, then_target, else_target) => {
+                vec![(then_target, "true"), (else_target, "false")]
+            }
+            terminator => terminator
+                .successors()
+                .into_iter()
+                .map(|target| (target, ""))
+                .collect(),
+        };
+
+        for (target, label) in edges {
+            graph.push_str(&format!(
+                "        \"{}{}\" -> \"{}{}\" [label=\"{}\"];\n",
+                escape(&prefix),
+                BlockId(index),
+                escape(&prefix),
+                target.block,
+                label
+            ));
+        }
+    }
+
+    graph.push_str("    }\n");
+}
+
+/// Draws every function's control flow graph in its own cluster.
+pub fn control_flow_graphs(module: &Module, source_map: &SourceMap) -> String {
+    let mut graph =
+        String::from("digraph cfg {\n    node [shape=box, fontname=monospace, labeljust=l];\n");
+
+    for function in &module.functions {
+        control_flow_graph(function, &mut graph, source_map);
+    }
+
+    graph.push_str("}\n");
+
+    return graph;
+}
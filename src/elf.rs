@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+
+use crate::x86_64::{AsmModule, MachineCode, RelocationKind};
+
+const SECTION_PROGBITS: u32 = 1;
+const SECTION_SYMTAB: u32 = 2;
+const SECTION_STRTAB: u32 = 3;
+const SECTION_RELA: u32 = 4;
+
+const FLAG_WRITE: u64 = 0x1;
+const FLAG_ALLOC: u64 = 0x2;
+const FLAG_EXECINSTR: u64 = 0x4;
+const FLAG_INFO_LINK: u64 = 0x40;
+
+const SYMBOL_NOTYPE: u8 = 0;
+const SYMBOL_OBJECT: u8 = 1;
+const SYMBOL_FUNC: u8 = 2;
+const SYMBOL_SECTION: u8 = 3;
+const SYMBOL_FILE: u8 = 4;
+
+const BIND_LOCAL: u8 = 0;
+const BIND_GLOBAL: u8 = 1;
+
+const SECTION_INDEX_ABSOLUTE: u16 = 0xFFF1;
+
+const RELOCATION_64: u64 = 1;
+const RELOCATION_PC32: u64 = 2;
+const RELOCATION_PLT32: u64 = 4;
+
+// Indices of the sections other sections and symbols refer to, in the order they're written.
+const TEXT: u16 = 1;
+const RODATA: u16 = 2;
+const DATA: u16 = 3;
+const DEBUG_LINE: u16 = 4;
+const SYMTAB: u16 = 7;
+const STRTAB: u16 = 8;
+const SHSTRTAB: u16 = 10;
+
+// Symbol indices of the section symbols, which follow the file symbol.
+const TEXT_SYMBOL: usize = 2;
+const RODATA_SYMBOL: usize = 3;
+const DATA_SYMBOL: usize = 4;
+
+fn unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+
+        value >>= 7;
+
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        return StringTable { bytes: vec![0] };
+    }
+
+    fn add(&mut self, text: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+
+        self.bytes.extend_from_slice(text.as_bytes());
+        self.bytes.push(0);
+
+        return offset;
+    }
+}
+
+struct Symbol {
+    name: u32,
+    kind: u8,
+    bind: u8,
+    section: u16,
+    value: u64,
+    size: u64,
+}
+
+impl Symbol {
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.name.to_le_bytes());
+        output.push(self.bind << 4 | self.kind);
+        output.push(0);
+        output.extend_from_slice(&self.section.to_le_bytes());
+        output.extend_from_slice(&self.value.to_le_bytes());
+        output.extend_from_slice(&self.size.to_le_bytes());
+    }
+}
+
+fn relocation(output: &mut Vec<u8>, offset: usize, symbol: usize, kind: u64, addend: i64) {
+    output.extend_from_slice(&(offset as u64).to_le_bytes());
+    output.extend_from_slice(&((symbol as u64) << 32 | kind).to_le_bytes());
+    output.extend_from_slice(&addend.to_le_bytes());
+}
+
+/// Builds a DWARF 4 line number program with one sequence covering all of `.text`. Returns the
+/// section along with the position of the start address, which needs a relocation.
+fn debug_line(code: &MachineCode, files: &[String]) -> (Vec<u8>, usize) {
+    let mut header: Vec<u8> = vec![
+        1,            // minimum_instruction_length
+        1,            // maximum_operations_per_instruction
+        1,            // default_is_stmt
+        (-5i8) as u8, // line_base
+        14,           // line_range
+        13,           // opcode_base
+    ];
+
+    header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+
+    // No include directories, then the source files in the compilation directory.
+    header.push(0);
+
+    for file in files {
+        header.extend_from_slice(file.as_bytes());
+        header.extend_from_slice(&[0, 0, 0, 0]);
+    }
+
+    header.push(0);
+
+    let mut program: Vec<u8> = vec![0, 9, 0x02];
+    let address_position = program.len();
+
+    program.extend_from_slice(&[0; 8]);
+
+    let mut address = 0;
+    let mut file = 1;
+    let mut line = 1;
+    let mut column = 0;
+
+    for (index, (offset, row_file, row_line, row_column)) in code.lines.iter().enumerate() {
+        // Only the last marker before an instruction describes it.
+        if code
+            .lines
+            .get(index + 1)
+            .is_some_and(|next| next.0 == *offset)
+        {
+            continue;
+        }
+
+        if *offset != address {
+            program.push(0x02);
+            unsigned(&mut program, (*offset - address) as u64);
+            address = *offset;
+        }
+
+        if *row_file != file {
+            program.push(0x04);
+            unsigned(&mut program, *row_file as u64);
+            file = *row_file;
+        }
+
+        if row_line + 1 != line {
+            program.push(0x03);
+            signed(&mut program, (row_line + 1) as i64 - line as i64);
+            line = row_line + 1;
+        }
+
+        if row_column + 1 != column {
+            program.push(0x05);
+            unsigned(&mut program, (row_column + 1) as u64);
+            column = row_column + 1;
+        }
+
+        program.push(0x01);
+    }
+
+    if code.text.len() != address {
+        program.push(0x02);
+        unsigned(&mut program, (code.text.len() - address) as u64);
+    }
+
+    program.extend_from_slice(&[0, 1, 0x01]);
+
+    let mut section: Vec<u8> = Vec::new();
+    let unit_length = 2 + 4 + header.len() + program.len();
+
+    section.extend_from_slice(&(unit_length as u32).to_le_bytes());
+    section.extend_from_slice(&4u16.to_le_bytes());
+    section.extend_from_slice(&(header.len() as u32).to_le_bytes());
+
+    let address_position = section.len() + header.len() + address_position;
+
+    section.extend(header);
+    section.extend(program);
+
+    return (section, address_position);
+}
+
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    contents: Vec<u8>,
+    link: u32,
+    info: u32,
+    alignment: u64,
+    entry_size: u64,
+}
+
+/// Writes a module as a relocatable ELF64 object for x86-64 Linux, ready for `ld` or `cc`. Calls
+/// and data accesses are left to the linker through relocations, and `.debug_line` maps the code
+/// back to the module's source file.
+pub fn write(module: &AsmModule) -> Vec<u8> {
+    let code = module.assemble();
+
+    let mut strings = StringTable::new();
+    let mut section_names = StringTable::new();
+
+    // Labels in .rodata and .data are local, so they're referenced through their section.
+    let mut data_labels: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut rodata: Vec<u8> = Vec::new();
+    let mut data: Vec<u8> = Vec::new();
+
+    for (label, text) in &module.strings {
+        data_labels.insert(label, (RODATA_SYMBOL, rodata.len()));
+        rodata.extend_from_slice(text.as_bytes());
+        rodata.push(0);
+    }
+
+    let mut symbols: Vec<Symbol> = vec![
+        Symbol {
+            name: 0,
+            kind: SYMBOL_NOTYPE,
+            bind: BIND_LOCAL,
+            section: 0,
+            value: 0,
+            size: 0,
+        },
+        Symbol {
+            name: strings.add(&module.files[0]),
+            kind: SYMBOL_FILE,
+            bind: BIND_LOCAL,
+            section: SECTION_INDEX_ABSOLUTE,
+            value: 0,
+            size: 0,
+        },
+    ];
+
+    for section in [TEXT, RODATA, DATA] {
+        symbols.push(Symbol {
+            name: 0,
+            kind: SYMBOL_SECTION,
+            bind: BIND_LOCAL,
+            section,
+            value: 0,
+            size: 0,
+        });
+    }
+
+    for (label, value) in &module.data {
+        symbols.push(Symbol {
+            name: strings.add(label),
+            kind: SYMBOL_OBJECT,
+            bind: BIND_LOCAL,
+            section: DATA,
+            value: data.len() as u64,
+            size: 4,
+        });
+
+        data_labels.insert(label, (DATA_SYMBOL, data.len()));
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut symbol_indices: HashMap<&str, usize> = HashMap::new();
+
+    for global in [false, true] {
+        for function in code
+            .functions
+            .iter()
+            .filter(|function| function.global == global)
+        {
+            symbol_indices.insert(&function.name, symbols.len());
+            symbols.push(Symbol {
+                name: strings.add(&function.name),
+                kind: SYMBOL_FUNC,
+                bind: if global { BIND_GLOBAL } else { BIND_LOCAL },
+                section: TEXT,
+                value: function.offset as u64,
+                size: function.size as u64,
+            });
+        }
+    }
+
+    let first_global = symbols.len() - code.functions.iter().filter(|f| f.global).count();
+
+    let mut text_relocations: Vec<u8> = Vec::new();
+
+    for entry in &code.relocations {
+        let kind = match entry.kind {
+            RelocationKind::Pc32 => RELOCATION_PC32,
+            RelocationKind::Plt32 => RELOCATION_PLT32,
+        };
+
+        if let Some((symbol, offset)) = data_labels.get(entry.symbol.as_str()) {
+            let addend = entry.addend + *offset as i64;
+
+            relocation(&mut text_relocations, entry.offset, *symbol, kind, addend);
+
+            continue;
+        }
+
+        // Anything not defined in the module comes from a library, like printf.
+        let symbol = match symbol_indices.get(entry.symbol.as_str()) {
+            Some(symbol) => *symbol,
+            None => {
+                symbol_indices.insert(&entry.symbol, symbols.len());
+                symbols.push(Symbol {
+                    name: strings.add(&entry.symbol),
+                    kind: SYMBOL_NOTYPE,
+                    bind: BIND_GLOBAL,
+                    section: 0,
+                    value: 0,
+                    size: 0,
+                });
+
+                symbols.len() - 1
+            }
+        };
+
+        relocation(
+            &mut text_relocations,
+            entry.offset,
+            symbol,
+            kind,
+            entry.addend,
+        );
+    }
+
+    let (debug_line, address_position) = debug_line(&code, &module.files);
+    let mut debug_line_relocations: Vec<u8> = Vec::new();
+
+    relocation(
+        &mut debug_line_relocations,
+        address_position,
+        TEXT_SYMBOL,
+        RELOCATION_64,
+        0,
+    );
+
+    let mut symbol_table: Vec<u8> = Vec::new();
+
+    for symbol in &symbols {
+        symbol.write(&mut symbol_table);
+    }
+
+    let section = |name: u32, kind: u32, flags: u64, contents: Vec<u8>, alignment: u64| Section {
+        name,
+        kind,
+        flags,
+        contents,
+        link: 0,
+        info: 0,
+        alignment,
+        entry_size: 0,
+    };
+
+    let mut sections = vec![
+        section(0, 0, 0, Vec::new(), 0),
+        section(
+            section_names.add(".text"),
+            SECTION_PROGBITS,
+            FLAG_ALLOC | FLAG_EXECINSTR,
+            code.text,
+            16,
+        ),
+        section(
+            section_names.add(".rodata"),
+            SECTION_PROGBITS,
+            FLAG_ALLOC,
+            rodata,
+            1,
+        ),
+        section(
+            section_names.add(".data"),
+            SECTION_PROGBITS,
+            FLAG_ALLOC | FLAG_WRITE,
+            data,
+            4,
+        ),
+        section(
+            section_names.add(".debug_line"),
+            SECTION_PROGBITS,
+            0,
+            debug_line,
+            1,
+        ),
+        Section {
+            link: SYMTAB as u32,
+            info: TEXT as u32,
+            entry_size: 24,
+            ..section(
+                section_names.add(".rela.text"),
+                SECTION_RELA,
+                FLAG_INFO_LINK,
+                text_relocations,
+                8,
+            )
+        },
+        Section {
+            link: SYMTAB as u32,
+            info: DEBUG_LINE as u32,
+            entry_size: 24,
+            ..section(
+                section_names.add(".rela.debug_line"),
+                SECTION_RELA,
+                FLAG_INFO_LINK,
+                debug_line_relocations,
+                8,
+            )
+        },
+        Section {
+            link: STRTAB as u32,
+            info: first_global as u32,
+            entry_size: 24,
+            ..section(
+                section_names.add(".symtab"),
+                SECTION_SYMTAB,
+                0,
+                symbol_table,
+                8,
+            )
+        },
+        section(
+            section_names.add(".strtab"),
+            SECTION_STRTAB,
+            0,
+            strings.bytes,
+            1,
+        ),
+        // Marks the stack as non-executable.
+        section(
+            section_names.add(".note.GNU-stack"),
+            SECTION_PROGBITS,
+            0,
+            Vec::new(),
+            1,
+        ),
+    ];
+
+    let shstrtab_name = section_names.add(".shstrtab");
+
+    sections.push(section(
+        shstrtab_name,
+        SECTION_STRTAB,
+        0,
+        section_names.bytes,
+        1,
+    ));
+
+    let mut output: Vec<u8> = vec![0; 64];
+    let mut offsets: Vec<u64> = Vec::new();
+
+    for section in &sections {
+        while section.alignment > 1 && !(output.len() as u64).is_multiple_of(section.alignment) {
+            output.push(0);
+        }
+
+        offsets.push(output.len() as u64);
+        output.extend_from_slice(&section.contents);
+    }
+
+    while !output.len().is_multiple_of(8) {
+        output.push(0);
+    }
+
+    let section_headers = output.len() as u64;
+
+    for (section, offset) in sections.iter().zip(offsets) {
+        let offset = if section.kind == 0 { 0 } else { offset };
+
+        output.extend_from_slice(&section.name.to_le_bytes());
+        output.extend_from_slice(&section.kind.to_le_bytes());
+        output.extend_from_slice(&section.flags.to_le_bytes());
+        output.extend_from_slice(&0u64.to_le_bytes());
+        output.extend_from_slice(&offset.to_le_bytes());
+        output.extend_from_slice(&(section.contents.len() as u64).to_le_bytes());
+        output.extend_from_slice(&section.link.to_le_bytes());
+        output.extend_from_slice(&section.info.to_le_bytes());
+        output.extend_from_slice(&section.alignment.to_le_bytes());
+        output.extend_from_slice(&section.entry_size.to_le_bytes());
+    }
+
+    let mut header: Vec<u8> = vec![0x7F, b'E', b'L', b'F', 2, 1, 1, 0];
+
+    header.extend_from_slice(&[0; 8]);
+    header.extend_from_slice(&1u16.to_le_bytes()); // ET_REL
+    header.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    header.extend_from_slice(&1u32.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&section_headers.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+    header.extend_from_slice(&SHSTRTAB.to_le_bytes());
+
+    output[..64].copy_from_slice(&header);
+
+    return output;
+}
@@ -0,0 +1,482 @@
+use std::collections::HashSet;
+
+use crate::diagnostic::Diagnostic;
+use crate::json::{self, Json};
+use crate::source_map::{FileId, SourceMap, Span};
+use crate::syntax_tree::{self, Node, Symbol};
+use crate::tokenizer::{self, Token, TokenType};
+
+const INDENT: &str = "    ";
+
+/// Operators the parser builds out of two adjacent symbols.
+const MULTISYMBOL_OPERATORS: &[&str] = &["&&", "||", "<=", ">=", "==", "!="];
+
+#[derive(PartialEq)]
+enum WordKind {
+    Comment,
+    Operator,
+    UnaryOperator,
+    Symbol,
+    Keyword,
+    Type,
+    Word,
+}
+
+/// A token that gets printed, along with how many line breaks came before it in the source.
+struct Word {
+    text: String,
+    kind: WordKind,
+    newlines: usize,
+}
+
+impl Word {
+    fn is(&self, text: &str) -> bool {
+        return self.kind != WordKind::Comment && self.text == text;
+    }
+
+    fn is_name(&self) -> bool {
+        return self.kind == WordKind::Word || self.kind == WordKind::Type;
+    }
+
+    /// Whether a line ending with this word goes on to the next line, like one ending with `+`.
+    fn continues_statement(&self) -> bool {
+        return matches!(self.kind, WordKind::Operator | WordKind::UnaryOperator)
+            || self.is(",")
+            || self.is(":")
+            || self.is("(")
+            || self.is("[");
+    }
+}
+
+/// Finds the offsets of operators that the parser made unary, since `-` means both.
+fn unary_operators(nodes: &[Node], positions: &mut HashSet<u32>) {
+    for node in nodes {
+        match node {
+            Node::Operation(operation_node) => {
+                if operation_node.values.len() == 1 {
+                    positions.insert(operation_node.operator.span.lo);
+                }
+
+                unary_operators(&operation_node.values, positions);
+            }
+            Node::Block(block_node) => unary_operators(&block_node.content, positions),
+            Node::Assignment(assignment_node) => {
+                unary_operators(std::slice::from_ref(&assignment_node.value), positions)
+            }
+            Node::VariableDefinition(definition_node) => unary_operators(
+                std::slice::from_ref(&definition_node.assignment.value),
+                positions,
+            ),
+            Node::FunctionDefinition(function_node) => {
+                unary_operators(&function_node.block.content, positions)
+            }
+            Node::Group(group_node) => unary_operators(&group_node.content, positions),
+            Node::Array(array_node) => unary_operators(&array_node.values, positions),
+            Node::Comptime(comptime_node) => {
+                unary_operators(std::slice::from_ref(&comptime_node.value), positions)
+            }
+            Node::Call(call_node) => unary_operators(&call_node.arguments, positions),
+            Node::If(if_node) => {
+                unary_operators(std::slice::from_ref(&if_node.condition), positions);
+                unary_operators(&if_node.block.content, positions);
+
+                if let Some(else_node) = &if_node.else_node {
+                    unary_operators(std::slice::from_ref(else_node), positions);
+                }
+            }
+            Node::Forever(forever_node) => unary_operators(&forever_node.block.content, positions),
+            Node::Return(return_node) => {
+                if let Some(value) = &return_node.value {
+                    unary_operators(std::slice::from_ref(value), positions);
+                }
+            }
+            Node::Index(index_node) => {
+                unary_operators(std::slice::from_ref(&index_node.index), positions)
+            }
+            Node::IndexAssignment(assignment_node) => {
+                unary_operators(
+                    std::slice::from_ref(&assignment_node.target.index),
+                    positions,
+                );
+                unary_operators(std::slice::from_ref(&assignment_node.value), positions);
+            }
+            Node::Defer(defer_node) => {
+                unary_operators(std::slice::from_ref(&defer_node.statement), positions)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn words(tokens: &[Token], unary: &HashSet<u32>) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut newlines = 0;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = &tokens[index];
+
+        index += 1;
+
+        let mut kind = match token.token_type {
+            TokenType::WhiteSpace => continue,
+            TokenType::Seperator => {
+                newlines += 1;
+
+                continue;
+            }
+            TokenType::Comment => WordKind::Comment,
+            TokenType::Symbol(_) => WordKind::Symbol,
+            TokenType::Operator(_) if unary.contains(&token.span.lo) => WordKind::UnaryOperator,
+            TokenType::Operator(_) => WordKind::Operator,
+            TokenType::Keyword(_) => WordKind::Keyword,
+            TokenType::Type(_) => WordKind::Type,
+            TokenType::Boolean(_) | TokenType::Number | TokenType::Name | TokenType::Error => {
+                WordKind::Word
+            }
+        };
+
+        let mut text = String::from(token.content);
+
+        // The ABI of `extern "C"` is a name in quotes, which is kept together as one word.
+        if let TokenType::Symbol(Symbol::Quote) = token.token_type
+            && let [name, close, ..] = &tokens[index..]
+            && let (TokenType::Name, TokenType::Symbol(Symbol::Quote)) =
+                (&name.token_type, &close.token_type)
+            && name.span.lo == token.span.hi
+            && close.span.lo == name.span.hi
+        {
+            text = format!("\"{}\"", name.content);
+            kind = WordKind::Word;
+            index += 2;
+        }
+
+        // Mirror how the parser joins operators, which only happens when nothing separates them.
+        if kind == WordKind::Operator
+            && let Some(next) = tokens.get(index)
+            && next.span.lo == token.span.hi
+            && MULTISYMBOL_OPERATORS.contains(&format!("{}{}", text, next.content).as_str())
+        {
+            text.push_str(next.content);
+            index += 1;
+        }
+
+        words.push(Word {
+            text,
+            kind,
+            newlines,
+        });
+
+        newlines = 0;
+    }
+
+    return words;
+}
+
+/// Whether two words on the same line are separated by a space.
+fn spaced(previous: &Word, word: &Word) -> bool {
+    if word.kind == WordKind::Comment {
+        return true;
+    }
+
+    if word.is(",") || word.is(";") || word.is(")") || word.is("]") || word.is(":") || word.is(".")
+    {
+        return false;
+    }
+
+    if previous.is("(") || previous.is("[") || previous.is(".") {
+        return false;
+    }
+
+    if previous.kind == WordKind::UnaryOperator {
+        return false;
+    }
+
+    // A `*` after a type makes it a pointer type, like `i32*`.
+    if word.is("*") && previous.kind == WordKind::Type {
+        return false;
+    }
+
+    if word.is("(") {
+        return !previous.is_name();
+    }
+
+    if word.is("[") {
+        return !(previous.is_name() || previous.is(")") || previous.is("]"));
+    }
+
+    return true;
+}
+
+fn layout(words: &[Word]) -> String {
+    let mut output = String::new();
+    let mut line = String::new();
+    let mut depth: usize = 0;
+    let mut line_depth: usize = 0;
+    let mut nesting: usize = 0;
+    let mut import = false;
+    let mut continued = false;
+    let mut previous_code: Option<&Word> = None;
+
+    for (index, word) in words.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| &words[index]);
+
+        // The braces of an import list names rather than statements, so they stay on one line.
+        if word.is("{") && previous.is_some_and(|previous| previous.is("import")) {
+            import = true;
+        }
+
+        if word.is("}") && !import {
+            depth = depth.saturating_sub(1);
+        }
+
+        let line_break = match previous {
+            None => false,
+            Some(_) if import => false,
+            Some(previous) if previous.is("}") && word.is("from") => false,
+            Some(_) if word.is("}") => true,
+            Some(previous) if previous.is("{") || previous.kind == WordKind::Comment => true,
+            Some(previous) if previous.is("}") => !word.is("else"),
+            Some(_) if word.is("{") || word.is("else") => false,
+            Some(_) => word.newlines > 0 && nesting == 0,
+        };
+
+        if line_break {
+            output.push_str(&INDENT.repeat(line_depth));
+            output.push_str(&line);
+            output.push('\n');
+
+            let after_open = previous.is_some_and(|previous| previous.is("{"));
+            let after_definition = depth == 0 && previous.is_some_and(|previous| previous.is("}"));
+
+            // Top level definitions are always separated by a blank line.
+            if (word.newlines > 1 || after_definition) && !after_open && !word.is("}") {
+                output.push('\n');
+            }
+
+            line.clear();
+
+            // A line that carries on the statement before it, like after a line ending with `+`,
+            // is indented one more level.
+            continued = nesting > 0
+                || word.is(".")
+                || previous_code.is_some_and(|previous| previous.continues_statement());
+        }
+
+        if line.is_empty() {
+            line_depth = depth + continued as usize;
+        } else if let Some(previous) = previous
+            && spaced(previous, word)
+        {
+            line.push(' ');
+        }
+
+        line.push_str(&word.text);
+
+        if word.kind != WordKind::Comment {
+            previous_code = Some(word);
+        }
+
+        if import {
+            import = !word.is("}");
+        } else if word.is("{") {
+            depth += 1;
+        } else if word.is("(") || word.is("[") {
+            nesting += 1;
+        } else if word.is(")") || word.is("]") {
+            nesting = nesting.saturating_sub(1);
+        }
+    }
+
+    if !line.is_empty() {
+        output.push_str(&INDENT.repeat(line_depth));
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    return output;
+}
+
+/// Writes the tree without spans, so trees parsed from differently formatted code can be compared.
+fn shape(json: &Json, output: &mut String) {
+    match json {
+        Json::Object(fields) => {
+            for (name, value) in fields {
+                if name != "span" && name != "lines" && name != "characters" {
+                    output.push_str(name);
+                    output.push(':');
+                    shape(value, output);
+                    output.push(';');
+                }
+            }
+        }
+        Json::Array(values) => {
+            for value in values {
+                output.push('(');
+                shape(value, output);
+                output.push(')');
+            }
+        }
+        value => output.push_str(&value.to_string()),
+    }
+}
+
+fn tree_shape(nodes: &[Node], source_map: &SourceMap) -> String {
+    let mut output = String::new();
+
+    shape(&json::syntax_tree(nodes, source_map), &mut output);
+
+    return output;
+}
+
+/// Re-prints source code with normalized indentation, spacing, brace placement and blank lines,
+/// keeping comments. Fails rather than returning code that would parse differently. The formatted
+/// code is added to the source map as well, since it gets parsed to check it.
+pub fn format(source_map: &mut SourceMap, file: FileId) -> Result<String, Diagnostic> {
+    let code = source_map.source(file);
+
+    if let Some(diagnostic) = tokenizer::errors(code, file).into_iter().next() {
+        return Err(diagnostic);
+    }
+
+    let tokens = tokenizer::tokenize(code, file);
+    let tree = syntax_tree::build_syntax_tree(tokens.iter().copied());
+
+    // Where code that didn't parse ends depends on its line breaks, so it can't be re-laid out.
+    if let Some(diagnostic) = syntax_tree::errors(&tree).into_iter().next() {
+        return Err(diagnostic);
+    }
+
+    let mut unary = HashSet::new();
+
+    unary_operators(&tree, &mut unary);
+
+    let formatted = layout(&words(&tokens, &unary));
+
+    let formatted_file = source_map.add(
+        format!("{} (formatted)", source_map.name(file)),
+        formatted.clone(),
+    );
+
+    let formatted_tree =
+        syntax_tree::build_syntax_tree(tokenizer::Lexer::new(&formatted, formatted_file));
+
+    if tree_shape(&tree, source_map) != tree_shape(&formatted_tree, source_map) {
+        return Err(Diagnostic::error(
+            String::from("Formatting would change the meaning of the program"),
+            Span::new(file, 0, 0),
+        ));
+    }
+
+    return Ok(formatted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_code(code: &str) -> String {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+
+        return match format(&mut source_map, file) {
+            Ok(formatted) => formatted,
+            Err(diagnostic) => panic!("{}", diagnostic.display(&source_map)),
+        };
+    }
+
+    fn shape_of(code: &str) -> String {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+        let tree = syntax_tree::build_syntax_tree(tokenizer::Lexer::new(code, file));
+
+        return tree_shape(&tree, &source_map);
+    }
+
+    /// Formats code, then checks the result parses to the same tree and formats to itself.
+    fn assert_formats(code: &str, expected: &str) {
+        let formatted = format_code(code);
+
+        assert_eq!(formatted, expected);
+        assert_eq!(shape_of(&formatted), shape_of(code));
+        assert_eq!(format_code(&formatted), formatted);
+    }
+
+    #[test]
+    fn normalizes_spacing_and_braces() {
+        assert_formats(
+            "i32 add:i32 a,i32 b{return a+b}\nvoid main\n{\nif add(1,2)>2{print(-1)}\nelse{print(2)}}",
+            "i32 add: i32 a, i32 b {\n    return a + b\n}\n\nvoid main {\n    if add(1, 2) > 2 {\n        print(-1)\n    } else {\n        print(2)\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_blank_lines() {
+        assert_formats(
+            "// Entry point\nvoid main {\n  i32 a = 1 // one\n\n\n  print(a)\n}\n",
+            "// Entry point\nvoid main {\n    i32 a = 1 // one\n\n    print(a)\n}\n",
+        );
+    }
+
+    #[test]
+    fn indents_continuation_lines() {
+        assert_formats(
+            "void main {\ni32 a = 1 + // why\n2\ni32 b = a *\n  3 +\n      4\n}\n",
+            "void main {\n    i32 a = 1 + // why\n        2\n    i32 b = a *\n        3 +\n        4\n}\n",
+        );
+        assert_formats(
+            "i32 add: i32 a,\ni32 b {\nreturn a + b\n}\n",
+            "i32 add: i32 a,\n    i32 b {\n    return a + b\n}\n",
+        );
+    }
+
+    #[test]
+    fn formats_every_construct() {
+        let code = "import { sqrt } from Math
+extern \"C\" i32* calloc: u32 count, u32 size
+
+const i32 SIZE = comptime 4 * 2
+i32[SIZE] values = [1, 2, 3]
+
+extern \"C\" i32 twice: i32 n {
+    return n * 2
+}
+
+void main {
+    i32* p = alloc(SIZE)
+    defer free(p)
+    p[0] = !true && false || 1 <= 2 == true
+    forever {
+        if p[0] != 0 {
+            break
+        }
+        p[0] = twice(values[1]); p = resize(p, 4)
+    }
+    println(sqrt(4))
+}
+";
+
+        assert_formats(code, code);
+    }
+
+    #[test]
+    fn formats_bundled_code_stably() {
+        for code in [
+            include_str!("../std/Math.mt"),
+            include_str!("../playground/main.mt"),
+        ] {
+            let formatted = format_code(code);
+
+            assert_eq!(shape_of(&formatted), shape_of(code));
+            assert_eq!(format_code(&formatted), formatted);
+        }
+    }
+
+    #[test]
+    fn refuses_code_that_does_not_parse() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from("void main { ) }"));
+
+        assert!(format(&mut source_map, file).is_err());
+    }
+}
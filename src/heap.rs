@@ -0,0 +1,139 @@
+use crate::interpreter::Value;
+use crate::syntax_tree::Type;
+
+fn zero(element_type: Type) -> Value {
+    match element_type {
+        Type::U32 => Value::U32(0),
+        Type::F32 => Value::F32(0.0),
+        Type::Boolean => Value::Boolean(false),
+        _ => Value::I32(0),
+    }
+}
+
+fn count(value: &Value) -> Result<usize, String> {
+    match value {
+        Value::I32(count) if *count >= 0 => return Ok(*count as usize),
+        Value::U32(count) => return Ok(*count as usize),
+        _ => return Err(format!("Can't allocate {} values", value)),
+    }
+}
+
+/// Resizes values to `count`, zeroing any new ones. Running out of memory is an error like in
+/// compiled code, rather than aborting the whole process.
+fn grow(values: &mut Vec<Value>, count: usize, element_type: Type) -> Result<(), String> {
+    if values
+        .try_reserve_exact(count.saturating_sub(values.len()))
+        .is_err()
+    {
+        return Err(String::from("Out of memory"));
+    }
+
+    values.resize(count, zero(element_type));
+
+    return Ok(());
+}
+
+/// The memory `alloc` hands out, shared by the interpreter and the VM. Pointers are indices of
+/// allocations, which are never reused, so using memory after it's freed is always caught rather
+/// than reading whatever took its place.
+pub struct Heap {
+    /// The values of each allocation, or `None` once it's freed.
+    allocations: Vec<Option<Vec<Value>>>,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        return Heap {
+            allocations: Vec::new(),
+        };
+    }
+
+    /// Allocates `count` values of a type, all starting out as zero.
+    pub fn alloc(&mut self, element_type: Type, count_value: &Value) -> Result<Value, String> {
+        let mut values = Vec::new();
+
+        grow(&mut values, count(count_value)?, element_type)?;
+
+        self.allocations.push(Some(values));
+
+        return Ok(Value::Pointer(element_type, self.allocations.len() - 1));
+    }
+
+    fn allocation(&mut self, pointer: &Value) -> Result<&mut Vec<Value>, String> {
+        let Value::Pointer(_, address) = pointer else {
+            return Err(format!("Expected a pointer but got {}", pointer));
+        };
+
+        return match self.allocations.get_mut(*address) {
+            Some(Some(values)) => Ok(values),
+            _ => Err(String::from("Pointer is used after it was freed")),
+        };
+    }
+
+    /// Moves the values to a new allocation of `count` values, freeing the old one like C's
+    /// `realloc` may. Values past the old end start out as zero.
+    pub fn resize(&mut self, pointer: &Value, count_value: &Value) -> Result<Value, String> {
+        let count = count(count_value)?;
+        let mut values = std::mem::take(self.allocation(pointer)?);
+
+        let Value::Pointer(element_type, address) = pointer else {
+            unreachable!()
+        };
+
+        if let Err(error) = grow(&mut values, count, *element_type) {
+            self.allocations[*address] = Some(values);
+
+            return Err(error);
+        }
+
+        self.allocations[*address] = None;
+        self.allocations.push(Some(values));
+
+        return Ok(Value::Pointer(*element_type, self.allocations.len() - 1));
+    }
+
+    pub fn free(&mut self, pointer: &Value) -> Result<(), String> {
+        if let Value::Pointer(_, address) = pointer
+            && let Some(None) = self.allocations.get(*address)
+        {
+            return Err(String::from("Pointer is freed twice"));
+        }
+
+        self.allocation(pointer)?;
+
+        if let Value::Pointer(_, address) = pointer {
+            self.allocations[*address] = None;
+        }
+
+        return Ok(());
+    }
+
+    fn element(&mut self, pointer: &Value, index: &Value) -> Result<&mut Value, String> {
+        let values = self.allocation(pointer)?;
+        let length = values.len();
+
+        let offset = match index {
+            Value::I32(index) => usize::try_from(*index).ok(),
+            Value::U32(index) => Some(*index as usize),
+            _ => return Err(format!("Expected an integer index but got {}", index)),
+        };
+
+        return match offset.and_then(|offset| values.get_mut(offset)) {
+            Some(value) => Ok(value),
+            None => Err(format!(
+                "Index {} is out of bounds for {} values",
+                index, length
+            )),
+        };
+    }
+
+    pub fn load(&mut self, pointer: &Value, index: &Value) -> Result<Value, String> {
+        return Ok(self.element(pointer, index)?.clone());
+    }
+
+    pub fn store(&mut self, pointer: &Value, index: &Value, value: Value) -> Result<(), String> {
+        *self.element(pointer, index)? = value;
+
+        return Ok(());
+    }
+}
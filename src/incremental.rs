@@ -0,0 +1,959 @@
+use crate::source_map::{FileId, Span};
+use crate::syntax_tree::{self, BlockNode, IndexNode, Node, Symbol, Type, TypeNode};
+use crate::tokenizer::{Lexer, Token, TokenType};
+
+/// A change to a file, replacing the bytes in `span` with `text`.
+pub struct Edit {
+    pub span: Span,
+    pub text: String,
+}
+
+/// A token without its text, so tokens can be kept while the text they came from is edited.
+#[derive(Clone, Copy)]
+struct StoredToken {
+    token_type: TokenType,
+    span: Span,
+}
+
+fn token_span(token: &StoredToken) -> Span {
+    return token.span;
+}
+
+fn shift_token(token: &mut StoredToken, delta: i64) {
+    shift(&mut token.span, delta);
+}
+
+/// Where the spans stop being up to date. Everything from `index` on is really `delta` bytes
+/// further on, so an edit only moves the spans between it and the last edit, not the whole rest of
+/// the file.
+#[derive(Clone, Copy, Default)]
+struct Gap {
+    index: usize,
+    delta: i64,
+}
+
+impl Gap {
+    /// Moves the gap to `index`, bringing the spans it passes up to date or out of date. At the
+    /// end there's nothing left to be out of date, so moving the gap back from there is free.
+    fn move_to<T>(&mut self, items: &mut [T], index: usize, shift_item: fn(&mut T, i64)) {
+        if self.delta != 0 && index > self.index {
+            for item in &mut items[self.index..index] {
+                shift_item(item, self.delta);
+            }
+        } else if self.delta != 0 {
+            for item in &mut items[index..self.index] {
+                shift_item(item, -self.delta);
+            }
+        }
+
+        self.index = index;
+
+        if index == items.len() {
+            self.delta = 0;
+        }
+    }
+
+    /// The first index whose real span doesn't satisfy `predicate`, which has to be true for a
+    /// start of the items and false for the rest.
+    fn partition_point<T>(
+        &self,
+        items: &[T],
+        span: fn(&T) -> Span,
+        predicate: impl Fn(Span) -> bool,
+    ) -> usize {
+        let (before, after) = items.split_at(self.index.min(items.len()));
+        let index = before.partition_point(|item| predicate(span(item)));
+
+        if index < before.len() {
+            return index;
+        }
+
+        return index
+            + after.partition_point(|item| {
+                let mut span = span(item);
+
+                shift(&mut span, self.delta);
+
+                predicate(span)
+            });
+    }
+}
+
+/// The tokens and syntax tree of a file that is being edited. Each edit re-lexes only the lines
+/// it touches and re-parses only the top level nodes around it, keeping every other node, like
+/// untouched function definitions.
+pub struct IncrementalTree {
+    text: String,
+    file: FileId,
+    tokens: Vec<StoredToken>,
+    token_gap: Gap,
+    nodes: Vec<Node>,
+    node_gap: Gap,
+    /// Whether every bracket in the file is closed.
+    balanced: bool,
+}
+
+impl IncrementalTree {
+    pub fn new(text: String, file: FileId) -> IncrementalTree {
+        let tokens = Lexer::new(&text, file)
+            .map(|token| StoredToken {
+                token_type: token.token_type,
+                span: token.span,
+            })
+            .collect();
+
+        let mut tree = IncrementalTree {
+            text,
+            file,
+            tokens,
+            token_gap: Gap::default(),
+            nodes: Vec::new(),
+            node_gap: Gap::default(),
+            balanced: false,
+        };
+
+        tree.parse();
+
+        return tree;
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.text;
+    }
+
+    pub fn nodes(&mut self) -> &[Node] {
+        let length = self.nodes.len();
+
+        self.node_gap.move_to(&mut self.nodes, length, shift_node);
+
+        return &self.nodes;
+    }
+
+    /// The tokens from `start` up to `end`, which have to be before the token gap.
+    fn tokens_in(&self, start: usize, end: usize) -> impl Iterator<Item = Token<'_>> {
+        return self.tokens[start..end].iter().map(|token| Token {
+            content: &self.text[token.span.lo as usize..token.span.hi as usize],
+            token_type: token.token_type,
+            span: token.span,
+        });
+    }
+
+    /// Parses the whole file again, for edits that change more than the nodes around them.
+    fn parse(&mut self) {
+        let length = self.tokens.len();
+
+        self.token_gap
+            .move_to(&mut self.tokens, length, shift_token);
+
+        self.balanced = balanced(&self.tokens);
+        self.nodes = syntax_tree::build_syntax_tree(self.tokens_in(0, length));
+        self.node_gap = Gap::default();
+    }
+
+    pub fn edit(&mut self, edit: &Edit) {
+        let lo = edit.span.lo as usize;
+        let hi = edit.span.hi as usize;
+        let delta = edit.text.len() as i64 - (hi - lo) as i64;
+
+        // Tokens never span lines, so re-lexing from the start of the first edited line to the end
+        // of the last one gives the same tokens as lexing the whole file.
+        let line_start = self.text[..lo].rfind('\n').map_or(0, |index| index + 1);
+        let old_line_end = self.text[hi..]
+            .find('\n')
+            .map_or(self.text.len(), |index| hi + index);
+
+        self.text.replace_range(lo..hi, &edit.text);
+
+        let line_end = (old_line_end as i64 + delta) as usize;
+
+        let first = self
+            .token_gap
+            .partition_point(&self.tokens, token_span, |span| {
+                (span.lo as usize) < line_start
+            });
+        let last = self
+            .token_gap
+            .partition_point(&self.tokens, token_span, |span| {
+                (span.lo as usize) < old_line_end
+            });
+
+        self.token_gap.move_to(&mut self.tokens, last, shift_token);
+
+        let relexed: Vec<StoredToken> = Lexer::starting_at(&self.text, self.file, line_start)
+            .take_while(|token| (token.span.lo as usize) < line_end)
+            .map(|token| StoredToken {
+                token_type: token.token_type,
+                span: token.span,
+            })
+            .collect();
+
+        self.token_gap.index = first + relexed.len();
+        self.token_gap.delta += delta;
+        self.tokens.splice(first..last, relexed);
+
+        // Brackets are only matched up within the re-parsed nodes, which gives the same tree only
+        // when the ones around them are all closed too.
+        if !self.balanced {
+            self.parse();
+
+            return;
+        }
+
+        let lines = Lines {
+            start: line_start,
+            old_end: old_line_end,
+            end: line_end,
+        };
+
+        let touched_start = self
+            .node_gap
+            .partition_point(&self.nodes, Node::get_span, |span| {
+                (span.hi as usize) < line_start
+            });
+        let touched_end = self
+            .node_gap
+            .partition_point(&self.nodes, Node::get_span, |span| {
+                (span.lo as usize) <= old_line_end
+            });
+
+        if touched_end == touched_start + 1 && self.edit_body(touched_start, lo, hi, &lines, delta)
+        {
+            return;
+        }
+
+        // The nodes touching the edited lines are re-parsed along with the nodes out to the nearest
+        // function definition on each side. If those functions come out the same, the edit didn't
+        // change how the nodes around it are grouped and the rest of the tree can be kept.
+        let mut start = touched_start;
+
+        while start > 0 {
+            start -= 1;
+
+            if let Node::FunctionDefinition(_) = self.nodes[start] {
+                break;
+            }
+        }
+
+        let mut end = touched_end;
+
+        while end < self.nodes.len() {
+            end += 1;
+
+            if let Node::FunctionDefinition(_) = self.nodes[end - 1] {
+                break;
+            }
+        }
+
+        self.node_gap.move_to(&mut self.nodes, end, shift_node);
+
+        let mut window_lo = line_start;
+        let mut window_hi = line_end;
+
+        if start < end {
+            window_lo = window_lo.min(self.nodes[start].get_span().lo as usize);
+            window_hi = window_hi.max((self.nodes[end - 1].get_span().hi as i64 + delta) as usize);
+        }
+
+        // Spans can leave out brackets around the last value in a node, like the `)` of
+        // `i32 x = (1)`, so past the functions on each side the window goes to the end of the file.
+        if start == 0 {
+            window_lo = 0;
+        }
+
+        if end == self.nodes.len() {
+            window_hi = self.text.len();
+        }
+
+        let (token_start, token_end) = self.token_window(window_lo, window_hi);
+
+        // The bodies of the functions the edit doesn't reach are left out, and moved into the
+        // re-parsed functions afterwards, since a body parses the same whatever is around it.
+        let bodies: Vec<Body> = (start..end)
+            .filter_map(|index| self.untouched_body(index, lo, hi, delta))
+            .collect();
+
+        let mut segments = Vec::new();
+        let mut segment_start = token_start;
+
+        for body in &bodies {
+            let (body_start, body_end) =
+                self.token_window(body.span.lo as usize + 1, body.span.hi as usize - 1);
+
+            segments.push((segment_start, body_start));
+            segment_start = body_end;
+        }
+
+        segments.push((segment_start, token_end));
+
+        if !balanced(
+            segments
+                .iter()
+                .flat_map(|&(start, end)| &self.tokens[start..end]),
+        ) {
+            self.parse();
+
+            return;
+        }
+
+        let mut reparsed = syntax_tree::build_syntax_tree(
+            segments
+                .iter()
+                .flat_map(|&(start, end)| self.tokens_in(start, end)),
+        );
+
+        let same_start =
+            start == touched_start || same_function(&self.nodes[start], reparsed.first(), 0);
+
+        let same_end =
+            end == touched_end || same_function(&self.nodes[end - 1], reparsed.last(), delta);
+
+        if !same_start || !same_end || !self.restore_bodies(&mut reparsed, &bodies) {
+            self.parse();
+
+            return;
+        }
+
+        self.node_gap.index = start + reparsed.len();
+        self.node_gap.delta += delta;
+        self.nodes.splice(start..end, reparsed);
+    }
+
+    /// Re-parses only the statements around an edit inside the body of the function at `index`,
+    /// with the statement on each side to check the edit didn't change where they end. An edit
+    /// inside the block of a single statement, like an `if`, goes down into that block. Returns
+    /// whether it could.
+    fn edit_body(&mut self, index: usize, lo: usize, hi: usize, lines: &Lines, delta: i64) -> bool {
+        self.node_gap
+            .move_to(&mut self.nodes, index + 1, shift_node);
+
+        let Node::FunctionDefinition(function) = &mut self.nodes[index] else {
+            return false;
+        };
+
+        // The statement and which of its blocks to go into, for each block on the way down.
+        let mut path = Vec::new();
+        let mut block = &mut function.block;
+
+        if lo <= block.span.lo as usize || hi >= block.span.hi as usize {
+            return false;
+        }
+
+        loop {
+            let (touched_start, touched_end) = touched(&block.content, lines);
+
+            if touched_end != touched_start + 1 {
+                break;
+            }
+
+            let inner = blocks_mut(&mut block.content[touched_start])
+                .into_iter()
+                .enumerate()
+                .find(|(_, inner)| lo > inner.span.lo as usize && hi < inner.span.hi as usize);
+
+            let Some((which, inner)) = inner else {
+                break;
+            };
+
+            path.push((touched_start, which));
+            block = inner;
+        }
+
+        let open = block.span.lo as usize;
+        let close = block.span.hi as usize - 1;
+
+        // The braces have to come out of the edit as they were, which a `//` typed before the `}`
+        // would change.
+        if !self.is_symbol_at(open, Symbol::OpenCurlyBracket)
+            || !self.is_symbol_at((close as i64 + delta) as usize, Symbol::ClosedCurlyBracket)
+        {
+            return false;
+        }
+
+        let content = &self.block_at(index, &path).content;
+
+        let (touched_start, touched_end) = touched(content, lines);
+
+        let start = touched_start.saturating_sub(1);
+        let end = (touched_end + 1).min(content.len());
+
+        let window_lo = match start < touched_start {
+            true => content[start].get_span().lo as usize,
+            false => open + 1,
+        };
+        let window_hi = match end > touched_end {
+            true => content[end - 1].get_span().hi as usize,
+            false => close,
+        };
+
+        let close = (close as i64 + delta) as usize;
+
+        let window_lo = window_lo.min(lines.start).max(open + 1);
+        let window_hi = ((window_hi as i64 + delta) as usize)
+            .max(lines.end)
+            .min(close);
+
+        let (body_start, body_end) = self.token_window(open + 1, close);
+        let (token_start, token_end) = self.token_window(window_lo, window_hi);
+
+        let Some((token_start, token_end)) = widen_to_balanced(
+            &self.tokens[body_start..body_end],
+            token_start - body_start,
+            token_end - body_start,
+        )
+        .map(|(start, end)| (body_start + start, body_start + end)) else {
+            return false;
+        };
+
+        // The statements are parsed as the body of a function, so they're parsed just like they
+        // are in the real one.
+        let wrapper = |content, token_type, position| Token {
+            content,
+            token_type,
+            span: Span::new(self.file, position, position),
+        };
+
+        let mut reparsed = syntax_tree::build_syntax_tree(
+            [
+                wrapper("void", TokenType::Type(Type::Void), window_lo),
+                wrapper("body", TokenType::Name, window_lo),
+                wrapper("{", TokenType::Symbol(Symbol::OpenCurlyBracket), window_lo),
+            ]
+            .into_iter()
+            .chain(self.tokens_in(token_start, token_end))
+            .chain([wrapper(
+                "}",
+                TokenType::Symbol(Symbol::ClosedCurlyBracket),
+                window_hi,
+            )]),
+        );
+
+        let [Node::FunctionDefinition(wrapper)] = &mut reparsed[..] else {
+            return false;
+        };
+
+        let statements = std::mem::take(&mut wrapper.block.content);
+
+        let content = &self.block_at(index, &path).content;
+
+        if start < touched_start && !same_node(&content[start], statements.first(), 0) {
+            return false;
+        }
+
+        if end > touched_end && !same_node(&content[end - 1], statements.last(), delta) {
+            return false;
+        }
+
+        // This moves the statements after the edit and stretches the blocks around it. The ones
+        // being replaced come out wrong, but they're replaced right after.
+        stretch_node(&mut self.nodes[index], lo, hi, delta);
+
+        self.block_at(index, &path)
+            .content
+            .splice(start..end, statements);
+
+        self.node_gap.delta += delta;
+
+        return true;
+    }
+
+    /// The block found by going down `path` from the body of the function at `index`.
+    fn block_at(&mut self, index: usize, path: &[(usize, usize)]) -> &mut BlockNode {
+        let Node::FunctionDefinition(function) = &mut self.nodes[index] else {
+            unreachable!()
+        };
+
+        let mut block = &mut function.block;
+
+        for &(statement, which) in path {
+            block = blocks_mut(&mut block.content[statement]).swap_remove(which);
+        }
+
+        return block;
+    }
+
+    /// The body of the function at `index` if the edit doesn't reach inside it, with the span it
+    /// has now.
+    fn untouched_body(&self, index: usize, lo: usize, hi: usize, delta: i64) -> Option<Body> {
+        let Node::FunctionDefinition(function) = &self.nodes[index] else {
+            return None;
+        };
+
+        let mut span = function.block.span;
+
+        if span.hi as usize <= lo {
+            return Some(Body {
+                index,
+                span,
+                delta: 0,
+            });
+        }
+
+        shift(&mut span, delta);
+
+        if span.lo as usize >= (hi as i64 + delta) as usize
+            && self.is_symbol_at(span.lo as usize, Symbol::OpenCurlyBracket)
+        {
+            return Some(Body { index, span, delta });
+        }
+
+        return None;
+    }
+
+    /// Moves the content of each left out body into the re-parsed function with the same block,
+    /// returning whether every one of them found its function.
+    fn restore_bodies(&mut self, reparsed: &mut [Node], bodies: &[Body]) -> bool {
+        let mut bodies = bodies.iter().peekable();
+
+        for node in reparsed {
+            let Node::FunctionDefinition(function) = node else {
+                continue;
+            };
+
+            let Some(body) = bodies.next_if(|body| body.span == function.block.span) else {
+                continue;
+            };
+
+            let Node::FunctionDefinition(old) = &mut self.nodes[body.index] else {
+                unreachable!()
+            };
+
+            function.block.content = std::mem::take(&mut old.block.content);
+
+            shift_nodes(&mut function.block.content, body.delta);
+        }
+
+        return bodies.next().is_none();
+    }
+
+    /// The tokens starting from `lo` up to `hi`, moving the token gap past them.
+    fn token_window(&mut self, lo: usize, hi: usize) -> (usize, usize) {
+        let start = self
+            .token_gap
+            .partition_point(&self.tokens, token_span, |span| (span.lo as usize) < lo);
+        let end = self
+            .token_gap
+            .partition_point(&self.tokens, token_span, |span| (span.lo as usize) < hi);
+
+        self.token_gap
+            .move_to(&mut self.tokens, end.max(self.token_gap.index), shift_token);
+
+        return (start, end);
+    }
+
+    /// Whether there's a `symbol` token at `position`.
+    fn is_symbol_at(&self, position: usize, symbol: Symbol) -> bool {
+        let index = self
+            .token_gap
+            .partition_point(&self.tokens, token_span, |span| {
+                (span.lo as usize) < position
+            });
+
+        let Some(token) = self.tokens.get(index) else {
+            return false;
+        };
+
+        let mut span = token.span;
+
+        if index >= self.token_gap.index {
+            shift(&mut span, self.token_gap.delta);
+        }
+
+        return span.lo as usize == position
+            && matches!(token.token_type, TokenType::Symbol(found) if found == symbol);
+    }
+}
+
+/// The lines an edit touched, from the start of the first to the end of the last, which ends at
+/// `old_end` before the edit and `end` after it.
+struct Lines {
+    start: usize,
+    old_end: usize,
+    end: usize,
+}
+
+/// A function body left out of a re-parse, from the function at `index`. `span` is the span of
+/// its block after the edit, and `delta` how far the edit moved its content.
+struct Body {
+    index: usize,
+    span: Span,
+    delta: i64,
+}
+
+/// Whether a node at the edge of the re-parsed nodes came out the same, `delta` bytes further on.
+/// Only functions are compared, since the edge is only something else at the start or end of the
+/// file, where nothing else could be grouped with it.
+/// The statements in `content` on the lines an edit touched, as a range of indices.
+fn touched(content: &[Node], lines: &Lines) -> (usize, usize) {
+    let start = content.partition_point(|node| (node.get_span().hi as usize) < lines.start);
+    let end = content.partition_point(|node| (node.get_span().lo as usize) <= lines.old_end);
+
+    return (start, end);
+}
+
+/// The blocks directly in a statement, like both blocks of an `if` with an `else`.
+fn blocks_mut(node: &mut Node) -> Vec<&mut BlockNode> {
+    match node {
+        Node::Block(node) => vec![node],
+        Node::Forever(node) => vec![&mut node.block],
+        Node::Defer(node) => blocks_mut(&mut node.statement),
+        Node::If(node) => {
+            let mut blocks = vec![&mut node.block];
+
+            if let Some(else_node) = &mut node.else_node {
+                blocks.extend(blocks_mut(else_node));
+            }
+
+            return blocks;
+        }
+        _ => vec![],
+    }
+}
+
+fn same_function(old: &Node, new: Option<&Node>, delta: i64) -> bool {
+    let Node::FunctionDefinition(old) = old else {
+        return true;
+    };
+
+    let mut span = old.span;
+
+    shift(&mut span, delta);
+
+    return matches!(new, Some(Node::FunctionDefinition(new)) if new.span == span);
+}
+
+/// Whether a statement at the edge of the re-parsed statements came out as the same kind of node
+/// over the same tokens, `delta` bytes further on.
+fn same_node(old: &Node, new: Option<&Node>, delta: i64) -> bool {
+    let mut span = old.get_span();
+
+    shift(&mut span, delta);
+
+    return matches!(new, Some(new) if new.get_span() == span
+        && std::mem::discriminant(old) == std::mem::discriminant(new));
+}
+
+/// Which kind of bracket a token is, and whether it opens or closes one.
+fn bracket(token: &StoredToken) -> Option<(usize, i64)> {
+    return match token.token_type {
+        TokenType::Symbol(Symbol::OpenCurlyBracket) => Some((0, 1)),
+        TokenType::Symbol(Symbol::ClosedCurlyBracket) => Some((0, -1)),
+        TokenType::Symbol(Symbol::OpenParen) => Some((1, 1)),
+        TokenType::Symbol(Symbol::ClosedParen) => Some((1, -1)),
+        TokenType::Symbol(Symbol::OpenSquareBracket) => Some((2, 1)),
+        TokenType::Symbol(Symbol::ClosedSquareBracket) => Some((2, -1)),
+        _ => None,
+    };
+}
+
+/// Widens `start..end` to the smallest range of `tokens` around it that closes every bracket
+/// opened in it and opens every one closed in it, since spans can leave out brackets around the
+/// last value in a node, like the `)` of `x = (a)`.
+fn widen_to_balanced(
+    tokens: &[StoredToken],
+    mut start: usize,
+    mut end: usize,
+) -> Option<(usize, usize)> {
+    let mut depths = [0; 3];
+    let mut lowest = [0; 3];
+
+    for (kind, change) in tokens[start..end].iter().filter_map(bracket) {
+        depths[kind] += change;
+        lowest[kind] = lowest[kind].min(depths[kind]);
+    }
+
+    // The brackets closed in the range but opened before it, and the ones opened but not closed.
+    let mut unopened = lowest.map(|depth| -depth);
+    let mut unclosed = [0, 1, 2].map(|kind| depths[kind] - lowest[kind]);
+
+    while unopened != [0; 3] {
+        start = start.checked_sub(1)?;
+
+        if let Some((kind, change)) = bracket(&tokens[start]) {
+            if change < 0 {
+                unopened[kind] += 1;
+            } else if unopened[kind] > 0 {
+                unopened[kind] -= 1;
+            } else {
+                unclosed[kind] += 1;
+            }
+        }
+    }
+
+    while unclosed != [0; 3] {
+        if let Some((kind, change)) = bracket(tokens.get(end)?) {
+            if change > 0 {
+                unclosed[kind] += 1;
+            } else if unclosed[kind] > 0 {
+                unclosed[kind] -= 1;
+            } else {
+                return None;
+            }
+        }
+
+        end += 1;
+    }
+
+    return Some((start, end));
+}
+
+/// Whether every bracket in the tokens is closed within them. An unclosed bracket could be closed
+/// by one outside the re-parsed nodes, so then the whole file has to be parsed again. Each kind of
+/// bracket is matched by its own pass, so they are counted separately.
+fn balanced<'a>(tokens: impl IntoIterator<Item = &'a StoredToken>) -> bool {
+    let mut depths = [0; 3];
+
+    for (kind, change) in tokens.into_iter().filter_map(bracket) {
+        depths[kind] += change;
+
+        if depths[kind] < 0 {
+            return false;
+        }
+    }
+
+    return depths == [0; 3];
+}
+
+fn shift(span: &mut Span, delta: i64) {
+    span.lo = (span.lo as i64 + delta) as u32;
+    span.hi = (span.hi as i64 + delta) as u32;
+}
+
+/// Moves a node and everything in it by `delta` bytes, for nodes after an edit.
+fn shift_node(node: &mut Node, delta: i64) {
+    visit_spans(node, &mut |span| shift(span, delta));
+}
+
+fn shift_nodes(nodes: &mut [Node], delta: i64) {
+    for node in nodes {
+        shift_node(node, delta);
+    }
+}
+
+/// Moves the spans in a node for an edit that replaced `lo..hi` and moved what came after by
+/// `delta`. Spans after the edit move, and spans around it grow or shrink. Spans that overlap only
+/// part of the edit are wrong afterwards, so those nodes have to be replaced.
+fn stretch_node(node: &mut Node, lo: usize, hi: usize, delta: i64) {
+    visit_spans(node, &mut |span| {
+        if span.hi as usize <= lo {
+            return;
+        }
+
+        match span.lo as usize >= hi {
+            true => shift(span, delta),
+            false => span.hi = (span.hi as i64 + delta) as u32,
+        }
+    });
+}
+
+fn visit_type(node: &mut TypeNode, visit: &mut impl FnMut(&mut Span)) {
+    visit(&mut node.span);
+
+    if let Some(size) = &mut node.array_size {
+        visit_spans(size, visit);
+    }
+}
+
+fn visit_index(node: &mut IndexNode, visit: &mut impl FnMut(&mut Span)) {
+    visit(&mut node.span);
+    visit(&mut node.name.span);
+    visit_spans(&mut node.index, visit);
+}
+
+fn visit_nodes(nodes: &mut [Node], visit: &mut impl FnMut(&mut Span)) {
+    for node in nodes {
+        visit_spans(node, visit);
+    }
+}
+
+/// Calls `visit` with the span of a node and of everything in it.
+fn visit_spans(node: &mut Node, visit: &mut impl FnMut(&mut Span)) {
+    match node {
+        Node::String => {}
+        Node::Keyword(node) => visit(&mut node.span),
+        Node::Type(node) => visit_type(node, visit),
+        Node::Operator(node) => visit(&mut node.span),
+        Node::Symbol(node) => visit(&mut node.span),
+        Node::Number(node) => visit(&mut node.span),
+        Node::Boolean(node) => visit(&mut node.span),
+        Node::Name(node) => visit(&mut node.span),
+        Node::Block(node) => {
+            visit(&mut node.span);
+            visit_nodes(&mut node.content, visit);
+        }
+        Node::Assignment(node) => {
+            visit(&mut node.span);
+            visit(&mut node.name.span);
+            visit_spans(&mut node.value, visit);
+        }
+        Node::VariableDefinition(node) => {
+            visit(&mut node.span);
+            visit_type(&mut node.node_type, visit);
+            visit(&mut node.assignment.span);
+            visit(&mut node.assignment.name.span);
+            visit_spans(&mut node.assignment.value, visit);
+        }
+        Node::FunctionDefinition(node) => {
+            visit(&mut node.span);
+            visit_type(&mut node.node_type, visit);
+            visit(&mut node.name.span);
+
+            for parameter in &mut node.parameters {
+                visit(&mut parameter.span);
+                visit_type(&mut parameter.node_type, visit);
+                visit(&mut parameter.name.span);
+            }
+
+            visit(&mut node.block.span);
+            visit_nodes(&mut node.block.content, visit);
+        }
+        Node::ExternFunction(node) => {
+            visit(&mut node.span);
+            visit_type(&mut node.node_type, visit);
+            visit(&mut node.name.span);
+
+            for parameter in &mut node.parameters {
+                visit(&mut parameter.span);
+                visit_type(&mut parameter.node_type, visit);
+                visit(&mut parameter.name.span);
+            }
+        }
+        Node::Operation(node) => {
+            visit(&mut node.span);
+            visit(&mut node.operator.span);
+            visit_nodes(&mut node.values, visit);
+        }
+        Node::Group(node) => {
+            visit(&mut node.span);
+            visit_nodes(&mut node.content, visit);
+        }
+        Node::Array(node) => {
+            visit(&mut node.span);
+            visit_nodes(&mut node.values, visit);
+        }
+        Node::Comptime(node) => {
+            visit(&mut node.span);
+            visit_spans(&mut node.value, visit);
+        }
+        Node::Call(node) => {
+            visit(&mut node.span);
+            visit(&mut node.name.span);
+            visit_nodes(&mut node.arguments, visit);
+        }
+        Node::If(node) => {
+            visit(&mut node.span);
+            visit_spans(&mut node.condition, visit);
+            visit(&mut node.block.span);
+            visit_nodes(&mut node.block.content, visit);
+
+            if let Some(else_node) = &mut node.else_node {
+                visit_spans(else_node, visit);
+            }
+        }
+        Node::Forever(node) => {
+            visit(&mut node.span);
+            visit(&mut node.block.span);
+            visit_nodes(&mut node.block.content, visit);
+        }
+        Node::Return(node) => {
+            visit(&mut node.span);
+
+            if let Some(value) = &mut node.value {
+                visit_spans(value, visit);
+            }
+        }
+        Node::Import(node) => {
+            visit(&mut node.span);
+            visit(&mut node.module.span);
+
+            for name in &mut node.names {
+                visit(&mut name.span);
+            }
+        }
+        Node::Index(node) => visit_index(node, visit),
+        Node::IndexAssignment(node) => {
+            visit(&mut node.span);
+            visit_index(&mut node.target, visit);
+            visit_spans(&mut node.value, visit);
+        }
+        Node::Defer(node) => {
+            visit(&mut node.span);
+            visit_spans(&mut node.statement, visit);
+        }
+        Node::Error(node) => visit(&mut node.span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "import { sqrt } from Math
+
+i32 twice: i32 n {
+    return n * 2
+}
+
+void main {
+    i32 a = (1 + 2) * 3
+    forever {
+        if a > 2 {
+            a = twice(a)
+            { i32 b = a }
+        } else {
+            defer print(a)
+        }
+        break
+    }
+    println(sqrt(4))
+}
+";
+
+    /// Edits a tree and checks it against a full parse of the text it ends up with.
+    fn assert_edit(tree: &mut IncrementalTree, lo: usize, hi: usize, text: &str) {
+        let file = FileId(0);
+
+        tree.edit(&Edit {
+            span: Span::new(file, lo, hi),
+            text: String::from(text),
+        });
+
+        let expected = syntax_tree::build_syntax_tree(Lexer::new(tree.text(), file));
+
+        assert!(
+            tree.nodes() == expected,
+            "{:?} at {}..{} gave a different tree for:\n{}",
+            text,
+            lo,
+            hi,
+            tree.text()
+        );
+    }
+
+    #[test]
+    fn inserts_match_full_parse() {
+        let mut tree = IncrementalTree::new(String::from(CODE), FileId(0));
+
+        for text in [
+            "x", "1 + ", "(", ")", "{", "}", "//", "\n", "if a {", "\"", "\n}\n",
+        ] {
+            for position in 0..=CODE.len() {
+                assert_edit(&mut tree, position, position, text);
+                assert_edit(&mut tree, position, position + text.len(), "");
+            }
+        }
+    }
+
+    #[test]
+    fn deletes_match_full_parse() {
+        let mut tree = IncrementalTree::new(String::from(CODE), FileId(0));
+
+        for length in [1, 2, 5, 13] {
+            for position in 0..=CODE.len() - length {
+                let removed = &CODE[position..position + length];
+
+                assert_edit(&mut tree, position, position + length, "");
+                assert_edit(&mut tree, position, position, removed);
+            }
+        }
+    }
+}
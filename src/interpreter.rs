@@ -0,0 +1,876 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::diagnostic::Diagnostic;
+use crate::heap::Heap;
+use crate::source_map::Span;
+use crate::syntax_tree::{
+    CallNode, FunctionDefinitionNode, IndexNode, Keyword, Node, OperationNode, Operator, Type,
+    VariableDefinitionNode,
+};
+use crate::type_checker::CheckedType;
+
+const MAX_CALL_DEPTH: usize = 512;
+
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    I32(i32),
+    U32(u32),
+    F32(f32),
+    Boolean(bool),
+    Array(Vec<Value>),
+    /// A pointer to an allocation on the heap, along with the type of its values.
+    Pointer(Type, usize),
+    Void,
+}
+
+impl Value {
+    pub fn value_type(&self) -> Option<Type> {
+        match self {
+            Value::I32(_) => Some(Type::I32),
+            Value::U32(_) => Some(Type::U32),
+            Value::F32(_) => Some(Type::F32),
+            Value::Boolean(_) => Some(Type::Boolean),
+            Value::Array(_) | Value::Pointer(_, _) => None,
+            Value::Void => Some(Type::Void),
+        }
+    }
+}
+
+/// Formats a float the way compiled programs print it: C's `%g` with the fewest significant digits
+/// that read back as the same float, and `nan` for every NaN.
+pub fn f32_text(value: f32) -> String {
+    if value.is_nan() {
+        return String::from("nan");
+    }
+
+    if value.is_infinite() {
+        return String::from(if value < 0.0 { "-inf" } else { "inf" });
+    }
+
+    for precision in 1..9 {
+        let text = general_text(value, precision);
+
+        if text.parse::<f32>() == Ok(value) {
+            return text;
+        }
+    }
+
+    // Nine significant digits are enough for any float.
+    return general_text(value, 9);
+}
+
+/// `%.{precision}g`: scientific notation for exponents below -4 or from the precision up, and
+/// fixed notation otherwise, either way without trailing zeros.
+fn general_text(value: f32, precision: usize) -> String {
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    if exponent < -4 || exponent >= precision as i32 {
+        let sign = if exponent < 0 { '-' } else { '+' };
+
+        return format!(
+            "{}e{}{:02}",
+            without_trailing_zeros(mantissa),
+            sign,
+            exponent.abs()
+        );
+    }
+
+    let fixed = format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value);
+
+    return String::from(without_trailing_zeros(&fixed));
+}
+
+fn without_trailing_zeros(number: &str) -> &str {
+    if !number.contains('.') {
+        return number;
+    }
+
+    return number.trim_end_matches('0').trim_end_matches('.');
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::I32(value) => write!(f, "{}", value),
+            Value::U32(value) => write!(f, "{}", value),
+            Value::F32(value) => write!(f, "{}", f32_text(*value)),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Array(values) => {
+                write!(f, "[")?;
+
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", value)?;
+                }
+
+                write!(f, "]")
+            }
+            Value::Pointer(_, address) => write!(f, "pointer {}", address),
+            Value::Void => write!(f, "void"),
+        }
+    }
+}
+
+pub enum Flow {
+    Normal,
+    Break,
+    Return(Value),
+}
+
+fn error(message: String, node: &Node) -> Diagnostic {
+    return Diagnostic::error(message, node.get_span());
+}
+
+fn is_literal(node: &Node) -> bool {
+    matches!(node, Node::Number(_))
+}
+
+fn check_type(value: &Value, expected: &CheckedType, node: &Node) -> Result<(), Diagnostic> {
+    let matches = match (value, expected) {
+        (Value::Pointer(element_type, _), CheckedType::Pointer(expected)) => {
+            element_type == expected
+        }
+        (Value::Array(values), CheckedType::Array(expected, size)) => {
+            values.len() == *size
+                && values
+                    .iter()
+                    .all(|value| value.value_type() == Some(*expected))
+        }
+        (_, CheckedType::Scalar(expected)) => value.value_type() == Some(*expected),
+        _ => false,
+    };
+
+    if matches {
+        return Ok(());
+    }
+
+    return Err(error(
+        format!("Expected a value of type {} but got {}", expected, value),
+        node,
+    ));
+}
+
+pub struct Interpreter {
+    functions: HashMap<String, Rc<FunctionDefinitionNode>>,
+    /// Functions declared with `extern "C"`, which have no body to interpret.
+    externs: HashSet<String>,
+    scopes: Vec<HashMap<String, Value>>,
+    return_types: Vec<CheckedType>,
+    heap: Heap,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        return Interpreter {
+            functions: HashMap::new(),
+            externs: HashSet::new(),
+            scopes: vec![HashMap::new()],
+            return_types: Vec::new(),
+            heap: Heap::new(),
+        };
+    }
+
+    /// Registers the functions of a program and evaluates its global variables in order.
+    pub fn load(&mut self, nodes: Vec<Node>) -> Result<(), Diagnostic> {
+        let mut globals: Vec<Node> = Vec::new();
+
+        for node in nodes {
+            match node {
+                Node::FunctionDefinition(function_node) => {
+                    self.externs.remove(&function_node.name.value);
+                    self.functions
+                        .insert(function_node.name.value.clone(), Rc::new(function_node));
+                }
+                Node::ExternFunction(function_node) => {
+                    self.functions.remove(&function_node.name.value);
+                    self.externs.insert(function_node.name.value);
+                }
+                _ => globals.push(node),
+            }
+        }
+
+        for node in &globals {
+            if let Flow::Break | Flow::Return(_) = self.execute(node)? {
+                return Err(error(
+                    String::from("Control flow statement outside of a function"),
+                    node,
+                ));
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn run_main(&mut self) -> Result<Value, Diagnostic> {
+        let function = match self.functions.get("main") {
+            Some(function) => Rc::clone(function),
+            None => {
+                return Err(Diagnostic::resolved(
+                    String::from("No main function defined"),
+                    (0, 0),
+                    (0, 0),
+                ));
+            }
+        };
+
+        return self.call_function(&function, Vec::new(), function.name.span);
+    }
+
+    fn define(&mut self, name: &str, value: Value) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(String::from(name), value);
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut Value> {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(value) = scope.get_mut(name) {
+                return Some(value);
+            }
+        }
+
+        return None;
+    }
+
+    /// Runs the statements of a block in a new scope, then its deferred statements in reverse
+    /// however the block is left. An error stops everything, deferred statements included.
+    fn execute_block(&mut self, nodes: &[Node]) -> Result<Flow, Diagnostic> {
+        self.scopes.push(HashMap::new());
+
+        let mut flow = Flow::Normal;
+        let mut deferred: Vec<&Node> = Vec::new();
+
+        for node in nodes {
+            if let Node::Defer(defer_node) = node {
+                deferred.push(&defer_node.statement);
+
+                continue;
+            }
+
+            match self.execute(node) {
+                Ok(Flow::Normal) => {}
+                Ok(result) => {
+                    flow = result;
+
+                    break;
+                }
+                Err(diagnostic) => {
+                    self.scopes.pop();
+
+                    return Err(diagnostic);
+                }
+            }
+        }
+
+        for statement in deferred.into_iter().rev() {
+            let result = match self.execute(statement) {
+                Ok(Flow::Normal) => Ok(()),
+                Ok(_) => Err(error(
+                    String::from("Deferred code can't leave its block"),
+                    statement,
+                )),
+                Err(diagnostic) => Err(diagnostic),
+            };
+
+            if let Err(diagnostic) = result {
+                self.scopes.pop();
+
+                return Err(diagnostic);
+            }
+        }
+
+        self.scopes.pop();
+
+        return Ok(flow);
+    }
+
+    fn execute_variable_definition(
+        &mut self,
+        definition_node: &VariableDefinitionNode,
+    ) -> Result<(), Diagnostic> {
+        let node_type = &definition_node.node_type;
+        let value_node = &definition_node.assignment.value;
+
+        let value = self.evaluate_typed(value_node, Some(&node_type.node_type))?;
+
+        if let Some(array_size) = &node_type.array_size {
+            let size = match array_size.as_ref() {
+                Node::Number(number_node) => number_node.value as usize,
+                _ => return Err(error(String::from("Unknown array size"), array_size)),
+            };
+
+            match &value {
+                Value::Array(values) if values.len() == size => {
+                    for value in values {
+                        check_type(value, &CheckedType::Scalar(node_type.node_type), value_node)?;
+                    }
+                }
+                _ => {
+                    return Err(error(
+                        format!("Expected an array of {} values", size),
+                        value_node,
+                    ));
+                }
+            }
+        } else {
+            check_type(&value, &CheckedType::from_type_node(node_type), value_node)?;
+        }
+
+        self.define(&definition_node.assignment.name.value, value);
+
+        return Ok(());
+    }
+
+    /// Runs a single statement. Expressions used as statements are evaluated for their side
+    /// effects and their value is discarded.
+    pub fn execute(&mut self, node: &Node) -> Result<Flow, Diagnostic> {
+        match node {
+            Node::Block(block_node) => return self.execute_block(&block_node.content),
+            Node::VariableDefinition(definition_node) => {
+                self.execute_variable_definition(definition_node)?;
+            }
+            Node::Assignment(assignment_node) => {
+                let name = &assignment_node.name.value;
+
+                let expected = match self.lookup(name) {
+                    Some(Value::Pointer(element_type, _)) => {
+                        Some(CheckedType::Pointer(*element_type))
+                    }
+                    Some(value) => value.value_type().map(CheckedType::Scalar),
+                    None => {
+                        return Err(error(format!("Unknown variable {}", name), node));
+                    }
+                };
+
+                let element_type = expected.map(|expected| expected.element_type());
+                let value = self.evaluate_typed(&assignment_node.value, element_type.as_ref())?;
+
+                if let Some(expected) = &expected {
+                    check_type(&value, expected, &assignment_node.value)?;
+                }
+
+                *self.lookup(name).unwrap() = value;
+            }
+            Node::IndexAssignment(assignment_node) => {
+                let (pointer, index) = self.evaluate_index(&assignment_node.target)?;
+
+                let Value::Pointer(element_type, _) = pointer else {
+                    unreachable!()
+                };
+
+                let value = self.evaluate_typed(&assignment_node.value, Some(&element_type))?;
+
+                check_type(
+                    &value,
+                    &CheckedType::Scalar(element_type),
+                    &assignment_node.value,
+                )?;
+
+                self.heap
+                    .store(&pointer, &index, value)
+                    .map_err(|message| error(message, node))?;
+            }
+            Node::Defer(_) => {
+                return Err(error(
+                    String::from("defer can only be used inside a function"),
+                    node,
+                ));
+            }
+            Node::If(if_node) => {
+                match self.evaluate(&if_node.condition)? {
+                    Value::Boolean(true) => return self.execute_block(&if_node.block.content),
+                    Value::Boolean(false) => {
+                        if let Some(else_node) = &if_node.else_node {
+                            return self.execute(else_node);
+                        }
+                    }
+                    value => {
+                        return Err(error(
+                            format!("Expected a condition of type Boolean but got {}", value),
+                            &if_node.condition,
+                        ));
+                    }
+                };
+            }
+            Node::Forever(forever_node) => loop {
+                match self.execute_block(&forever_node.block.content)? {
+                    Flow::Normal => {}
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                }
+            },
+            Node::Return(return_node) => {
+                let return_type = self.return_types.last().copied();
+
+                let value = match &return_node.value {
+                    Some(value_node) => {
+                        let element_type =
+                            return_type.map(|return_type| return_type.element_type());
+                        let value = self.evaluate_typed(value_node, element_type.as_ref())?;
+
+                        if let Some(return_type) = &return_type {
+                            check_type(&value, return_type, value_node)?;
+                        }
+
+                        value
+                    }
+                    None if return_type.is_none_or(|return_type| {
+                        return_type == CheckedType::Scalar(Type::Void)
+                    }) =>
+                    {
+                        Value::Void
+                    }
+                    None => return Err(error(String::from("Expected a return value"), node)),
+                };
+
+                return Ok(Flow::Return(value));
+            }
+            Node::Keyword(keyword_node) if keyword_node.keyword == Keyword::Break => {
+                return Ok(Flow::Break);
+            }
+            Node::FunctionDefinition(function_node) => {
+                return Err(error(
+                    format!(
+                        "Function {} must be defined at the top level",
+                        function_node.name
+                    ),
+                    node,
+                ));
+            }
+            Node::ExternFunction(function_node) => {
+                return Err(error(
+                    format!(
+                        "Function {} must be defined at the top level",
+                        function_node.name
+                    ),
+                    node,
+                ));
+            }
+            _ => {
+                self.evaluate(node)?;
+            }
+        }
+
+        return Ok(Flow::Normal);
+    }
+
+    pub fn evaluate(&mut self, node: &Node) -> Result<Value, Diagnostic> {
+        return self.evaluate_typed(node, None);
+    }
+
+    /// Evaluates an expression. Integer literals take on the `expected` type when there is one,
+    /// and `i32` otherwise.
+    fn evaluate_typed(
+        &mut self,
+        node: &Node,
+        expected: Option<&Type>,
+    ) -> Result<Value, Diagnostic> {
+        match node {
+            Node::Number(number_node) => {
+                let value = number_node.value;
+
+                let result = match expected {
+                    Some(Type::U32) => u32::try_from(value).ok().map(Value::U32),
+                    Some(Type::F32) => Some(Value::F32(value as f32)),
+                    _ => i32::try_from(value).ok().map(Value::I32),
+                };
+
+                return result.ok_or_else(|| {
+                    error(format!("Integer literal {} is out of range", value), node)
+                });
+            }
+            Node::Boolean(boolean_node) => return Ok(Value::Boolean(boolean_node.value)),
+            Node::Name(name_node) => {
+                return match self.lookup(&name_node.value) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(error(format!("Unknown variable {}", name_node.value), node)),
+                };
+            }
+            Node::Array(array_node) => {
+                let mut values: Vec<Value> = Vec::new();
+
+                for value_node in &array_node.values {
+                    values.push(self.evaluate_typed(value_node, expected)?);
+                }
+
+                return Ok(Value::Array(values));
+            }
+            Node::Group(group_node) if group_node.content.len() == 1 => {
+                return self.evaluate_typed(&group_node.content[0], expected);
+            }
+            Node::Comptime(comptime_node) => {
+                return self.evaluate_typed(&comptime_node.value, expected);
+            }
+            Node::Operation(operation_node) => {
+                return self.evaluate_operation(operation_node, node, expected);
+            }
+            Node::Call(call_node) => return self.evaluate_call(call_node, node, expected),
+            Node::Index(index_node) => {
+                let (pointer, index) = self.evaluate_index(index_node)?;
+
+                return self
+                    .heap
+                    .load(&pointer, &index)
+                    .map_err(|message| error(message, node));
+            }
+            _ => {}
+        }
+
+        return Err(error(String::from("Expected an expression"), node));
+    }
+
+    /// Evaluates the pointer and the index of `pointer[index]`.
+    fn evaluate_index(&mut self, index_node: &IndexNode) -> Result<(Value, Value), Diagnostic> {
+        let name = &index_node.name;
+
+        let pointer = match self.lookup(&name.value) {
+            Some(pointer @ Value::Pointer(_, _)) => pointer.clone(),
+            Some(value) => {
+                return Err(Diagnostic::error(
+                    format!(
+                        "Only pointers can be indexed, but {} is {}",
+                        name.value, value
+                    ),
+                    name.span,
+                ));
+            }
+            None => {
+                return Err(Diagnostic::error(
+                    format!("Unknown variable {}", name.value),
+                    name.span,
+                ));
+            }
+        };
+
+        let index = self.evaluate(&index_node.index)?;
+
+        return Ok((pointer, index));
+    }
+
+    fn evaluate_operation(
+        &mut self,
+        operation_node: &OperationNode,
+        node: &Node,
+        expected: Option<&Type>,
+    ) -> Result<Value, Diagnostic> {
+        let operator = &operation_node.operator.operator;
+
+        let operand_expected = match operator {
+            Operator::Add
+            | Operator::Subtract
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Modulo
+            | Operator::BitwiseAnd
+            | Operator::BitwiseOr
+            | Operator::Negate => expected,
+            _ => None,
+        };
+
+        if operator.one_sided() {
+            let value = self.evaluate_typed(&operation_node.values[0], operand_expected)?;
+
+            return match (operator, value) {
+                (Operator::Not, Value::Boolean(value)) => Ok(Value::Boolean(!value)),
+                (Operator::Negate, Value::I32(value)) => Ok(Value::I32(value.wrapping_neg())),
+                (Operator::Negate, Value::F32(value)) => Ok(Value::F32(-value)),
+                (_, value) => Err(error(
+                    format!("Invalid operand {} for operator {}", value, operator),
+                    node,
+                )),
+            };
+        }
+
+        let value_a_node = &operation_node.values[0];
+        let value_b_node = &operation_node.values[1];
+
+        if let Operator::And | Operator::Or = operator {
+            let value_a = self.evaluate(value_a_node)?;
+
+            return match (operator, value_a) {
+                (Operator::And, Value::Boolean(false)) => Ok(Value::Boolean(false)),
+                (Operator::Or, Value::Boolean(true)) => Ok(Value::Boolean(true)),
+                (_, Value::Boolean(_)) => match self.evaluate(value_b_node)? {
+                    Value::Boolean(value_b) => Ok(Value::Boolean(value_b)),
+                    value_b => Err(error(
+                        format!("Invalid operand {} for operator {}", value_b, operator),
+                        node,
+                    )),
+                },
+                (_, value_a) => Err(error(
+                    format!("Invalid operand {} for operator {}", value_a, operator),
+                    node,
+                )),
+            };
+        }
+
+        // A literal operand takes the type of the other side, so `1 + a` works for any integer `a`.
+        let (value_a, value_b) = if is_literal(value_a_node) && !is_literal(value_b_node) {
+            let value_b = self.evaluate_typed(value_b_node, operand_expected)?;
+            let value_a = self.evaluate_typed(value_a_node, value_b.value_type().as_ref())?;
+
+            (value_a, value_b)
+        } else {
+            let value_a = self.evaluate_typed(value_a_node, operand_expected)?;
+            let value_b = self.evaluate_typed(value_b_node, value_a.value_type().as_ref())?;
+
+            (value_a, value_b)
+        };
+
+        if let Operator::Equal | Operator::NotEqual = operator {
+            if value_a.value_type() != value_b.value_type() {
+                return Err(error(
+                    format!("Cannot compare {} with {}", value_a, value_b),
+                    node,
+                ));
+            }
+
+            return Ok(Value::Boolean(
+                (value_a == value_b) == matches!(operator, Operator::Equal),
+            ));
+        }
+
+        let result = match (value_a, value_b) {
+            (Value::I32(a), Value::I32(b)) => match operator {
+                Operator::Add => Some(Value::I32(a.wrapping_add(b))),
+                Operator::Subtract => Some(Value::I32(a.wrapping_sub(b))),
+                Operator::Multiply => Some(Value::I32(a.wrapping_mul(b))),
+                Operator::Divide | Operator::Modulo if b == 0 => {
+                    return Err(error(String::from("Division by zero"), node));
+                }
+                Operator::Divide => Some(Value::I32(a.wrapping_div(b))),
+                Operator::Modulo => Some(Value::I32(a.wrapping_rem(b))),
+                Operator::BitwiseAnd => Some(Value::I32(a & b)),
+                Operator::BitwiseOr => Some(Value::I32(a | b)),
+                Operator::LessThan => Some(Value::Boolean(a < b)),
+                Operator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+                Operator::GreaterThan => Some(Value::Boolean(a > b)),
+                Operator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+                _ => None,
+            },
+            (Value::U32(a), Value::U32(b)) => match operator {
+                Operator::Add => Some(Value::U32(a.wrapping_add(b))),
+                Operator::Subtract => Some(Value::U32(a.wrapping_sub(b))),
+                Operator::Multiply => Some(Value::U32(a.wrapping_mul(b))),
+                Operator::Divide | Operator::Modulo if b == 0 => {
+                    return Err(error(String::from("Division by zero"), node));
+                }
+                Operator::Divide => Some(Value::U32(a / b)),
+                Operator::Modulo => Some(Value::U32(a % b)),
+                Operator::BitwiseAnd => Some(Value::U32(a & b)),
+                Operator::BitwiseOr => Some(Value::U32(a | b)),
+                Operator::LessThan => Some(Value::Boolean(a < b)),
+                Operator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+                Operator::GreaterThan => Some(Value::Boolean(a > b)),
+                Operator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+                _ => None,
+            },
+            (Value::F32(a), Value::F32(b)) => match operator {
+                Operator::Add => Some(Value::F32(a + b)),
+                Operator::Subtract => Some(Value::F32(a - b)),
+                Operator::Multiply => Some(Value::F32(a * b)),
+                Operator::Divide => Some(Value::F32(a / b)),
+                Operator::Modulo => Some(Value::F32(a % b)),
+                Operator::LessThan => Some(Value::Boolean(a < b)),
+                Operator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+                Operator::GreaterThan => Some(Value::Boolean(a > b)),
+                Operator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+                _ => None,
+            },
+            (Value::Boolean(a), Value::Boolean(b)) => match operator {
+                Operator::BitwiseAnd => Some(Value::Boolean(a & b)),
+                Operator::BitwiseOr => Some(Value::Boolean(a | b)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        return result
+            .ok_or_else(|| error(format!("Invalid operands for operator {}", operator), node));
+    }
+
+    fn evaluate_call(
+        &mut self,
+        call_node: &CallNode,
+        node: &Node,
+        expected: Option<&Type>,
+    ) -> Result<Value, Diagnostic> {
+        let name = &call_node.name.value;
+
+        if self.externs.contains(name) {
+            return Err(error(
+                format!(
+                    "{} is an extern \"C\" function, which only compiled programs can call",
+                    name
+                ),
+                node,
+            ));
+        }
+
+        let function = match self.functions.get(name) {
+            Some(function) => Rc::clone(function),
+            None if name == "alloc" || name == "resize" || name == "free" => {
+                return self.evaluate_memory_call(call_node, node, expected);
+            }
+            None if name == "print" || name == "println" => {
+                let mut values: Vec<String> = Vec::new();
+
+                for argument in &call_node.arguments {
+                    values.push(self.evaluate(argument)?.to_string());
+                }
+
+                if name == "println" {
+                    println!("{}", values.join(" "));
+                } else {
+                    print!("{}", values.join(" "));
+                }
+
+                return Ok(Value::Void);
+            }
+            None => return Err(error(format!("Unknown function {}", name), node)),
+        };
+
+        if function.parameters.len() != call_node.arguments.len() {
+            return Err(error(
+                format!(
+                    "Function {} expects {} arguments but got {}",
+                    name,
+                    function.parameters.len(),
+                    call_node.arguments.len()
+                ),
+                node,
+            ));
+        }
+
+        let mut arguments: Vec<Value> = Vec::new();
+
+        for (parameter, argument) in function.parameters.iter().zip(&call_node.arguments) {
+            let value = self.evaluate_typed(argument, Some(&parameter.node_type.node_type))?;
+
+            check_type(
+                &value,
+                &CheckedType::from_type_node(&parameter.node_type),
+                argument,
+            )?;
+
+            arguments.push(value);
+        }
+
+        return self.call_function(&function, arguments, node.get_span());
+    }
+
+    /// Calls `alloc`, `resize` or `free`. What `alloc` allocates comes from the pointer type
+    /// expected of it.
+    fn evaluate_memory_call(
+        &mut self,
+        call_node: &CallNode,
+        node: &Node,
+        expected: Option<&Type>,
+    ) -> Result<Value, Diagnostic> {
+        let name = call_node.name.value.as_str();
+
+        let parameter_count = match name {
+            "alloc" | "free" => 1,
+            _ => 2,
+        };
+
+        if call_node.arguments.len() != parameter_count {
+            return Err(error(
+                format!(
+                    "Function {} expects {} arguments but got {}",
+                    name,
+                    parameter_count,
+                    call_node.arguments.len()
+                ),
+                node,
+            ));
+        }
+
+        let result = match name {
+            "alloc" => {
+                let Some(element_type) = expected else {
+                    return Err(error(
+                        String::from("alloc can only be used where a pointer is expected"),
+                        node,
+                    ));
+                };
+
+                let count = self.evaluate(&call_node.arguments[0])?;
+
+                self.heap.alloc(*element_type, &count)
+            }
+            "resize" => {
+                let pointer = self.evaluate(&call_node.arguments[0])?;
+                let count = self.evaluate(&call_node.arguments[1])?;
+
+                self.heap.resize(&pointer, &count)
+            }
+            _ => {
+                let pointer = self.evaluate(&call_node.arguments[0])?;
+
+                self.heap.free(&pointer).map(|_| Value::Void)
+            }
+        };
+
+        return result.map_err(|message| error(message, node));
+    }
+
+    fn call_function(
+        &mut self,
+        function: &FunctionDefinitionNode,
+        arguments: Vec<Value>,
+        span: Span,
+    ) -> Result<Value, Diagnostic> {
+        if self.return_types.len() >= MAX_CALL_DEPTH {
+            return Err(Diagnostic::error(String::from("Stack overflow"), span));
+        }
+
+        // Functions only see globals and their own locals, so the caller's scopes are set aside.
+        let caller_scopes = self.scopes.split_off(1);
+
+        let mut parameter_scope: HashMap<String, Value> = HashMap::new();
+
+        for (parameter, value) in function.parameters.iter().zip(arguments) {
+            parameter_scope.insert(parameter.name.value.clone(), value);
+        }
+
+        self.scopes.push(parameter_scope);
+        self.return_types
+            .push(CheckedType::from_type_node(&function.node_type));
+
+        let flow = self.execute_block(&function.block.content);
+
+        self.return_types.pop();
+        self.scopes.truncate(1);
+        self.scopes.extend(caller_scopes);
+
+        return match flow? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal
+                if CheckedType::from_type_node(&function.node_type)
+                    == CheckedType::Scalar(Type::Void) =>
+            {
+                Ok(Value::Void)
+            }
+            Flow::Normal => Err(Diagnostic::error(
+                format!(
+                    "Function {} ended without returning a value",
+                    function.name.value
+                ),
+                span,
+            )),
+            Flow::Break => Err(Diagnostic::error(
+                String::from("Break outside of a loop"),
+                span,
+            )),
+        };
+    }
+}
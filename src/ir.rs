@@ -0,0 +1,650 @@
+use std::fmt;
+
+use crate::source_map::Span;
+use crate::syntax_tree::Type;
+
+/// Functions that every backend provides, so they can be called without being defined.
+pub const INTRINSICS: &[&str] = &["print", "println"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IrType {
+    I32,
+    U32,
+    F32,
+    Bool,
+    Void,
+    /// An address of heap memory, from `alloc` or from C. What it points to is up to the
+    /// instructions that use it.
+    Pointer,
+}
+
+impl IrType {
+    pub fn from_type(node_type: Type) -> Option<IrType> {
+        match node_type {
+            Type::I32 => Some(IrType::I32),
+            Type::U32 => Some(IrType::U32),
+            Type::F32 => Some(IrType::F32),
+            Type::Boolean => Some(IrType::Bool),
+            Type::Void => Some(IrType::Void),
+            Type::String => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, IrType::I32 | IrType::U32)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, IrType::I32 | IrType::F32)
+    }
+
+    /// The bytes a value takes up on the heap.
+    pub fn size(&self) -> u32 {
+        match self {
+            IrType::I32 | IrType::U32 | IrType::F32 => 4,
+            IrType::Bool => 1,
+            IrType::Void => 0,
+            IrType::Pointer => 8,
+        }
+    }
+}
+
+impl fmt::Display for IrType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrType::I32 => write!(f, "i32"),
+            IrType::U32 => write!(f, "u32"),
+            IrType::F32 => write!(f, "f32"),
+            IrType::Bool => write!(f, "bool"),
+            IrType::Void => write!(f, "void"),
+            IrType::Pointer => write!(f, "ptr"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    Float(f32),
+    Boolean(bool),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::Float(value) => write!(f, "{:?}", value),
+            Constant::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+pub const BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator::Add,
+    BinaryOperator::Subtract,
+    BinaryOperator::Multiply,
+    BinaryOperator::Divide,
+    BinaryOperator::Remainder,
+    BinaryOperator::And,
+    BinaryOperator::Or,
+    BinaryOperator::Equal,
+    BinaryOperator::NotEqual,
+    BinaryOperator::LessThan,
+    BinaryOperator::LessThanOrEqual,
+    BinaryOperator::GreaterThan,
+    BinaryOperator::GreaterThanOrEqual,
+];
+
+impl BinaryOperator {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEqual
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEqual
+        )
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryOperator::Add => write!(f, "add"),
+            BinaryOperator::Subtract => write!(f, "sub"),
+            BinaryOperator::Multiply => write!(f, "mul"),
+            BinaryOperator::Divide => write!(f, "div"),
+            BinaryOperator::Remainder => write!(f, "rem"),
+            BinaryOperator::And => write!(f, "and"),
+            BinaryOperator::Or => write!(f, "or"),
+            BinaryOperator::Equal => write!(f, "eq"),
+            BinaryOperator::NotEqual => write!(f, "ne"),
+            BinaryOperator::LessThan => write!(f, "lt"),
+            BinaryOperator::LessThanOrEqual => write!(f, "le"),
+            BinaryOperator::GreaterThan => write!(f, "gt"),
+            BinaryOperator::GreaterThanOrEqual => write!(f, "ge"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOperator::Negate => write!(f, "neg"),
+            UnaryOperator::Not => write!(f, "not"),
+        }
+    }
+}
+
+pub enum InstructionKind {
+    Constant(Constant),
+    Binary(BinaryOperator, ValueId, ValueId),
+    Unary(UnaryOperator, ValueId),
+    Call(String, Vec<ValueId>),
+    LoadGlobal(String),
+    StoreGlobal(String, ValueId),
+    /// Allocates a count of zeroed values of a type on the heap.
+    Alloc(IrType, ValueId),
+    /// Moves an allocation to one of a new count of values, zeroing any past the old end.
+    Resize(IrType, ValueId, ValueId),
+    Free(ValueId),
+    /// Reads the value at an index of a pointer, whose type is the result's.
+    Load(ValueId, ValueId),
+    /// Writes a value at an index of a pointer.
+    Store(ValueId, ValueId, ValueId),
+}
+
+pub struct Instruction {
+    pub result: Option<ValueId>,
+    pub kind: InstructionKind,
+    pub span: Span,
+}
+
+impl Instruction {
+    pub fn operands(&self) -> Vec<ValueId> {
+        match &self.kind {
+            InstructionKind::Constant(_) | InstructionKind::LoadGlobal(_) => Vec::new(),
+            InstructionKind::Binary(_, value_a, value_b) => vec![*value_a, *value_b],
+            InstructionKind::Unary(_, value) => vec![*value],
+            InstructionKind::Call(_, arguments) => arguments.clone(),
+            InstructionKind::StoreGlobal(_, value)
+            | InstructionKind::Alloc(_, value)
+            | InstructionKind::Free(value) => vec![*value],
+            InstructionKind::Resize(_, pointer, count) => vec![*pointer, *count],
+            InstructionKind::Load(pointer, index) => vec![*pointer, *index],
+            InstructionKind::Store(pointer, index, value) => vec![*pointer, *index, *value],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match &mut self.kind {
+            InstructionKind::Constant(_) | InstructionKind::LoadGlobal(_) => Vec::new(),
+            InstructionKind::Binary(_, value_a, value_b) => vec![value_a, value_b],
+            InstructionKind::Unary(_, value) => vec![value],
+            InstructionKind::Call(_, arguments) => arguments.iter_mut().collect(),
+            InstructionKind::StoreGlobal(_, value)
+            | InstructionKind::Alloc(_, value)
+            | InstructionKind::Free(value) => vec![value],
+            InstructionKind::Resize(_, pointer, count) => vec![pointer, count],
+            InstructionKind::Load(pointer, index) => vec![pointer, index],
+            InstructionKind::Store(pointer, index, value) => vec![pointer, index, value],
+        }
+    }
+}
+
+pub struct BlockCall {
+    pub block: BlockId,
+    pub arguments: Vec<ValueId>,
+}
+
+impl fmt::Display for BlockCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.block)?;
+
+        if !self.arguments.is_empty() {
+            write!(f, "({})", join(&self.arguments))?;
+        }
+
+        return Ok(());
+    }
+}
+
+pub enum Terminator {
+    Jump(BlockCall),
+    Branch(ValueId, BlockCall, BlockCall),
+    Return(Option<ValueId>),
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<&BlockCall> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch(_, then_target, else_target) => vec![then_target, else_target],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockCall> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch(_, then_target, else_target) => vec![then_target, else_target],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// Every value the terminator reads, including the arguments passed to successor blocks.
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Terminator::Jump(target) => target.arguments.clone(),
+            Terminator::Branch(condition, then_target, else_target) => {
+                let mut operands = vec![*condition];
+
+                operands.extend(&then_target.arguments);
+                operands.extend(&else_target.arguments);
+
+                operands
+            }
+            Terminator::Return(Some(value)) => vec![*value],
+            Terminator::Return(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Terminator::Jump(target) => target.arguments.iter_mut().collect(),
+            Terminator::Branch(condition, then_target, else_target) => {
+                let mut operands = vec![condition];
+
+                operands.extend(then_target.arguments.iter_mut());
+                operands.extend(else_target.arguments.iter_mut());
+
+                operands
+            }
+            Terminator::Return(Some(value)) => vec![value],
+            Terminator::Return(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch(condition, then_target, else_target) => {
+                write!(f, "branch {}, {}, {}", condition, then_target, else_target)
+            }
+            Terminator::Return(Some(value)) => write!(f, "return {}", value),
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+pub struct Block {
+    pub parameters: Vec<ValueId>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<IrType>,
+    pub return_type: IrType,
+    pub blocks: Vec<Block>,
+    pub value_types: Vec<IrType>,
+    /// Whether C code can call the function by its own name.
+    pub external: bool,
+    pub span: Span,
+}
+
+impl Function {
+    pub fn value_type(&self, value: ValueId) -> IrType {
+        return self.value_types[value.0];
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors: Vec<Vec<BlockId>> = vec![Vec::new(); self.blocks.len()];
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            for target in block.terminator.successors() {
+                if !predecessors[target.block.0].contains(&BlockId(index)) {
+                    predecessors[target.block.0].push(BlockId(index));
+                }
+            }
+        }
+
+        return predecessors;
+    }
+
+    /// Lists the blocks reachable from the entry in reverse postorder, so every block comes
+    /// before the blocks it jumps to except along loop back edges.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut postorder: Vec<BlockId> = Vec::new();
+        let mut stack: Vec<(BlockId, usize)> = vec![(BlockId(0), 0)];
+
+        visited[0] = true;
+
+        while let Some((block, successor_index)) = stack.pop() {
+            let successors = self.blocks[block.0].terminator.successors();
+
+            if successor_index == successors.len() {
+                postorder.push(block);
+
+                continue;
+            }
+
+            stack.push((block, successor_index + 1));
+
+            let successor = successors[successor_index].block;
+
+            if !visited[successor.0] {
+                visited[successor.0] = true;
+                stack.push((successor, 0));
+            }
+        }
+
+        postorder.reverse();
+
+        return postorder;
+    }
+
+    /// Finds the immediate dominator of every reachable block with Cooper, Harvey and Kennedy's
+    /// iterative algorithm. The entry block is its own immediate dominator.
+    pub fn immediate_dominators(&self) -> Vec<Option<BlockId>> {
+        let order = self.reverse_postorder();
+        let predecessors = self.predecessors();
+
+        let mut order_numbers = vec![usize::MAX; self.blocks.len()];
+
+        for (index, block) in order.iter().enumerate() {
+            order_numbers[block.0] = index;
+        }
+
+        let mut dominators: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+
+        dominators[0] = Some(BlockId(0));
+
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for block in order.iter().skip(1) {
+                let mut new_dominator: Option<BlockId> = None;
+
+                for predecessor in &predecessors[block.0] {
+                    if dominators[predecessor.0].is_none() {
+                        continue;
+                    }
+
+                    new_dominator = match new_dominator {
+                        None => Some(*predecessor),
+                        Some(mut other) => {
+                            let mut finger = *predecessor;
+
+                            while finger != other {
+                                while order_numbers[finger.0] > order_numbers[other.0] {
+                                    finger = dominators[finger.0].unwrap();
+                                }
+
+                                while order_numbers[other.0] > order_numbers[finger.0] {
+                                    other = dominators[other.0].unwrap();
+                                }
+                            }
+
+                            Some(finger)
+                        }
+                    };
+                }
+
+                if dominators[block.0] != new_dominator {
+                    dominators[block.0] = new_dominator;
+                    changed = true;
+                }
+            }
+        }
+
+        return dominators;
+    }
+}
+
+pub struct Global {
+    pub name: String,
+    pub global_type: IrType,
+    pub value: Constant,
+}
+
+/// A C function the module calls, which is linked in from outside.
+pub struct Extern {
+    pub name: String,
+    pub parameters: Vec<IrType>,
+    pub return_type: IrType,
+    pub span: Span,
+}
+
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub externs: Vec<Extern>,
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    /// Finds the parameter and return types of a function or an extern.
+    pub fn signature(&self, name: &str) -> Option<(&[IrType], IrType)> {
+        if let Some(function) = self.functions.iter().find(|function| function.name == name) {
+            return Some((&function.parameters, function.return_type));
+        }
+
+        return self
+            .externs
+            .iter()
+            .find(|extern_function| extern_function.name == name)
+            .map(|extern_function| {
+                (
+                    extern_function.parameters.as_slice(),
+                    extern_function.return_type,
+                )
+            });
+    }
+
+    pub fn is_extern(&self, name: &str) -> bool {
+        return self
+            .externs
+            .iter()
+            .any(|extern_function| extern_function.name == name);
+    }
+
+    /// Whether `name` links by its C name, as an extern or as a function exported to C.
+    pub fn is_c_symbol(&self, name: &str) -> bool {
+        return self.is_extern(name)
+            || self
+                .functions
+                .iter()
+                .any(|function| function.name == name && function.external);
+    }
+}
+
+fn display_signature(name: &str, parameters: &[IrType], return_type: IrType) -> String {
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|parameter| parameter.to_string())
+        .collect();
+
+    return format!(
+        "function @{}({}) -> {}",
+        name,
+        parameters.join(", "),
+        return_type
+    );
+}
+
+fn join(values: &[ValueId]) -> String {
+    return values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+}
+
+impl Function {
+    pub fn display_instruction(&self, instruction: &Instruction) -> String {
+        let mut text = String::new();
+
+        if let Some(result) = instruction.result {
+            text += &format!("{}: {} = ", result, self.value_type(result));
+        }
+
+        text += &match &instruction.kind {
+            InstructionKind::Constant(constant) => format!("const {}", constant),
+            InstructionKind::Binary(operator, value_a, value_b) => {
+                format!("{} {}, {}", operator, value_a, value_b)
+            }
+            InstructionKind::Unary(operator, value) => format!("{} {}", operator, value),
+            InstructionKind::Call(name, arguments) => {
+                format!("call @{}({})", name, join(arguments))
+            }
+            InstructionKind::LoadGlobal(name) => format!("load @{}", name),
+            InstructionKind::StoreGlobal(name, value) => format!("store @{}, {}", name, value),
+            InstructionKind::Alloc(element_type, count) => {
+                format!("alloc {}, {}", element_type, count)
+            }
+            InstructionKind::Resize(element_type, pointer, count) => {
+                format!("resize {}, {}, {}", element_type, pointer, count)
+            }
+            InstructionKind::Free(pointer) => format!("free {}", pointer),
+            InstructionKind::Load(pointer, index) => format!("load {}[{}]", pointer, index),
+            InstructionKind::Store(pointer, index, value) => {
+                format!("store {}[{}], {}", pointer, index, value)
+            }
+        };
+
+        return text;
+    }
+
+    /// The block header as printed in the textual IR, like `b1(v2: i32)`.
+    pub fn display_block_header(&self, block: BlockId) -> String {
+        let parameters = &self.blocks[block.0].parameters;
+
+        if parameters.is_empty() {
+            return block.to_string();
+        }
+
+        let parameters: Vec<String> = parameters
+            .iter()
+            .map(|parameter| format!("{}: {}", parameter, self.value_type(*parameter)))
+            .collect();
+
+        return format!("{}({})", block, parameters.join(", "));
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.external {
+            write!(f, "extern ")?;
+        }
+
+        writeln!(
+            f,
+            "{} {{",
+            display_signature(&self.name, &self.parameters, self.return_type)
+        )?;
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", self.display_block_header(BlockId(index)))?;
+
+            for instruction in &block.instructions {
+                write!(f, "    ")?;
+
+                writeln!(f, "{}", self.display_instruction(instruction))?;
+            }
+
+            writeln!(f, "    {}", block.terminator)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(
+                f,
+                "global @{}: {} = {}",
+                global.name, global.global_type, global.value
+            )?;
+        }
+
+        if !self.externs.is_empty() && !self.globals.is_empty() {
+            writeln!(f)?;
+        }
+
+        for extern_function in &self.externs {
+            writeln!(
+                f,
+                "extern {}",
+                display_signature(
+                    &extern_function.name,
+                    &extern_function.parameters,
+                    extern_function.return_type
+                )
+            )?;
+        }
+
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 || !self.globals.is_empty() || !self.externs.is_empty() {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "{}", function)?;
+        }
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,1136 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::ir::{
+    BinaryOperator, Block, BlockCall, BlockId, Constant, Extern, Function, Global, INTRINSICS,
+    Instruction, InstructionKind, IrType, Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::syntax_tree::{
+    CallNode, FunctionDefinitionNode, IndexNode, Keyword, Node, OperationNode, Operator,
+    ParameterNode, TypeNode,
+};
+
+fn error(message: String, node: &Node) -> Diagnostic {
+    return Diagnostic::error(message, node.get_span());
+}
+
+fn lower_type(type_node: &TypeNode) -> Result<IrType, Diagnostic> {
+    if type_node.array_size.is_some() {
+        return Err(Diagnostic::error(
+            String::from("Arrays are not supported by the IR yet"),
+            type_node.span,
+        ));
+    }
+
+    // What a pointer points to is tracked by the lowering, since the instructions that use it
+    // say what they read or write.
+    if type_node.pointer {
+        return Ok(IrType::Pointer);
+    }
+
+    return IrType::from_type(type_node.node_type).ok_or_else(|| {
+        Diagnostic::error(
+            format!(
+                "The type {} is not supported by the IR yet",
+                type_node.node_type
+            ),
+            type_node.span,
+        )
+    });
+}
+
+/// What values of a pointer type point to, or `None` for other types.
+fn lower_element_type(type_node: &TypeNode) -> Option<IrType> {
+    if !type_node.pointer {
+        return None;
+    }
+
+    return IrType::from_type(type_node.node_type);
+}
+
+struct Signature {
+    parameters: Vec<IrType>,
+    return_type: IrType,
+    parameter_elements: Vec<Option<IrType>>,
+    return_element: Option<IrType>,
+}
+
+impl Signature {
+    fn new(parameters: &[ParameterNode], return_type: &TypeNode) -> Result<Signature, Diagnostic> {
+        let mut signature = Signature {
+            parameters: Vec::new(),
+            return_type: lower_type(return_type)?,
+            parameter_elements: Vec::new(),
+            return_element: lower_element_type(return_type),
+        };
+
+        for parameter in parameters {
+            signature.parameters.push(lower_type(&parameter.node_type)?);
+            signature
+                .parameter_elements
+                .push(lower_element_type(&parameter.node_type));
+        }
+
+        return Ok(signature);
+    }
+}
+
+/// Builds the SSA form of a single function. Variables are tracked per block and turned into block
+/// parameters where control flow joins, following Braun et al.'s "Simple and Efficient
+/// Construction of Static Single Assignment Form".
+struct FunctionBuilder<'a> {
+    function: Function,
+    terminated: Vec<bool>,
+    predecessors: Vec<Vec<BlockId>>,
+    sealed: Vec<bool>,
+    incomplete_parameters: Vec<Vec<(usize, ValueId)>>,
+    definitions: Vec<HashMap<usize, ValueId>>,
+    variable_types: Vec<IrType>,
+    /// What each pointer variable points to, since the IR's pointers don't say.
+    variable_elements: Vec<Option<IrType>>,
+    scopes: Vec<HashMap<String, usize>>,
+    /// The statements deferred in each scope, which are lowered again wherever the scope is left.
+    deferred: Vec<Vec<&'a Node>>,
+    current_block: BlockId,
+    loop_exits: Vec<BlockId>,
+    /// The number of scopes around each enclosing loop, which a `break` doesn't leave.
+    loop_scopes: Vec<usize>,
+    signatures: &'a HashMap<String, Signature>,
+    globals: &'a HashMap<String, IrType>,
+}
+
+impl<'a> FunctionBuilder<'a> {
+    fn new_value(&mut self, value_type: IrType) -> ValueId {
+        self.function.value_types.push(value_type);
+
+        return ValueId(self.function.value_types.len() - 1);
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.function.blocks.push(Block {
+            parameters: Vec::new(),
+            instructions: Vec::new(),
+            terminator: Terminator::Unreachable,
+        });
+        self.terminated.push(false);
+        self.predecessors.push(Vec::new());
+        self.sealed.push(false);
+        self.incomplete_parameters.push(Vec::new());
+        self.definitions.push(HashMap::new());
+
+        return BlockId(self.function.blocks.len() - 1);
+    }
+
+    fn new_parameter(&mut self, block: BlockId, value_type: IrType) -> ValueId {
+        let parameter = self.new_value(value_type);
+
+        self.function.blocks[block.0].parameters.push(parameter);
+
+        return parameter;
+    }
+
+    /// Continues lowering in a block nothing jumps to, used after `return` and `break`.
+    fn start_dead_block(&mut self) {
+        let block = self.new_block();
+
+        self.seal_block(block);
+        self.current_block = block;
+    }
+
+    fn emit(&mut self, kind: InstructionKind, result_type: IrType, node: &Node) -> ValueId {
+        let result = self.new_value(result_type);
+
+        self.function.blocks[self.current_block.0]
+            .instructions
+            .push(Instruction {
+                result: Some(result),
+                kind,
+                span: node.get_span(),
+            });
+
+        return result;
+    }
+
+    fn emit_void(&mut self, kind: InstructionKind, node: &Node) {
+        self.function.blocks[self.current_block.0]
+            .instructions
+            .push(Instruction {
+                result: None,
+                kind,
+                span: node.get_span(),
+            });
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = self.current_block;
+
+        for target in terminator.successors() {
+            if !self.predecessors[target.block.0].contains(&block) {
+                self.predecessors[target.block.0].push(block);
+            }
+        }
+
+        self.function.blocks[block.0].terminator = terminator;
+        self.terminated[block.0] = true;
+    }
+
+    fn jump(&mut self, block: BlockId) {
+        self.terminate(Terminator::Jump(BlockCall {
+            block,
+            arguments: Vec::new(),
+        }));
+    }
+
+    fn write_variable(&mut self, variable: usize, block: BlockId, value: ValueId) {
+        self.definitions[block.0].insert(variable, value);
+    }
+
+    fn read_variable(&mut self, variable: usize, block: BlockId) -> ValueId {
+        if let Some(value) = self.definitions[block.0].get(&variable) {
+            return *value;
+        }
+
+        let variable_type = self.variable_types[variable];
+
+        let value = if !self.sealed[block.0] {
+            let parameter = self.new_parameter(block, variable_type);
+
+            self.incomplete_parameters[block.0].push((variable, parameter));
+
+            parameter
+        } else if self.predecessors[block.0].len() == 1 {
+            let predecessor = self.predecessors[block.0][0];
+
+            self.read_variable(variable, predecessor)
+        } else {
+            let parameter = self.new_parameter(block, variable_type);
+
+            self.write_variable(variable, block, parameter);
+            self.add_parameter_arguments(variable, block);
+
+            parameter
+        };
+
+        self.write_variable(variable, block, value);
+
+        return value;
+    }
+
+    fn add_parameter_arguments(&mut self, variable: usize, block: BlockId) {
+        for predecessor in self.predecessors[block.0].clone() {
+            let value = self.read_variable(variable, predecessor);
+
+            for target in self.function.blocks[predecessor.0]
+                .terminator
+                .successors_mut()
+            {
+                if target.block == block {
+                    target.arguments.push(value);
+                }
+            }
+        }
+    }
+
+    /// Marks a block as having all of its predecessors, completing any parameters that were
+    /// created for it while its predecessors were still unknown.
+    fn seal_block(&mut self, block: BlockId) {
+        let incomplete_parameters = std::mem::take(&mut self.incomplete_parameters[block.0]);
+
+        for (variable, _) in incomplete_parameters {
+            self.add_parameter_arguments(variable, block);
+        }
+
+        self.sealed[block.0] = true;
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(variable) = scope.get(name) {
+                return Some(*variable);
+            }
+        }
+
+        return None;
+    }
+
+    fn define_variable(
+        &mut self,
+        name: &str,
+        variable_type: IrType,
+        element_type: Option<IrType>,
+        value: ValueId,
+    ) {
+        self.variable_types.push(variable_type);
+        self.variable_elements.push(element_type);
+
+        let variable = self.variable_types.len() - 1;
+
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(String::from(name), variable);
+
+        self.write_variable(variable, self.current_block, value);
+    }
+
+    fn lower_expression(
+        &mut self,
+        node: &Node,
+        expected: Option<IrType>,
+    ) -> Result<ValueId, Diagnostic> {
+        match node {
+            Node::Number(number_node) => {
+                let (constant, value_type) = match expected {
+                    Some(IrType::F32) => (Constant::Float(number_node.value as f32), IrType::F32),
+                    Some(IrType::U32) => (Constant::Integer(number_node.value), IrType::U32),
+                    _ => (Constant::Integer(number_node.value), IrType::I32),
+                };
+
+                return Ok(self.emit(InstructionKind::Constant(constant), value_type, node));
+            }
+            Node::Boolean(boolean_node) => {
+                return Ok(self.emit(
+                    InstructionKind::Constant(Constant::Boolean(boolean_node.value)),
+                    IrType::Bool,
+                    node,
+                ));
+            }
+            Node::Name(name_node) => {
+                if let Some(variable) = self.lookup(&name_node.value) {
+                    return Ok(self.read_variable(variable, self.current_block));
+                }
+
+                if let Some(global_type) = self.globals.get(&name_node.value) {
+                    return Ok(self.emit(
+                        InstructionKind::LoadGlobal(name_node.value.clone()),
+                        *global_type,
+                        node,
+                    ));
+                }
+
+                return Err(error(format!("Unknown variable {}", name_node.value), node));
+            }
+            Node::Comptime(comptime_node) => {
+                return self.lower_expression(&comptime_node.value, expected);
+            }
+            Node::Group(group_node) if group_node.content.len() == 1 => {
+                return self.lower_expression(&group_node.content[0], expected);
+            }
+            Node::Operation(operation_node) => {
+                return self.lower_operation(operation_node, node, expected);
+            }
+            Node::Call(call_node) => {
+                return match self.lower_call(call_node, node)? {
+                    Some(value) => Ok(value),
+                    None => Err(error(
+                        format!("Function {} doesn't return a value", call_node.name.value),
+                        node,
+                    )),
+                };
+            }
+            Node::Array(_) => {
+                return Err(error(
+                    String::from("Arrays are not supported by the IR yet"),
+                    node,
+                ));
+            }
+            Node::Index(index_node) => {
+                let (pointer, index, element_type) = self.lower_index(index_node)?;
+
+                return Ok(self.emit(InstructionKind::Load(pointer, index), element_type, node));
+            }
+            _ => {}
+        }
+
+        return Err(error(String::from("Expected an expression"), node));
+    }
+
+    /// Lowers a value going somewhere of a declared type, whose element type says what `alloc`
+    /// allocates when the declared type is a pointer.
+    fn lower_value(
+        &mut self,
+        node: &Node,
+        value_type: IrType,
+        element_type: Option<IrType>,
+    ) -> Result<ValueId, Diagnostic> {
+        if let Node::Call(call_node) = node
+            && call_node.name.value == "alloc"
+            && !self.signatures.contains_key("alloc")
+            && let Some(element_type) = element_type
+        {
+            let count = self.lower_expression(&call_node.arguments[0], Some(IrType::I32))?;
+
+            return Ok(self.emit(
+                InstructionKind::Alloc(element_type, count),
+                IrType::Pointer,
+                node,
+            ));
+        }
+
+        return self.lower_expression(node, Some(value_type));
+    }
+
+    /// Works out what the pointer an expression gives back points to, from the declarations it
+    /// comes from.
+    fn pointee(&self, node: &Node) -> Option<IrType> {
+        match node {
+            Node::Name(name_node) => {
+                return self
+                    .lookup(&name_node.value)
+                    .and_then(|variable| self.variable_elements[variable]);
+            }
+            Node::Call(call_node) => match self.signatures.get(&call_node.name.value) {
+                Some(signature) => return signature.return_element,
+                None if call_node.name.value == "resize" => {
+                    return self.pointee(&call_node.arguments[0]);
+                }
+                None => return None,
+            },
+            Node::Comptime(comptime_node) => return self.pointee(&comptime_node.value),
+            Node::Group(group_node) if group_node.content.len() == 1 => {
+                return self.pointee(&group_node.content[0]);
+            }
+            _ => return None,
+        }
+    }
+
+    /// Lowers the pointer and index of `pointer[index]`, along with the type of its values.
+    fn lower_index(
+        &mut self,
+        index_node: &IndexNode,
+    ) -> Result<(ValueId, ValueId, IrType), Diagnostic> {
+        let name = &index_node.name;
+
+        let Some(variable) = self.lookup(&name.value) else {
+            return Err(Diagnostic::error(
+                format!("Unknown variable {}", name.value),
+                name.span,
+            ));
+        };
+
+        let Some(element_type) = self.variable_elements[variable] else {
+            return Err(Diagnostic::error(
+                format!("Only pointers can be indexed, but {} isn't one", name.value),
+                name.span,
+            ));
+        };
+
+        if element_type == IrType::Void {
+            return Err(Diagnostic::error(
+                format!("{} points to void, so it can't be indexed", name.value),
+                name.span,
+            ));
+        }
+
+        let pointer = self.read_variable(variable, self.current_block);
+        let index = self.lower_expression(&index_node.index, None)?;
+
+        return Ok((pointer, index, element_type));
+    }
+
+    fn lower_short_circuit(
+        &mut self,
+        operation_node: &OperationNode,
+    ) -> Result<ValueId, Diagnostic> {
+        let value_a = self.lower_expression(&operation_node.values[0], Some(IrType::Bool))?;
+
+        let right_block = self.new_block();
+        let merge_block = self.new_block();
+        let result = self.new_parameter(merge_block, IrType::Bool);
+
+        let right_target = BlockCall {
+            block: right_block,
+            arguments: Vec::new(),
+        };
+        let merge_target = BlockCall {
+            block: merge_block,
+            arguments: vec![value_a],
+        };
+
+        if let Operator::And = operation_node.operator.operator {
+            self.terminate(Terminator::Branch(value_a, right_target, merge_target));
+        } else {
+            self.terminate(Terminator::Branch(value_a, merge_target, right_target));
+        }
+
+        self.seal_block(right_block);
+        self.current_block = right_block;
+
+        let value_b = self.lower_expression(&operation_node.values[1], Some(IrType::Bool))?;
+
+        self.terminate(Terminator::Jump(BlockCall {
+            block: merge_block,
+            arguments: vec![value_b],
+        }));
+
+        self.seal_block(merge_block);
+        self.current_block = merge_block;
+
+        return Ok(result);
+    }
+
+    fn lower_operation(
+        &mut self,
+        operation_node: &OperationNode,
+        node: &Node,
+        expected: Option<IrType>,
+    ) -> Result<ValueId, Diagnostic> {
+        let operator = &operation_node.operator.operator;
+
+        if let Operator::And | Operator::Or = operator {
+            return self.lower_short_circuit(operation_node);
+        }
+
+        let operand_expected = match operator {
+            Operator::Add
+            | Operator::Subtract
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Modulo
+            | Operator::BitwiseAnd
+            | Operator::BitwiseOr
+            | Operator::Negate => expected,
+            _ => None,
+        };
+
+        if operator.one_sided() {
+            let value = self.lower_expression(&operation_node.values[0], operand_expected)?;
+            let value_type = self.function.value_type(value);
+
+            let unary_operator = match operator {
+                Operator::Not => UnaryOperator::Not,
+                _ => UnaryOperator::Negate,
+            };
+
+            return Ok(self.emit(
+                InstructionKind::Unary(unary_operator, value),
+                value_type,
+                node,
+            ));
+        }
+
+        let value_a_node = &operation_node.values[0];
+        let value_b_node = &operation_node.values[1];
+
+        // A literal operand takes the type of the other side, matching the type checker.
+        let (value_a, value_b) = if let Node::Number(_) = value_a_node
+            && !matches!(value_b_node, Node::Number(_))
+        {
+            let value_b = self.lower_expression(value_b_node, operand_expected)?;
+            let value_b_type = self.function.value_type(value_b);
+            let value_a = self.lower_expression(value_a_node, Some(value_b_type))?;
+
+            (value_a, value_b)
+        } else {
+            let value_a = self.lower_expression(value_a_node, operand_expected)?;
+            let value_a_type = self.function.value_type(value_a);
+            let value_b = self.lower_expression(value_b_node, Some(value_a_type))?;
+
+            (value_a, value_b)
+        };
+
+        let binary_operator = match operator {
+            Operator::Add => BinaryOperator::Add,
+            Operator::Subtract => BinaryOperator::Subtract,
+            Operator::Multiply => BinaryOperator::Multiply,
+            Operator::Divide => BinaryOperator::Divide,
+            Operator::Modulo => BinaryOperator::Remainder,
+            Operator::BitwiseAnd => BinaryOperator::And,
+            Operator::BitwiseOr => BinaryOperator::Or,
+            Operator::Equal => BinaryOperator::Equal,
+            Operator::NotEqual => BinaryOperator::NotEqual,
+            Operator::LessThan => BinaryOperator::LessThan,
+            Operator::LessThanOrEqual => BinaryOperator::LessThanOrEqual,
+            Operator::GreaterThan => BinaryOperator::GreaterThan,
+            Operator::GreaterThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+            _ => {
+                return Err(error(
+                    format!("Operator {} is not supported by the IR yet", operator),
+                    node,
+                ));
+            }
+        };
+
+        let result_type = if binary_operator.is_comparison() {
+            IrType::Bool
+        } else {
+            self.function.value_type(value_a)
+        };
+
+        return Ok(self.emit(
+            InstructionKind::Binary(binary_operator, value_a, value_b),
+            result_type,
+            node,
+        ));
+    }
+
+    fn lower_call(
+        &mut self,
+        call_node: &CallNode,
+        node: &Node,
+    ) -> Result<Option<ValueId>, Diagnostic> {
+        let name = &call_node.name.value;
+        let signatures = self.signatures;
+
+        let mut arguments: Vec<ValueId> = Vec::new();
+
+        let return_type = match signatures.get(name) {
+            Some(signature) => {
+                for (index, argument) in call_node.arguments.iter().enumerate() {
+                    arguments.push(self.lower_value(
+                        argument,
+                        signature.parameters[index],
+                        signature.parameter_elements[index],
+                    )?);
+                }
+
+                signature.return_type
+            }
+            None if INTRINSICS.contains(&name.as_str()) => {
+                for argument in &call_node.arguments {
+                    arguments.push(self.lower_expression(argument, None)?);
+                }
+
+                IrType::Void
+            }
+            None if name == "alloc" => {
+                return Err(error(
+                    String::from("alloc can only be used where a pointer is expected"),
+                    node,
+                ));
+            }
+            None if name == "resize" => {
+                let pointer = self.lower_expression(&call_node.arguments[0], None)?;
+                let count = self.lower_expression(&call_node.arguments[1], Some(IrType::I32))?;
+
+                let Some(element_type) = self.pointee(&call_node.arguments[0]) else {
+                    return Err(error(
+                        String::from("Can't tell what the resized pointer points to"),
+                        node,
+                    ));
+                };
+
+                return Ok(Some(self.emit(
+                    InstructionKind::Resize(element_type, pointer, count),
+                    IrType::Pointer,
+                    node,
+                )));
+            }
+            None if name == "free" => {
+                let pointer = self.lower_expression(&call_node.arguments[0], None)?;
+
+                self.emit_void(InstructionKind::Free(pointer), node);
+
+                return Ok(None);
+            }
+            None => return Err(error(format!("Unknown function {}", name), node)),
+        };
+
+        let kind = InstructionKind::Call(name.clone(), arguments);
+
+        if return_type == IrType::Void {
+            self.emit_void(kind, node);
+
+            return Ok(None);
+        }
+
+        return Ok(Some(self.emit(kind, return_type, node)));
+    }
+
+    fn lower_block(&mut self, nodes: &'a [Node]) -> Result<(), Diagnostic> {
+        self.scopes.push(HashMap::new());
+        self.deferred.push(Vec::new());
+
+        for node in nodes {
+            self.lower_statement(node)?;
+        }
+
+        self.leave_scopes(self.scopes.len() - 1)?;
+        self.scopes.pop();
+        self.deferred.pop();
+
+        return Ok(());
+    }
+
+    /// Lowers the statements deferred in the scopes from `depth` inward, innermost first, for code
+    /// that leaves them. Each statement only sees the scopes that were around when it was deferred.
+    fn leave_scopes(&mut self, depth: usize) -> Result<(), Diagnostic> {
+        for scope in (depth..self.scopes.len()).rev() {
+            for statement in self.deferred[scope].clone().into_iter().rev() {
+                let hidden_scopes = self.scopes.split_off(scope + 1);
+                let hidden_deferred = self.deferred.split_off(scope + 1);
+
+                let result = self.lower_block(std::slice::from_ref(statement));
+
+                self.scopes.extend(hidden_scopes);
+                self.deferred.extend(hidden_deferred);
+
+                result?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn lower_statement(&mut self, node: &'a Node) -> Result<(), Diagnostic> {
+        match node {
+            Node::Block(block_node) => self.lower_block(&block_node.content)?,
+            Node::VariableDefinition(definition_node) => {
+                let variable_type = lower_type(&definition_node.node_type)?;
+                let element_type = lower_element_type(&definition_node.node_type);
+
+                let value = self.lower_value(
+                    &definition_node.assignment.value,
+                    variable_type,
+                    element_type,
+                )?;
+
+                self.define_variable(
+                    &definition_node.assignment.name.value,
+                    variable_type,
+                    element_type,
+                    value,
+                );
+            }
+            Node::Assignment(assignment_node) => {
+                let name = &assignment_node.name.value;
+
+                if let Some(variable) = self.lookup(name) {
+                    let variable_type = self.variable_types[variable];
+                    let element_type = self.variable_elements[variable];
+
+                    let value =
+                        self.lower_value(&assignment_node.value, variable_type, element_type)?;
+
+                    self.write_variable(variable, self.current_block, value);
+                } else if let Some(global_type) = self.globals.get(name) {
+                    let value =
+                        self.lower_expression(&assignment_node.value, Some(*global_type))?;
+
+                    self.emit_void(InstructionKind::StoreGlobal(name.clone(), value), node);
+                } else {
+                    return Err(error(format!("Unknown variable {}", name), node));
+                }
+            }
+            Node::If(if_node) => {
+                let condition = self.lower_expression(&if_node.condition, Some(IrType::Bool))?;
+
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let merge_block = match if_node.else_node {
+                    Some(_) => self.new_block(),
+                    None => else_block,
+                };
+
+                self.terminate(Terminator::Branch(
+                    condition,
+                    BlockCall {
+                        block: then_block,
+                        arguments: Vec::new(),
+                    },
+                    BlockCall {
+                        block: else_block,
+                        arguments: Vec::new(),
+                    },
+                ));
+
+                self.seal_block(then_block);
+                self.current_block = then_block;
+                self.lower_block(&if_node.block.content)?;
+
+                if !self.terminated[self.current_block.0] {
+                    self.jump(merge_block);
+                }
+
+                if let Some(else_node) = &if_node.else_node {
+                    self.seal_block(else_block);
+                    self.current_block = else_block;
+                    self.lower_statement(else_node)?;
+
+                    if !self.terminated[self.current_block.0] {
+                        self.jump(merge_block);
+                    }
+                }
+
+                self.seal_block(merge_block);
+                self.current_block = merge_block;
+            }
+            Node::Forever(forever_node) => {
+                let header_block = self.new_block();
+                let exit_block = self.new_block();
+
+                self.jump(header_block);
+                self.current_block = header_block;
+
+                self.loop_exits.push(exit_block);
+                self.loop_scopes.push(self.scopes.len());
+                self.lower_block(&forever_node.block.content)?;
+                self.loop_scopes.pop();
+                self.loop_exits.pop();
+
+                if !self.terminated[self.current_block.0] {
+                    self.jump(header_block);
+                }
+
+                self.seal_block(header_block);
+                self.seal_block(exit_block);
+                self.current_block = exit_block;
+            }
+            Node::Return(return_node) => {
+                let value = match &return_node.value {
+                    Some(value_node) => {
+                        let return_element = self.signatures[&self.function.name].return_element;
+
+                        Some(self.lower_value(
+                            value_node,
+                            self.function.return_type,
+                            return_element,
+                        )?)
+                    }
+                    None => None,
+                };
+
+                self.leave_scopes(0)?;
+                self.terminate(Terminator::Return(value));
+                self.start_dead_block();
+            }
+            Node::Keyword(keyword_node) if keyword_node.keyword == Keyword::Break => {
+                let exit_block = match self.loop_exits.last() {
+                    Some(exit_block) => *exit_block,
+                    None => return Err(error(String::from("Break outside of a loop"), node)),
+                };
+
+                let depth = *self.loop_scopes.last().unwrap();
+
+                self.leave_scopes(depth)?;
+                self.jump(exit_block);
+                self.start_dead_block();
+            }
+            Node::Call(call_node) => {
+                self.lower_call(call_node, node)?;
+            }
+            Node::Defer(defer_node) => {
+                self.deferred
+                    .last_mut()
+                    .unwrap()
+                    .push(&defer_node.statement);
+            }
+            Node::IndexAssignment(assignment_node) => {
+                let (pointer, index, element_type) = self.lower_index(&assignment_node.target)?;
+
+                let value = self.lower_expression(&assignment_node.value, Some(element_type))?;
+
+                self.emit_void(InstructionKind::Store(pointer, index, value), node);
+            }
+            _ => {
+                self.lower_expression(node, None)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = vec![false; self.function.blocks.len()];
+        let mut stack = vec![BlockId(0)];
+
+        while let Some(block) = stack.pop() {
+            if reachable[block.0] {
+                continue;
+            }
+
+            reachable[block.0] = true;
+
+            for target in self.function.blocks[block.0].terminator.successors() {
+                stack.push(target.block);
+            }
+        }
+
+        let mut new_ids: Vec<Option<BlockId>> = Vec::new();
+        let mut count = 0;
+
+        for is_reachable in &reachable {
+            if *is_reachable {
+                new_ids.push(Some(BlockId(count)));
+                count += 1;
+            } else {
+                new_ids.push(None);
+            }
+        }
+
+        let blocks = std::mem::take(&mut self.function.blocks);
+
+        for (index, mut block) in blocks.into_iter().enumerate() {
+            if !reachable[index] {
+                continue;
+            }
+
+            for target in block.terminator.successors_mut() {
+                target.block = new_ids[target.block.0].unwrap();
+            }
+
+            self.function.blocks.push(block);
+        }
+    }
+
+    fn replace_value(&mut self, old: ValueId, new: ValueId) {
+        for block in &mut self.function.blocks {
+            for instruction in &mut block.instructions {
+                for operand in instruction.operands_mut() {
+                    if *operand == old {
+                        *operand = new;
+                    }
+                }
+            }
+
+            for operand in block.terminator.operands_mut() {
+                if *operand == old {
+                    *operand = new;
+                }
+            }
+        }
+    }
+
+    /// Removes block parameters that always receive the same value, which the construction
+    /// leaves behind at joins where a variable wasn't actually changed.
+    fn remove_trivial_parameters(&mut self) {
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            let predecessors = self.function.predecessors();
+
+            for (block, block_predecessors) in predecessors.iter().enumerate().skip(1) {
+                let mut index = 0;
+
+                while index < self.function.blocks[block].parameters.len() {
+                    let parameter = self.function.blocks[block].parameters[index];
+
+                    let mut incoming: Vec<ValueId> = Vec::new();
+
+                    for predecessor in block_predecessors {
+                        for target in self.function.blocks[predecessor.0].terminator.successors() {
+                            if target.block.0 == block {
+                                let argument = target.arguments[index];
+
+                                if argument != parameter && !incoming.contains(&argument) {
+                                    incoming.push(argument);
+                                }
+                            }
+                        }
+                    }
+
+                    if incoming.len() != 1 {
+                        index += 1;
+
+                        continue;
+                    }
+
+                    self.function.blocks[block].parameters.remove(index);
+
+                    for predecessor in block_predecessors {
+                        for target in self.function.blocks[predecessor.0]
+                            .terminator
+                            .successors_mut()
+                        {
+                            if target.block.0 == block {
+                                target.arguments.remove(index);
+                            }
+                        }
+                    }
+
+                    self.replace_value(parameter, incoming[0]);
+
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Renumbers values in the order they're defined so printed IR reads top to bottom.
+    fn renumber_values(&mut self) {
+        let mut new_ids: Vec<Option<ValueId>> = vec![None; self.function.value_types.len()];
+        let mut value_types: Vec<IrType> = Vec::new();
+
+        let mut renumber = |value: &mut ValueId, value_types: &mut Vec<IrType>| {
+            if new_ids[value.0].is_none() {
+                value_types.push(self.function.value_types[value.0]);
+                new_ids[value.0] = Some(ValueId(value_types.len() - 1));
+            }
+
+            *value = new_ids[value.0].unwrap();
+        };
+
+        for block in &mut self.function.blocks {
+            for parameter in &mut block.parameters {
+                renumber(parameter, &mut value_types);
+            }
+
+            for instruction in &mut block.instructions {
+                if let Some(result) = &mut instruction.result {
+                    renumber(result, &mut value_types);
+                }
+            }
+        }
+
+        for block in &mut self.function.blocks {
+            for instruction in &mut block.instructions {
+                for operand in instruction.operands_mut() {
+                    renumber(operand, &mut value_types);
+                }
+            }
+
+            for operand in block.terminator.operands_mut() {
+                renumber(operand, &mut value_types);
+            }
+        }
+
+        self.function.value_types = value_types;
+    }
+}
+
+fn lower_function<'a>(
+    function_node: &'a FunctionDefinitionNode,
+    signatures: &'a HashMap<String, Signature>,
+    globals: &'a HashMap<String, IrType>,
+) -> Result<Function, Diagnostic> {
+    let signature = &signatures[&function_node.name.value];
+
+    let mut builder = FunctionBuilder {
+        function: Function {
+            name: function_node.name.value.clone(),
+            parameters: signature.parameters.clone(),
+            return_type: signature.return_type,
+            blocks: Vec::new(),
+            value_types: Vec::new(),
+            external: function_node.external,
+            span: function_node.span,
+        },
+        terminated: Vec::new(),
+        predecessors: Vec::new(),
+        sealed: Vec::new(),
+        incomplete_parameters: Vec::new(),
+        definitions: Vec::new(),
+        variable_types: Vec::new(),
+        variable_elements: Vec::new(),
+        scopes: vec![HashMap::new()],
+        deferred: vec![Vec::new()],
+        current_block: BlockId(0),
+        loop_exits: Vec::new(),
+        loop_scopes: Vec::new(),
+        signatures,
+        globals,
+    };
+
+    let entry_block = builder.new_block();
+
+    builder.seal_block(entry_block);
+
+    for (index, parameter) in function_node.parameters.iter().enumerate() {
+        let parameter_type = signature.parameters[index];
+        let value = builder.new_parameter(entry_block, parameter_type);
+
+        builder.define_variable(
+            &parameter.name.value,
+            parameter_type,
+            signature.parameter_elements[index],
+            value,
+        );
+    }
+
+    builder.lower_block(&function_node.block.content)?;
+
+    if !builder.terminated[builder.current_block.0] {
+        if signature.return_type == IrType::Void {
+            builder.terminate(Terminator::Return(None));
+        } else {
+            builder.terminate(Terminator::Unreachable);
+        }
+    }
+
+    builder.remove_unreachable_blocks();
+    builder.remove_trivial_parameters();
+    builder.renumber_values();
+
+    return Ok(builder.function);
+}
+
+/// Lowers a type checked syntax tree to IR.
+pub fn lower(nodes: &[Node]) -> Result<Module, Diagnostic> {
+    let mut signatures: HashMap<String, Signature> = HashMap::new();
+    let mut global_types: HashMap<String, IrType> = HashMap::new();
+
+    let mut module = Module {
+        globals: Vec::new(),
+        externs: Vec::new(),
+        functions: Vec::new(),
+    };
+
+    for node in nodes {
+        match node {
+            Node::ExternFunction(function_node) => {
+                let signature =
+                    Signature::new(&function_node.parameters, &function_node.node_type)?;
+
+                module.externs.push(Extern {
+                    name: function_node.name.value.clone(),
+                    parameters: signature.parameters.clone(),
+                    return_type: signature.return_type,
+                    span: function_node.span,
+                });
+
+                signatures.insert(function_node.name.value.clone(), signature);
+            }
+            Node::FunctionDefinition(function_node) => {
+                signatures.insert(
+                    function_node.name.value.clone(),
+                    Signature::new(&function_node.parameters, &function_node.node_type)?,
+                );
+            }
+            Node::VariableDefinition(definition_node) => {
+                let global_type = lower_type(&definition_node.node_type)?;
+
+                if global_type == IrType::Pointer {
+                    return Err(Diagnostic::error(
+                        String::from("Pointer globals are not supported by the IR yet"),
+                        definition_node.node_type.span,
+                    ));
+                }
+
+                let value = match (definition_node.assignment.value.as_ref(), global_type) {
+                    (Node::Number(number_node), IrType::F32) => {
+                        Constant::Float(number_node.value as f32)
+                    }
+                    (Node::Number(number_node), _) => Constant::Integer(number_node.value),
+                    (Node::Boolean(boolean_node), _) => Constant::Boolean(boolean_node.value),
+                    (value, _) => {
+                        return Err(error(
+                            String::from("Global initializers must be compile time constants"),
+                            value,
+                        ));
+                    }
+                };
+
+                global_types.insert(definition_node.assignment.name.value.clone(), global_type);
+
+                module.globals.push(Global {
+                    name: definition_node.assignment.name.value.clone(),
+                    global_type,
+                    value,
+                });
+            }
+            _ => return Err(error(String::from("Expected a definition"), node)),
+        }
+    }
+
+    for node in nodes {
+        if let Node::FunctionDefinition(function_node) = node {
+            module
+                .functions
+                .push(lower_function(function_node, &signatures, &global_types)?);
+        }
+    }
+
+    return Ok(module);
+}
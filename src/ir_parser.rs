@@ -0,0 +1,707 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::ir::{
+    BINARY_OPERATORS, Block, BlockCall, BlockId, Constant, Extern, Function, Global, Instruction,
+    InstructionKind, IrType, Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::source_map::{FileId, Span};
+
+const PUNCTUATION_CHARACTERS: &[char] = &['(', ')', ',', ':', '=', '{', '}', '[', ']'];
+
+struct Word {
+    content: String,
+    span: Span,
+}
+
+/// Splits IR text into words and punctuation. `//` starts a comment that runs to the end of the
+/// line, which is handy when writing IR by hand.
+fn split_words(code: &str, file: FileId) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut line_start = 0;
+
+    for line in code.split_inclusive('\n') {
+        let text = match line.find("//") {
+            Some(index) => &line[..index],
+            None => line,
+        };
+
+        let mut characters = text.char_indices().peekable();
+
+        let mut push = |start: usize, end: usize| {
+            words.push(Word {
+                content: String::from(&text[start..end]),
+                span: Span::new(file, line_start + start, line_start + end),
+            });
+        };
+
+        while let Some((index, character)) = characters.next() {
+            if character.is_whitespace() {
+                continue;
+            }
+
+            if character == '-' && characters.next_if(|(_, next)| *next == '>').is_some() {
+                push(index, index + 2);
+
+                continue;
+            }
+
+            if PUNCTUATION_CHARACTERS.contains(&character) {
+                push(index, index + 1);
+
+                continue;
+            }
+
+            let mut end = index + character.len_utf8();
+
+            while let Some((next_index, next)) = characters.next_if(|(_, next)| {
+                !next.is_whitespace() && !PUNCTUATION_CHARACTERS.contains(next)
+            }) {
+                end = next_index + next.len_utf8();
+            }
+
+            push(index, end);
+        }
+
+        line_start += line.len();
+    }
+
+    return words;
+}
+
+fn parse_type(content: &str) -> Option<IrType> {
+    match content {
+        "i32" => Some(IrType::I32),
+        "u32" => Some(IrType::U32),
+        "f32" => Some(IrType::F32),
+        "bool" => Some(IrType::Bool),
+        "void" => Some(IrType::Void),
+        "ptr" => Some(IrType::Pointer),
+        _ => None,
+    }
+}
+
+fn parse_constant(content: &str, constant_type: IrType) -> Option<Constant> {
+    match constant_type {
+        IrType::F32 => content.parse::<f32>().ok().map(Constant::Float),
+        IrType::Bool => content.parse::<bool>().ok().map(Constant::Boolean),
+        IrType::Void | IrType::Pointer => None,
+        _ => content.parse::<i64>().ok().map(Constant::Integer),
+    }
+}
+
+/// Values and blocks keep the numbers written in the text until the whole function is read, then
+/// get renumbered densely in the order they're defined.
+struct FunctionParser {
+    value_types: HashMap<usize, IrType>,
+    value_order: Vec<usize>,
+    block_order: Vec<usize>,
+}
+
+struct Parser {
+    file: FileId,
+    words: Vec<Word>,
+    index: usize,
+}
+
+impl Parser {
+    fn span(&self) -> Span {
+        match self.words.get(self.index).or(self.words.last()) {
+            Some(word) => return word.span,
+            None => return Span::new(self.file, 0, 0),
+        }
+    }
+
+    fn error(&self, message: String) -> Diagnostic {
+        return Diagnostic::error(message, self.span());
+    }
+
+    fn peek(&self) -> Option<&str> {
+        return self.words.get(self.index).map(|word| word.content.as_str());
+    }
+
+    fn next(&mut self) -> Result<String, Diagnostic> {
+        match self.words.get(self.index) {
+            Some(word) => {
+                self.index += 1;
+
+                return Ok(word.content.clone());
+            }
+            None => return Err(self.error(String::from("Unexpected end of input"))),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Diagnostic> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(format!("Expected '{}'", expected)));
+        }
+
+        self.index += 1;
+
+        return Ok(());
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        if self.peek() == Some(expected) {
+            self.index += 1;
+
+            return true;
+        }
+
+        return false;
+    }
+
+    fn prefixed_word(&mut self, prefix: &str, what: &str) -> Result<String, Diagnostic> {
+        if let Some(content) = self.peek()
+            && let Some(rest) = content.strip_prefix(prefix)
+            && !rest.is_empty()
+        {
+            let rest = String::from(rest);
+
+            self.index += 1;
+
+            return Ok(rest);
+        }
+
+        return Err(self.error(format!("Expected {}", what)));
+    }
+
+    fn prefixed_number(&mut self, prefix: &str, what: &str) -> Result<usize, Diagnostic> {
+        let content = self.prefixed_word(prefix, what)?;
+
+        return content.parse::<usize>().map_err(|_| {
+            self.index -= 1;
+
+            self.error(format!("Expected {}", what))
+        });
+    }
+
+    fn ir_type(&mut self) -> Result<IrType, Diagnostic> {
+        if let Some(content) = self.peek()
+            && let Some(ir_type) = parse_type(content)
+        {
+            self.index += 1;
+
+            return Ok(ir_type);
+        }
+
+        return Err(self.error(String::from("Expected a type")));
+    }
+
+    fn value(&mut self) -> Result<ValueId, Diagnostic> {
+        return Ok(ValueId(self.prefixed_number("%", "a value")?));
+    }
+
+    fn block_id(&mut self) -> Result<BlockId, Diagnostic> {
+        return Ok(BlockId(self.prefixed_number("bb", "a block")?));
+    }
+
+    fn values(&mut self) -> Result<Vec<ValueId>, Diagnostic> {
+        let mut values: Vec<ValueId> = Vec::new();
+
+        self.expect("(")?;
+
+        if !self.eat(")") {
+            loop {
+                values.push(self.value()?);
+
+                if self.eat(")") {
+                    break;
+                }
+
+                self.expect(",")?;
+            }
+        }
+
+        return Ok(values);
+    }
+
+    /// Reads the `%pointer[%index]` that loads and stores go through.
+    fn element(&mut self) -> Result<(ValueId, ValueId), Diagnostic> {
+        let pointer = self.value()?;
+
+        self.expect("[")?;
+
+        let index = self.value()?;
+
+        self.expect("]")?;
+
+        return Ok((pointer, index));
+    }
+
+    fn block_call(&mut self) -> Result<BlockCall, Diagnostic> {
+        let block = self.block_id()?;
+
+        let arguments = if self.peek() == Some("(") {
+            self.values()?
+        } else {
+            Vec::new()
+        };
+
+        return Ok(BlockCall { block, arguments });
+    }
+
+    fn define_value(
+        &mut self,
+        function_parser: &mut FunctionParser,
+        value: ValueId,
+        value_type: IrType,
+    ) -> Result<(), Diagnostic> {
+        if function_parser
+            .value_types
+            .insert(value.0, value_type)
+            .is_some()
+        {
+            self.index -= 1;
+
+            return Err(self.error(format!("{} is defined more than once", value)));
+        }
+
+        function_parser.value_order.push(value.0);
+
+        return Ok(());
+    }
+
+    fn global(&mut self) -> Result<Global, Diagnostic> {
+        self.expect("global")?;
+
+        let name = self.prefixed_word("@", "a global name")?;
+
+        self.expect(":")?;
+
+        let global_type = self.ir_type()?;
+
+        self.expect("=")?;
+
+        let content = self.next()?;
+
+        let value = match parse_constant(&content, global_type) {
+            Some(value) => value,
+            None => {
+                self.index -= 1;
+
+                return Err(self.error(format!("Expected a {} constant", global_type)));
+            }
+        };
+
+        return Ok(Global {
+            name,
+            global_type,
+            value,
+        });
+    }
+
+    fn terminator(&mut self) -> Result<Option<Terminator>, Diagnostic> {
+        match self.peek() {
+            Some("jump") => {
+                self.index += 1;
+
+                return Ok(Some(Terminator::Jump(self.block_call()?)));
+            }
+            Some("branch") => {
+                self.index += 1;
+
+                let condition = self.value()?;
+
+                self.expect(",")?;
+
+                let then_target = self.block_call()?;
+
+                self.expect(",")?;
+
+                let else_target = self.block_call()?;
+
+                return Ok(Some(Terminator::Branch(
+                    condition,
+                    then_target,
+                    else_target,
+                )));
+            }
+            Some("return") => {
+                self.index += 1;
+
+                if let Some(content) = self.peek()
+                    && content.starts_with('%')
+                {
+                    return Ok(Some(Terminator::Return(Some(self.value()?))));
+                }
+
+                return Ok(Some(Terminator::Return(None)));
+            }
+            Some("unreachable") => {
+                self.index += 1;
+
+                return Ok(Some(Terminator::Unreachable));
+            }
+            _ => return Ok(None),
+        }
+    }
+
+    fn instruction(
+        &mut self,
+        function_parser: &mut FunctionParser,
+    ) -> Result<Instruction, Diagnostic> {
+        let span = self.span();
+
+        let mut result: Option<(ValueId, IrType)> = None;
+
+        if let Some(content) = self.peek()
+            && content.starts_with('%')
+        {
+            let value = self.value()?;
+
+            self.expect(":")?;
+
+            let value_type = self.ir_type()?;
+
+            self.expect("=")?;
+
+            result = Some((value, value_type));
+        }
+
+        let operation = self.next()?;
+
+        let kind = if operation == "const" {
+            let constant_type = match result {
+                Some((_, constant_type)) => constant_type,
+                None => return Err(self.error(String::from("Constants need a result"))),
+            };
+
+            let content = self.next()?;
+
+            match parse_constant(&content, constant_type) {
+                Some(constant) => InstructionKind::Constant(constant),
+                None => {
+                    self.index -= 1;
+
+                    return Err(self.error(format!("Expected a {} constant", constant_type)));
+                }
+            }
+        } else if let Some(operator) = BINARY_OPERATORS
+            .iter()
+            .find(|operator| operator.to_string() == operation)
+        {
+            let value_a = self.value()?;
+
+            self.expect(",")?;
+
+            let value_b = self.value()?;
+
+            InstructionKind::Binary(*operator, value_a, value_b)
+        } else if operation == "neg" {
+            InstructionKind::Unary(UnaryOperator::Negate, self.value()?)
+        } else if operation == "not" {
+            InstructionKind::Unary(UnaryOperator::Not, self.value()?)
+        } else if operation == "call" {
+            let name = self.prefixed_word("@", "a function name")?;
+
+            InstructionKind::Call(name, self.values()?)
+        } else if operation == "load" && self.peek().is_some_and(|word| word.starts_with('%')) {
+            let (pointer, index) = self.element()?;
+
+            InstructionKind::Load(pointer, index)
+        } else if operation == "load" {
+            InstructionKind::LoadGlobal(self.prefixed_word("@", "a global name")?)
+        } else if operation == "store" && self.peek().is_some_and(|word| word.starts_with('%')) {
+            let (pointer, index) = self.element()?;
+
+            self.expect(",")?;
+
+            InstructionKind::Store(pointer, index, self.value()?)
+        } else if operation == "store" {
+            let name = self.prefixed_word("@", "a global name")?;
+
+            self.expect(",")?;
+
+            InstructionKind::StoreGlobal(name, self.value()?)
+        } else if operation == "alloc" {
+            let element_type = self.ir_type()?;
+
+            self.expect(",")?;
+
+            InstructionKind::Alloc(element_type, self.value()?)
+        } else if operation == "resize" {
+            let element_type = self.ir_type()?;
+
+            self.expect(",")?;
+
+            let pointer = self.value()?;
+
+            self.expect(",")?;
+
+            InstructionKind::Resize(element_type, pointer, self.value()?)
+        } else if operation == "free" {
+            InstructionKind::Free(self.value()?)
+        } else {
+            self.index -= 1;
+
+            return Err(self.error(format!("Unknown instruction {}", operation)));
+        };
+
+        if let Some((value, value_type)) = result
+            && function_parser
+                .value_types
+                .insert(value.0, value_type)
+                .is_some()
+        {
+            return Err(Diagnostic::error(
+                format!("{} is defined more than once", value),
+                span,
+            ));
+        }
+
+        if let Some((value, _)) = result {
+            function_parser.value_order.push(value.0);
+        }
+
+        return Ok(Instruction {
+            result: result.map(|(value, _)| value),
+            kind,
+            span,
+        });
+    }
+
+    fn block(&mut self, function_parser: &mut FunctionParser) -> Result<Block, Diagnostic> {
+        let label = self.block_id()?;
+
+        if function_parser.block_order.contains(&label.0) {
+            self.index -= 1;
+
+            return Err(self.error(format!("{} is defined more than once", label)));
+        }
+
+        function_parser.block_order.push(label.0);
+
+        let mut parameters: Vec<ValueId> = Vec::new();
+
+        if self.eat("(") {
+            loop {
+                let parameter = self.value()?;
+
+                self.expect(":")?;
+
+                let parameter_type = self.ir_type()?;
+
+                self.define_value(function_parser, parameter, parameter_type)?;
+
+                parameters.push(parameter);
+
+                if self.eat(")") {
+                    break;
+                }
+
+                self.expect(",")?;
+            }
+        }
+
+        self.expect(":")?;
+
+        let mut instructions: Vec<Instruction> = Vec::new();
+
+        loop {
+            if let Some(terminator) = self.terminator()? {
+                return Ok(Block {
+                    parameters,
+                    instructions,
+                    terminator,
+                });
+            }
+
+            if self.peek() == Some("}") {
+                return Err(self.error(format!("{} has no terminator", label)));
+            }
+
+            instructions.push(self.instruction(function_parser)?);
+        }
+    }
+
+    fn signature(&mut self) -> Result<(String, Vec<IrType>, IrType), Diagnostic> {
+        self.expect("function")?;
+
+        let name = self.prefixed_word("@", "a function name")?;
+
+        let mut parameters: Vec<IrType> = Vec::new();
+
+        self.expect("(")?;
+
+        if !self.eat(")") {
+            loop {
+                parameters.push(self.ir_type()?);
+
+                if self.eat(")") {
+                    break;
+                }
+
+                self.expect(",")?;
+            }
+        }
+
+        self.expect("->")?;
+
+        let return_type = self.ir_type()?;
+
+        return Ok((name, parameters, return_type));
+    }
+
+    /// Parses a function, or an extern, which is a signature after `extern` without a body.
+    fn item(&mut self, module: &mut Module) -> Result<(), Diagnostic> {
+        let span = self.span();
+        let external = self.eat("extern");
+        let (name, parameters, return_type) = self.signature()?;
+
+        if external && self.peek() != Some("{") {
+            module.externs.push(Extern {
+                name,
+                parameters,
+                return_type,
+                span,
+            });
+
+            return Ok(());
+        }
+
+        self.expect("{")?;
+
+        let mut function_parser = FunctionParser {
+            value_types: HashMap::new(),
+            value_order: Vec::new(),
+            block_order: Vec::new(),
+        };
+
+        let mut blocks: Vec<Block> = Vec::new();
+
+        while !self.eat("}") {
+            blocks.push(self.block(&mut function_parser)?);
+        }
+
+        let mut function = Function {
+            name,
+            parameters,
+            return_type,
+            blocks,
+            value_types: Vec::new(),
+            external,
+            span,
+        };
+
+        self.renumber(&mut function, &function_parser)?;
+        module.functions.push(function);
+
+        return Ok(());
+    }
+
+    fn renumber(
+        &self,
+        function: &mut Function,
+        function_parser: &FunctionParser,
+    ) -> Result<(), Diagnostic> {
+        let mut value_ids: HashMap<usize, ValueId> = HashMap::new();
+
+        for (index, value) in function_parser.value_order.iter().enumerate() {
+            value_ids.insert(*value, ValueId(index));
+            function
+                .value_types
+                .push(function_parser.value_types[value]);
+        }
+
+        let mut block_ids: HashMap<usize, BlockId> = HashMap::new();
+
+        for (index, block) in function_parser.block_order.iter().enumerate() {
+            block_ids.insert(*block, BlockId(index));
+        }
+
+        for block in &function.blocks {
+            let mut values: Vec<ValueId> = block.parameters.clone();
+
+            for instruction in &block.instructions {
+                values.extend(instruction.result);
+                values.extend(instruction.operands());
+            }
+
+            match &block.terminator {
+                Terminator::Branch(condition, _, _) => values.push(*condition),
+                Terminator::Return(Some(value)) => values.push(*value),
+                _ => {}
+            }
+
+            for target in block.terminator.successors() {
+                values.extend(target.arguments.iter());
+            }
+
+            if let Some(value) = values
+                .iter()
+                .find(|value| !value_ids.contains_key(&value.0))
+            {
+                return Err(Diagnostic::error(
+                    format!("In function @{}: {} is never defined", function.name, value),
+                    function.span,
+                ));
+            }
+        }
+
+        for block in &mut function.blocks {
+            for parameter in &mut block.parameters {
+                *parameter = value_ids[&parameter.0];
+            }
+
+            for instruction in &mut block.instructions {
+                if let Some(result) = &mut instruction.result {
+                    *result = value_ids[&result.0];
+                }
+
+                for operand in instruction.operands_mut() {
+                    *operand = value_ids[&operand.0];
+                }
+            }
+
+            for operand in block.terminator.operands_mut() {
+                *operand = value_ids[&operand.0];
+            }
+
+            for target in block.terminator.successors_mut() {
+                target.block = match block_ids.get(&target.block.0) {
+                    Some(block_id) => *block_id,
+                    None => {
+                        return Err(Diagnostic::error(
+                            format!(
+                                "In function @{}: {} is never defined",
+                                function.name, target.block
+                            ),
+                            function.span,
+                        ));
+                    }
+                };
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Parses the text form of a module, as printed by `Module`'s `Display`. Instructions keep the
+/// span they were written at so verifier errors point back into the text.
+pub fn parse(code: &str, file: FileId) -> Result<Module, Diagnostic> {
+    let mut parser = Parser {
+        file,
+        words: split_words(code, file),
+        index: 0,
+    };
+
+    let mut module = Module {
+        globals: Vec::new(),
+        externs: Vec::new(),
+        functions: Vec::new(),
+    };
+
+    while let Some(content) = parser.peek() {
+        if content == "global" {
+            module.globals.push(parser.global()?);
+        } else if content == "function" || content == "extern" {
+            parser.item(&mut module)?;
+        } else {
+            return Err(parser.error(format!("Unexpected '{}'", content)));
+        }
+    }
+
+    return Ok(module);
+}
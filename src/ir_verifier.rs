@@ -0,0 +1,835 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::ir::{
+    BinaryOperator, BlockCall, BlockId, Constant, Function, INTRINSICS, Instruction,
+    InstructionKind, IrType, Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::source_map::Span;
+
+/// Where a value is defined. Parameters sit at position 0 and instructions count up from 1, so a
+/// value defined earlier in the same block always has a smaller position.
+#[derive(Clone, Copy)]
+struct Definition {
+    block: BlockId,
+    position: usize,
+}
+
+/// Whether an integer constant is in the range of an integer type.
+fn integer_fits(value: i64, integer_type: IrType) -> bool {
+    match integer_type {
+        IrType::U32 => u32::try_from(value).is_ok(),
+        _ => i32::try_from(value).is_ok(),
+    }
+}
+
+/// Heap memory holds numbers and booleans, but not pointers or nothing.
+fn check_element_type(element_type: IrType) -> Result<(), String> {
+    match element_type {
+        IrType::Void | IrType::Pointer => {
+            return Err(format!("Heap memory can't hold {}", element_type));
+        }
+        _ => return Ok(()),
+    }
+}
+
+fn check_pointer(found: IrType) -> Result<(), String> {
+    if found != IrType::Pointer {
+        return Err(format!("Expected a ptr but found {}", found));
+    }
+
+    return Ok(());
+}
+
+fn check_count(found: IrType) -> Result<(), String> {
+    if !found.is_integer() {
+        return Err(format!("Expected an integer count but found {}", found));
+    }
+
+    return Ok(());
+}
+
+fn check_index(found: IrType) -> Result<(), String> {
+    if !found.is_integer() {
+        return Err(format!("Expected an integer index but found {}", found));
+    }
+
+    return Ok(());
+}
+
+/// Computes which blocks dominate each block, with the usual iterative data flow algorithm.
+fn dominators(function: &Function) -> Vec<Vec<bool>> {
+    let block_count = function.blocks.len();
+    let predecessors = function.predecessors();
+
+    let mut dominators: Vec<Vec<bool>> = vec![vec![true; block_count]; block_count];
+
+    dominators[0] = vec![false; block_count];
+    dominators[0][0] = true;
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for block in 1..block_count {
+            let mut new_dominators = vec![true; block_count];
+
+            for predecessor in &predecessors[block] {
+                for (index, dominates) in new_dominators.iter_mut().enumerate() {
+                    *dominates = *dominates && dominators[predecessor.0][index];
+                }
+            }
+
+            new_dominators[block] = true;
+
+            if new_dominators != dominators[block] {
+                dominators[block] = new_dominators;
+                changed = true;
+            }
+        }
+    }
+
+    return dominators;
+}
+
+struct FunctionVerifier<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    globals: HashMap<&'a str, IrType>,
+    definitions: HashMap<ValueId, Definition>,
+    dominators: Vec<Vec<bool>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> FunctionVerifier<'a> {
+    fn report(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic::error(
+            format!("In function @{}: {}", self.function.name, message),
+            span,
+        ));
+    }
+
+    fn report_function(&mut self, message: String) {
+        self.report(message, self.function.span);
+    }
+
+    fn define(&mut self, value: ValueId, definition: Definition) -> bool {
+        if value.0 >= self.function.value_types.len() {
+            self.report_function(format!("{} has no type", value));
+
+            return false;
+        }
+
+        if self.definitions.contains_key(&value) {
+            self.report_function(format!("{} is defined more than once", value));
+
+            return false;
+        }
+
+        self.definitions.insert(value, definition);
+
+        return true;
+    }
+
+    fn collect_definitions(&mut self) {
+        for (block_index, block) in self.function.blocks.iter().enumerate() {
+            for parameter in &block.parameters {
+                self.define(
+                    *parameter,
+                    Definition {
+                        block: BlockId(block_index),
+                        position: 0,
+                    },
+                );
+            }
+
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                if let Some(result) = instruction.result {
+                    self.define(
+                        result,
+                        Definition {
+                            block: BlockId(block_index),
+                            position: index + 1,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks that a value is defined before the use at the given location and returns its type.
+    fn use_value(
+        &mut self,
+        value: ValueId,
+        block: BlockId,
+        position: usize,
+        span: Span,
+    ) -> Option<IrType> {
+        let definition = match self.definitions.get(&value) {
+            Some(definition) => *definition,
+            None => {
+                self.report(format!("{} is never defined", value), span);
+
+                return None;
+            }
+        };
+
+        let available = if definition.block == block {
+            definition.position < position
+        } else {
+            self.dominators[block.0][definition.block.0]
+        };
+
+        if !available {
+            self.report(format!("{} is used before it is defined", value), span);
+        }
+
+        return Some(self.function.value_type(value));
+    }
+
+    fn expect_type(&mut self, found: IrType, expected: IrType, what: &str, span: Span) {
+        if found != expected {
+            self.report(
+                format!("Expected {} to be {} but found {}", what, expected, found),
+                span,
+            );
+        }
+    }
+
+    /// Works out the type an instruction produces, or `None` if it doesn't produce a value.
+    fn instruction_type(
+        &mut self,
+        instruction: &Instruction,
+        operand_types: &[IrType],
+    ) -> Result<Option<IrType>, String> {
+        match &instruction.kind {
+            InstructionKind::Constant(constant) => {
+                let constant_type = instruction
+                    .result
+                    .map(|result| self.function.value_type(result));
+
+                return match (constant, constant_type) {
+                    (Constant::Integer(value), constant_type) => {
+                        let constant_type = constant_type
+                            .filter(IrType::is_integer)
+                            .unwrap_or(IrType::I32);
+
+                        if !integer_fits(*value, constant_type) {
+                            return Err(format!(
+                                "Constant {} doesn't fit in {}",
+                                value, constant_type
+                            ));
+                        }
+
+                        Ok(Some(constant_type))
+                    }
+                    (Constant::Float(_), _) => Ok(Some(IrType::F32)),
+                    (Constant::Boolean(_), _) => Ok(Some(IrType::Bool)),
+                };
+            }
+            InstructionKind::Binary(operator, _, _) => {
+                let (value_a_type, value_b_type) = (operand_types[0], operand_types[1]);
+
+                if value_a_type != value_b_type {
+                    return Err(format!(
+                        "Operator {} needs operands of the same type but found {} and {}",
+                        operator, value_a_type, value_b_type
+                    ));
+                }
+
+                let supported = match operator {
+                    BinaryOperator::And | BinaryOperator::Or => {
+                        value_a_type.is_integer() || value_a_type == IrType::Bool
+                    }
+                    BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                        value_a_type != IrType::Void
+                    }
+                    _ => value_a_type.is_integer() || value_a_type == IrType::F32,
+                };
+
+                if !supported {
+                    return Err(format!(
+                        "Operator {} can't be applied to {}",
+                        operator, value_a_type
+                    ));
+                }
+
+                if operator.is_comparison() {
+                    return Ok(Some(IrType::Bool));
+                }
+
+                return Ok(Some(value_a_type));
+            }
+            InstructionKind::Unary(operator, _) => {
+                let value_type = operand_types[0];
+
+                let supported = match operator {
+                    UnaryOperator::Negate => value_type.is_signed(),
+                    UnaryOperator::Not => value_type.is_integer() || value_type == IrType::Bool,
+                };
+
+                if !supported {
+                    return Err(format!(
+                        "Operator {} can't be applied to {}",
+                        operator, value_type
+                    ));
+                }
+
+                return Ok(Some(value_type));
+            }
+            InstructionKind::Call(name, _) => {
+                let (parameters, return_type) = match self.module.signature(name) {
+                    Some(signature) => signature,
+                    None if INTRINSICS.contains(&name.as_str()) => {
+                        if operand_types.contains(&IrType::Pointer) {
+                            return Err(format!("@{} can't print a ptr", name));
+                        }
+
+                        return Ok(None);
+                    }
+                    None => return Err(format!("Unknown function @{}", name)),
+                };
+
+                if parameters.len() != operand_types.len() {
+                    return Err(format!(
+                        "Function @{} takes {} arguments but was given {}",
+                        name,
+                        parameters.len(),
+                        operand_types.len()
+                    ));
+                }
+
+                for (index, (parameter, argument)) in
+                    parameters.iter().zip(operand_types).enumerate()
+                {
+                    if parameter != argument {
+                        return Err(format!(
+                            "Argument {} of @{} should be {} but found {}",
+                            index, name, parameter, argument
+                        ));
+                    }
+                }
+
+                if return_type == IrType::Void {
+                    return Ok(None);
+                }
+
+                return Ok(Some(return_type));
+            }
+            InstructionKind::LoadGlobal(name) => {
+                return match self.globals.get(name.as_str()) {
+                    Some(global_type) => Ok(Some(*global_type)),
+                    None => Err(format!("Unknown global @{}", name)),
+                };
+            }
+            InstructionKind::StoreGlobal(name, _) => {
+                return match self.globals.get(name.as_str()) {
+                    Some(global_type) if *global_type == operand_types[0] => Ok(None),
+                    Some(global_type) => Err(format!(
+                        "Can't store {} in global @{} of type {}",
+                        operand_types[0], name, global_type
+                    )),
+                    None => Err(format!("Unknown global @{}", name)),
+                };
+            }
+            InstructionKind::Alloc(element_type, _) => {
+                check_element_type(*element_type)?;
+                check_count(operand_types[0])?;
+
+                return Ok(Some(IrType::Pointer));
+            }
+            InstructionKind::Resize(element_type, _, _) => {
+                check_element_type(*element_type)?;
+                check_pointer(operand_types[0])?;
+                check_count(operand_types[1])?;
+
+                return Ok(Some(IrType::Pointer));
+            }
+            InstructionKind::Free(_) => {
+                check_pointer(operand_types[0])?;
+
+                return Ok(None);
+            }
+            InstructionKind::Load(_, _) => {
+                check_pointer(operand_types[0])?;
+                check_index(operand_types[1])?;
+
+                let Some(result) = instruction.result else {
+                    return Err(String::from("A load needs a result to load into"));
+                };
+
+                let element_type = self.function.value_type(result);
+
+                check_element_type(element_type)?;
+
+                return Ok(Some(element_type));
+            }
+            InstructionKind::Store(_, _, _) => {
+                check_pointer(operand_types[0])?;
+                check_index(operand_types[1])?;
+                check_element_type(operand_types[2])?;
+
+                return Ok(None);
+            }
+        }
+    }
+
+    fn verify_instruction(&mut self, instruction: &Instruction, block: BlockId, position: usize) {
+        let span = instruction.span;
+
+        let mut operand_types: Vec<IrType> = Vec::new();
+
+        for operand in instruction.operands() {
+            match self.use_value(operand, block, position, span) {
+                Some(operand_type) => operand_types.push(operand_type),
+                None => return,
+            }
+        }
+
+        let result_type = match self.instruction_type(instruction, &operand_types) {
+            Ok(result_type) => result_type,
+            Err(message) => {
+                self.report(message, span);
+
+                return;
+            }
+        };
+
+        match (instruction.result, result_type) {
+            (Some(result), Some(result_type)) => {
+                let found = self.function.value_type(result);
+
+                self.expect_type(found, result_type, &result.to_string(), span);
+            }
+            (Some(result), None) => self.report(
+                format!(
+                    "{} is assigned from an instruction without a result",
+                    result
+                ),
+                span,
+            ),
+            (None, Some(_)) | (None, None) => {}
+        }
+    }
+
+    fn verify_block_call(&mut self, target: &BlockCall, block: BlockId, position: usize) {
+        let span = self.function.span;
+
+        let parameters = match self.function.blocks.get(target.block.0) {
+            Some(target_block) => &target_block.parameters,
+            None => {
+                self.report_function(format!("Unknown block {}", target.block));
+
+                return;
+            }
+        };
+
+        if parameters.len() != target.arguments.len() {
+            self.report_function(format!(
+                "{} takes {} arguments but {} passes {}",
+                target.block,
+                parameters.len(),
+                block,
+                target.arguments.len()
+            ));
+
+            return;
+        }
+
+        for (parameter, argument) in parameters.iter().zip(&target.arguments) {
+            if let Some(argument_type) = self.use_value(*argument, block, position, span) {
+                let parameter_type = self.function.value_type(*parameter);
+
+                self.expect_type(
+                    argument_type,
+                    parameter_type,
+                    &format!("the argument for {}", parameter),
+                    span,
+                );
+            }
+        }
+    }
+
+    fn verify_terminator(&mut self, terminator: &Terminator, block: BlockId, position: usize) {
+        let span = self.function.span;
+
+        match terminator {
+            Terminator::Jump(target) => self.verify_block_call(target, block, position),
+            Terminator::Branch(condition, then_target, else_target) => {
+                if let Some(condition_type) = self.use_value(*condition, block, position, span) {
+                    self.expect_type(condition_type, IrType::Bool, "the branch condition", span);
+                }
+
+                self.verify_block_call(then_target, block, position);
+                self.verify_block_call(else_target, block, position);
+            }
+            Terminator::Return(Some(value)) => {
+                if let Some(value_type) = self.use_value(*value, block, position, span) {
+                    self.expect_type(
+                        value_type,
+                        self.function.return_type,
+                        "the returned value",
+                        span,
+                    );
+                }
+            }
+            Terminator::Return(None) => {
+                if self.function.return_type != IrType::Void {
+                    self.report_function(format!("{} returns without a value", block));
+                }
+            }
+            Terminator::Unreachable => {}
+        }
+    }
+
+    fn verify(&mut self) {
+        let function = self.function;
+
+        if function.blocks.is_empty() {
+            self.report_function(String::from("Function has no blocks"));
+
+            return;
+        }
+
+        let entry_types: Vec<IrType> = function.blocks[0]
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.0 < function.value_types.len())
+            .map(|parameter| function.value_type(*parameter))
+            .collect();
+
+        if entry_types != function.parameters {
+            self.report_function(String::from(
+                "The entry block parameters don't match the function parameters",
+            ));
+        }
+
+        self.collect_definitions();
+
+        for (block_index, block) in function.blocks.iter().enumerate() {
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                self.verify_instruction(instruction, BlockId(block_index), index + 1);
+            }
+
+            self.verify_terminator(
+                &block.terminator,
+                BlockId(block_index),
+                block.instructions.len() + 1,
+            );
+        }
+    }
+}
+
+/// Checks that a module is well formed: every value is defined once before it is used, operand
+/// types agree, and every jump passes the right arguments to its target.
+pub fn verify(module: &Module) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    let mut globals: HashMap<&str, IrType> = HashMap::new();
+
+    for global in &module.globals {
+        if globals
+            .insert(global.name.as_str(), global.global_type)
+            .is_some()
+        {
+            diagnostics.push(Diagnostic::resolved(
+                format!("Global @{} is defined more than once", global.name),
+                (0, 0),
+                (0, 0),
+            ));
+        }
+
+        if let Constant::Integer(value) = global.value
+            && !integer_fits(value, global.global_type)
+        {
+            diagnostics.push(Diagnostic::resolved(
+                format!(
+                    "Global @{} is {}, which doesn't fit in {}",
+                    global.name, value, global.global_type
+                ),
+                (0, 0),
+                (0, 0),
+            ));
+        }
+    }
+
+    for (index, extern_function) in module.externs.iter().enumerate() {
+        if module.externs[..index]
+            .iter()
+            .any(|other| other.name == extern_function.name)
+        {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "Extern @{} is declared more than once",
+                    extern_function.name
+                ),
+                extern_function.span,
+            ));
+        }
+    }
+
+    for (index, function) in module.functions.iter().enumerate() {
+        if function.external && function.name == "main" {
+            diagnostics.push(Diagnostic::error(
+                String::from("Function @main can't be extern, since it's wrapped by the C main"),
+                function.span,
+            ));
+        }
+
+        if module.is_extern(&function.name) {
+            diagnostics.push(Diagnostic::error(
+                format!("Function @{} is also declared as an extern", function.name),
+                function.span,
+            ));
+        }
+
+        if module.functions[..index]
+            .iter()
+            .any(|other| other.name == function.name)
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("Function @{} is defined more than once", function.name),
+                function.span,
+            ));
+        }
+
+        let mut verifier = FunctionVerifier {
+            module,
+            function,
+            globals: globals.clone(),
+            definitions: HashMap::new(),
+            dominators: dominators(function),
+            diagnostics: Vec::new(),
+        };
+
+        verifier.verify();
+
+        diagnostics.append(&mut verifier.diagnostics);
+    }
+
+    return diagnostics;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_parser;
+    use crate::source_map::SourceMap;
+
+    /// The messages of every diagnostic the verifier reports for a module written as text.
+    fn verify_text(code: &str) -> Vec<String> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.ir"), String::from(code));
+
+        let module = match ir_parser::parse(source_map.source(file), file) {
+            Ok(module) => module,
+            Err(diagnostic) => panic!("{}", diagnostic.display(&source_map)),
+        };
+
+        return verify(&module)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+    }
+
+    fn assert_rejected(code: &str, message: &str) {
+        let messages = verify_text(code);
+
+        assert!(
+            messages.iter().any(|found| found.contains(message)),
+            "expected an error containing {:?}, got {:?}",
+            message,
+            messages
+        );
+    }
+
+    #[test]
+    fn accepts_a_well_formed_module() {
+        let messages = verify_text(
+            "global @g: u32 = 4294967295
+
+            function @max(i32, i32) -> i32 {
+            bb0(%0: i32, %1: i32):
+                %2: bool = gt %0, %1
+                branch %2, bb1, bb2
+            bb1:
+                return %0
+            bb2:
+                return %1
+            }
+
+            function @main() -> void {
+            bb0:
+                %0: i32 = const -2147483648
+                %1: i32 = const 2147483647
+                %2: i32 = call @max(%0, %1)
+                call @print(%2)
+                return
+            }",
+        );
+
+        assert!(messages.is_empty(), "{:?}", messages);
+    }
+
+    #[test]
+    fn rejects_constants_out_of_range() {
+        let function = |constant: &str| {
+            format!(
+                "function @main() -> void {{
+                bb0:
+                    {}
+                    return
+                }}",
+                constant
+            )
+        };
+
+        assert_rejected(
+            &function("%0: i32 = const 99999999999"),
+            "Constant 99999999999 doesn't fit in i32",
+        );
+        assert_rejected(
+            &function("%0: i32 = const 2147483648"),
+            "doesn't fit in i32",
+        );
+        assert_rejected(&function("%0: u32 = const -1"), "doesn't fit in u32");
+        assert_rejected("global @g: i32 = 4294967295", "doesn't fit in i32");
+    }
+
+    #[test]
+    fn rejects_mismatched_operands() {
+        assert_rejected(
+            "function @main() -> void {
+            bb0:
+                %0: i32 = const 1
+                %1: bool = const true
+                %2: i32 = add %0, %1
+                return
+            }",
+            "Operator add needs operands of the same type but found i32 and bool",
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_dont_dominate_their_uses() {
+        assert_rejected(
+            "function @f(bool) -> i32 {
+            bb0(%0: bool):
+                branch %0, bb1, bb2
+            bb1:
+                %1: i32 = const 1
+                jump bb2
+            bb2:
+                return %1
+            }",
+            "%1 is used before it is defined",
+        );
+    }
+
+    #[test]
+    fn rejects_calls_with_the_wrong_arguments() {
+        assert_rejected(
+            "function @f(i32) -> void {
+            bb0(%0: i32):
+                return
+            }
+
+            function @main() -> void {
+            bb0:
+                %0: bool = const true
+                call @f(%0)
+                return
+            }",
+            "Argument 0 of @f should be i32 but found bool",
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_functions() {
+        assert_rejected(
+            "function @f() -> void {
+            bb0:
+                return
+            }
+
+            function @f() -> void {
+            bb0:
+                return
+            }",
+            "Function @f is defined more than once",
+        );
+    }
+
+    #[test]
+    fn rejects_returning_the_wrong_type() {
+        assert_rejected(
+            "function @f() -> i32 {
+            bb0:
+                %0: bool = const false
+                return %0
+            }",
+            "Expected the returned value to be i32 but found bool",
+        );
+    }
+
+    #[test]
+    fn accepts_heap_memory() {
+        let messages = verify_text(
+            "function @main() -> void {
+            bb0:
+                %0: u32 = const 4
+                %1: ptr = alloc f32, %0
+                %2: i32 = const 1
+                %3: f32 = const 2.5
+                store %1[%2], %3
+                %4: f32 = load %1[%2]
+                %5: ptr = resize f32, %1, %2
+                free %5
+                return
+            }",
+        );
+
+        assert!(messages.is_empty(), "{:?}", messages);
+    }
+
+    #[test]
+    fn rejects_misused_heap_memory() {
+        let function = |instructions: &str| {
+            format!(
+                "function @main() -> void {{
+                bb0:
+                    %0: i32 = const 1
+                    %1: bool = const true
+                    %2: ptr = alloc i32, %0
+                    {}
+                    return
+                }}",
+                instructions
+            )
+        };
+
+        assert_rejected(
+            &function("%3: ptr = alloc i32, %1"),
+            "Expected an integer count but found bool",
+        );
+        assert_rejected(
+            &function("%3: ptr = alloc void, %0"),
+            "Heap memory can't hold void",
+        );
+        assert_rejected(
+            &function("%3: i32 = load %0[%0]"),
+            "Expected a ptr but found i32",
+        );
+        assert_rejected(
+            &function("%3: i32 = load %2[%1]"),
+            "Expected an integer index but found bool",
+        );
+        assert_rejected(
+            &function("%3: ptr = load %2[%0]"),
+            "Heap memory can't hold ptr",
+        );
+        assert_rejected(&function("store %2[%0], %2"), "Heap memory can't hold ptr");
+        assert_rejected(&function("free %0"), "Expected a ptr but found i32");
+    }
+}
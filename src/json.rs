@@ -0,0 +1,695 @@
+use std::fmt;
+
+use crate::diagnostic::{Diagnostic, Location};
+use crate::source_map::{SourceMap, Span};
+use crate::syntax_tree::{
+    AssignmentNode, BlockNode, IndexNode, NameNode, Node, OperatorNode, ParameterNode, TypeNode,
+};
+use crate::tokenizer::Token;
+
+/// Bumped whenever a field is renamed or removed, or its meaning changes. Adding fields doesn't
+/// change the version, so readers should ignore fields they don't know.
+pub const SCHEMA_VERSION: i64 = 2;
+
+pub enum Json {
+    Null,
+    Boolean(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    /// Fields are kept in insertion order so the output is stable.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        return Json::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (String::from(name), value))
+                .collect(),
+        );
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => return Some(value),
+            _ => return None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<i64> {
+        match self {
+            Json::Number(value) => return Some(*value),
+            _ => return None,
+        }
+    }
+
+    /// Follows a path of object fields, like `["textDocument", "uri"]`.
+    pub fn path(&self, names: &[&str]) -> Option<&Json> {
+        let mut value = self;
+
+        for name in names {
+            value = value.get(name)?;
+        }
+
+        return Some(value);
+    }
+
+    /// Looks up a field of an object.
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => {
+                return fields
+                    .iter()
+                    .find(|(field_name, _)| field_name == name)
+                    .map(|(_, value)| value);
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for character in value.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            character if (character as u32) < 0x20 => write!(f, "\\u{:04x}", character as u32)?,
+            character => write!(f, "{}", character)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Boolean(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", value)?;
+                }
+
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    characters: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_white_space(&mut self) {
+        while self
+            .characters
+            .next_if(|character| character.is_ascii_whitespace())
+            .is_some()
+        {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_white_space();
+
+        match self.characters.next() {
+            Some(character) if character == expected => return Ok(()),
+            Some(character) => return Err(format!("Expected {} but got {}", expected, character)),
+            None => return Err(format!("Expected {} but the input ended", expected)),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            if self.characters.next() != Some(expected) {
+                return Err(format!("Expected {}", keyword));
+            }
+        }
+
+        return Ok(value);
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+
+        for _ in 0..4 {
+            let digit = self
+                .characters
+                .next()
+                .and_then(|character| character.to_digit(16))
+                .ok_or_else(|| String::from("Invalid unicode escape"))?;
+
+            value = value * 16 + digit;
+        }
+
+        return Ok(value);
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+
+        let mut value = String::new();
+
+        loop {
+            let character = match self.characters.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.characters.next() {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let mut code = self.hex_digits()?;
+
+                        // Characters outside the basic plane are written as a surrogate pair.
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.keyword("\\u", Json::Null)?;
+
+                            let low = self.hex_digits()?;
+
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                        }
+
+                        char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    Some(character) => character,
+                    None => return Err(String::from("Unterminated string")),
+                },
+                Some(character) => character,
+                None => return Err(String::from("Unterminated string")),
+            };
+
+            value.push(character);
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut text = String::new();
+
+        while let Some(character) = self
+            .characters
+            .next_if(|character| matches!(character, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+        {
+            text.push(character);
+        }
+
+        if let Ok(value) = text.parse::<i64>() {
+            return Ok(Json::Number(value));
+        }
+
+        // Only integers are stored, so fractions are truncated.
+        return match text.parse::<f64>() {
+            Ok(value) => Ok(Json::Number(value as i64)),
+            Err(_) => Err(format!("Invalid number {}", text)),
+        };
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_white_space();
+
+        match self.characters.peek() {
+            Some('n') => return self.keyword("null", Json::Null),
+            Some('t') => return self.keyword("true", Json::Boolean(true)),
+            Some('f') => return self.keyword("false", Json::Boolean(false)),
+            Some('"') => return Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.characters.next();
+                self.skip_white_space();
+
+                let mut values = Vec::new();
+
+                if self.characters.next_if_eq(&']').is_some() {
+                    return Ok(Json::Array(values));
+                }
+
+                loop {
+                    values.push(self.value()?);
+                    self.skip_white_space();
+
+                    match self.characters.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Json::Array(values)),
+                        _ => return Err(String::from("Expected , or ] in an array")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.characters.next();
+                self.skip_white_space();
+
+                let mut fields = Vec::new();
+
+                if self.characters.next_if_eq(&'}').is_some() {
+                    return Ok(Json::Object(fields));
+                }
+
+                loop {
+                    let name = self.string()?;
+
+                    self.expect(':')?;
+                    fields.push((name, self.value()?));
+                    self.skip_white_space();
+
+                    match self.characters.next() {
+                        Some(',') => self.skip_white_space(),
+                        Some('}') => return Ok(Json::Object(fields)),
+                        _ => return Err(String::from("Expected , or } in an object")),
+                    }
+                }
+            }
+            Some(_) => return self.number(),
+            None => return Err(String::from("Expected a value but the input ended")),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        characters: text.chars().peekable(),
+    };
+
+    let value = parser.value()?;
+
+    parser.skip_white_space();
+
+    if parser.characters.next().is_some() {
+        return Err(String::from("Unexpected text after the value"));
+    }
+
+    return Ok(value);
+}
+
+fn field(name: &str, value: Json) -> (String, Json) {
+    return (String::from(name), value);
+}
+
+fn range(range: (usize, usize)) -> Json {
+    return Json::Array(vec![
+        Json::Number(range.0 as i64),
+        Json::Number(range.1 as i64),
+    ]);
+}
+
+/// Wraps a list in a top level object that carries the schema version.
+fn document(name: &str, values: Vec<Json>) -> Json {
+    return Json::Object(vec![
+        field("schema_version", Json::Number(SCHEMA_VERSION)),
+        field(name, Json::Array(values)),
+    ]);
+}
+
+/// The byte offsets of a span, followed by the lines and characters it covers with an inclusive
+/// end, as diagnostics show them.
+fn span_fields(span: Span, source_map: &SourceMap) -> Vec<(String, Json)> {
+    let (lines, characters) = source_map.lines_and_characters(span);
+
+    return vec![
+        field(
+            "span",
+            Json::Array(vec![
+                Json::Number(span.lo as i64),
+                Json::Number(span.hi as i64),
+            ]),
+        ),
+        field("lines", range(lines)),
+        field("characters", range(characters)),
+    ];
+}
+
+/// Every node is an object with a `kind`, its span and its `children` in source order. Leaf
+/// values like names, numbers and operators go in `value`.
+struct NodeJson<'a> {
+    kind: &'static str,
+    span: Span,
+    source_map: &'a SourceMap,
+    fields: Vec<(String, Json)>,
+    children: Vec<Json>,
+}
+
+impl<'a> NodeJson<'a> {
+    fn new(kind: &'static str, span: Span, source_map: &'a SourceMap) -> NodeJson<'a> {
+        return NodeJson {
+            kind,
+            span,
+            source_map,
+            fields: Vec::new(),
+            children: Vec::new(),
+        };
+    }
+
+    fn value(mut self, value: Json) -> NodeJson<'a> {
+        self.fields.push(field("value", value));
+
+        return self;
+    }
+
+    fn field(mut self, name: &str, value: Json) -> NodeJson<'a> {
+        self.fields.push(field(name, value));
+
+        return self;
+    }
+
+    fn child(mut self, child: Json) -> NodeJson<'a> {
+        self.children.push(child);
+
+        return self;
+    }
+
+    fn children<'b>(mut self, nodes: impl IntoIterator<Item = &'b Node>) -> NodeJson<'a> {
+        let source_map = self.source_map;
+
+        self.children
+            .extend(nodes.into_iter().map(|child| node(child, source_map)));
+
+        return self;
+    }
+
+    fn build(self) -> Json {
+        let mut fields = vec![field("kind", Json::String(String::from(self.kind)))];
+
+        fields.extend(span_fields(self.span, self.source_map));
+
+        fields.extend(self.fields);
+        fields.push(field("children", Json::Array(self.children)));
+
+        return Json::Object(fields);
+    }
+}
+
+fn name(name_node: &NameNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Name", name_node.span, source_map)
+        .value(Json::String(name_node.value.clone()))
+        .build();
+}
+
+fn type_node(type_node: &TypeNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Type", type_node.span, source_map)
+        .value(Json::String(type_node.node_type.to_string()))
+        .field("pointer", Json::Boolean(type_node.pointer))
+        .children(type_node.array_size.as_deref())
+        .build();
+}
+
+fn operator(operator_node: &OperatorNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Operator", operator_node.span, source_map)
+        .value(Json::String(operator_node.operator.to_string()))
+        .build();
+}
+
+fn block(block_node: &BlockNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Block", block_node.span, source_map)
+        .children(&block_node.content)
+        .build();
+}
+
+fn assignment(assignment_node: &AssignmentNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Assignment", assignment_node.span, source_map)
+        .child(name(&assignment_node.name, source_map))
+        .child(node(&assignment_node.value, source_map))
+        .build();
+}
+
+fn index(index_node: &IndexNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Index", index_node.span, source_map)
+        .child(name(&index_node.name, source_map))
+        .child(node(&index_node.index, source_map))
+        .build();
+}
+
+fn parameter(parameter_node: &ParameterNode, source_map: &SourceMap) -> Json {
+    return NodeJson::new("Parameter", parameter_node.span, source_map)
+        .child(type_node(&parameter_node.node_type, source_map))
+        .child(name(&parameter_node.name, source_map))
+        .build();
+}
+
+pub fn node(node: &Node, source_map: &SourceMap) -> Json {
+    let span = node.get_span();
+
+    let json = match node {
+        Node::String => NodeJson::new("String", span, source_map),
+        Node::Keyword(keyword_node) => NodeJson::new("Keyword", span, source_map)
+            .value(Json::String(keyword_node.keyword.to_string())),
+        Node::Type(node) => return type_node(node, source_map),
+        Node::Operator(node) => return operator(node, source_map),
+        Node::Symbol(symbol_node) => NodeJson::new("Symbol", span, source_map)
+            .value(Json::String(symbol_node.symbol.to_string())),
+        Node::Number(number_node) => {
+            NodeJson::new("Number", span, source_map).value(Json::Number(number_node.value))
+        }
+        Node::Boolean(boolean_node) => {
+            NodeJson::new("Boolean", span, source_map).value(Json::Boolean(boolean_node.value))
+        }
+        Node::Name(node) => return name(node, source_map),
+        Node::Block(node) => return block(node, source_map),
+        Node::Assignment(node) => return assignment(node, source_map),
+        Node::VariableDefinition(definition_node) => {
+            NodeJson::new("VariableDefinition", span, source_map)
+                .field("constant", Json::Boolean(definition_node.constant))
+                .child(type_node(&definition_node.node_type, source_map))
+                .child(assignment(&definition_node.assignment, source_map))
+        }
+        Node::FunctionDefinition(function_node) => {
+            let mut json = NodeJson::new("FunctionDefinition", span, source_map)
+                .field("external", Json::Boolean(function_node.external))
+                .child(type_node(&function_node.node_type, source_map))
+                .child(name(&function_node.name, source_map));
+
+            for parameter_node in &function_node.parameters {
+                json = json.child(parameter(parameter_node, source_map));
+            }
+
+            json.child(block(&function_node.block, source_map))
+        }
+        Node::ExternFunction(function_node) => {
+            let mut json = NodeJson::new("ExternFunction", span, source_map)
+                .child(type_node(&function_node.node_type, source_map))
+                .child(name(&function_node.name, source_map));
+
+            for parameter_node in &function_node.parameters {
+                json = json.child(parameter(parameter_node, source_map));
+            }
+
+            json
+        }
+        Node::Operation(operation_node) => NodeJson::new("Operation", span, source_map)
+            .child(operator(&operation_node.operator, source_map))
+            .children(&operation_node.values),
+        Node::Group(group_node) => {
+            NodeJson::new("Group", span, source_map).children(&group_node.content)
+        }
+        Node::Array(array_node) => {
+            NodeJson::new("Array", span, source_map).children(&array_node.values)
+        }
+        Node::Comptime(comptime_node) => {
+            NodeJson::new("Comptime", span, source_map).children([comptime_node.value.as_ref()])
+        }
+        Node::Call(call_node) => NodeJson::new("Call", span, source_map)
+            .child(name(&call_node.name, source_map))
+            .children(&call_node.arguments),
+        Node::If(if_node) => NodeJson::new("If", span, source_map)
+            .children([if_node.condition.as_ref()])
+            .child(block(&if_node.block, source_map))
+            .children(if_node.else_node.as_deref()),
+        Node::Forever(forever_node) => {
+            NodeJson::new("Forever", span, source_map).child(block(&forever_node.block, source_map))
+        }
+        Node::Return(return_node) => {
+            NodeJson::new("Return", span, source_map).children(return_node.value.as_deref())
+        }
+        Node::Import(import_node) => {
+            let mut json = NodeJson::new("Import", span, source_map)
+                .field("module", name(&import_node.module, source_map));
+
+            for name_node in &import_node.names {
+                json = json.child(name(name_node, source_map));
+            }
+
+            json
+        }
+        Node::Index(node) => return index(node, source_map),
+        Node::IndexAssignment(assignment_node) => {
+            NodeJson::new("IndexAssignment", span, source_map)
+                .child(index(&assignment_node.target, source_map))
+                .children([assignment_node.value.as_ref()])
+        }
+        Node::Defer(defer_node) => {
+            NodeJson::new("Defer", span, source_map).children([defer_node.statement.as_ref()])
+        }
+        Node::Error(error_node) => NodeJson::new("Error", span, source_map)
+            .field("message", Json::String(error_node.message.clone())),
+    };
+
+    return json.build();
+}
+
+pub fn tokens(tokens: &[Token], source_map: &SourceMap) -> Json {
+    let tokens = tokens
+        .iter()
+        .map(|token| {
+            let mut fields = vec![
+                field("type", Json::String(token.token_type.to_string())),
+                field("content", Json::String(String::from(token.content))),
+            ];
+
+            fields.extend(span_fields(token.span, source_map));
+
+            Json::Object(fields)
+        })
+        .collect();
+
+    return document("tokens", tokens);
+}
+
+pub fn syntax_tree(nodes: &[Node], source_map: &SourceMap) -> Json {
+    return document(
+        "nodes",
+        nodes.iter().map(|child| node(child, source_map)).collect(),
+    );
+}
+
+pub fn diagnostics(diagnostics: &[Diagnostic], source_map: &SourceMap) -> Json {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut fields = vec![
+                field("severity", Json::String(String::from("error"))),
+                field("message", Json::String(diagnostic.message.clone())),
+            ];
+
+            match diagnostic.location {
+                Location::Span(span) => fields.extend(span_fields(span, source_map)),
+                Location::Resolved(lines, characters) => {
+                    fields.push(field("lines", range(lines)));
+                    fields.push(field("characters", range(characters)));
+                }
+            }
+
+            Json::Object(fields)
+        })
+        .collect();
+
+    return document("diagnostics", diagnostics);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::source_map::FileId;
+    use crate::syntax_tree;
+    use crate::tokenizer::{self, Lexer};
+
+    fn source(code: &str) -> (SourceMap, FileId) {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+
+        return (source_map, file);
+    }
+
+    #[test]
+    fn writes_tokens() {
+        let (source_map, file) = source("a = -1\n1x");
+        let list: Vec<Token> = Lexer::new(source_map.source(file), file).collect();
+
+        assert_eq!(
+            tokens(&list, &source_map).to_string(),
+            concat!(
+                r#"{"schema_version":2,"tokens":["#,
+                r#"{"type":"Name","content":"a","span":[0,1],"lines":[0,0],"characters":[0,0]},"#,
+                r#"{"type":"White Space","content":" ","span":[1,2],"lines":[0,0],"characters":[1,1]},"#,
+                r#"{"type":"Operator","content":"=","span":[2,3],"lines":[0,0],"characters":[2,2]},"#,
+                r#"{"type":"White Space","content":" ","span":[3,4],"lines":[0,0],"characters":[3,3]},"#,
+                r#"{"type":"Operator","content":"-","span":[4,5],"lines":[0,0],"characters":[4,4]},"#,
+                r#"{"type":"Number","content":"1","span":[5,6],"lines":[0,0],"characters":[5,5]},"#,
+                r#"{"type":"Seperator","content":"\n","span":[6,7],"lines":[0,0],"characters":[6,6]},"#,
+                r#"{"type":"Error","content":"1x","span":[7,9],"lines":[1,1],"characters":[0,1]}"#,
+                r#"]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn writes_syntax_trees() {
+        let (source_map, file) = source("i32 a = -1");
+        let tree = syntax_tree::build_syntax_tree(Lexer::new(source_map.source(file), file));
+
+        assert_eq!(
+            syntax_tree(&tree, &source_map).to_string(),
+            concat!(
+                r#"{"schema_version":2,"nodes":["#,
+                r#"{"kind":"VariableDefinition","span":[0,10],"lines":[0,0],"characters":[0,9],"constant":false,"children":["#,
+                r#"{"kind":"Type","span":[0,3],"lines":[0,0],"characters":[0,2],"value":"I32","pointer":false,"children":[]},"#,
+                r#"{"kind":"Assignment","span":[4,10],"lines":[0,0],"characters":[4,9],"children":["#,
+                r#"{"kind":"Name","span":[4,5],"lines":[0,0],"characters":[4,4],"value":"a","children":[]},"#,
+                r#"{"kind":"Operation","span":[8,10],"lines":[0,0],"characters":[8,9],"children":["#,
+                r#"{"kind":"Operator","span":[8,9],"lines":[0,0],"characters":[8,8],"value":"-","children":[]},"#,
+                r#"{"kind":"Number","span":[9,10],"lines":[0,0],"characters":[9,9],"value":1,"children":[]}"#,
+                r#"]}]}]}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn writes_diagnostics() {
+        let (source_map, file) = source("a = 1\n1x");
+
+        let mut list = tokenizer::errors(source_map.source(file), file);
+
+        list.push(Diagnostic::resolved(String::from("Bad"), (1, 1), (2, 3)));
+
+        assert_eq!(
+            diagnostics(&list, &source_map).to_string(),
+            concat!(
+                r#"{"schema_version":2,"diagnostics":["#,
+                r#"{"severity":"error","message":"Invalid number 1x, names can't start with a digit","span":[6,8],"lines":[1,1],"characters":[0,1]},"#,
+                r#"{"severity":"error","message":"Bad","lines":[1,1],"characters":[2,3]}"#,
+                r#"]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        let text = r#"{"a":[1,-2,true,false,null],"b":"\"\\\n\t\u0001é"}"#;
+
+        assert_eq!(parse(text).unwrap().to_string(), text);
+        assert_eq!(
+            parse(" { \"a\" : [ ] } ").unwrap().to_string(),
+            r#"{"a":[]}"#
+        );
+        assert!(parse("{\"a\":1} 2").is_err());
+        assert!(parse("[1,").is_err());
+    }
+}
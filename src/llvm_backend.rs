@@ -0,0 +1,1035 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::ir::{
+    BinaryOperator, BlockCall, BlockId, Constant, Function, Instruction, InstructionKind, IrType,
+    Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::source_map::SourceMap;
+
+/// Runtime support shared by every module. Division helpers report division by zero the same way
+/// the interpreter does, and wrap `i32::MIN / -1` instead of trapping. Heap allocations keep their
+/// size in bytes just before their values, so resizing knows what to zero.
+const PRELUDE: &str = "declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32)
+declare ptr @calloc(i64, i64)
+declare ptr @realloc(ptr, i64)
+declare ptr @memset(ptr, i32, i64)
+declare void @free(ptr)
+declare i32 @snprintf(ptr, i64, ptr, ...)
+declare float @strtof(ptr, ptr)
+
+@.true = private unnamed_addr constant [5 x i8] c\"true\\00\"
+@.false = private unnamed_addr constant [6 x i8] c\"false\\00\"
+@.division_by_zero = private unnamed_addr constant [42 x i8] c\"Error: Division by zero %d, %d -> %d, %d\\0A\\00\"
+@.negative_count = private unnamed_addr constant [35 x i8] c\"Error: Can't allocate %lld values\\0A\\00\"
+@.out_of_memory = private unnamed_addr constant [22 x i8] c\"Error: Out of memory\\0A\\00\"
+@.f32_format = private unnamed_addr constant [5 x i8] c\"%.*g\\00\"
+@.text = private unnamed_addr constant [3 x i8] c\"%s\\00\"
+@.nan = private unnamed_addr constant [4 x i8] c\"nan\\00\"
+
+define internal void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.division_by_zero, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i32 @mt_div_i32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %check
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+check:
+  %minimum = icmp eq i32 %a, -2147483648
+  %negative_one = icmp eq i32 %b, -1
+  %overflow = and i1 %minimum, %negative_one
+  br i1 %overflow, label %wrap, label %divide
+wrap:
+  ret i32 -2147483648
+divide:
+  %result = sdiv i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_rem_i32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %check
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+check:
+  %negative_one = icmp eq i32 %b, -1
+  br i1 %negative_one, label %wrap, label %divide
+wrap:
+  ret i32 0
+divide:
+  %result = srem i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_div_u32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %divide
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+divide:
+  %result = udiv i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_rem_u32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %divide
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+divide:
+  %result = urem i32 %a, %b
+  ret i32 %result
+}
+
+define internal void @mt_allocation_failed(i64 %count) {
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %negative_count, label %out_of_memory
+negative_count:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.negative_count, i64 %count)
+  call void @exit(i32 1)
+  unreachable
+out_of_memory:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.out_of_memory)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal ptr @mt_heap_alloc(i64 %count, i64 %size) {
+  %bytes = mul i64 %count, %size
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %error, label %allocate
+allocate:
+  %total = add i64 %bytes, 8
+  %allocation = call ptr @calloc(i64 1, i64 %total)
+  %failed = icmp eq ptr %allocation, null
+  br i1 %failed, label %error, label %allocated
+error:
+  call void @mt_allocation_failed(i64 %count)
+  unreachable
+allocated:
+  store i64 %bytes, ptr %allocation
+  %values = getelementptr i8, ptr %allocation, i64 8
+  ret ptr %values
+}
+
+define internal ptr @mt_heap_resize(ptr %pointer, i64 %count, i64 %size) {
+  %bytes = mul i64 %count, %size
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %error, label %reallocate
+reallocate:
+  %old_allocation = getelementptr i8, ptr %pointer, i64 -8
+  %old_bytes = load i64, ptr %old_allocation
+  %total = add i64 %bytes, 8
+  %allocation = call ptr @realloc(ptr %old_allocation, i64 %total)
+  %failed = icmp eq ptr %allocation, null
+  br i1 %failed, label %error, label %reallocated
+error:
+  call void @mt_allocation_failed(i64 %count)
+  unreachable
+reallocated:
+  store i64 %bytes, ptr %allocation
+  %values = getelementptr i8, ptr %allocation, i64 8
+  %grown = icmp sgt i64 %bytes, %old_bytes
+  br i1 %grown, label %zero, label %done
+zero:
+  %tail = getelementptr i8, ptr %values, i64 %old_bytes
+  %tail_bytes = sub i64 %bytes, %old_bytes
+  call ptr @memset(ptr %tail, i32 0, i64 %tail_bytes)
+  br label %done
+done:
+  ret ptr %values
+}
+
+define internal void @mt_heap_free(ptr %pointer) {
+  %allocation = getelementptr i8, ptr %pointer, i64 -8
+  call void @free(ptr %allocation)
+  ret void
+}
+
+define internal void @mt_print_f32(float %value) {
+entry:
+  %text = alloca [32 x i8]
+  %nan = fcmp uno float %value, %value
+  br i1 %nan, label %print_nan, label %format
+print_nan:
+  call i32 (ptr, ...) @printf(ptr @.nan)
+  ret void
+format:
+  %precision = phi i32 [ 1, %entry ], [ %next_precision, %retry ]
+  %extended = fpext float %value to double
+  call i32 (ptr, i64, ptr, ...) @snprintf(ptr %text, i64 32, ptr @.f32_format, i32 %precision, double %extended)
+  %parsed = call float @strtof(ptr %text, ptr null)
+  %same = fcmp oeq float %parsed, %value
+  %last = icmp eq i32 %precision, 9
+  %done = or i1 %same, %last
+  br i1 %done, label %print, label %retry
+retry:
+  %next_precision = add i32 %precision, 1
+  br label %format
+print:
+  call i32 (ptr, ...) @printf(ptr @.text, ptr %text)
+  ret void
+}
+";
+
+fn llvm_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::I32 | IrType::U32 => "i32",
+        IrType::F32 => "float",
+        IrType::Bool => "i1",
+        IrType::Void => "void",
+        IrType::Pointer => "ptr",
+    }
+}
+
+/// The C ABI passes `bool` as a byte holding 0 or 1, which LLVM has to be told about.
+fn c_parameter_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::Bool => "i1 zeroext",
+        _ => llvm_type(ir_type),
+    }
+}
+
+fn c_return_type(ir_type: IrType) -> &'static str {
+    match ir_type {
+        IrType::Bool => "zeroext i1",
+        _ => llvm_type(ir_type),
+    }
+}
+
+/// The functions `PRELUDE` declares, which externs of the same name mustn't declare again.
+const PRELUDE_FUNCTIONS: &[&str] = &[
+    "printf", "dprintf", "exit", "calloc", "realloc", "memset", "free", "snprintf", "strtof",
+];
+
+/// Externs and functions exported to C go by their C name, and everything else gets a prefix so
+/// it can't clash with C.
+fn function_name(module: &Module, name: &str) -> String {
+    if module.is_c_symbol(name) {
+        return String::from(name);
+    }
+
+    return format!("mt_{}", name);
+}
+
+/// LLVM only accepts float constants written as exact doubles, so they're always printed in hex.
+fn constant(constant: &Constant) -> String {
+    match constant {
+        Constant::Integer(value) => format!("{}", *value as u32 as i32),
+        Constant::Float(value) => format!("0x{:016X}", (*value as f64).to_bits()),
+        Constant::Boolean(value) => format!("{}", value),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for byte in text.bytes() {
+        if byte == b'"' || byte == b'\\' || !(0x20..0x7f).contains(&byte) {
+            escaped.push_str(&format!("\\{:02X}", byte));
+        } else {
+            escaped.push(byte as char);
+        }
+    }
+
+    return escaped;
+}
+
+/// Numbers metadata nodes and collects their definitions, which go at the end of the module.
+struct Metadata {
+    nodes: Vec<String>,
+}
+
+impl Metadata {
+    fn add(&mut self, node: String) -> usize {
+        self.nodes.push(node);
+
+        return self.nodes.len() - 1;
+    }
+}
+
+struct FunctionGenerator<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    source_map: &'a SourceMap,
+    metadata: &'a mut Metadata,
+    strings: &'a mut Vec<String>,
+    subprogram: usize,
+    constants: HashMap<ValueId, String>,
+    output: String,
+    temporary_count: usize,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    fn line(&mut self, line: &str) {
+        self.output.push_str("  ");
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn temporary(&mut self) -> String {
+        self.temporary_count += 1;
+
+        return format!("%t{}", self.temporary_count - 1);
+    }
+
+    /// Constants are folded into their uses, since LLVM has no instruction that just makes one.
+    fn operand(&self, value: ValueId) -> String {
+        match self.constants.get(&value) {
+            Some(constant) => return constant.clone(),
+            None => return format!("%v{}", value.0),
+        }
+    }
+
+    fn typed_operand(&self, value: ValueId) -> String {
+        return format!(
+            "{} {}",
+            llvm_type(self.function.value_type(value)),
+            self.operand(value)
+        );
+    }
+
+    /// Widens an integer to the i64 that sizes and offsets are counted in.
+    fn widen(&mut self, value: ValueId) -> String {
+        let extension = if self.function.value_type(value) == IrType::U32 {
+            "zext"
+        } else {
+            "sext"
+        };
+
+        let widened = self.temporary();
+        let statement = format!(
+            "{} = {} {} to i64",
+            widened,
+            extension,
+            self.typed_operand(value)
+        );
+
+        self.line(&statement);
+
+        return widened;
+    }
+
+    /// Works out the address of `pointer[index]`.
+    fn element(&mut self, element_type: IrType, pointer: ValueId, index: ValueId) -> String {
+        let index = self.widen(index);
+        let address = self.temporary();
+        let statement = format!(
+            "{} = getelementptr {}, ptr {}, i64 {}",
+            address,
+            llvm_type(element_type),
+            self.operand(pointer),
+            index
+        );
+
+        self.line(&statement);
+
+        return address;
+    }
+
+    fn location(&mut self, instruction: &Instruction) -> String {
+        let (lines, characters) = self.source_map.lines_and_characters(instruction.span);
+
+        let location = self.metadata.add(format!(
+            "!DILocation(line: {}, column: {}, scope: !{})",
+            lines.0 + 1,
+            characters.0 + 1,
+            self.subprogram
+        ));
+
+        return format!(", !dbg !{}", location);
+    }
+
+    /// Prints the arguments separated by spaces, followed by a newline for `println`. Floats are
+    /// printed by `mt_print_f32`, so the text on either side of one goes to its own `printf`.
+    fn print(&mut self, arguments: &[ValueId], newline: bool, location: &str) {
+        let mut text = String::new();
+        let mut values: Vec<String> = Vec::new();
+
+        let printed = arguments.iter().filter(|argument| {
+            !matches!(
+                self.function.value_type(**argument),
+                IrType::Void | IrType::Pointer
+            )
+        });
+
+        for (index, argument) in printed.enumerate() {
+            if index > 0 {
+                text.push(' ');
+            }
+
+            match self.function.value_type(*argument) {
+                IrType::I32 => {
+                    text.push_str("%d");
+                    values.push(self.typed_operand(*argument));
+                }
+                IrType::U32 => {
+                    text.push_str("%u");
+                    values.push(self.typed_operand(*argument));
+                }
+                IrType::F32 => {
+                    self.printf(&mut text, &mut values, location);
+
+                    let statement = format!(
+                        "call void @mt_print_f32({}){}",
+                        self.typed_operand(*argument),
+                        location
+                    );
+
+                    self.line(&statement);
+                }
+                IrType::Bool => {
+                    let boolean_text = self.temporary();
+                    let statement = format!(
+                        "{} = select {}, ptr @.true, ptr @.false",
+                        boolean_text,
+                        self.typed_operand(*argument)
+                    );
+
+                    self.line(&statement);
+
+                    text.push_str("%s");
+                    values.push(format!("ptr {}", boolean_text));
+                }
+                IrType::Void | IrType::Pointer => unreachable!(),
+            }
+        }
+
+        if newline {
+            text.push('\n');
+        }
+
+        self.printf(&mut text, &mut values, location);
+    }
+
+    /// Calls `printf` with the format text and values collected so far, if there's any text.
+    fn printf(&mut self, text: &mut String, values: &mut Vec<String>, location: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.strings.push(std::mem::take(text));
+
+        let mut arguments = vec![format!("ptr @.string.{}", self.strings.len() - 1)];
+
+        arguments.append(values);
+
+        let statement = format!(
+            "call i32 (ptr, ...) @printf({}){}",
+            arguments.join(", "),
+            location
+        );
+
+        self.line(&statement);
+    }
+
+    fn binary(
+        &self,
+        operator: BinaryOperator,
+        value_a: ValueId,
+        value_b: ValueId,
+        instruction: &Instruction,
+    ) -> String {
+        let value_type = self.function.value_type(value_a);
+        let operands = format!(
+            "{} {}, {}",
+            llvm_type(value_type),
+            self.operand(value_a),
+            self.operand(value_b)
+        );
+
+        if let (BinaryOperator::Divide | BinaryOperator::Remainder, IrType::I32 | IrType::U32) =
+            (operator, value_type)
+        {
+            let helper = if operator == BinaryOperator::Divide {
+                "div"
+            } else {
+                "rem"
+            };
+
+            let (lines, characters) = self.source_map.lines_and_characters(instruction.span);
+
+            return format!(
+                "call i32 @mt_{}_{}(i32 {}, i32 {}, i32 {}, i32 {}, i32 {}, i32 {})",
+                helper,
+                value_type,
+                self.operand(value_a),
+                self.operand(value_b),
+                lines.0,
+                characters.0,
+                lines.1,
+                characters.1
+            );
+        }
+
+        let float = value_type == IrType::F32;
+        let signed = value_type != IrType::U32;
+
+        let opcode = match operator {
+            BinaryOperator::Add if float => "fadd",
+            BinaryOperator::Add => "add",
+            BinaryOperator::Subtract if float => "fsub",
+            BinaryOperator::Subtract => "sub",
+            BinaryOperator::Multiply if float => "fmul",
+            BinaryOperator::Multiply => "mul",
+            BinaryOperator::Divide => "fdiv",
+            BinaryOperator::Remainder => "frem",
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+            BinaryOperator::Equal if float => "fcmp oeq",
+            BinaryOperator::Equal => "icmp eq",
+            BinaryOperator::NotEqual if float => "fcmp une",
+            BinaryOperator::NotEqual => "icmp ne",
+            BinaryOperator::LessThan if float => "fcmp olt",
+            BinaryOperator::LessThan if signed => "icmp slt",
+            BinaryOperator::LessThan => "icmp ult",
+            BinaryOperator::LessThanOrEqual if float => "fcmp ole",
+            BinaryOperator::LessThanOrEqual if signed => "icmp sle",
+            BinaryOperator::LessThanOrEqual => "icmp ule",
+            BinaryOperator::GreaterThan if float => "fcmp ogt",
+            BinaryOperator::GreaterThan if signed => "icmp sgt",
+            BinaryOperator::GreaterThan => "icmp ugt",
+            BinaryOperator::GreaterThanOrEqual if float => "fcmp oge",
+            BinaryOperator::GreaterThanOrEqual if signed => "icmp sge",
+            BinaryOperator::GreaterThanOrEqual => "icmp uge",
+        };
+
+        return format!("{} {}", opcode, operands);
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let location = self.location(instruction);
+
+        let expression = match &instruction.kind {
+            InstructionKind::Constant(_) => return,
+            InstructionKind::Binary(operator, value_a, value_b) => {
+                self.binary(*operator, *value_a, *value_b, instruction)
+            }
+            InstructionKind::Unary(operator, value) => {
+                let value_type = self.function.value_type(*value);
+                let operand = self.typed_operand(*value);
+
+                match (operator, value_type) {
+                    (UnaryOperator::Negate, IrType::F32) => format!("fneg {}", operand),
+                    (UnaryOperator::Negate, _) => format!("sub i32 0, {}", self.operand(*value)),
+                    (UnaryOperator::Not, IrType::Bool) => format!("xor {}, true", operand),
+                    (UnaryOperator::Not, _) => format!("xor {}, -1", operand),
+                }
+            }
+            InstructionKind::Call(name, arguments) if name == "print" || name == "println" => {
+                self.print(arguments, name == "println", &location);
+
+                return;
+            }
+            InstructionKind::Call(name, arguments) => {
+                let return_type = match instruction.result {
+                    Some(result) => self.function.value_type(result),
+                    None => IrType::Void,
+                };
+
+                let c_symbol = self.module.is_c_symbol(name);
+
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| {
+                        if c_symbol {
+                            return format!(
+                                "{} {}",
+                                c_parameter_type(self.function.value_type(*argument)),
+                                self.operand(*argument)
+                            );
+                        }
+
+                        return self.typed_operand(*argument);
+                    })
+                    .collect();
+
+                let return_type = if c_symbol {
+                    c_return_type(return_type)
+                } else {
+                    llvm_type(return_type)
+                };
+
+                format!(
+                    "call {} @{}({})",
+                    return_type,
+                    function_name(self.module, name),
+                    arguments.join(", ")
+                )
+            }
+            InstructionKind::LoadGlobal(name) => {
+                let global_type = self.function.value_type(instruction.result.unwrap());
+
+                format!("load {}, ptr @mt_global_{}", llvm_type(global_type), name)
+            }
+            InstructionKind::StoreGlobal(name, value) => {
+                format!(
+                    "store {}, ptr @mt_global_{}",
+                    self.typed_operand(*value),
+                    name
+                )
+            }
+            InstructionKind::Alloc(element_type, count) => {
+                let count = self.widen(*count);
+
+                format!(
+                    "call ptr @mt_heap_alloc(i64 {}, i64 {})",
+                    count,
+                    element_type.size()
+                )
+            }
+            InstructionKind::Resize(element_type, pointer, count) => {
+                let count = self.widen(*count);
+
+                format!(
+                    "call ptr @mt_heap_resize(ptr {}, i64 {}, i64 {})",
+                    self.operand(*pointer),
+                    count,
+                    element_type.size()
+                )
+            }
+            InstructionKind::Free(pointer) => {
+                format!("call void @mt_heap_free(ptr {})", self.operand(*pointer))
+            }
+            InstructionKind::Load(pointer, index) => {
+                let element_type = self.function.value_type(instruction.result.unwrap());
+                let address = self.element(element_type, *pointer, *index);
+
+                format!("load {}, ptr {}", llvm_type(element_type), address)
+            }
+            InstructionKind::Store(pointer, index, value) => {
+                let element_type = self.function.value_type(*value);
+                let address = self.element(element_type, *pointer, *index);
+
+                format!("store {}, ptr {}", self.typed_operand(*value), address)
+            }
+        };
+
+        let statement = match instruction.result {
+            Some(result) => format!("%v{} = {}{}", result.0, expression, location),
+            None => format!("{}{}", expression, location),
+        };
+
+        self.line(&statement);
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        let statement = match terminator {
+            Terminator::Jump(target) => format!("br label %{}", target.block),
+            Terminator::Branch(_, then_target, else_target)
+                if then_target.block == else_target.block =>
+            {
+                format!("br label %{}", then_target.block)
+            }
+            Terminator::Branch(condition, then_target, else_target) => format!(
+                "br i1 {}, label %{}, label %{}",
+                self.operand(*condition),
+                then_target.block,
+                else_target.block
+            ),
+            Terminator::Return(Some(value)) => format!("ret {}", self.typed_operand(*value)),
+            Terminator::Return(None) => String::from("ret void"),
+            Terminator::Unreachable => String::from("unreachable"),
+        };
+
+        self.line(&statement);
+    }
+
+    /// Works out the incoming value for each block parameter along every edge, as phi operands.
+    /// A branch with both sides going to the same block picks its arguments with selects named
+    /// after the parameter and predecessor, since a phi can only have one entry per edge.
+    fn incoming(&self) -> Vec<Vec<Vec<(String, BlockId)>>> {
+        let function = self.function;
+
+        let mut incoming: Vec<Vec<Vec<(String, BlockId)>>> = function
+            .blocks
+            .iter()
+            .map(|block| vec![Vec::new(); block.parameters.len()])
+            .collect();
+
+        let mut add = |target: &BlockCall, arguments: Vec<String>, predecessor: BlockId| {
+            for (index, argument) in arguments.into_iter().enumerate() {
+                incoming[target.block.0][index].push((argument, predecessor));
+            }
+        };
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            let predecessor = BlockId(index);
+
+            match &block.terminator {
+                Terminator::Branch(_, then_target, else_target)
+                    if then_target.block == else_target.block =>
+                {
+                    let selected = function.blocks[then_target.block.0]
+                        .parameters
+                        .iter()
+                        .map(|parameter| format!("%v{}.{}", parameter.0, index))
+                        .collect();
+
+                    add(then_target, selected, predecessor);
+                }
+                terminator => {
+                    for target in terminator.successors() {
+                        let arguments = target
+                            .arguments
+                            .iter()
+                            .map(|argument| self.operand(*argument))
+                            .collect();
+
+                        add(target, arguments, predecessor);
+                    }
+                }
+            }
+        }
+
+        return incoming;
+    }
+
+    fn generate(&mut self) {
+        let function = self.function;
+
+        let parameters: Vec<String> = function.blocks[0]
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let parameter_type = function.value_type(*parameter);
+
+                if function.external {
+                    return format!("{} %v{}", c_parameter_type(parameter_type), parameter.0);
+                }
+
+                return format!("{} %v{}", llvm_type(parameter_type), parameter.0);
+            })
+            .collect();
+
+        for block in &function.blocks {
+            for instruction in &block.instructions {
+                if let InstructionKind::Constant(value) = &instruction.kind {
+                    self.constants
+                        .insert(instruction.result.unwrap(), constant(value));
+                }
+            }
+        }
+
+        let incoming = self.incoming();
+
+        let (linkage, return_type) = if function.external {
+            ("", c_return_type(function.return_type))
+        } else {
+            ("internal ", llvm_type(function.return_type))
+        };
+
+        self.output.push_str(&format!(
+            "define {}{} @{}({}) !dbg !{} {{\n",
+            linkage,
+            return_type,
+            function_name(self.module, &function.name),
+            parameters.join(", "),
+            self.subprogram
+        ));
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            self.output.push_str(&format!("{}:\n", BlockId(index)));
+
+            if index > 0 {
+                for (parameter, incoming) in block.parameters.iter().zip(&incoming[index]) {
+                    let entries: Vec<String> = incoming
+                        .iter()
+                        .map(|(argument, predecessor)| {
+                            format!("[ {}, %{} ]", argument, predecessor)
+                        })
+                        .collect();
+
+                    let statement = format!(
+                        "%v{} = phi {} {}",
+                        parameter.0,
+                        llvm_type(function.value_type(*parameter)),
+                        entries.join(", ")
+                    );
+
+                    self.line(&statement);
+                }
+            }
+
+            for instruction in &block.instructions {
+                self.instruction(instruction);
+            }
+
+            if let Terminator::Branch(condition, then_target, else_target) = &block.terminator
+                && then_target.block == else_target.block
+            {
+                let parameters = &function.blocks[then_target.block.0].parameters;
+
+                for ((parameter, then_argument), else_argument) in parameters
+                    .iter()
+                    .zip(&then_target.arguments)
+                    .zip(&else_target.arguments)
+                {
+                    let statement = format!(
+                        "%v{}.{} = select i1 {}, {}, {}",
+                        parameter.0,
+                        index,
+                        self.operand(*condition),
+                        self.typed_operand(*then_argument),
+                        self.typed_operand(*else_argument)
+                    );
+
+                    self.line(&statement);
+                }
+            }
+
+            self.terminator(&block.terminator);
+        }
+
+        self.output.push_str("}\n");
+    }
+}
+
+/// Translates a verified module to textual LLVM IR that `llc` or `clang` can compile, with debug
+/// locations resolved from the instruction spans through `source_map`.
+pub fn generate(module: &Module, source_map: &SourceMap, source_path: &str) -> String {
+    let mut metadata = Metadata { nodes: Vec::new() };
+    let mut strings: Vec<String> = Vec::new();
+
+    let path = Path::new(source_path);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from(source_path));
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+        _ => String::from("."),
+    };
+
+    let compile_unit = metadata.add(String::new());
+    let file = metadata.add(format!(
+        "!DIFile(filename: \"{}\", directory: \"{}\")",
+        escape(&file_name),
+        escape(&directory)
+    ));
+
+    metadata.nodes[compile_unit] = format!(
+        "distinct !DICompileUnit(language: DW_LANG_C99, file: !{}, producer: \"metor\", isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)",
+        file
+    );
+
+    let types = metadata.add(String::from("!{null}"));
+    let subroutine_type = metadata.add(format!("!DISubroutineType(types: !{})", types));
+    let debug_version = metadata.add(String::from("!{i32 2, !\"Debug Info Version\", i32 3}"));
+    let dwarf_version = metadata.add(String::from("!{i32 7, !\"Dwarf Version\", i32 4}"));
+
+    let mut output = format!("; ModuleID = '{}'\n", source_path);
+
+    output.push_str(&format!(
+        "source_filename = \"{}\"\n\n",
+        escape(source_path)
+    ));
+
+    for global in &module.globals {
+        output.push_str(&format!(
+            "@mt_global_{} = internal global {} {}\n",
+            global.name,
+            llvm_type(global.global_type),
+            constant(&global.value)
+        ));
+    }
+
+    if !module.globals.is_empty() {
+        output.push('\n');
+    }
+
+    output.push_str(PRELUDE);
+
+    for extern_function in &module.externs {
+        if PRELUDE_FUNCTIONS.contains(&extern_function.name.as_str()) {
+            continue;
+        }
+
+        let parameters: Vec<&str> = extern_function
+            .parameters
+            .iter()
+            .map(|parameter| c_parameter_type(*parameter))
+            .collect();
+
+        output.push_str(&format!(
+            "\ndeclare {} @{}({})",
+            c_return_type(extern_function.return_type),
+            extern_function.name,
+            parameters.join(", ")
+        ));
+    }
+
+    if !module.externs.is_empty() {
+        output.push('\n');
+    }
+
+    // Functions imported from a module get their module's file.
+    let mut files: HashMap<&str, usize> = HashMap::from([(source_path, file)]);
+
+    for function in &module.functions {
+        let (lines, _) = source_map.lines_and_characters(function.span);
+        let name = source_map.name(function.span.file);
+
+        let function_file = *files.entry(name).or_insert_with(|| {
+            metadata.add(format!(
+                "!DIFile(filename: \"{}\", directory: \".\")",
+                escape(name)
+            ))
+        });
+
+        let subprogram = metadata.add(format!(
+            "distinct !DISubprogram(name: \"{}\", linkageName: \"{}\", scope: !{}, file: !{}, line: {}, type: !{}, scopeLine: {}, spFlags: DISPFlagDefinition, unit: !{})",
+            function.name,
+            function_name(module, &function.name),
+            function_file,
+            function_file,
+            lines.0 + 1,
+            subroutine_type,
+            lines.0 + 1,
+            compile_unit
+        ));
+
+        let mut generator = FunctionGenerator {
+            module,
+            function,
+            source_map,
+            metadata: &mut metadata,
+            strings: &mut strings,
+            subprogram,
+            constants: HashMap::new(),
+            output: String::new(),
+            temporary_count: 0,
+        };
+
+        generator.generate();
+
+        output.push('\n');
+        output.push_str(&generator.output);
+    }
+
+    let main = module
+        .functions
+        .iter()
+        .find(|function| function.name == "main" && function.parameters.is_empty());
+
+    if let Some(main) = main {
+        output.push_str("\ndefine i32 @main() {\n");
+
+        if main.return_type == IrType::I32 {
+            output.push_str("  %result = call i32 @mt_main()\n  ret i32 %result\n");
+        } else {
+            output.push_str(&format!(
+                "  call {} @mt_main()\n  ret i32 0\n",
+                llvm_type(main.return_type)
+            ));
+        }
+
+        output.push_str("}\n");
+    }
+
+    if !strings.is_empty() {
+        output.push('\n');
+    }
+
+    for (index, string) in strings.iter().enumerate() {
+        output.push_str(&format!(
+            "@.string.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+            index,
+            string.len() + 1,
+            escape(string)
+        ));
+    }
+
+    output.push_str(&format!(
+        "\n!llvm.dbg.cu = !{{!{}}}\n!llvm.module.flags = !{{!{}, !{}}}\n\n",
+        compile_unit, debug_version, dwarf_version
+    ));
+
+    for (index, node) in metadata.nodes.iter().enumerate() {
+        output.push_str(&format!("!{} = {}\n", index, node));
+    }
+
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+    use crate::ir_lowering;
+
+    /// Generates LLVM IR for `tests/llvm/<name>.mt` and compares it with `tests/llvm/<name>.ll`.
+    /// Running the tests with `UPDATE_SNAPSHOTS=1` writes the output to the `.ll` file instead.
+    fn assert_snapshot(name: &str) {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/llvm");
+        let source_path = format!("{}.mt", name);
+        let snapshot_path = directory.join(format!("{}.ll", name));
+
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(
+            source_path.clone(),
+            fs::read_to_string(directory.join(&source_path)).unwrap(),
+        );
+
+        let tree = match crate::analyze(&mut source_map, file) {
+            Ok(tree) => tree,
+            Err(diagnostics) => panic!("{}", diagnostics[0].display(&source_map)),
+        };
+
+        let module = match ir_lowering::lower(&tree) {
+            Ok(module) => module,
+            Err(diagnostic) => panic!("{}", diagnostic.display(&source_map)),
+        };
+
+        let output = generate(&module, &source_map, &source_path);
+
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot_path, output).unwrap();
+
+            return;
+        }
+
+        let expected = fs::read_to_string(&snapshot_path).unwrap_or_default();
+
+        assert!(
+            output == expected,
+            "The LLVM IR for {} differs from {}, run the tests with UPDATE_SNAPSHOTS=1 if that's \
+             expected:\n{}",
+            source_path,
+            snapshot_path.display(),
+            output
+        );
+    }
+
+    #[test]
+    fn calls() {
+        assert_snapshot("calls");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_snapshot("control_flow");
+    }
+
+    #[test]
+    fn memory() {
+        assert_snapshot("memory");
+    }
+
+    #[test]
+    fn externs() {
+        assert_snapshot("externs");
+    }
+}
@@ -0,0 +1,1015 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use crate::constant_folding;
+use crate::diagnostic::{Diagnostic, Location};
+use crate::incremental::{Edit, IncrementalTree};
+use crate::json::{self, Json};
+use crate::memory_checker;
+use crate::modules;
+use crate::name_resolution::{self, DefinitionKind, Resolution};
+use crate::source_map::{ColumnEncoding, FileId, SourceMap, Span};
+use crate::syntax_tree;
+use crate::tokenizer::{self, Lexer, TokenType};
+use crate::type_checker;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The semantic token types, in the order their indices are sent in.
+const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "type",
+    "function",
+    "variable",
+    "parameter",
+    "number",
+    "operator",
+    "comment",
+];
+const TOKEN_MODIFIERS: &[&str] = &["declaration", "readonly"];
+
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+const SYMBOL_CONSTANT: i64 = 14;
+
+/// A JSON-RPC error code and message.
+type ResponseError = (i64, String);
+
+/// What the server knows about an open document, rebuilt whenever it changes. The syntax tree is
+/// kept so an edit only re-parses what it touched. Tokens are lexed again when they're needed
+/// rather than kept.
+struct Document {
+    source_map: SourceMap,
+    file: FileId,
+    tree: IncrementalTree,
+    resolution: Resolution,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn analyze(uri: &str, mut tree: IncrementalTree) -> Document {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(String::from(uri), String::from(tree.text()));
+
+    let resolution = name_resolution::resolve(tree.nodes());
+
+    let mut diagnostics = tokenizer::errors(tree.text(), file);
+
+    diagnostics.extend(syntax_tree::errors(tree.nodes()));
+
+    // Later passes expect a tree that parsed, like they do in `check`.
+    if diagnostics.is_empty() {
+        let mut folded_tree = tree.nodes().to_vec();
+
+        diagnostics = modules::link(&mut folded_tree, &mut source_map);
+
+        if diagnostics.is_empty() {
+            diagnostics = constant_folding::fold_constants(&mut folded_tree);
+        }
+
+        if diagnostics.is_empty() {
+            diagnostics = type_checker::check(&folded_tree);
+        }
+
+        if diagnostics.is_empty() {
+            diagnostics = memory_checker::check(&folded_tree);
+        }
+    }
+
+    return Document {
+        source_map,
+        file,
+        tree,
+        resolution,
+        diagnostics,
+    };
+}
+
+fn position((line, character): (usize, usize)) -> Json {
+    return Json::object(vec![
+        ("line", Json::Number(line as i64)),
+        ("character", Json::Number(character as i64)),
+    ]);
+}
+
+impl Document {
+    /// The range of a span, with columns counted in the encoding the client asked for.
+    fn range(&self, span: Span, encoding: ColumnEncoding) -> Json {
+        return Json::object(vec![
+            (
+                "start",
+                position(self.source_map.location(self.file, span.lo, encoding)),
+            ),
+            (
+                "end",
+                position(self.source_map.location(self.file, span.hi, encoding)),
+            ),
+        ]);
+    }
+
+    fn location(&self, uri: &str, span: Span, encoding: ColumnEncoding) -> Json {
+        return Json::object(vec![
+            ("uri", Json::String(String::from(uri))),
+            ("range", self.range(span, encoding)),
+        ]);
+    }
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Result<Json, String>> {
+    let mut length: Option<usize> = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Some(Err(String::from("Missing Content-Length header")));
+    };
+
+    let mut body = vec![0; length];
+
+    input.read_exact(&mut body).ok()?;
+
+    return Some(json::parse(&String::from_utf8_lossy(&body)));
+}
+
+/// Picks UTF-8 columns when the client supports them, and the UTF-16 default of LSP otherwise.
+fn position_encoding(params: &Json) -> ColumnEncoding {
+    if let Some(Json::Array(encodings)) =
+        params.path(&["capabilities", "general", "positionEncodings"])
+        && encodings
+            .iter()
+            .any(|encoding| encoding.as_str() == Some("utf-8"))
+    {
+        return ColumnEncoding::Utf8;
+    }
+
+    return ColumnEncoding::Utf16;
+}
+
+fn capabilities(encoding: ColumnEncoding) -> Json {
+    let strings = |values: &[&str]| {
+        Json::Array(
+            values
+                .iter()
+                .map(|value| Json::String(String::from(*value)))
+                .collect(),
+        )
+    };
+
+    return Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                (
+                    "positionEncoding",
+                    Json::String(String::from(match encoding {
+                        ColumnEncoding::Utf8 => "utf-8",
+                        _ => "utf-16",
+                    })),
+                ),
+                ("textDocumentSync", Json::Number(2)),
+                ("hoverProvider", Json::Boolean(true)),
+                ("definitionProvider", Json::Boolean(true)),
+                ("referencesProvider", Json::Boolean(true)),
+                ("documentSymbolProvider", Json::Boolean(true)),
+                (
+                    "semanticTokensProvider",
+                    Json::object(vec![
+                        (
+                            "legend",
+                            Json::object(vec![
+                                ("tokenTypes", strings(TOKEN_TYPES)),
+                                ("tokenModifiers", strings(TOKEN_MODIFIERS)),
+                            ]),
+                        ),
+                        ("full", Json::Boolean(true)),
+                    ]),
+                ),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![("name", Json::String(String::from("metor")))]),
+        ),
+    ]);
+}
+
+struct Server<W: Write> {
+    documents: HashMap<String, Document>,
+    encoding: ColumnEncoding,
+    shut_down: bool,
+    output: W,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Json) {
+        let body = message.to_string();
+
+        let _ = write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = self.output.flush();
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::String(String::from("2.0"))),
+            ("method", Json::String(String::from(method))),
+            ("params", params),
+        ]));
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) {
+        let diagnostics = match self.documents.get(uri) {
+            Some(document) => document
+                .diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    let range = match diagnostic.location {
+                        Location::Span(span) => document.range(span, self.encoding),
+                        // Resolved positions include their last character, while ranges end
+                        // just after it.
+                        Location::Resolved(lines, characters) => Json::object(vec![
+                            ("start", position((lines.0, characters.0))),
+                            ("end", position((lines.1, characters.1 + 1))),
+                        ]),
+                    };
+
+                    Json::object(vec![
+                        ("range", range),
+                        ("severity", Json::Number(1)),
+                        ("source", Json::String(String::from("metor"))),
+                        ("message", Json::String(diagnostic.message.clone())),
+                    ])
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.notify(
+            "textDocument/publishDiagnostics",
+            Json::object(vec![
+                ("uri", Json::String(String::from(uri))),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        );
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .map(String::from);
+
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri)) => {
+                if let Some(text) = params
+                    .path(&["textDocument", "text"])
+                    .and_then(Json::as_str)
+                {
+                    let tree = IncrementalTree::new(String::from(text), FileId(0));
+
+                    self.documents.insert(uri.clone(), analyze(&uri, tree));
+                    self.publish_diagnostics(&uri);
+                }
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                if let Some(Json::Array(changes)) = params.get("contentChanges")
+                    && let Some(document) = self.documents.remove(&uri)
+                {
+                    let mut tree = document.tree;
+
+                    for change in changes {
+                        self.apply_change(&mut tree, change);
+                    }
+
+                    self.documents.insert(uri.clone(), analyze(&uri, tree));
+                    self.publish_diagnostics(&uri);
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri);
+            }
+            ("exit", _) => process::exit(if self.shut_down { 0 } else { 1 }),
+            _ => {}
+        }
+    }
+
+    /// Applies one of the changes of a `didChange`, which replaces its range, or the whole text
+    /// when it has none.
+    fn apply_change(&self, tree: &mut IncrementalTree, change: &Json) {
+        let Some(text) = change.get("text").and_then(Json::as_str) else {
+            return;
+        };
+
+        let Some(range) = change.get("range") else {
+            *tree = IncrementalTree::new(String::from(text), FileId(0));
+
+            return;
+        };
+
+        // Ranges are in the text as it is after the changes before this one.
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::new(), String::from(tree.text()));
+
+        let offset = |name| {
+            let line = range.path(&[name, "line"]).and_then(Json::as_number);
+            let character = range.path(&[name, "character"]).and_then(Json::as_number);
+
+            return match (line, character) {
+                (Some(line), Some(character)) => Some(source_map.offset(
+                    file,
+                    (line as usize, character as usize),
+                    self.encoding,
+                )),
+                _ => None,
+            };
+        };
+
+        if let (Some(lo), Some(hi)) = (offset("start"), offset("end")) {
+            tree.edit(&Edit {
+                span: Span::new(file, lo as usize, hi.max(lo) as usize),
+                text: String::from(text),
+            });
+        }
+    }
+
+    /// Finds the document and the byte offset a request is about.
+    fn target<'a>(
+        &'a self,
+        params: &'a Json,
+    ) -> Result<(&'a str, &'a Document, u32), ResponseError> {
+        let invalid = || {
+            (
+                INVALID_PARAMS,
+                String::from("Expected a text document position"),
+            )
+        };
+
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or_else(invalid)?;
+        let line = params
+            .path(&["position", "line"])
+            .and_then(Json::as_number)
+            .ok_or_else(invalid)?;
+        let character = params
+            .path(&["position", "character"])
+            .and_then(Json::as_number)
+            .ok_or_else(invalid)?;
+
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("{} is not open", uri)))?;
+
+        let offset = document.source_map.offset(
+            document.file,
+            (line as usize, character as usize),
+            self.encoding,
+        );
+
+        return Ok((uri, document, offset));
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (_, document, offset) = self.target(params)?;
+        let resolution = &document.resolution;
+
+        let Some(index) = resolution.definition_at(offset) else {
+            return Ok(Json::Null);
+        };
+
+        let definition = &resolution.definitions[index];
+
+        let text = match definition.kind {
+            DefinitionKind::Function => format!("(function) {}", definition.description),
+            kind => {
+                let kind = match kind {
+                    DefinitionKind::Global if definition.constant => "global constant",
+                    DefinitionKind::Global => "global",
+                    DefinitionKind::Parameter => "parameter",
+                    _ if definition.constant => "constant",
+                    _ => "local",
+                };
+
+                format!("({}) {}: {}", kind, definition.name, definition.description)
+            }
+        };
+
+        return Ok(Json::object(vec![(
+            "contents",
+            Json::object(vec![
+                ("kind", Json::String(String::from("markdown"))),
+                ("value", Json::String(format!("```metor\n{}\n```", text))),
+            ]),
+        )]));
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (uri, document, offset) = self.target(params)?;
+
+        return match document.resolution.definition_at(offset) {
+            Some(index) => {
+                let definition = &document.resolution.definitions[index];
+
+                Ok(document.location(uri, definition.span, self.encoding))
+            }
+            None => Ok(Json::Null),
+        };
+    }
+
+    fn references(&self, params: &Json) -> Result<Json, ResponseError> {
+        let (uri, document, offset) = self.target(params)?;
+        let resolution = &document.resolution;
+
+        let Some(index) = resolution.definition_at(offset) else {
+            return Ok(Json::Null);
+        };
+
+        let mut locations = Vec::new();
+
+        if let Some(Json::Boolean(true)) = params.path(&["context", "includeDeclaration"]) {
+            let definition = &resolution.definitions[index];
+
+            locations.push(document.location(uri, definition.span, self.encoding));
+        }
+
+        for reference in resolution.references_to(index) {
+            locations.push(document.location(uri, reference.span, self.encoding));
+        }
+
+        return Ok(Json::Array(locations));
+    }
+
+    fn document_symbols(&self, params: &Json) -> Result<Json, ResponseError> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, String::from("Expected a text document")))?;
+
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(Json::Null);
+        };
+
+        let definitions = &document.resolution.definitions;
+
+        let symbol = |index: usize, children: Vec<Json>| {
+            let definition = &definitions[index];
+
+            let kind = match definition.kind {
+                DefinitionKind::Function => SYMBOL_FUNCTION,
+                _ if definition.constant => SYMBOL_CONSTANT,
+                _ => SYMBOL_VARIABLE,
+            };
+
+            Json::object(vec![
+                ("name", Json::String(definition.name.clone())),
+                ("detail", Json::String(definition.description.clone())),
+                ("kind", Json::Number(kind)),
+                ("range", document.range(definition.node_span, self.encoding)),
+                (
+                    "selectionRange",
+                    document.range(definition.span, self.encoding),
+                ),
+                ("children", Json::Array(children)),
+            ])
+        };
+
+        let mut symbols = Vec::new();
+
+        for (index, definition) in definitions.iter().enumerate() {
+            if definition.function.is_some() {
+                continue;
+            }
+
+            let children = definitions
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| child.function == Some(index))
+                .map(|(child, _)| symbol(child, Vec::new()))
+                .collect();
+
+            symbols.push(symbol(index, children));
+        }
+
+        return Ok(Json::Array(symbols));
+    }
+
+    fn semantic_tokens(&self, params: &Json) -> Result<Json, ResponseError> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, String::from("Expected a text document")))?;
+
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(Json::Null);
+        };
+
+        let resolution = &document.resolution;
+
+        // Where each name is, what it names, and whether it's the definition.
+        let mut names: HashMap<u32, (usize, bool)> = HashMap::new();
+
+        for (index, definition) in resolution.definitions.iter().enumerate() {
+            names.insert(definition.span.lo, (index, true));
+        }
+
+        for reference in &resolution.references {
+            names.insert(reference.span.lo, (reference.definition, false));
+        }
+
+        let mut data: Vec<Json> = Vec::new();
+        let mut previous = (0, 0);
+
+        let source_map = &document.source_map;
+
+        for token in Lexer::new(source_map.source(document.file), document.file) {
+            let mut modifiers = 0;
+
+            let token_type = match token.token_type {
+                TokenType::Comment => "comment",
+                TokenType::Operator(_) => "operator",
+                TokenType::Keyword(_) | TokenType::Boolean(_) => "keyword",
+                TokenType::Type(_) => "type",
+                TokenType::Number => "number",
+                TokenType::Name => match names.get(&token.span.lo) {
+                    Some((index, declaration)) => {
+                        let definition = &resolution.definitions[*index];
+
+                        if *declaration {
+                            modifiers |= 1;
+                        }
+
+                        if definition.constant && definition.kind != DefinitionKind::Function {
+                            modifiers |= 2;
+                        }
+
+                        match definition.kind {
+                            DefinitionKind::Function => "function",
+                            DefinitionKind::Parameter => "parameter",
+                            _ => "variable",
+                        }
+                    }
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let token_type = TOKEN_TYPES.iter().position(|name| *name == token_type);
+
+            // Tokens never span lines, so their length is the difference of their columns.
+            let start = source_map.location(document.file, token.span.lo, self.encoding);
+            let end = source_map.location(document.file, token.span.hi, self.encoding);
+
+            let delta_line = start.0 - previous.0;
+            let delta_start = if delta_line == 0 {
+                start.1 - previous.1
+            } else {
+                start.1
+            };
+
+            data.extend(
+                [
+                    delta_line as i64,
+                    delta_start as i64,
+                    (end.1 - start.1) as i64,
+                    token_type.unwrap_or(0) as i64,
+                    modifiers,
+                ]
+                .map(Json::Number),
+            );
+
+            previous = start;
+        }
+
+        return Ok(Json::object(vec![("data", Json::Array(data))]));
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, ResponseError> {
+        match method {
+            "initialize" => {
+                self.encoding = position_encoding(params);
+
+                return Ok(capabilities(self.encoding));
+            }
+            "shutdown" => {
+                self.shut_down = true;
+
+                return Ok(Json::Null);
+            }
+            "textDocument/hover" => return self.hover(params),
+            "textDocument/definition" => return self.definition(params),
+            "textDocument/references" => return self.references(params),
+            "textDocument/documentSymbol" => return self.document_symbols(params),
+            "textDocument/semanticTokens/full" => return self.semantic_tokens(params),
+            _ => return Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        }
+    }
+}
+
+/// Serves the Language Server Protocol over stdin and stdout until the client exits.
+pub fn run() -> i32 {
+    return serve(io::stdin().lock(), io::stdout());
+}
+
+/// Answers the messages read from `input` until the client exits or the input ends.
+fn serve(mut input: impl BufRead, output: impl Write) -> i32 {
+    let mut server = Server {
+        documents: HashMap::new(),
+        encoding: ColumnEncoding::Utf16,
+        shut_down: false,
+        output,
+    };
+
+    while let Some(message) = read_message(&mut input) {
+        let message = match message {
+            Ok(message) => message,
+            Err(error) => {
+                eprintln!("Error: Invalid message: {}", error);
+
+                continue;
+            }
+        };
+
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+
+        let Some(id) = message.get("id") else {
+            server.notification(method, params);
+
+            continue;
+        };
+
+        let id = match id {
+            Json::Number(id) => Json::Number(*id),
+            Json::String(id) => Json::String(id.clone()),
+            _ => Json::Null,
+        };
+
+        let outcome = match server.request(method, params) {
+            Ok(result) => ("result", result),
+            Err((code, message)) => (
+                "error",
+                Json::object(vec![
+                    ("code", Json::Number(code)),
+                    ("message", Json::String(message)),
+                ]),
+            ),
+        };
+
+        server.send(Json::object(vec![
+            ("jsonrpc", Json::String(String::from("2.0"))),
+            ("id", id),
+            outcome,
+        ]));
+    }
+
+    // The client went away without asking the server to exit.
+    return if server.shut_down { 0 } else { 1 };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///test.mt";
+
+    const CODE: &str = "\
+i32 double: i32 n {
+    return n + n
+}
+
+void main {
+    i32 x = double(2)
+    println(x)
+}
+";
+
+    fn message(id: Option<i64>, method: &str, params: Json) -> String {
+        let mut fields = vec![("jsonrpc", Json::String(String::from("2.0")))];
+
+        if let Some(id) = id {
+            fields.push(("id", Json::Number(id)));
+        }
+
+        fields.push(("method", Json::String(String::from(method))));
+        fields.push(("params", params));
+
+        let body = Json::object(fields).to_string();
+
+        return format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    }
+
+    fn document() -> Json {
+        return Json::object(vec![("uri", Json::String(String::from(URI)))]);
+    }
+
+    fn at(line: i64, character: i64) -> Json {
+        return Json::object(vec![
+            ("textDocument", document()),
+            ("position", position((line as usize, character as usize))),
+        ]);
+    }
+
+    fn references(line: i64, character: i64, include_declaration: bool) -> Json {
+        return Json::object(vec![
+            ("textDocument", document()),
+            ("position", position((line as usize, character as usize))),
+            (
+                "context",
+                Json::object(vec![(
+                    "includeDeclaration",
+                    Json::Boolean(include_declaration),
+                )]),
+            ),
+        ]);
+    }
+
+    fn range(start: (usize, usize), end: (usize, usize)) -> String {
+        return Json::object(vec![("start", position(start)), ("end", position(end))]).to_string();
+    }
+
+    /// Opens `CODE`, sends `messages` after it, and returns everything the server sent back after
+    /// answering `initialize`. Requests get the id of their place in `messages`, counting from 1.
+    fn session(messages: Vec<(&str, Json)>) -> Vec<Json> {
+        let mut input = message(Some(0), "initialize", Json::object(vec![]));
+
+        input += &message(
+            None,
+            "textDocument/didOpen",
+            Json::object(vec![(
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::String(String::from(URI))),
+                    ("text", Json::String(String::from(CODE))),
+                ]),
+            )]),
+        );
+
+        for (index, (method, params)) in messages.into_iter().enumerate() {
+            let id = if method.starts_with("textDocument/did") {
+                None
+            } else {
+                Some(index as i64 + 1)
+            };
+
+            input += &message(id, method, params);
+        }
+
+        let mut output = Vec::new();
+
+        serve(input.as_bytes(), &mut output);
+
+        let mut output = output.as_slice();
+        let mut responses = Vec::new();
+
+        while let Some(response) = read_message(&mut output) {
+            responses.push(response.unwrap());
+        }
+
+        return responses.split_off(1);
+    }
+
+    fn result(responses: &[Json], id: i64) -> &Json {
+        return responses
+            .iter()
+            .find(|response| response.get("id").and_then(Json::as_number) == Some(id))
+            .and_then(|response| response.get("result"))
+            .unwrap();
+    }
+
+    fn diagnostics(responses: &[Json]) -> Vec<String> {
+        return responses
+            .iter()
+            .filter_map(|response| response.path(&["params", "diagnostics"]))
+            .map(|diagnostics| diagnostics.to_string())
+            .collect();
+    }
+
+    #[test]
+    fn hovers_over_names() {
+        let responses = session(vec![
+            ("textDocument/hover", at(6, 12)),
+            ("textDocument/hover", at(0, 16)),
+            ("textDocument/hover", at(5, 12)),
+            ("textDocument/hover", at(5, 19)),
+        ]);
+
+        let hover = |id| {
+            return result(&responses, id)
+                .path(&["contents", "value"])
+                .and_then(Json::as_str)
+                .map(String::from);
+        };
+
+        assert_eq!(hover(1).unwrap(), "```metor\n(local) x: I32\n```");
+        assert_eq!(hover(2).unwrap(), "```metor\n(parameter) n: I32\n```");
+        assert_eq!(
+            hover(3).unwrap(),
+            "```metor\n(function) double(n: I32) -> I32\n```"
+        );
+        assert_eq!(hover(4), None);
+    }
+
+    #[test]
+    fn finds_definitions() {
+        let responses = session(vec![
+            ("textDocument/definition", at(5, 14)),
+            ("textDocument/definition", at(1, 15)),
+            ("textDocument/definition", at(3, 0)),
+        ]);
+
+        assert_eq!(
+            result(&responses, 1).get("uri").unwrap().as_str(),
+            Some(URI)
+        );
+        assert_eq!(
+            result(&responses, 1).get("range").unwrap().to_string(),
+            range((0, 4), (0, 10))
+        );
+        assert_eq!(
+            result(&responses, 2).get("range").unwrap().to_string(),
+            range((0, 16), (0, 17))
+        );
+        assert_eq!(result(&responses, 3).to_string(), "null");
+    }
+
+    #[test]
+    fn finds_references() {
+        let responses = session(vec![
+            ("textDocument/references", references(1, 11, true)),
+            ("textDocument/references", references(1, 11, false)),
+            ("textDocument/references", references(4, 7, false)),
+        ]);
+
+        let ranges = |id| {
+            let Json::Array(locations) = result(&responses, id) else {
+                panic!("Expected locations");
+            };
+
+            return locations
+                .iter()
+                .map(|location| location.get("range").unwrap().to_string())
+                .collect::<Vec<_>>();
+        };
+
+        assert_eq!(
+            ranges(1),
+            [
+                range((0, 16), (0, 17)),
+                range((1, 11), (1, 12)),
+                range((1, 15), (1, 16)),
+            ]
+        );
+        assert_eq!(
+            ranges(2),
+            [range((1, 11), (1, 12)), range((1, 15), (1, 16))]
+        );
+        assert_eq!(ranges(3), Vec::<String>::new());
+    }
+
+    #[test]
+    fn lists_document_symbols() {
+        let responses = session(vec![(
+            "textDocument/documentSymbol",
+            Json::object(vec![("textDocument", document())]),
+        )]);
+
+        let Json::Array(symbols) = result(&responses, 1) else {
+            panic!("Expected symbols");
+        };
+
+        let field = |symbol: &Json, name| symbol.get(name).unwrap().to_string();
+
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(field(&symbols[0], "name"), "\"double\"");
+        assert_eq!(field(&symbols[0], "detail"), "\"double(n: I32) -> I32\"");
+        assert_eq!(field(&symbols[0], "kind"), SYMBOL_FUNCTION.to_string());
+        assert_eq!(field(&symbols[0], "range"), range((0, 0), (2, 1)));
+        assert_eq!(field(&symbols[0], "selectionRange"), range((0, 4), (0, 10)));
+        assert_eq!(
+            field(&symbols[0], "children"),
+            format!(
+                "[{{\"name\":\"n\",\"detail\":\"I32\",\"kind\":{},\"range\":{},\"selectionRange\":{},\"children\":[]}}]",
+                SYMBOL_VARIABLE,
+                range((0, 12), (0, 17)),
+                range((0, 16), (0, 17))
+            )
+        );
+        assert_eq!(field(&symbols[1], "name"), "\"main\"");
+        assert_eq!(field(&symbols[1], "range"), range((4, 0), (7, 1)));
+    }
+
+    #[test]
+    fn encodes_semantic_tokens() {
+        let responses = session(vec![(
+            "textDocument/semanticTokens/full",
+            Json::object(vec![("textDocument", document())]),
+        )]);
+
+        let Some(Json::Array(data)) = result(&responses, 1).get("data") else {
+            panic!("Expected semantic tokens");
+        };
+
+        let tokens: Vec<String> = data
+            .chunks(5)
+            .map(|token| {
+                let token: Vec<String> = token.iter().map(Json::to_string).collect();
+
+                return token.join(" ");
+            })
+            .collect();
+
+        // Line, start, length, type and modifiers, with lines and starts relative to the token
+        // before.
+        assert_eq!(
+            tokens[..8],
+            [
+                "0 0 3 1 0", // i32
+                "0 4 6 2 1", // double, declared
+                "0 8 3 1 0", // i32
+                "0 4 1 4 1", // n, declared
+                "1 4 6 0 0", // return
+                "0 7 1 4 0", // n
+                "0 2 1 6 0", // +
+                "0 2 1 4 0", // n
+            ]
+        );
+        assert_eq!(tokens.len(), 16);
+    }
+
+    #[test]
+    fn applies_ranged_changes() {
+        let change = |start, end, text: &str| {
+            return Json::object(vec![
+                ("textDocument", document()),
+                (
+                    "contentChanges",
+                    Json::Array(vec![Json::object(vec![
+                        (
+                            "range",
+                            Json::object(vec![("start", position(start)), ("end", position(end))]),
+                        ),
+                        ("text", Json::String(String::from(text))),
+                    ])]),
+                ),
+            ]);
+        };
+
+        let responses = session(vec![
+            ("textDocument/didChange", change((5, 8), (5, 9), "y")),
+            ("textDocument/hover", at(5, 8)),
+            ("textDocument/hover", at(6, 12)),
+            ("textDocument/didChange", change((6, 12), (6, 13), "y")),
+            ("textDocument/definition", at(6, 12)),
+        ]);
+
+        assert_eq!(
+            diagnostics(&responses),
+            [
+                String::from("[]"),
+                format!(
+                    "[{{\"range\":{},\"severity\":1,\"source\":\"metor\",\"message\":\"Unknown variable x\"}}]",
+                    range((6, 12), (6, 13))
+                ),
+                String::from("[]"),
+            ]
+        );
+        assert_eq!(
+            result(&responses, 2)
+                .path(&["contents", "value"])
+                .unwrap()
+                .as_str(),
+            Some("```metor\n(local) y: I32\n```")
+        );
+        assert_eq!(result(&responses, 3).to_string(), "null");
+        assert_eq!(
+            result(&responses, 5).get("range").unwrap().to_string(),
+            range((5, 8), (5, 9))
+        );
+    }
+}
@@ -1,20 +1,844 @@
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use source_map::{FileId, SourceMap, Span};
+
+mod bytecode;
+mod bytecode_compiler;
+mod c_backend;
+mod concrete_syntax_tree;
+mod constant_folding;
+mod diagnostic;
+mod dot;
+mod elf;
+mod formatter;
+mod heap;
+mod incremental;
+mod interpreter;
+mod ir;
+mod ir_lowering;
+mod ir_parser;
+mod ir_verifier;
+mod json;
+mod llvm_backend;
+mod lsp;
+mod memory_checker;
+mod modules;
+mod name_resolution;
+mod repl;
+mod source_map;
 mod syntax_tree;
 mod tokenizer;
+mod type_checker;
+mod unicode;
+mod vm;
+mod wasm_backend;
+#[cfg(test)]
+mod wasm_validator;
+mod x86_64;
+mod x86_64_backend;
+
+/// How many characters `bench` types, each one also deleted again. Every edit is checked against a
+/// full parse, which takes most of the time.
+const BENCH_EDITS: usize = 100;
+
+const USAGE: &str = "\
+Usage: metor <command> [options] <file>
+
+Commands:
+  run <file>                Run a program with the interpreter, or a .mtbc file with the vm
+  check <file>              Type check a program without running it
+  build <file> -o <out>     Compile a program to a native executable
+  fmt <file>                Print the formatted source of a program, keeping comments
+  tokens <file>             Print the tokens of a program
+  ast <file>                Print the syntax tree of a program
+  bench <file>              Time a full parse against re-parsing after single character edits
+  repl                      Start an interactive session
+  lsp                       Start a language server that talks over stdin and stdout
+  vm <file>                 Run a program with the bytecode vm
+  bytecode <file> -o <out>  Compile a program to a .mtbc bytecode file
+  disassemble <file>        Print the bytecode of a program
+  ir <file>                 Print the IR of a program
+  c <file>                  Print the generated C
+  llvm <file>               Print the generated LLVM IR
+  asm <file>                Print the generated x86-64 assembly
+  wasm <file> -o <out>      Compile a program to a WebAssembly module
+  object <file> -o <out>    Compile a program to an ELF object file
+
+Options:
+  -o <path>         Write the output to a file instead of stdout
+  --emit=<stage>    Make build stop at tokens, ast, ir, c or asm and output that instead.
+                    cst outputs the lossless syntax tree with every token and comment,
+                    dot outputs the syntax tree and cfg the control flow graphs as Graphviz
+  --check           Make fmt only check that a program is formatted, for CI
+  -l <library>      Make build link a C library that extern \"C\" functions come from
+  --json            Output tokens, syntax trees or diagnostics as JSON
+  -                 Read the program from stdin instead of a file
+
+Exit codes:
+  0  Success
+  1  The program has errors, failed at runtime, or a file could not be read or written
+  2  Invalid usage";
+
+/// Reads source code from a file, or from stdin when the path is `-`, and adds it to the source
+/// map.
+fn read_source(source_map: &mut SourceMap, path: &str) -> Option<FileId> {
+    let mut code = String::new();
+
+    let result = if path == "-" {
+        io::stdin().read_to_string(&mut code).map(|_| ())
+    } else {
+        fs::read_to_string(path).map(|read| code = read)
+    };
+
+    if let Err(error) = result {
+        eprintln!("Error: Could not read {}: {}", source_name(path), error);
+
+        return None;
+    }
+
+    return Some(source_map.add(String::from(source_name(path)), code));
+}
+
+/// The name a path is shown as in diagnostics and generated debug information.
+fn source_name(path: &str) -> &str {
+    if path == "-" {
+        return "<stdin>";
+    }
+
+    return path;
+}
+
+/// Writes text to a file, or prints it when there is no output path.
+fn write_text(text: &str, output: Option<&str>) -> i32 {
+    let Some(output) = output else {
+        let result = io::stdout().write_all(text.as_bytes());
+
+        // A closed pipe just means the reader, like `head`, has seen enough.
+        if let Err(error) = result
+            && error.kind() != io::ErrorKind::BrokenPipe
+        {
+            eprintln!("Error: Could not write to stdout: {}", error);
+
+            return 1;
+        }
+
+        return 0;
+    };
+
+    if let Err(error) = fs::write(output, text) {
+        eprintln!("Error: Could not write {}: {}", output, error);
+
+        return 1;
+    }
+
+    return 0;
+}
+
+fn run(path: &str) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let Some(file) = read_source(&mut source_map, path) else {
+        return 1;
+    };
+
+    let Some(tree) = check_tree(&mut source_map, file) else {
+        return 1;
+    };
+
+    let mut interpreter = interpreter::Interpreter::new();
+
+    if let Err(diagnostic) = interpreter.load(tree) {
+        eprintln!("{}", diagnostic.display(&source_map));
+
+        return 1;
+    }
+
+    if let Err(diagnostic) = interpreter.run_main() {
+        eprintln!("{}", diagnostic.display(&source_map));
+
+        return 1;
+    }
+
+    return 0;
+}
+
+fn report(diagnostics: Vec<diagnostic::Diagnostic>, source_map: &SourceMap) -> bool {
+    let failed = !diagnostics.is_empty();
+
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.display(source_map));
+    }
+
+    return failed;
+}
+
+/// Parses a file, links its imports, folds constants and type checks it. The modules it imports
+/// are added to the source map.
+fn analyze(
+    source_map: &mut SourceMap,
+    file: FileId,
+) -> Result<Vec<syntax_tree::Node>, Vec<diagnostic::Diagnostic>> {
+    let diagnostics = tokenizer::errors(source_map.source(file), file);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let tokens = tokenizer::Lexer::new(source_map.source(file), file);
+
+    let mut tree = syntax_tree::build_syntax_tree(tokens);
+
+    let diagnostics = syntax_tree::errors(&tree);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let diagnostics = modules::link(&mut tree, source_map);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let diagnostics = constant_folding::fold_constants(&mut tree);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let diagnostics = type_checker::check(&tree);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let diagnostics = memory_checker::check(&tree);
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    return Ok(tree);
+}
+
+/// Like `analyze`, but reports any diagnostics.
+fn check_tree(source_map: &mut SourceMap, file: FileId) -> Option<Vec<syntax_tree::Node>> {
+    match analyze(source_map, file) {
+        Ok(tree) => return Some(tree),
+        Err(diagnostics) => {
+            report(diagnostics, source_map);
+
+            return None;
+        }
+    }
+}
+
+/// Reads a `.mt` file and compiles it to bytecode, or reads a precompiled `.mtbc` file.
+fn load_program(path: &str) -> Option<bytecode::Program> {
+    let mut source_map = SourceMap::new();
+
+    let program = if path.ends_with(".mtbc") {
+        match fs::read(path) {
+            Ok(bytes) => bytecode::Program::from_bytes(&bytes),
+            Err(error) => {
+                eprintln!("Error: Could not read {}: {}", path, error);
+
+                return None;
+            }
+        }
+    } else {
+        let file = read_source(&mut source_map, path)?;
+
+        bytecode_compiler::compile(&check_tree(&mut source_map, file)?, &source_map)
+    };
+
+    return match program {
+        Ok(program) => Some(program),
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.display(&source_map));
+
+            None
+        }
+    };
+}
+
+fn run_bytecode(path: &str) -> i32 {
+    let program = match load_program(path) {
+        Some(program) => program,
+        None => return 1,
+    };
+
+    if let Err(diagnostic) = vm::Vm::new(&program).run_main() {
+        // Bytecode positions are already resolved, so no source is needed to show them.
+        eprintln!("{}", diagnostic.display(&SourceMap::new()));
+
+        return 1;
+    }
+
+    return 0;
+}
+
+fn write_bytecode(path: &str, output: &str) -> i32 {
+    let program = match load_program(path) {
+        Some(program) => program,
+        None => return 1,
+    };
+
+    if let Err(error) = fs::write(output, program.to_bytes()) {
+        eprintln!("Error: Could not write {}: {}", output, error);
+
+        return 1;
+    }
+
+    return 0;
+}
+
+fn disassemble(path: &str, output: Option<&str>) -> i32 {
+    match load_program(path) {
+        Some(program) => return write_text(&program.to_string(), output),
+        None => return 1,
+    }
+}
+
+/// Reads a `.mt` file and lowers it, or reads a `.ir` file directly, returning a verified module.
+/// The file is added to the source map, which resolves the module's spans.
+fn load_module(source_map: &mut SourceMap, path: &str) -> Option<ir::Module> {
+    let file = read_source(source_map, path)?;
+
+    let module = if path.ends_with(".ir") {
+        ir_parser::parse(source_map.source(file), file)
+    } else {
+        ir_lowering::lower(&check_tree(source_map, file)?)
+    };
+
+    let module = match module {
+        Ok(module) => module,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.display(source_map));
+
+            return None;
+        }
+    };
+
+    if report(ir_verifier::verify(&module), source_map) {
+        return None;
+    }
+
+    return Some(module);
+}
+
+/// A stage of compilation that `--emit` can stop at and output.
+#[derive(Clone, Copy)]
+enum Emit {
+    Tokens,
+    Ast,
+    Ir,
+    C,
+    Llvm,
+    Asm,
+    Cst,
+    Dot,
+    Cfg,
+}
+
+impl Emit {
+    fn parse(stage: &str) -> Option<Emit> {
+        match stage {
+            "tokens" => return Some(Emit::Tokens),
+            "ast" => return Some(Emit::Ast),
+            "ir" => return Some(Emit::Ir),
+            "c" => return Some(Emit::C),
+            "llvm" => return Some(Emit::Llvm),
+            "asm" => return Some(Emit::Asm),
+            "cst" => return Some(Emit::Cst),
+            "dot" => return Some(Emit::Dot),
+            "cfg" => return Some(Emit::Cfg),
+            _ => return None,
+        }
+    }
+}
+
+/// Runs the compiler up to a stage and writes out what that stage produced. Tokens and syntax
+/// trees can be written as JSON instead of text.
+fn emit(path: &str, stage: Emit, json: bool, output: Option<&str>) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let text = match stage {
+        Emit::Tokens | Emit::Ast | Emit::Cst | Emit::Dot => {
+            let Some(file) = read_source(&mut source_map, path) else {
+                return 1;
+            };
+
+            let tokens = tokenizer::tokenize(source_map.source(file), file);
+            let mut text = String::new();
+
+            if json && let Emit::Tokens = stage {
+                text = format!("{}\n", json::tokens(&tokens, &source_map));
+            } else if json {
+                let tree = syntax_tree::build_syntax_tree(tokens.iter().copied());
+
+                text = format!("{}\n", json::syntax_tree(&tree, &source_map));
+            } else if let Emit::Cst = stage {
+                let tree = syntax_tree::build_syntax_tree(tokens.iter().copied());
+                let concrete_tree = concrete_syntax_tree::build(tokens, &tree, &source_map);
+
+                text = format!("{}\n", concrete_tree.display());
+            } else if let Emit::Dot = stage {
+                let tree = syntax_tree::build_syntax_tree(tokens.iter().copied());
+
+                text = dot::syntax_tree(&tree, &source_map);
+            } else if let Emit::Tokens = stage {
+                for token in &tokens {
+                    text.push_str(&format!("{}\n", token));
+                }
+            } else {
+                for node in syntax_tree::build_syntax_tree(tokens) {
+                    text.push_str(&format!("{}\n", node.display(0)));
+                }
+            }
+
+            text
+        }
+        Emit::Ir | Emit::C | Emit::Llvm | Emit::Asm | Emit::Cfg => {
+            let Some(module) = load_module(&mut source_map, path) else {
+                return 1;
+            };
+
+            let name = source_name(path);
+
+            match stage {
+                Emit::C => c_backend::generate(&module, &source_map),
+                Emit::Llvm => llvm_backend::generate(&module, &source_map, name),
+                Emit::Asm => x86_64_backend::generate(&module, &source_map, name).to_string(),
+                Emit::Cfg => dot::control_flow_graphs(&module, &source_map),
+                _ => module.to_string(),
+            }
+        }
+    };
+
+    return write_text(&text, output);
+}
+
+/// Checks a program. With `json`, the diagnostics are printed to stdout as a JSON document even
+/// when there are none.
+fn check(path: &str, json: bool) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let Some(file) = read_source(&mut source_map, path) else {
+        return 1;
+    };
+
+    let diagnostics = match analyze(&mut source_map, file) {
+        Ok(_) => Vec::new(),
+        Err(diagnostics) => diagnostics,
+    };
+
+    let failed = !diagnostics.is_empty();
+
+    if json {
+        println!("{}", json::diagnostics(&diagnostics, &source_map));
+    } else {
+        report(diagnostics, &source_map);
+    }
+
+    return if failed { 1 } else { 0 };
+}
+
+/// Times parsing a whole program against re-parsing it after single character edits, typing and
+/// deleting a character at the start of names spread through the file.
+fn bench(path: &str) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let Some(file) = read_source(&mut source_map, path) else {
+        return 1;
+    };
+
+    let code = source_map.source(file);
+
+    let start = Instant::now();
+    syntax_tree::build_syntax_tree(tokenizer::tokenize(code, file));
+    let full = start.elapsed();
+
+    let names: Vec<Span> = tokenizer::Lexer::new(code, file)
+        .filter(|token| matches!(token.token_type, tokenizer::TokenType::Name))
+        .map(|token| token.span)
+        .collect();
+
+    let step = names.len().div_ceil(BENCH_EDITS).max(1);
+
+    let edits: Vec<incremental::Edit> = names
+        .iter()
+        .step_by(step)
+        .flat_map(|name| {
+            [
+                incremental::Edit {
+                    span: Span::new(file, name.lo as usize, name.lo as usize),
+                    text: String::from("x"),
+                },
+                incremental::Edit {
+                    span: Span::new(file, name.lo as usize, name.lo as usize + 1),
+                    text: String::new(),
+                },
+            ]
+        })
+        .collect();
+
+    let mut incremental_tree = incremental::IncrementalTree::new(String::from(code), file);
+    let mut total = Duration::ZERO;
+    let mut slowest = Duration::ZERO;
+
+    for edit in &edits {
+        let start = Instant::now();
+        incremental_tree.edit(edit);
+        let elapsed = start.elapsed();
+
+        total += elapsed;
+        slowest = slowest.max(elapsed);
+    }
+
+    // The edits are made again and checked against a full parse after each one, separately so the
+    // full parses don't slow down the timed edits.
+    let mut incremental_tree = incremental::IncrementalTree::new(String::from(code), file);
+
+    for edit in &edits {
+        incremental_tree.edit(edit);
+
+        let expected =
+            syntax_tree::build_syntax_tree(tokenizer::tokenize(incremental_tree.text(), file));
+
+        if incremental_tree.nodes() != expected {
+            eprintln!(
+                "Error: The re-parsed syntax tree differs from a full parse after an edit on line {}",
+                source_map.lines_and_characters(edit.span).0.0 + 1
+            );
+
+            return 1;
+        }
+    }
+
+    println!("Full parse: {:?}", full);
+
+    if !edits.is_empty() {
+        println!(
+            "Edit: {:?} on average, {:?} at most, over {} edits",
+            total / edits.len() as u32,
+            slowest,
+            edits.len()
+        );
+    }
+
+    return 0;
+}
+
+/// Formats a program. With `check`, nothing is written and the exit code says whether the
+/// program was already formatted.
+fn format(path: &str, check: bool, output: Option<&str>) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let Some(file) = read_source(&mut source_map, path) else {
+        return 1;
+    };
+
+    let formatted = match formatter::format(&mut source_map, file) {
+        Ok(formatted) => formatted,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.display(&source_map));
+
+            return 1;
+        }
+    };
+
+    if check {
+        if formatted != source_map.source(file) {
+            eprintln!("{} is not formatted", source_name(path));
+
+            return 1;
+        }
+
+        return 0;
+    }
+
+    return write_text(&formatted, output);
+}
+
+fn write_wasm(path: &str, output: &str) -> i32 {
+    let module = match load_module(&mut SourceMap::new(), path) {
+        Some(module) => module,
+        None => return 1,
+    };
+
+    if let Err(error) = fs::write(output, wasm_backend::generate(&module)) {
+        eprintln!("Error: Could not write {}: {}", output, error);
+
+        return 1;
+    }
+
+    return 0;
+}
+
+fn write_object(path: &str, output: &str) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let module = match load_module(&mut source_map, path) {
+        Some(module) => module,
+        None => return 1,
+    };
+
+    let object = elf::write(&x86_64_backend::generate(
+        &module,
+        &source_map,
+        source_name(path),
+    ));
+
+    if let Err(error) = fs::write(output, object) {
+        eprintln!("Error: Could not write {}: {}", output, error);
+
+        return 1;
+    }
+
+    return 0;
+}
+
+/// Compiles a file to a native executable by generating C and handing it to the system `cc`, which
+/// also links the `libraries` that externs come from.
+fn build(path: &str, output: &str, libraries: &[String]) -> i32 {
+    let mut source_map = SourceMap::new();
+
+    let module = match load_module(&mut source_map, path) {
+        Some(module) => module,
+        None => return 1,
+    };
+
+    let c_path = env::temp_dir().join(format!("metor-{}.c", process::id()));
+
+    if let Err(error) = fs::write(&c_path, c_backend::generate(&module, &source_map)) {
+        eprintln!("Error: Could not write {}: {}", c_path.display(), error);
+
+        return 1;
+    }
+
+    let status = process::Command::new("cc")
+        .arg("-std=c99")
+        .arg("-o")
+        .arg(output)
+        .arg(&c_path)
+        .args(libraries.iter().map(|library| format!("-l{}", library)))
+        .arg("-lm")
+        .status();
+
+    let _ = fs::remove_file(&c_path);
+
+    match status {
+        Ok(status) if status.success() => return 0,
+        Ok(_) => {
+            eprintln!("Error: cc failed to compile {}", source_name(path));
+
+            return 1;
+        }
+        Err(error) => {
+            eprintln!("Error: Could not run cc: {}", error);
+
+            return 1;
+        }
+    }
+}
+
+const COMMANDS: &[&str] = &[
+    "run",
+    "check",
+    "build",
+    "fmt",
+    "tokens",
+    "ast",
+    "bench",
+    "repl",
+    "lsp",
+    "vm",
+    "bytecode",
+    "disassemble",
+    "ir",
+    "c",
+    "llvm",
+    "asm",
+    "wasm",
+    "object",
+];
+
+struct Options {
+    command: String,
+    input: Option<String>,
+    output: Option<String>,
+    emit: Option<Emit>,
+    json: bool,
+    check: bool,
+    libraries: Vec<String>,
+}
+
+fn parse_options(arguments: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        command: arguments[0].clone(),
+        input: None,
+        output: None,
+        emit: None,
+        json: false,
+        check: false,
+        libraries: Vec::new(),
+    };
+
+    let mut arguments = arguments[1..].iter();
+
+    while let Some(argument) = arguments.next() {
+        if argument == "-o" {
+            match arguments.next() {
+                Some(output) => options.output = Some(output.clone()),
+                None => return Err(String::from("-o expects a path")),
+            }
+        } else if argument == "-l" {
+            match arguments.next() {
+                Some(library) => options.libraries.push(library.clone()),
+                None => return Err(String::from("-l expects a library name")),
+            }
+        } else if let Some(stage) = argument.strip_prefix("--emit=") {
+            match Emit::parse(stage) {
+                Some(stage) => options.emit = Some(stage),
+                None => return Err(format!("Unknown stage {} for --emit", stage)),
+            }
+        } else if argument == "--check" {
+            options.check = true;
+        } else if argument == "--json" {
+            options.json = true;
+        } else if argument.starts_with('-') && argument != "-" {
+            return Err(format!("Unknown option {}", argument));
+        } else if options.input.is_none() {
+            options.input = Some(argument.clone());
+        } else {
+            return Err(format!("Unexpected argument {}", argument));
+        }
+    }
+
+    return Ok(options);
+}
+
+/// Runs a command, returning its exit code, or an error message when it was used wrongly.
+fn execute(options: &Options) -> Result<i32, String> {
+    let command = options.command.as_str();
+    let output = options.output.as_deref();
+
+    if !COMMANDS.contains(&command) {
+        return Err(format!("Unknown command {}", command));
+    }
+
+    if options.emit.is_some() && command != "build" {
+        return Err(format!("{} does not take --emit", command));
+    }
+
+    let json_output = match command {
+        "tokens" | "ast" | "check" => true,
+        "build" => matches!(options.emit, Some(Emit::Tokens | Emit::Ast)),
+        _ => false,
+    };
+
+    if options.json && !json_output {
+        return Err(format!(
+            "--json only works with tokens, ast, check and build --emit=tokens|ast, not {}",
+            command
+        ));
+    }
+
+    if options.check && command != "fmt" {
+        return Err(format!("{} does not take --check", command));
+    }
+
+    if !options.libraries.is_empty() && (command != "build" || options.emit.is_some()) {
+        return Err(String::from("-l only works with build"));
+    }
+
+    if command == "repl" || command == "lsp" {
+        if options.input.is_some() || output.is_some() {
+            return Err(format!("{} does not take a file", command));
+        }
+
+        if command == "lsp" {
+            return Ok(lsp::run());
+        }
+
+        repl::run();
+
+        return Ok(0);
+    }
+
+    let Some(path) = options.input.as_deref() else {
+        return Err(format!("{} expects a file, or - for stdin", command));
+    };
+
+    let required_output = || match output {
+        Some(output) => Ok(output),
+        None => Err(format!("{} expects an output path with -o", command)),
+    };
+
+    let no_output = || match output {
+        Some(_) => Err(format!("{} does not take -o", command)),
+        None => Ok(()),
+    };
+
+    let code = match command {
+        "run" if path.ends_with(".mtbc") => no_output().map(|_| run_bytecode(path))?,
+        "run" => no_output().map(|_| run(path))?,
+        "vm" => no_output().map(|_| run_bytecode(path))?,
+        "check" => no_output().map(|_| check(path, options.json))?,
+        "bench" => no_output().map(|_| bench(path))?,
+        "fmt" if options.check => no_output().map(|_| format(path, true, None))?,
+        "fmt" => format(path, false, output),
+        "tokens" => emit(path, Emit::Tokens, options.json, output),
+        "ast" => emit(path, Emit::Ast, options.json, output),
+        "ir" => emit(path, Emit::Ir, false, output),
+        "c" => emit(path, Emit::C, false, output),
+        "llvm" => emit(path, Emit::Llvm, false, output),
+        "asm" => emit(path, Emit::Asm, false, output),
+        "disassemble" => disassemble(path, output),
+        "bytecode" => write_bytecode(path, required_output()?),
+        "wasm" => write_wasm(path, required_output()?),
+        "object" => write_object(path, required_output()?),
+        "build" => match options.emit {
+            Some(stage) => emit(path, stage, options.json, output),
+            None => build(path, required_output()?, &options.libraries),
+        },
+        _ => unreachable!(),
+    };
+
+    return Ok(code);
+}
 
 fn main() {
-    let code = include_str!("../playground/main.mt");
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    if let Some("help" | "--help" | "-h") = arguments.first().map(String::as_str) {
+        println!("{}", USAGE);
+
+        return;
+    }
 
-    let tokens = tokenizer::tokenize(code);
+    if arguments.is_empty() {
+        eprintln!("{}", USAGE);
 
-    for token in &tokens {
-        println!("{}", token);
+        process::exit(2);
     }
 
-    print!("\n\n");
+    let result = parse_options(&arguments).and_then(|options| execute(&options));
 
-    let tree = syntax_tree::build_syntax_tree(&tokens);
+    match result {
+        Ok(code) => process::exit(code),
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("Run metor help to see the commands");
 
-    for node in tree {
-        println!("{}", node.display(0));
+            process::exit(2);
+        }
     }
 }
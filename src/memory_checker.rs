@@ -0,0 +1,650 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::Diagnostic;
+use crate::source_map::Span;
+use crate::syntax_tree::{
+    CallNode, ExternFunctionNode, FunctionDefinitionNode, Keyword, NameNode, Node,
+};
+
+/// How many times a loop body or the whole program is analyzed before giving up on reaching a
+/// fixed point. Analyzing less only means fewer bugs are found, never that correct code is rejected.
+const MAX_PASSES: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Live,
+    Freed,
+    /// Freed on some paths but not on others.
+    MaybeFreed,
+}
+
+impl State {
+    fn join(self, other: State) -> State {
+        if self == other {
+            return self;
+        }
+
+        return State::MaybeFreed;
+    }
+}
+
+/// What is known at one point of a function. Allocations are named by the span of the code that
+/// made them: an `alloc`, `resize` or call returning a pointer, or a pointer parameter.
+#[derive(Clone, PartialEq)]
+struct Flow {
+    /// The allocations each pointer variable may point to, for every scope.
+    scopes: Vec<HashMap<String, HashSet<Span>>>,
+    allocations: HashMap<Span, State>,
+    /// The allocations of the parameters, which are kept even once nothing points to them, since
+    /// callers want to know whether they're freed.
+    parameters: HashSet<Span>,
+}
+
+impl Flow {
+    /// The allocations that matter from here on, because something still points to them.
+    fn reachable(&self) -> HashSet<Span> {
+        let mut reachable = self.parameters.clone();
+
+        for scope in &self.scopes {
+            for targets in scope.values() {
+                reachable.extend(targets);
+            }
+        }
+
+        return reachable;
+    }
+
+    fn lookup(&self, name: &str) -> Option<&HashSet<Span>> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name));
+    }
+
+    fn assign(&mut self, name: &str, targets: HashSet<Span>) {
+        if let Some(scope) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.contains_key(name))
+        {
+            scope.insert(name.to_string(), targets);
+        }
+    }
+
+    /// Marks the allocations as freed. An allocation is only known to be freed if it's the only
+    /// one the pointer may point to.
+    fn free(&mut self, targets: &HashSet<Span>, state: State) {
+        let certain = targets.len() == 1 && state == State::Freed;
+
+        for target in targets {
+            let previous = self.allocations.get(target).copied().unwrap_or(State::Live);
+
+            if certain || previous == State::Freed {
+                self.allocations.insert(*target, State::Freed);
+            } else {
+                self.allocations.insert(*target, State::MaybeFreed);
+            }
+        }
+    }
+}
+
+/// Joins the flows of two paths that meet, where `None` is a path that can't get there.
+fn join(first: Option<Flow>, second: Option<Flow>) -> Option<Flow> {
+    let (Some(mut first), Some(second)) = (first.clone(), second.clone()) else {
+        return first.or(second);
+    };
+
+    // What happened to an allocation on a path where nothing points to it anymore can't affect
+    // later code, like an allocation freed in one pass of a loop before the pointer is reassigned.
+    let first_reachable = first.reachable();
+    let second_reachable = second.reachable();
+
+    first
+        .allocations
+        .retain(|allocation, _| first_reachable.contains(allocation));
+
+    for (allocation, state) in second.allocations {
+        if !second_reachable.contains(&allocation) {
+            continue;
+        }
+
+        let joined = match first.allocations.get(&allocation) {
+            Some(previous) => previous.join(state),
+            None => state,
+        };
+
+        first.allocations.insert(allocation, joined);
+    }
+
+    for (scope, other) in first.scopes.iter_mut().zip(second.scopes) {
+        for (name, targets) in other {
+            scope.entry(name).or_default().extend(targets);
+        }
+    }
+
+    return Some(first);
+}
+
+/// Finds uses of memory after it's freed and memory that's freed twice. Pointers can be copied
+/// freely, so rather than rejecting code that might be wrong, this follows the allocations each
+/// pointer may point to through every path of a function and only reports what is freed on a path
+/// that reaches the use. Pointers in globals and allocations reached through them aren't followed.
+struct MemoryChecker<'a> {
+    functions: HashMap<&'a str, &'a FunctionDefinitionNode>,
+    /// C functions, which are assumed to keep the pointers they get alive, apart from `free`.
+    externs: HashMap<&'a str, &'a ExternFunctionNode>,
+    /// The state every pointer parameter of each function is left in when it returns.
+    summaries: HashMap<&'a str, Vec<State>>,
+    /// The statements deferred in each scope, parallel to the scopes of the flow.
+    deferred: Vec<Vec<&'a Node>>,
+    /// The number of scopes around each enclosing loop and the flows that break out of it.
+    loops: Vec<(usize, Option<Flow>)>,
+    /// The flows at every point the current function returns.
+    exits: Option<Flow>,
+    /// Set while a loop body is analyzed again to reach a fixed point, or while the summaries are,
+    /// so the same mistake isn't reported for every pass.
+    muted: bool,
+    reported: HashSet<(String, Span)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> MemoryChecker<'a> {
+    fn report(&mut self, message: String, span: Span) {
+        // Deferred code is followed once for every way its scope is left, so it can report the
+        // same mistake more than once.
+        if self.muted || !self.reported.insert((message.clone(), span)) {
+            return;
+        }
+
+        self.diagnostics.push(Diagnostic::error(message, span));
+    }
+
+    fn states(flow: &Flow, targets: &HashSet<Span>) -> Vec<State> {
+        return targets
+            .iter()
+            .map(|target| flow.allocations.get(target).copied().unwrap_or(State::Live))
+            .collect();
+    }
+
+    fn check_use(&mut self, flow: &Flow, name: &NameNode) {
+        let Some(targets) = flow.lookup(&name.value) else {
+            return;
+        };
+
+        let states = MemoryChecker::states(flow, targets);
+
+        if !states.is_empty() && states.iter().all(|state| *state == State::Freed) {
+            self.report(
+                format!("{} is used after it was freed", name.value),
+                name.span,
+            );
+        } else if states.iter().any(|state| *state != State::Live) {
+            self.report(
+                format!("{} may be used after it was freed", name.value),
+                name.span,
+            );
+        }
+    }
+
+    fn check_free(&mut self, flow: &mut Flow, argument: &Node) {
+        let targets = self.expression(flow, argument);
+
+        if let Node::Name(name) = argument {
+            let states = MemoryChecker::states(flow, &targets);
+
+            if !states.is_empty() && states.iter().all(|state| *state == State::Freed) {
+                self.report(format!("{} is freed twice", name.value), name.span);
+            } else if states.iter().any(|state| *state != State::Live) {
+                self.report(format!("{} may already be freed", name.value), name.span);
+            }
+        }
+
+        flow.free(&targets, State::Freed);
+    }
+
+    fn call(&mut self, flow: &mut Flow, call_node: &CallNode, span: Span) -> HashSet<Span> {
+        let name = call_node.name.value.as_str();
+        let arguments = &call_node.arguments;
+
+        if let Some(function_node) = self.functions.get(name).copied() {
+            for (index, argument) in arguments.iter().enumerate() {
+                let targets = self.expression(flow, argument);
+
+                if let Node::Name(variable) = argument {
+                    self.check_use(flow, variable);
+                }
+
+                let state = self
+                    .summaries
+                    .get(name)
+                    .and_then(|summary| summary.get(index))
+                    .copied()
+                    .unwrap_or(State::Live);
+
+                if state != State::Live {
+                    flow.free(&targets, state);
+                }
+            }
+
+            if function_node.node_type.pointer {
+                flow.allocations.insert(span, State::Live);
+
+                return HashSet::from([span]);
+            }
+
+            return HashSet::new();
+        }
+
+        if let Some(function_node) = self.externs.get(name).copied()
+            && name != "free"
+        {
+            for argument in arguments {
+                self.expression(flow, argument);
+
+                if let Node::Name(variable) = argument {
+                    self.check_use(flow, variable);
+                }
+            }
+
+            if function_node.node_type.pointer {
+                flow.allocations.insert(span, State::Live);
+
+                return HashSet::from([span]);
+            }
+
+            return HashSet::new();
+        }
+
+        match (name, arguments.as_slice()) {
+            ("alloc", [count]) => {
+                self.expression(flow, count);
+                flow.allocations.insert(span, State::Live);
+
+                return HashSet::from([span]);
+            }
+            ("resize", [pointer, count]) => {
+                let targets = self.expression(flow, pointer);
+
+                if let Node::Name(name) = pointer {
+                    self.check_use(flow, name);
+                }
+
+                self.expression(flow, count);
+                flow.free(&targets, State::Freed);
+                flow.allocations.insert(span, State::Live);
+
+                return HashSet::from([span]);
+            }
+            ("free", [pointer]) => self.check_free(flow, pointer),
+            _ => {
+                for argument in arguments {
+                    self.expression(flow, argument);
+                }
+            }
+        }
+
+        return HashSet::new();
+    }
+
+    /// Follows an expression, returning the allocations its value may point to.
+    fn expression(&mut self, flow: &mut Flow, node: &Node) -> HashSet<Span> {
+        match node {
+            Node::Name(name_node) => {
+                return flow.lookup(&name_node.value).cloned().unwrap_or_default();
+            }
+            Node::Call(call_node) => return self.call(flow, call_node, node.get_span()),
+            Node::Index(index_node) => {
+                self.check_use(flow, &index_node.name);
+                self.expression(flow, &index_node.index);
+            }
+            _ => {
+                for child in node.children() {
+                    self.expression(flow, child);
+                }
+            }
+        }
+
+        return HashSet::new();
+    }
+
+    /// Applies the statements deferred in the scopes from `depth` inward, innermost first, for code
+    /// that leaves them. Each statement only sees the scopes that were around when it was deferred.
+    fn leave_scopes(&mut self, flow: &mut Option<Flow>, depth: usize) {
+        for scope in (depth..self.deferred.len()).rev() {
+            for statement in self.deferred[scope].clone().into_iter().rev() {
+                let Some(current) = flow else {
+                    return;
+                };
+
+                let hidden_scopes = current.scopes.split_off(scope + 1);
+                let hidden_deferred = self.deferred.split_off(scope + 1);
+
+                self.block(flow, std::slice::from_ref(statement));
+
+                if let Some(current) = flow {
+                    current.scopes.extend(hidden_scopes);
+                }
+
+                self.deferred.extend(hidden_deferred);
+            }
+        }
+    }
+
+    fn block(&mut self, flow: &mut Option<Flow>, nodes: &'a [Node]) {
+        let Some(current) = flow else {
+            return;
+        };
+
+        current.scopes.push(HashMap::new());
+        self.deferred.push(Vec::new());
+
+        for node in nodes {
+            self.statement(flow, node);
+        }
+
+        self.leave_scopes(flow, self.deferred.len() - 1);
+        self.deferred.pop();
+
+        if let Some(current) = flow {
+            current.scopes.pop();
+        }
+    }
+
+    fn statement(&mut self, flow: &mut Option<Flow>, node: &'a Node) {
+        let Some(current) = flow else {
+            return;
+        };
+
+        match node {
+            Node::VariableDefinition(definition_node) => {
+                let targets = self.expression(current, &definition_node.assignment.value);
+
+                if definition_node.node_type.pointer {
+                    current
+                        .scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert(definition_node.assignment.name.value.clone(), targets);
+                }
+            }
+            Node::Assignment(assignment_node) => {
+                let targets = self.expression(current, &assignment_node.value);
+
+                current.assign(&assignment_node.name.value, targets);
+            }
+            Node::IndexAssignment(assignment_node) => {
+                self.check_use(current, &assignment_node.target.name);
+                self.expression(current, &assignment_node.target.index);
+                self.expression(current, &assignment_node.value);
+            }
+            Node::Block(block_node) => self.block(flow, &block_node.content),
+            Node::If(if_node) => {
+                self.expression(current, &if_node.condition);
+
+                let mut otherwise = flow.clone();
+
+                self.block(flow, &if_node.block.content);
+
+                if let Some(else_node) = &if_node.else_node {
+                    self.statement(&mut otherwise, else_node);
+                }
+
+                *flow = join(flow.take(), otherwise);
+            }
+            Node::Forever(forever_node) => {
+                let muted = self.muted;
+                let exits = self.exits.clone();
+                let mut entry = flow.clone();
+
+                // Analyze the body until what's known at its start no longer changes.
+                self.muted = true;
+
+                for _ in 0..MAX_PASSES {
+                    let mut body = entry.clone();
+
+                    self.loops.push((self.deferred.len(), None));
+                    self.block(&mut body, &forever_node.block.content);
+                    self.loops.pop();
+
+                    let next = join(entry.clone(), body);
+
+                    if next == entry {
+                        break;
+                    }
+
+                    entry = next;
+                }
+
+                self.muted = muted;
+                self.exits = exits;
+
+                self.loops.push((self.deferred.len(), None));
+                self.block(&mut entry, &forever_node.block.content);
+
+                *flow = self.loops.pop().unwrap().1;
+            }
+            Node::Return(return_node) => {
+                let mut targets = HashSet::new();
+
+                if let Some(value) = &return_node.value {
+                    targets = self.expression(current, value);
+
+                    if let Node::Name(name) = value.as_ref() {
+                        self.check_use(current, name);
+                    }
+                }
+
+                self.leave_scopes(flow, 0);
+
+                if let Some(current) = flow
+                    && !targets.is_empty()
+                    && MemoryChecker::states(current, &targets)
+                        .iter()
+                        .all(|state| *state == State::Freed)
+                    && let Some(value) = &return_node.value
+                {
+                    self.report(
+                        String::from("The returned pointer is freed by deferred code"),
+                        value.get_span(),
+                    );
+                }
+
+                self.exits = join(self.exits.take(), flow.take());
+            }
+            Node::Keyword(keyword_node) if keyword_node.keyword == Keyword::Break => {
+                let Some((depth, _)) = self.loops.last() else {
+                    return;
+                };
+
+                self.leave_scopes(flow, *depth);
+
+                let (_, breaks) = self.loops.last_mut().unwrap();
+
+                *breaks = join(breaks.take(), flow.take());
+            }
+            Node::Defer(defer_node) => self
+                .deferred
+                .last_mut()
+                .unwrap()
+                .push(&defer_node.statement),
+            _ => {
+                self.expression(current, node);
+            }
+        }
+    }
+
+    /// Follows a function from its start, returning the state it leaves each parameter in.
+    fn function(&mut self, function_node: &'a FunctionDefinitionNode) -> Vec<State> {
+        let mut variables = HashMap::new();
+        let mut allocations = HashMap::new();
+        let mut parameters = HashSet::new();
+
+        for parameter in &function_node.parameters {
+            if parameter.node_type.pointer {
+                variables.insert(
+                    parameter.name.value.clone(),
+                    HashSet::from([parameter.span]),
+                );
+                allocations.insert(parameter.span, State::Live);
+                parameters.insert(parameter.span);
+            }
+        }
+
+        let mut flow = Some(Flow {
+            scopes: vec![variables],
+            allocations,
+            parameters,
+        });
+
+        self.deferred = vec![Vec::new()];
+        self.loops.clear();
+        self.exits = None;
+
+        self.block(&mut flow, &function_node.block.content);
+
+        let exits = join(self.exits.take(), flow);
+
+        return function_node
+            .parameters
+            .iter()
+            .map(|parameter| match &exits {
+                Some(exits) if parameter.node_type.pointer => exits
+                    .allocations
+                    .get(&parameter.span)
+                    .copied()
+                    .unwrap_or(State::Live),
+                _ => State::Live,
+            })
+            .collect();
+    }
+}
+
+pub fn check(nodes: &[Node]) -> Vec<Diagnostic> {
+    let mut checker = MemoryChecker {
+        functions: HashMap::new(),
+        externs: HashMap::new(),
+        summaries: HashMap::new(),
+        deferred: Vec::new(),
+        loops: Vec::new(),
+        exits: None,
+        muted: true,
+        reported: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for node in nodes {
+        match node {
+            Node::FunctionDefinition(function_node) => {
+                checker
+                    .functions
+                    .insert(&function_node.name.value, function_node);
+            }
+            Node::ExternFunction(function_node) => {
+                checker
+                    .externs
+                    .insert(&function_node.name.value, function_node);
+            }
+            _ => {}
+        }
+    }
+
+    let functions: Vec<&FunctionDefinitionNode> = checker.functions.values().copied().collect();
+
+    // Which parameters a function frees depends on the functions it calls, so the summaries are
+    // worked out again until they no longer change before anything is reported.
+    for _ in 0..MAX_PASSES {
+        let mut changed = false;
+
+        for function_node in &functions {
+            let summary = checker.function(function_node);
+
+            if checker.summaries.get(function_node.name.value.as_str()) != Some(&summary) {
+                checker.summaries.insert(&function_node.name.value, summary);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    checker.muted = false;
+
+    for node in nodes {
+        if let Node::FunctionDefinition(function_node) = node {
+            checker.function(function_node);
+        }
+    }
+
+    return checker.diagnostics;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_map::SourceMap;
+
+    fn messages(code: &str) -> Vec<String> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+
+        return match crate::analyze(&mut source_map, file) {
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect(),
+        };
+    }
+
+    #[test]
+    fn follows_aliases() {
+        assert_eq!(
+            messages(
+                "i32 main {\n    i32* p = alloc(4)\n    i32* q = p\n    free(p)\n    q[0] = 1\n    \
+                 free(q)\n    return 0\n}"
+            ),
+            ["q is used after it was freed", "q is freed twice"]
+        );
+    }
+
+    #[test]
+    fn reports_frees_on_some_paths() {
+        assert_eq!(
+            messages(
+                "i32 main: i32 n {\n    i32* p = alloc(4)\n    if n > 1 {\n        free(p)\n    }\n    \
+                 p[0] = 1\n    return 0\n}"
+            ),
+            ["p may be used after it was freed"]
+        );
+        assert_eq!(
+            messages(
+                "i32 main {\n    i32* p = alloc(4)\n    forever {\n        free(p)\n    }\n    \
+                 return 0\n}"
+            ),
+            ["p may already be freed"]
+        );
+    }
+
+    #[test]
+    fn reports_returning_deferred_frees() {
+        assert_eq!(
+            messages(
+                "i32* make {\n    i32* p = alloc(4)\n    defer free(p)\n    return p\n}\n\n\
+                 i32 main {\n    i32* p = make()\n    return 0\n}"
+            ),
+            ["The returned pointer is freed by deferred code"]
+        );
+    }
+
+    #[test]
+    fn accepts_frees_on_every_path() {
+        assert!(
+            messages(
+                "i32 main {\n    i32 i = 0\n    forever {\n        i32* p = alloc(4)\n        \
+                 p[0] = i\n        free(p)\n        i = i + 1\n        if i == 3 {\n            \
+                 break\n        }\n    }\n    i32* q = alloc(1)\n    if i == 3 {\n        \
+                 free(q)\n    } else {\n        free(q)\n    }\n    return 0\n}"
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::Diagnostic;
+use crate::source_map::SourceMap;
+use crate::syntax_tree::{self, ImportNode, NameNode, Node};
+use crate::tokenizer;
+
+/// A module that comes with the compiler, so programs can import from it without any files of
+/// their own.
+struct Module {
+    name: &'static str,
+    /// The Metor source of the functions the module defines, if it defines any.
+    source: Option<&'static str>,
+    /// Functions every backend provides, which the module exports without defining them.
+    intrinsics: &'static [&'static str],
+}
+
+const MODULES: &[Module] = &[
+    Module {
+        name: "Math",
+        source: Some(include_str!("../std/Math.mt")),
+        intrinsics: &[],
+    },
+    Module {
+        name: "Memory",
+        source: None,
+        intrinsics: &["alloc", "resize", "free"],
+    },
+    Module {
+        name: "Standard",
+        source: None,
+        intrinsics: &["print", "println"],
+    },
+];
+
+fn error(message: String, name: &NameNode) -> Diagnostic {
+    return Diagnostic::error(message, name.span);
+}
+
+fn function_name(node: &Node) -> Option<&str> {
+    return match node {
+        Node::FunctionDefinition(function_node) => Some(&function_node.name.value),
+        Node::ExternFunction(function_node) => Some(&function_node.name.value),
+        _ => None,
+    };
+}
+
+/// The names of the functions a definition calls, so they can be pointed at the module's own.
+fn calls(node: &mut Node) -> Vec<&mut NameNode> {
+    let mut names: Vec<&mut NameNode> = Vec::new();
+    let mut stack: Vec<&mut Node> = vec![node];
+
+    while let Some(node) = stack.pop() {
+        if let Node::Call(call_node) = node {
+            names.push(&mut call_node.name);
+            stack.extend(&mut call_node.arguments);
+        } else {
+            stack.extend(node.children_mut());
+        }
+    }
+
+    return names;
+}
+
+/// Parses a module's source, adding it to the source map so the spans of what it defines resolve.
+fn parse(module: &Module, source_map: &mut SourceMap) -> Vec<Node> {
+    let Some(source) = module.source else {
+        return Vec::new();
+    };
+
+    let file = source_map.add(format!("std/{}.mt", module.name), String::from(source));
+
+    return syntax_tree::build_syntax_tree(tokenizer::Lexer::new(source_map.source(file), file));
+}
+
+/// Tracks the names at the top level of the program while linking. A module's functions keep
+/// their own names when they're imported, while the ones they only call get a name of their own,
+/// so they can't clash with the program's.
+struct Linker {
+    /// The program's own functions and the ones linked in so far.
+    defined: HashSet<String>,
+    /// The definitions of each module that haven't been linked yet.
+    modules: HashMap<&'static str, Vec<Node>>,
+    /// The name each module function has in the program.
+    names: HashMap<(&'static str, String), String>,
+    linked: Vec<Node>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linker {
+    fn defines(&self, module: &'static str, name: &str) -> bool {
+        return self.names.contains_key(&(module, String::from(name)))
+            || self.modules[module]
+                .iter()
+                .any(|node| function_name(node) == Some(name));
+    }
+
+    /// Links a function from a module along with the module's functions it calls, and returns
+    /// the name it has in the program.
+    fn link(&mut self, module: &'static str, name: &str) -> String {
+        let key = (module, String::from(name));
+
+        let linked_name = match self.names.get(&key) {
+            Some(linked_name) => linked_name.clone(),
+            None => {
+                let mut linked_name = format!("{}_{}", module, name);
+
+                while self.defined.contains(&linked_name) {
+                    linked_name.push('_');
+                }
+
+                self.defined.insert(linked_name.clone());
+                self.names.insert(key, linked_name.clone());
+
+                linked_name
+            }
+        };
+
+        let definitions = self.modules.get_mut(module).unwrap();
+
+        // It's already linked, or is being linked further up, for a recursive call.
+        let Some(index) = definitions
+            .iter()
+            .position(|node| function_name(node) == Some(name))
+        else {
+            return linked_name;
+        };
+
+        let mut definition = definitions.remove(index);
+
+        if let Node::FunctionDefinition(function_node) = &mut definition {
+            function_node.name.value = linked_name.clone();
+        }
+
+        // Calls to functions the module doesn't define are to intrinsics.
+        for call in calls(&mut definition) {
+            if self.defines(module, &call.value) {
+                call.value = self.link(module, &call.value);
+            }
+        }
+
+        self.linked.push(definition);
+
+        return linked_name;
+    }
+
+    /// Checks the names an import brings in and reserves them, returning the ones to link.
+    fn import(
+        &mut self,
+        import_node: &ImportNode,
+        source_map: &mut SourceMap,
+    ) -> Vec<(&'static str, String)> {
+        let Some(module) = MODULES
+            .iter()
+            .find(|module| module.name == import_node.module.value)
+        else {
+            self.diagnostics.push(error(
+                format!("There is no module named {}", import_node.module.value),
+                &import_node.module,
+            ));
+
+            return Vec::new();
+        };
+
+        if !self.modules.contains_key(module.name) {
+            self.modules.insert(module.name, parse(module, source_map));
+        }
+
+        let mut imported = Vec::new();
+
+        for name in &import_node.names {
+            if module.intrinsics.contains(&name.value.as_str()) {
+                continue;
+            }
+
+            let key = (module.name, name.value.clone());
+
+            if !self.defines(module.name, &name.value) {
+                self.diagnostics.push(error(
+                    format!("{} has no {}", module.name, name.value),
+                    name,
+                ));
+            } else if self.names.get(&key) == Some(&name.value) {
+                // It's imported twice.
+            } else if self.defined.contains(&name.value) {
+                self.diagnostics
+                    .push(error(format!("{} is already defined", name.value), name));
+            } else {
+                self.defined.insert(name.value.clone());
+                self.names.insert(key.clone(), name.value.clone());
+                imported.push(key);
+            }
+        }
+
+        return imported;
+    }
+}
+
+/// Replaces the imports at the top level of a program with the functions they import from the
+/// bundled modules, along with the module functions those call. Only what's imported is added, so
+/// a module's other functions don't clash with the program's.
+pub fn link(nodes: &mut Vec<Node>, source_map: &mut SourceMap) -> Vec<Diagnostic> {
+    let mut linker = Linker {
+        defined: HashSet::new(),
+        modules: HashMap::new(),
+        names: HashMap::new(),
+        linked: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for node in nodes.iter() {
+        if let Some(name) = function_name(node) {
+            linker.defined.insert(String::from(name));
+        }
+    }
+
+    let mut imports: Vec<ImportNode> = Vec::new();
+    let mut index = 0;
+
+    while index < nodes.len() {
+        if let Node::Import(_) = nodes[index] {
+            let Node::Import(import_node) = nodes.remove(index) else {
+                unreachable!()
+            };
+
+            imports.push(import_node);
+        } else {
+            index += 1;
+        }
+    }
+
+    // Every imported name is reserved before anything is linked, so a function another one calls
+    // is shared with the program when the program imports it too.
+    let mut imported = Vec::new();
+
+    for import_node in &imports {
+        imported.extend(linker.import(import_node, source_map));
+    }
+
+    for (module, name) in imported {
+        linker.link(module, &name);
+    }
+
+    nodes.append(&mut linker.linked);
+
+    return linker.diagnostics;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Links a program, returning its top-level functions with the functions each one calls, or
+    /// the messages of the errors.
+    fn link_code(code: &str) -> Result<Vec<(String, Vec<String>)>, Vec<String>> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+        let mut tree = syntax_tree::build_syntax_tree(tokenizer::Lexer::new(code, file));
+
+        let diagnostics = link(&mut tree, &mut source_map);
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect());
+        }
+
+        return Ok(tree
+            .iter_mut()
+            .filter_map(|node| {
+                let name = String::from(function_name(node)?);
+                let mut called: Vec<String> =
+                    calls(node).iter().map(|call| call.value.clone()).collect();
+
+                called.sort();
+                called.dedup();
+
+                Some((name, called))
+            })
+            .collect());
+    }
+
+    fn function<'a>(functions: &'a [(String, Vec<String>)], name: &str) -> &'a [String] {
+        return &functions.iter().find(|(other, _)| other == name).unwrap().1;
+    }
+
+    #[test]
+    fn keeps_module_helpers_out_of_the_program() {
+        let functions = link_code(
+            "import { clamp } from Math
+            i32 min: i32 a {
+                return a
+            }
+            void main {
+                println(clamp(min(15), 0, 10))
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(function(&functions, "main"), ["clamp", "min", "println"]);
+        assert_eq!(function(&functions, "min"), [] as [String; 0]);
+        assert_eq!(function(&functions, "clamp"), ["Math_max", "Math_min"]);
+        assert!(functions.iter().any(|(name, _)| name == "Math_min"));
+    }
+
+    #[test]
+    fn shares_helpers_the_program_imports() {
+        let functions = link_code(
+            "import { clamp } from Math
+            import { min } from Math
+            void main {
+                println(clamp(min(15, 20), 0, 10))
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(function(&functions, "clamp"), ["Math_max", "min"]);
+        assert_eq!(
+            functions
+                .iter()
+                .filter(|(name, _)| name.ends_with("min"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn avoids_names_the_program_uses() {
+        let functions = link_code(
+            "import { clamp } from Math
+            i32 Math_min: i32 a {
+                return a
+            }
+            void main {
+                println(clamp(Math_min(15), 0, 10))
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(function(&functions, "clamp"), ["Math_max", "Math_min_"]);
+    }
+
+    #[test]
+    fn reports_bad_imports() {
+        assert_eq!(
+            link_code("import { nothing } from Math").unwrap_err(),
+            ["Math has no nothing"]
+        );
+        assert_eq!(
+            link_code("import { max } from Nowhere").unwrap_err(),
+            ["There is no module named Nowhere"]
+        );
+        assert_eq!(
+            link_code("import { max } from Math\ni32 max: i32 a {\n    return a\n}").unwrap_err(),
+            ["max is already defined"]
+        );
+    }
+}
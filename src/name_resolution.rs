@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use crate::source_map::Span;
+use crate::syntax_tree::{NameNode, Node, ParameterNode, TypeNode};
+use crate::type_checker::CheckedType;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DefinitionKind {
+    Function,
+    Global,
+    Parameter,
+    Local,
+}
+
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// The type of a variable, like `I32[3]`, or the signature of a function.
+    pub description: String,
+    pub constant: bool,
+    /// The span of the defining name.
+    pub span: Span,
+    /// The span of the whole definition.
+    pub node_span: Span,
+    /// The function a parameter or local variable belongs to.
+    pub function: Option<usize>,
+}
+
+/// A use of a name, such as a variable read, an assignment or a call.
+pub struct Reference {
+    pub definition: usize,
+    pub span: Span,
+}
+
+pub struct Resolution {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+}
+
+fn contains(span: Span, offset: u32) -> bool {
+    // A cursor just after a name still counts as being on it.
+    return span.lo <= offset && offset <= span.hi;
+}
+
+impl Resolution {
+    /// Finds the definition of the name at a byte offset, whether the name is a use or the
+    /// definition itself.
+    pub fn definition_at(&self, offset: u32) -> Option<usize> {
+        for (index, definition) in self.definitions.iter().enumerate() {
+            if contains(definition.span, offset) {
+                return Some(index);
+            }
+        }
+
+        for reference in &self.references {
+            if contains(reference.span, offset) {
+                return Some(reference.definition);
+            }
+        }
+
+        return None;
+    }
+
+    pub fn references_to(&self, definition: usize) -> impl Iterator<Item = &Reference> {
+        return self
+            .references
+            .iter()
+            .filter(move |reference| reference.definition == definition);
+    }
+}
+
+/// Tracks which definition every name refers to, following the interpreter's scoping: globals
+/// are visible after they're defined, functions everywhere, and a function body only sees the
+/// globals and its own locals.
+struct Resolver {
+    definitions: Vec<Definition>,
+    references: Vec<Reference>,
+    functions: HashMap<String, usize>,
+    scopes: Vec<HashMap<String, usize>>,
+    function: Option<usize>,
+}
+
+impl Resolver {
+    fn define(
+        &mut self,
+        name: &NameNode,
+        kind: DefinitionKind,
+        description: String,
+        constant: bool,
+        node_span: Span,
+    ) -> usize {
+        let index = self.definitions.len();
+
+        self.definitions.push(Definition {
+            name: name.value.clone(),
+            kind,
+            description,
+            constant,
+            span: name.span,
+            node_span,
+            function: self.function,
+        });
+
+        if kind != DefinitionKind::Function {
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert(name.value.clone(), index);
+        }
+
+        return index;
+    }
+
+    fn reference(&mut self, name: &NameNode, definition: Option<usize>) {
+        if let Some(definition) = definition {
+            self.references.push(Reference {
+                definition,
+                span: name.span,
+            });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(definition) = scope.get(name) {
+                return Some(*definition);
+            }
+        }
+
+        return None;
+    }
+
+    fn declare_function(
+        &mut self,
+        name: &NameNode,
+        parameters: &[ParameterNode],
+        return_type: &TypeNode,
+        span: Span,
+    ) {
+        let parameters: Vec<String> = parameters
+            .iter()
+            .map(|parameter| {
+                format!(
+                    "{}: {}",
+                    parameter.name.value,
+                    CheckedType::from_type_node(&parameter.node_type)
+                )
+            })
+            .collect();
+
+        let description = format!(
+            "{}({}) -> {}",
+            name.value,
+            parameters.join(", "),
+            CheckedType::from_type_node(return_type)
+        );
+
+        let index = self.define(name, DefinitionKind::Function, description, true, span);
+
+        self.functions.insert(name.value.clone(), index);
+    }
+
+    fn expression(&mut self, node: &Node) {
+        match node {
+            Node::Name(name_node) => {
+                let definition = self.lookup(&name_node.value);
+
+                self.reference(name_node, definition);
+            }
+            Node::Call(call_node) => {
+                let definition = self.functions.get(&call_node.name.value).copied();
+
+                self.reference(&call_node.name, definition);
+
+                for argument in &call_node.arguments {
+                    self.expression(argument);
+                }
+            }
+            Node::Operation(operation_node) => {
+                for value in &operation_node.values {
+                    self.expression(value);
+                }
+            }
+            Node::Group(group_node) => {
+                for value in &group_node.content {
+                    self.expression(value);
+                }
+            }
+            Node::Array(array_node) => {
+                for value in &array_node.values {
+                    self.expression(value);
+                }
+            }
+            Node::Comptime(comptime_node) => self.expression(&comptime_node.value),
+            Node::Index(index_node) => {
+                let definition = self.lookup(&index_node.name.value);
+
+                self.reference(&index_node.name, definition);
+                self.expression(&index_node.index);
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        self.scopes.push(HashMap::new());
+
+        for node in nodes {
+            self.statement(node);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, node: &Node) {
+        match node {
+            Node::VariableDefinition(definition_node) => {
+                if let Some(array_size) = &definition_node.node_type.array_size {
+                    self.expression(array_size);
+                }
+
+                self.expression(&definition_node.assignment.value);
+
+                let kind = match self.function {
+                    Some(_) => DefinitionKind::Local,
+                    None => DefinitionKind::Global,
+                };
+
+                self.define(
+                    &definition_node.assignment.name,
+                    kind,
+                    CheckedType::from_type_node(&definition_node.node_type).to_string(),
+                    definition_node.constant,
+                    definition_node.span,
+                );
+            }
+            Node::Assignment(assignment_node) => {
+                let definition = self.lookup(&assignment_node.name.value);
+
+                self.reference(&assignment_node.name, definition);
+                self.expression(&assignment_node.value);
+            }
+            Node::IndexAssignment(assignment_node) => {
+                let definition = self.lookup(&assignment_node.target.name.value);
+
+                self.reference(&assignment_node.target.name, definition);
+                self.expression(&assignment_node.target.index);
+                self.expression(&assignment_node.value);
+            }
+            Node::Defer(defer_node) => self.block(std::slice::from_ref(&defer_node.statement)),
+            Node::Block(block_node) => self.block(&block_node.content),
+            Node::If(if_node) => {
+                self.expression(&if_node.condition);
+                self.block(&if_node.block.content);
+
+                if let Some(else_node) = &if_node.else_node {
+                    self.statement(else_node);
+                }
+            }
+            Node::Forever(forever_node) => self.block(&forever_node.block.content),
+            Node::Return(return_node) => {
+                if let Some(value) = &return_node.value {
+                    self.expression(value);
+                }
+            }
+            Node::FunctionDefinition(function_node) => {
+                let function = self.functions.get(&function_node.name.value).copied();
+
+                // Function bodies only see globals, so the scopes of anything enclosing are hidden.
+                let enclosing = self.scopes.split_off(1);
+
+                self.function = function;
+                self.scopes.push(HashMap::new());
+
+                for parameter in &function_node.parameters {
+                    self.define(
+                        &parameter.name,
+                        DefinitionKind::Parameter,
+                        CheckedType::from_type_node(&parameter.node_type).to_string(),
+                        false,
+                        parameter.span,
+                    );
+                }
+
+                self.block(&function_node.block.content);
+
+                self.scopes.truncate(1);
+                self.scopes.extend(enclosing);
+                self.function = None;
+            }
+            node => self.expression(node),
+        }
+    }
+}
+
+pub fn resolve(nodes: &[Node]) -> Resolution {
+    let mut resolver = Resolver {
+        definitions: Vec::new(),
+        references: Vec::new(),
+        functions: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        function: None,
+    };
+
+    for node in nodes {
+        match node {
+            Node::FunctionDefinition(function_node) => resolver.declare_function(
+                &function_node.name,
+                &function_node.parameters,
+                &function_node.node_type,
+                function_node.span,
+            ),
+            Node::ExternFunction(function_node) => resolver.declare_function(
+                &function_node.name,
+                &function_node.parameters,
+                &function_node.node_type,
+                function_node.span,
+            ),
+            _ => {}
+        }
+    }
+
+    for node in nodes {
+        resolver.statement(node);
+    }
+
+    return Resolution {
+        definitions: resolver.definitions,
+        references: resolver.references,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::source_map::FileId;
+    use crate::syntax_tree;
+    use crate::tokenizer;
+
+    /// Every reference as the offset of the name and the offset of the definition it resolved to.
+    fn references(code: &str) -> Vec<String> {
+        let nodes = syntax_tree::build_syntax_tree(tokenizer::Lexer::new(code, FileId(0)));
+        let resolution = resolve(&nodes);
+
+        return resolution
+            .references
+            .iter()
+            .map(|reference| {
+                let definition = &resolution.definitions[reference.definition];
+
+                return format!(
+                    "{} {} -> {}",
+                    definition.name, reference.span.lo, definition.span.lo
+                );
+            })
+            .collect();
+    }
+
+    const CODE: &str = "\
+i32 g = 1
+void f {
+    i32 a = g
+    i32 g = a
+    h(g)
+}
+void h: i32 a {
+    println(a, g, b)
+}
+i32 b = 2
+";
+
+    #[test]
+    fn follows_the_interpreter_scoping() {
+        assert_eq!(
+            references(CODE),
+            [
+                // The global `g`, before `f` shadows it.
+                "g 31 -> 4",
+                "a 45 -> 27",
+                // Functions are visible before they're defined.
+                "h 51 -> 63",
+                "g 53 -> 41",
+                // `h` sees its own `a` and the global `g`, but not `b`, which is defined after it.
+                "a 86 -> 70",
+                "g 89 -> 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_definitions_at_both_ends_of_a_name() {
+        let nodes = syntax_tree::build_syntax_tree(tokenizer::Lexer::new(CODE, FileId(0)));
+        let resolution = resolve(&nodes);
+
+        let name_at = |offset| {
+            return resolution
+                .definition_at(offset)
+                .map(|index| resolution.definitions[index].span.lo);
+        };
+
+        assert_eq!(name_at(4), Some(4));
+        assert_eq!(name_at(5), Some(4));
+        assert_eq!(name_at(53), Some(41));
+        assert_eq!(name_at(54), Some(41));
+        assert_eq!(name_at(0), None);
+        assert_eq!(
+            resolution
+                .references_to(resolution.definition_at(4).unwrap())
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use std::io::{self, BufRead, Write};
+
+use crate::constant_folding;
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::{Interpreter, Value};
+use crate::modules;
+use crate::source_map::{FileId, SourceMap};
+use crate::syntax_tree::{self, Node, Symbol};
+use crate::tokenizer::{self, TokenType};
+use crate::type_checker::TypeChecker;
+
+const HELP: &str = "\
+:tokens <code>  Print the tokens of the code
+:ast <code>     Print the syntax tree of the code
+:type <expr>    Print the type of an expression without evaluating it
+:help           Print this message
+:quit           Leave the repl";
+
+/// How many more `{` than `}` the code has. An entry is only finished once this is zero.
+fn open_braces(code: &str) -> i32 {
+    let mut depth = 0;
+
+    // Only the token types matter here, so the code doesn't need to be in the source map.
+    for token in tokenizer::Lexer::new(code, FileId::default()) {
+        match token.token_type {
+            TokenType::Symbol(Symbol::OpenCurlyBracket) => depth += 1,
+            TokenType::Symbol(Symbol::ClosedCurlyBracket) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    return depth;
+}
+
+fn is_expression(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Number(_)
+            | Node::Boolean(_)
+            | Node::Name(_)
+            | Node::Array(_)
+            | Node::Operation(_)
+            | Node::Call(_)
+            | Node::Group(_)
+            | Node::Comptime(_)
+            | Node::Index(_)
+    )
+}
+
+/// Keeps the variables and functions of every entry so far, both for the type checker and the
+/// interpreter. Every entry is its own file in the source map, since earlier definitions keep
+/// pointing into it.
+struct Repl {
+    checker: TypeChecker,
+    interpreter: Interpreter,
+    source_map: SourceMap,
+    entries: usize,
+}
+
+impl Repl {
+    fn report(&self, diagnostics: Vec<Diagnostic>) -> bool {
+        let failed = !diagnostics.is_empty();
+
+        for diagnostic in diagnostics {
+            println!("{}", diagnostic.display(&self.source_map));
+        }
+
+        return failed;
+    }
+
+    fn print_diagnostic(&self, diagnostic: Diagnostic) {
+        println!("{}", diagnostic.display(&self.source_map));
+    }
+
+    fn tokenize<'a>(&mut self, code: &'a str) -> tokenizer::Lexer<'a> {
+        self.entries += 1;
+
+        let file = self
+            .source_map
+            .add(format!("<repl {}>", self.entries), String::from(code));
+
+        return tokenizer::Lexer::new(code, file);
+    }
+
+    fn parse(&mut self, code: &str) -> Option<Vec<Node>> {
+        let tokens = self.tokenize(code);
+
+        if self.report(tokenizer::errors(code, tokens.file())) {
+            return None;
+        }
+
+        let mut tree = syntax_tree::build_syntax_tree(tokens);
+
+        if self.report(syntax_tree::errors(&tree)) {
+            return None;
+        }
+
+        let diagnostics = modules::link(&mut tree, &mut self.source_map);
+
+        if self.report(diagnostics) {
+            return None;
+        }
+
+        if self.report(constant_folding::fold_constants(&mut tree)) {
+            return None;
+        }
+
+        return Some(tree);
+    }
+
+    fn print_type(&mut self, code: &str) {
+        let Some(tree) = self.parse(code) else {
+            return;
+        };
+
+        if tree.len() != 1 || !is_expression(&tree[0]) {
+            println!("Error: :type expects a single expression");
+
+            return;
+        }
+
+        match self.checker.expression_type(&tree[0], None) {
+            Ok(node_type) => println!("{}", node_type),
+            Err(diagnostic) => self.print_diagnostic(diagnostic),
+        }
+    }
+
+    fn evaluate(&mut self, code: &str) {
+        let Some(tree) = self.parse(code) else {
+            return;
+        };
+
+        for node in tree {
+            if is_expression(&node) {
+                let node_type = match self.checker.expression_type(&node, None) {
+                    Ok(node_type) => node_type,
+                    Err(diagnostic) => {
+                        self.print_diagnostic(diagnostic);
+
+                        return;
+                    }
+                };
+
+                match self.interpreter.evaluate(&node) {
+                    Ok(Value::Void) => {}
+                    Ok(value) => println!("{}: {}", value, node_type),
+                    Err(diagnostic) => {
+                        self.print_diagnostic(diagnostic);
+
+                        return;
+                    }
+                }
+
+                continue;
+            }
+
+            self.checker.check_entry(&node);
+
+            let diagnostics = self.checker.take_diagnostics();
+
+            if self.report(diagnostics) {
+                return;
+            }
+
+            if let Err(diagnostic) = self.interpreter.load(vec![node]) {
+                self.print_diagnostic(diagnostic);
+
+                return;
+            }
+        }
+    }
+
+    /// Runs a finished entry. Returns false when the repl should stop.
+    fn entry(&mut self, entry: &str) -> bool {
+        let trimmed = entry.trim();
+
+        let (command, argument) = match trimmed.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument),
+            None => (trimmed, ""),
+        };
+
+        match command {
+            "" => {}
+            ":quit" | ":q" => return false,
+            ":help" => println!("{}", HELP),
+            ":tokens" => {
+                for token in self.tokenize(argument) {
+                    println!("{}", token);
+                }
+            }
+            ":ast" => {
+                for node in syntax_tree::build_syntax_tree(self.tokenize(argument)) {
+                    println!("{}", node.display(0));
+                }
+            }
+            ":type" => self.print_type(argument),
+            _ if command.starts_with(':') => {
+                println!("Error: Unknown command {}, try :help", command)
+            }
+            _ => self.evaluate(entry),
+        }
+
+        return true;
+    }
+}
+
+/// Reads entries from stdin until it closes. Lines are collected into one entry until its
+/// braces are balanced, so functions and blocks can span several lines.
+pub fn run() {
+    let mut repl = Repl {
+        checker: TypeChecker::new(),
+        interpreter: Interpreter::new(),
+        source_map: SourceMap::new(),
+        entries: 0,
+    };
+
+    let stdin = io::stdin();
+    let mut entry = String::new();
+
+    loop {
+        print!("{}", if entry.is_empty() { "> " } else { "... " });
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        entry.push_str(&line);
+
+        if open_braces(&entry) > 0 {
+            continue;
+        }
+
+        if !repl.entry(&entry) {
+            return;
+        }
+
+        entry.clear();
+    }
+
+    println!();
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+
+use crate::unicode;
+
+/// Identifies a file that was added to a `SourceMap`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct FileId(pub u32);
+
+/// A range of bytes in a source file, from `lo` up to but not including `hi`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Span {
+    pub file: FileId,
+    pub lo: u32,
+    pub hi: u32,
+}
+
+impl Span {
+    pub fn new(file: FileId, lo: usize, hi: usize) -> Span {
+        return Span {
+            file,
+            lo: lo as u32,
+            hi: hi as u32,
+        };
+    }
+
+    /// The smallest span covering both spans, which are in the same file.
+    pub fn to(self, other: Span) -> Span {
+        return Span {
+            file: self.file,
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        };
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.lo, self.hi)
+    }
+}
+
+/// What a column counts from the start of its line.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColumnEncoding {
+    /// Bytes.
+    Utf8,
+    /// UTF-16 code units, which is what editors speaking LSP count by default.
+    Utf16,
+    /// User perceived characters, which is what diagnostics show.
+    Grapheme,
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    /// The byte offset every line starts at.
+    line_starts: Vec<u32>,
+}
+
+fn column(text: &str, encoding: ColumnEncoding) -> usize {
+    match encoding {
+        ColumnEncoding::Utf8 => return text.len(),
+        ColumnEncoding::Utf16 => return text.encode_utf16().count(),
+        ColumnEncoding::Grapheme => return unicode::graphemes(text),
+    }
+}
+
+impl SourceFile {
+    fn new(name: String, source: String) -> SourceFile {
+        let mut line_starts = vec![0];
+
+        for (index, character) in source.char_indices() {
+            if character == '\n' {
+                line_starts.push(index as u32 + 1);
+            }
+        }
+
+        return SourceFile {
+            name,
+            source,
+            line_starts,
+        };
+    }
+
+    fn line(&self, offset: u32) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => return line,
+            Err(line) => return line - 1,
+        }
+    }
+
+    /// The text of a line, without its line break.
+    fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line] as usize;
+        let end = match self.line_starts.get(line + 1) {
+            Some(end) => *end as usize - 1,
+            None => self.source.len(),
+        };
+
+        return self.source[start..end].trim_end_matches('\r');
+    }
+}
+
+/// Holds every source file the compiler has seen, so spans can be turned back into names, lines
+/// and columns.
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        return SourceMap { files: Vec::new() };
+    }
+
+    pub fn add(&mut self, name: String, source: String) -> FileId {
+        self.files.push(SourceFile::new(name, source));
+
+        return FileId(self.files.len() as u32 - 1);
+    }
+
+    pub fn file(&self, file: FileId) -> &SourceFile {
+        return &self.files[file.0 as usize];
+    }
+
+    pub fn name(&self, file: FileId) -> &str {
+        return &self.file(file).name;
+    }
+
+    pub fn source(&self, file: FileId) -> &str {
+        return &self.file(file).source;
+    }
+
+    /// The zero based line and column of a byte offset.
+    pub fn location(&self, file: FileId, offset: u32, encoding: ColumnEncoding) -> (usize, usize) {
+        let source_file = self.file(file);
+        let offset = offset.min(source_file.source.len() as u32);
+        let line = source_file.line(offset);
+        let start = source_file.line_starts[line] as usize;
+
+        return (
+            line,
+            column(&source_file.source[start..offset as usize], encoding),
+        );
+    }
+
+    /// The byte offset of a zero based line and column, clamped to the end of the line.
+    pub fn offset(&self, file: FileId, location: (usize, usize), encoding: ColumnEncoding) -> u32 {
+        let source_file = self.file(file);
+
+        let Some(start) = source_file.line_starts.get(location.0) else {
+            return source_file.source.len() as u32;
+        };
+
+        let text = source_file.line_text(location.0);
+
+        // Columns are counted in one pass over the line, rather than measuring every prefix.
+        let index = match encoding {
+            ColumnEncoding::Utf8 => text
+                .char_indices()
+                .map(|(index, _)| index)
+                .find(|index| *index >= location.1),
+            ColumnEncoding::Utf16 => {
+                let mut units = 0;
+
+                text.char_indices()
+                    .find(|(_, character)| {
+                        let reached = units >= location.1;
+
+                        units += character.len_utf16();
+
+                        return reached;
+                    })
+                    .map(|(index, _)| index)
+            }
+            ColumnEncoding::Grapheme => unicode::grapheme_starts(text).nth(location.1),
+        };
+
+        return *start + index.unwrap_or(text.len()) as u32;
+    }
+
+    /// The lines and grapheme columns a span covers, with the end pointing at its last character
+    /// rather than past it, which is how diagnostics and debug information show spans.
+    pub fn lines_and_characters(&self, span: Span) -> ((usize, usize), (usize, usize)) {
+        let start = self.location(span.file, span.lo, ColumnEncoding::Grapheme);
+
+        let source = self.source(span.file);
+        let hi = (span.hi.max(span.lo) as usize).min(source.len());
+
+        let lo = (span.lo as usize).min(hi);
+
+        // The end is the last grapheme, which can be several characters like an emoji with a
+        // skin tone.
+        let last = span.lo
+            + unicode::grapheme_starts(&source[lo..hi])
+                .last()
+                .unwrap_or(0) as u32;
+
+        let end = self.location(span.file, last, ColumnEncoding::Grapheme);
+
+        return ((start.0, end.0), (start.1, end.1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+
+    // "é" is two bytes and one UTF-16 unit, "😀" four bytes and two units, and the family emoji is
+    // five characters joined into one grapheme.
+    const SOURCE: &str = "a = 1\r\né😀b\n👨\u{200d}👩\u{200d}👧 c\n";
+
+    fn source_map() -> (SourceMap, FileId) {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(SOURCE));
+
+        return (source_map, file);
+    }
+
+    #[test]
+    fn locates_offsets_in_every_encoding() {
+        let (source_map, file) = source_map();
+        let b = SOURCE.find('b').unwrap() as u32;
+        let c = SOURCE.find('c').unwrap() as u32;
+
+        assert_eq!(source_map.location(file, 0, ColumnEncoding::Utf8), (0, 0));
+        assert_eq!(
+            source_map.location(file, 4, ColumnEncoding::Grapheme),
+            (0, 4)
+        );
+
+        assert_eq!(source_map.location(file, b, ColumnEncoding::Utf8), (1, 6));
+        assert_eq!(source_map.location(file, b, ColumnEncoding::Utf16), (1, 3));
+        assert_eq!(
+            source_map.location(file, b, ColumnEncoding::Grapheme),
+            (1, 2)
+        );
+
+        assert_eq!(source_map.location(file, c, ColumnEncoding::Utf8), (2, 19));
+        assert_eq!(source_map.location(file, c, ColumnEncoding::Utf16), (2, 9));
+        assert_eq!(
+            source_map.location(file, c, ColumnEncoding::Grapheme),
+            (2, 2)
+        );
+    }
+
+    #[test]
+    fn finds_offsets_in_every_encoding() {
+        let (source_map, file) = source_map();
+        let b = SOURCE.find('b').unwrap() as u32;
+        let c = SOURCE.find('c').unwrap() as u32;
+
+        assert_eq!(source_map.offset(file, (1, 6), ColumnEncoding::Utf8), b);
+        assert_eq!(source_map.offset(file, (1, 3), ColumnEncoding::Utf16), b);
+        assert_eq!(source_map.offset(file, (1, 2), ColumnEncoding::Grapheme), b);
+
+        assert_eq!(source_map.offset(file, (2, 19), ColumnEncoding::Utf8), c);
+        assert_eq!(source_map.offset(file, (2, 9), ColumnEncoding::Utf16), c);
+        assert_eq!(source_map.offset(file, (2, 2), ColumnEncoding::Grapheme), c);
+    }
+
+    #[test]
+    fn offsets_round_trip_through_locations() {
+        let (source_map, file) = source_map();
+
+        for encoding in [
+            ColumnEncoding::Utf8,
+            ColumnEncoding::Utf16,
+            ColumnEncoding::Grapheme,
+        ] {
+            // The `\n` of a `\r\n` has no column of its own.
+            for offset in unicode::grapheme_starts(SOURCE).filter(|offset| *offset != 6) {
+                let location = source_map.location(file, offset as u32, encoding);
+
+                assert_eq!(source_map.offset(file, location, encoding), offset as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_offsets_to_the_line() {
+        let (source_map, file) = source_map();
+
+        // Past the end of a line is its end, before the `\r\n`.
+        assert_eq!(source_map.offset(file, (0, 50), ColumnEncoding::Utf16), 5);
+        // The middle of a character is the start of the next one.
+        assert_eq!(source_map.offset(file, (1, 2), ColumnEncoding::Utf16), 13);
+        assert_eq!(source_map.offset(file, (1, 1), ColumnEncoding::Utf8), 9);
+        // Past the last line is the end of the file.
+        assert_eq!(
+            source_map.offset(file, (9, 0), ColumnEncoding::Utf8),
+            SOURCE.len() as u32
+        );
+    }
+
+    #[test]
+    fn names_the_file_in_diagnostics() {
+        let (source_map, file) = source_map();
+        let b = SOURCE.find('b').unwrap();
+
+        let diagnostic = Diagnostic::error(String::from("Bad b"), Span::new(file, b, b + 1));
+
+        assert_eq!(
+            diagnostic.display(&source_map),
+            "Error: Bad b in test.mt 1, 2 -> 1, 2"
+        );
+    }
+}
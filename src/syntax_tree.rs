@@ -1,8 +1,11 @@
 use std::fmt;
-use std::str;
 
+use crate::diagnostic::Diagnostic;
+use crate::source_map::Span;
 use crate::tokenizer::{self, TokenType};
+use crate::unicode;
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Operator {
     BitwiseAnd,
     BitwiseOr,
@@ -14,6 +17,7 @@ pub enum Operator {
     GreaterThanOrEqual,
     Assign,
     Equal,
+    NotEqual,
     Add,
     Subtract,
     Multiply,
@@ -21,34 +25,46 @@ pub enum Operator {
     Modulo,
     Access,
     Not,
+    Negate,
 }
 
 impl Operator {
     pub fn two_sided(&self) -> bool {
-        match self {
-            Operator::BitwiseAnd => true,
-            Operator::BitwiseOr => true,
-            Operator::And => true,
-            Operator::Or => true,
-            Operator::LessThan => true,
-            Operator::LessThanOrEqual => true,
-            Operator::GreaterThan => true,
-            Operator::GreaterThanOrEqual => true,
-            Operator::Equal => true,
-            Operator::Add => true,
-            Operator::Subtract => true,
-            Operator::Multiply => true,
-            Operator::Divide => true,
-            Operator::Modulo => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Operator::BitwiseAnd
+                | Operator::BitwiseOr
+                | Operator::And
+                | Operator::Or
+                | Operator::LessThan
+                | Operator::LessThanOrEqual
+                | Operator::GreaterThan
+                | Operator::GreaterThanOrEqual
+                | Operator::Equal
+                | Operator::NotEqual
+                | Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+        )
     }
 
     pub fn one_sided(&self) -> bool {
-        match self {
-            Operator::Not => true,
-            _ => false,
-        }
+        matches!(self, Operator::Not | Operator::Negate)
+    }
+
+    /// Whether the operator compares its operands, so it gives a boolean whatever they are.
+    pub fn comparison(&self) -> bool {
+        matches!(
+            self,
+            Operator::LessThan
+                | Operator::LessThanOrEqual
+                | Operator::GreaterThan
+                | Operator::GreaterThanOrEqual
+                | Operator::Equal
+                | Operator::NotEqual
+        )
     }
 
     pub fn priority(&self) -> usize {
@@ -62,6 +78,7 @@ impl Operator {
             Operator::GreaterThan => 5,
             Operator::GreaterThanOrEqual => 5,
             Operator::Equal => 5,
+            Operator::NotEqual => 5,
             Operator::Add => 2,
             Operator::Subtract => 2,
             Operator::Multiply => 1,
@@ -72,7 +89,7 @@ impl Operator {
     }
 
     pub fn priority_limit() -> usize {
-        6
+        7
     }
 }
 
@@ -89,6 +106,7 @@ impl fmt::Display for Operator {
             Operator::GreaterThanOrEqual => write!(f, ">="),
             Operator::Assign => write!(f, "="),
             Operator::Equal => write!(f, "=="),
+            Operator::NotEqual => write!(f, "!="),
             Operator::Add => write!(f, "+"),
             Operator::Subtract => write!(f, "-"),
             Operator::Multiply => write!(f, "*"),
@@ -96,49 +114,18 @@ impl fmt::Display for Operator {
             Operator::Modulo => write!(f, "%"),
             Operator::Access => write!(f, "."),
             Operator::Not => write!(f, "!"),
+            Operator::Negate => write!(f, "-"),
         }
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct OperatorNode {
     pub operator: Operator,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl OperatorNode {
-    pub fn from_token(token: &tokenizer::Token) -> OperatorNode {
-        return OperatorNode {
-            operator: if token.content == "&" {
-                Operator::BitwiseAnd
-            } else if token.content == "|" {
-                Operator::BitwiseOr
-            } else if token.content == "<" {
-                Operator::LessThan
-            } else if token.content == "=" {
-                Operator::Assign
-            } else if token.content == ">" {
-                Operator::GreaterThan
-            } else if token.content == "+" {
-                Operator::Add
-            } else if token.content == "-" {
-                Operator::Subtract
-            } else if token.content == "*" {
-                Operator::Multiply
-            } else if token.content == "/" {
-                Operator::Divide
-            } else if token.content == "%" {
-                Operator::Modulo
-            } else if token.content == "." {
-                Operator::Access
-            } else {
-                panic!("Encountered unknown operator {}", token.content)
-            },
-            lines: token.lines,
-            characters: token.characters,
-        };
-    }
-
     pub fn display(&self, depth: usize) -> String {
         return format!("{}Operator {}", " | ".repeat(depth), self.operator);
     }
@@ -146,14 +133,11 @@ impl OperatorNode {
 
 impl fmt::Display for OperatorNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Operator {} {}, {} -> {}, {}",
-            self.operator, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Operator {} {}", self.operator, self.span)
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Symbol {
     OpenParen,
     ClosedParen,
@@ -162,6 +146,10 @@ pub enum Symbol {
     SingleQuote,
     OpenCurlyBracket,
     ClosedCurlyBracket,
+    OpenSquareBracket,
+    ClosedSquareBracket,
+    Colon,
+    Semicolon,
 }
 
 impl fmt::Display for Symbol {
@@ -174,41 +162,21 @@ impl fmt::Display for Symbol {
             Symbol::SingleQuote => write!(f, "'"),
             Symbol::OpenCurlyBracket => write!(f, "{{"),
             Symbol::ClosedCurlyBracket => write!(f, "}}"),
+            Symbol::OpenSquareBracket => write!(f, "["),
+            Symbol::ClosedSquareBracket => write!(f, "]"),
+            Symbol::Colon => write!(f, ":"),
+            Symbol::Semicolon => write!(f, ";"),
         }
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct SymbolNode {
     pub symbol: Symbol,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl SymbolNode {
-    pub fn from_token(token: &tokenizer::Token) -> SymbolNode {
-        return SymbolNode {
-            symbol: if token.content == "(" {
-                Symbol::OpenParen
-            } else if token.content == ")" {
-                Symbol::ClosedParen
-            } else if token.content == "," {
-                Symbol::Comma
-            } else if token.content == "\"" {
-                Symbol::Quote
-            } else if token.content == "'" {
-                Symbol::SingleQuote
-            } else if token.content == "{" {
-                Symbol::OpenCurlyBracket
-            } else if token.content == "}" {
-                Symbol::ClosedCurlyBracket
-            } else {
-                panic!("Encountered unknown symbol {}", token.content)
-            },
-            lines: token.lines,
-            characters: token.characters,
-        };
-    }
-
     pub fn display(&self, depth: usize) -> String {
         return format!("{}Symbol {}", " | ".repeat(depth), self.symbol);
     }
@@ -216,14 +184,11 @@ impl SymbolNode {
 
 impl fmt::Display for SymbolNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Symbol {} {}, {} -> {}, {}",
-            self.symbol, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Symbol {} {}", self.symbol, self.span)
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Type {
     I32,
     U32,
@@ -246,65 +211,61 @@ impl fmt::Display for Type {
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct TypeNode {
     pub node_type: Type,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    /// Whether this is `T*`, a pointer to values of the type on the heap.
+    pub pointer: bool,
+    pub array_size: Option<Box<Node>>,
+    pub span: Span,
 }
 
 impl TypeNode {
-    pub fn from_token(token: &tokenizer::Token) -> TypeNode {
-        return TypeNode {
-            node_type: if token.content == "i32" {
-                Type::I32
-            } else if token.content == "u32" {
-                Type::U32
-            } else if token.content == "f32" {
-                Type::F32
-            } else if token.content == "string" {
-                Type::String
-            } else if token.content == "void" {
-                Type::Void
-            } else if token.content == "bool" {
-                Type::Boolean
-            } else {
-                panic!("Encountered unknown type {}", token.content)
-            },
-            lines: token.lines,
-            characters: token.characters,
-        };
+    fn type_name(&self) -> String {
+        if self.pointer {
+            return format!("{}*", self.node_type);
+        }
+
+        return self.node_type.to_string();
     }
 
     pub fn display(&self, depth: usize) -> String {
-        return format!("{}Type {}", " | ".repeat(depth), self.node_type);
+        if let Some(array_size) = &self.array_size {
+            return format!(
+                "{}Array Type {}\n{}",
+                " | ".repeat(depth),
+                self.type_name(),
+                array_size.display(depth + 1)
+            );
+        }
+
+        return format!("{}Type {}", " | ".repeat(depth), self.type_name());
     }
 }
 
 impl fmt::Display for TypeNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Type {} {}, {} -> {}, {}",
-            self.node_type, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        if let Some(array_size) = &self.array_size {
+            return write!(
+                f,
+                "Array Type {} [{}] {}",
+                self.type_name(),
+                array_size,
+                self.span
+            );
+        }
+
+        write!(f, "Type {} {}", self.type_name(), self.span)
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct BooleanNode {
     pub value: bool,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl BooleanNode {
-    pub fn from_token(token: &tokenizer::Token) -> BooleanNode {
-        return BooleanNode {
-            value: token.content == "true",
-            lines: token.lines,
-            characters: token.characters,
-        };
-    }
-
     pub fn display(&self, depth: usize) -> String {
         return format!("{}Boolean {}", " | ".repeat(depth), self.value);
     }
@@ -312,38 +273,17 @@ impl BooleanNode {
 
 impl fmt::Display for BooleanNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Boolean {} {}, {} -> {}, {}",
-            self.value, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Boolean {} {}", self.value, self.span)
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct NumberNode {
     pub value: i64,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
-const NUMBER_CHARS: &[char] = &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
-
 impl NumberNode {
-    pub fn is_number(string: &String) -> bool {
-        return !string.chars().any(|char| !NUMBER_CHARS.contains(&char));
-    }
-
-    pub fn from_token(token: &tokenizer::Token) -> NumberNode {
-        return NumberNode {
-            value: token
-                .content
-                .parse()
-                .expect(&format!("Unknown number encountered {}", token.content)),
-            lines: token.lines,
-            characters: token.characters,
-        };
-    }
-
     pub fn display(&self, depth: usize) -> String {
         return format!("{}Number {}", " | ".repeat(depth), self.value);
     }
@@ -351,26 +291,21 @@ impl NumberNode {
 
 impl fmt::Display for NumberNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Number {} {}, {} -> {}, {}",
-            self.value, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Number {} {}", self.value, self.span)
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct NameNode {
     pub value: String,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl NameNode {
     pub fn from_token(token: &tokenizer::Token) -> NameNode {
         return NameNode {
-            value: token.content.clone(),
-            lines: token.lines,
-            characters: token.characters,
+            value: unicode::nfc(token.content),
+            span: token.span,
         };
     }
 
@@ -381,18 +316,23 @@ impl NameNode {
 
 impl fmt::Display for NameNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Name {} {}, {} -> {}, {}",
-            self.value, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Name {} {}", self.value, self.span)
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Keyword {
     If,
     Forever,
     Return,
+    Const,
+    Comptime,
+    Else,
+    Break,
+    Import,
+    From,
+    Defer,
+    Extern,
 }
 
 impl fmt::Display for Keyword {
@@ -401,33 +341,25 @@ impl fmt::Display for Keyword {
             Keyword::If => write!(f, "If"),
             Keyword::Forever => write!(f, "Forever"),
             Keyword::Return => write!(f, "Return"),
+            Keyword::Const => write!(f, "Const"),
+            Keyword::Comptime => write!(f, "Comptime"),
+            Keyword::Else => write!(f, "Else"),
+            Keyword::Break => write!(f, "Break"),
+            Keyword::Import => write!(f, "Import"),
+            Keyword::From => write!(f, "From"),
+            Keyword::Defer => write!(f, "Defer"),
+            Keyword::Extern => write!(f, "Extern"),
         }
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct KeywordNode {
     pub keyword: Keyword,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl KeywordNode {
-    pub fn from_token(token: &tokenizer::Token) -> KeywordNode {
-        return KeywordNode {
-            keyword: if token.content == "if" {
-                Keyword::If
-            } else if token.content == "forever" {
-                Keyword::Forever
-            } else if token.content == "return" {
-                Keyword::Return
-            } else {
-                panic!("Encountered unknown keyword {}", token.content)
-            },
-            lines: token.lines,
-            characters: token.characters,
-        };
-    }
-
     pub fn display(&self, depth: usize) -> String {
         return format!("{}Keyword {}", " | ".repeat(depth), self.keyword);
     }
@@ -435,31 +367,25 @@ impl KeywordNode {
 
 impl fmt::Display for KeywordNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Keyword {} {}, {} -> {}, {}",
-            self.keyword, self.lines.0, self.characters.0, self.lines.1, self.characters.1
-        )
+        write!(f, "Keyword {} {}", self.keyword, self.span)
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct BlockNode {
     pub content: Vec<Node>,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl BlockNode {
     pub fn display(&self, depth: usize) -> String {
-        let mut sub_display = String::from("");
+        let mut display = format!("{}Block", " | ".repeat(depth));
 
         for node in &self.content {
-            sub_display += &format!("{}\n", node.display(depth + 1));
+            display += &format!("\n{}", node.display(depth + 1));
         }
 
-        sub_display.pop().unwrap();
-
-        return format!("{}Block\n{}", " | ".repeat(depth), sub_display);
+        return display;
     }
 }
 
@@ -471,19 +397,15 @@ impl fmt::Display for BlockNode {
             sub_display += &format!("{}\n", node);
         }
 
-        write!(
-            f,
-            "Block {}, {} -> {}, {}\n----\n{}----",
-            self.lines.0, self.characters.0, self.lines.1, self.characters.1, sub_display
-        )
+        write!(f, "Block {}\n----\n{}----", self.span, sub_display)
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct AssignmentNode {
     pub name: NameNode,
     pub value: Box<Node>,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl AssignmentNode {
@@ -501,24 +423,30 @@ impl fmt::Display for AssignmentNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Assignment {} = {}  {}, {} -> {}, {}",
-            self.name, self.value, self.lines.0, self.characters.0, self.lines.1, self.characters.1
+            "Assignment {} = {}  {}",
+            self.name, self.value, self.span
         )
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct VariableDefinitionNode {
+    pub constant: bool,
     pub node_type: TypeNode,
     pub assignment: AssignmentNode,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
 impl VariableDefinitionNode {
     pub fn display(&self, depth: usize) -> String {
         return format!(
-            "{}Variable Definition\n{}\n{}",
+            "{}{}\n{}\n{}",
             " | ".repeat(depth),
+            if self.constant {
+                "Constant Definition"
+            } else {
+                "Variable Definition"
+            },
             self.node_type.display(depth + 1),
             self.assignment.display(depth + 1)
         );
@@ -529,32 +457,77 @@ impl fmt::Display for VariableDefinitionNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Variable Definition {} {}  {}, {} -> {}, {}",
+            "{} {} {}  {}",
+            if self.constant {
+                "Constant Definition"
+            } else {
+                "Variable Definition"
+            },
             self.node_type,
             self.assignment,
-            self.lines.0,
-            self.characters.0,
-            self.lines.1,
-            self.characters.1
+            self.span
+        )
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ParameterNode {
+    pub node_type: TypeNode,
+    pub name: NameNode,
+    pub span: Span,
+}
+
+impl ParameterNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Parameter\n{}\n{}",
+            " | ".repeat(depth),
+            self.node_type.display(depth + 1),
+            self.name.display(depth + 1)
+        );
+    }
+}
+
+impl fmt::Display for ParameterNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Parameter {} {}  {}",
+            self.node_type, self.name, self.span
         )
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct FunctionDefinitionNode {
     pub node_type: TypeNode,
     pub name: NameNode,
+    pub parameters: Vec<ParameterNode>,
     pub block: BlockNode,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    /// Whether the function is `extern "C"`, so C code can call it by its own name.
+    pub external: bool,
+    pub span: Span,
 }
 
 impl FunctionDefinitionNode {
     pub fn display(&self, depth: usize) -> String {
+        let mut parameters_display = String::from("");
+
+        for parameter in &self.parameters {
+            parameters_display += &format!("{}\n", parameter.display(depth + 1));
+        }
+
         return format!(
-            "{}Function Definition\n{}\n{}\n{}",
+            "{}{}\n{}\n{}\n{}{}",
             " | ".repeat(depth),
+            if self.external {
+                "Extern Function Definition"
+            } else {
+                "Function Definition"
+            },
             self.node_type.display(depth + 1),
             self.name.display(depth + 1),
+            parameters_display,
             self.block.display(depth + 1),
         );
     }
@@ -564,289 +537,1005 @@ impl fmt::Display for FunctionDefinitionNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Function Definition {} {} {}  {}, {} -> {}, {}",
-            self.node_type,
-            self.name,
-            self.block,
-            self.lines.0,
-            self.characters.0,
-            self.lines.1,
-            self.characters.1
+            "Function Definition {} {} {}  {}",
+            self.node_type, self.name, self.block, self.span
         )
     }
 }
 
-pub struct OperationNode {
-    pub operator: OperatorNode,
-    pub values: Vec<Node>,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+/// A C function declared with `extern "C"`, which has no body for Metor to run.
+#[derive(Clone, PartialEq)]
+pub struct ExternFunctionNode {
+    pub node_type: TypeNode,
+    pub name: NameNode,
+    pub parameters: Vec<ParameterNode>,
+    pub span: Span,
 }
 
-impl OperationNode {
+impl ExternFunctionNode {
     pub fn display(&self, depth: usize) -> String {
-        let mut sub_display = String::from("");
+        let mut parameters_display = String::from("");
 
-        for node in &self.values {
-            sub_display += &format!("{}\n", node.display(depth + 1));
+        for parameter in &self.parameters {
+            parameters_display += &format!("\n{}", parameter.display(depth + 1));
         }
 
-        sub_display.pop().unwrap();
-
         return format!(
-            "{}Operation\n{}\n{}",
+            "{}Extern Function\n{}\n{}{}",
             " | ".repeat(depth),
-            self.operator.display(depth + 1),
-            sub_display
+            self.node_type.display(depth + 1),
+            self.name.display(depth + 1),
+            parameters_display,
         );
     }
 }
 
-impl fmt::Display for OperationNode {
+impl fmt::Display for ExternFunctionNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Operation")
+        write!(
+            f,
+            "Extern Function {} {}  {}",
+            self.node_type, self.name, self.span
+        )
     }
 }
 
-pub enum Node {
-    String,
-    Keyword(KeywordNode),
-    Type(TypeNode),
-    Operator(OperatorNode),
-    Symbol(SymbolNode),
-    Number(NumberNode),
-    Boolean(BooleanNode),
-    Name(NameNode),
-    Block(BlockNode),
-    Assignment(AssignmentNode),
-    VariableDefinition(VariableDefinitionNode),
-    FunctionDefinition(FunctionDefinitionNode),
-    Operation(OperationNode),
+#[derive(Clone, PartialEq)]
+pub struct CallNode {
+    pub name: NameNode,
+    pub arguments: Vec<Node>,
+    pub span: Span,
 }
 
-impl Node {
-    pub fn get_characters(&self) -> (usize, usize) {
-        match self {
-            Node::String => (0, 0),
-            Node::Keyword(node) => node.characters,
-            Node::Type(node) => node.characters,
-            Node::Operator(node) => node.characters,
-            Node::Symbol(node) => node.characters,
-            Node::Number(node) => node.characters,
-            Node::Boolean(node) => node.characters,
-            Node::Name(node) => node.characters,
-            Node::Block(node) => node.characters,
-            Node::Assignment(node) => node.characters,
-            Node::VariableDefinition(node) => node.characters,
-            Node::FunctionDefinition(node) => node.characters,
-            Node::Operation(node) => node.characters,
-        }
-    }
-
-    pub fn get_lines(&self) -> (usize, usize) {
-        match self {
-            Node::String => (0, 0),
-            Node::Keyword(node) => node.lines,
-            Node::Type(node) => node.lines,
-            Node::Operator(node) => node.lines,
-            Node::Symbol(node) => node.lines,
-            Node::Number(node) => node.lines,
-            Node::Boolean(node) => node.lines,
-            Node::Name(node) => node.lines,
-            Node::Block(node) => node.lines,
-            Node::Assignment(node) => node.lines,
-            Node::VariableDefinition(node) => node.lines,
-            Node::FunctionDefinition(node) => node.lines,
-            Node::Operation(node) => node.lines,
-        }
-    }
-
+impl CallNode {
     pub fn display(&self, depth: usize) -> String {
-        match self {
-            Node::String => String::from("String"),
-            Node::Keyword(node) => node.display(depth),
-            Node::Type(node) => node.display(depth),
-            Node::Operator(node) => node.display(depth),
-            Node::Symbol(node) => node.display(depth),
-            Node::Number(node) => node.display(depth),
-            Node::Boolean(node) => node.display(depth),
-            Node::Name(node) => node.display(depth),
-            Node::Block(node) => node.display(depth),
-            Node::Assignment(node) => node.display(depth),
-            Node::VariableDefinition(node) => node.display(depth),
-            Node::FunctionDefinition(node) => node.display(depth),
-            Node::Operation(node) => node.display(depth),
+        let mut sub_display = String::from("");
+
+        for node in &self.arguments {
+            sub_display += &format!("\n{}", node.display(depth + 1));
         }
+
+        return format!(
+            "{}Call\n{}{}",
+            " | ".repeat(depth),
+            self.name.display(depth + 1),
+            sub_display
+        );
     }
 }
 
-impl fmt::Display for Node {
+impl fmt::Display for CallNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Node::Keyword(keyword_node) => write!(f, "{}", keyword_node),
-            Node::Type(type_node) => write!(f, "{}", type_node),
-            Node::Name(name_node) => write!(f, "{}", name_node),
-            Node::Operator(operator_node) => write!(f, "{}", operator_node),
-            Node::Number(number_node) => write!(f, "{}", number_node),
-            Node::Boolean(boolean_node) => write!(f, "{}", boolean_node),
-            Node::Symbol(symbol_node) => write!(f, "{}", symbol_node),
-            Node::Block(block_node) => write!(f, "{}", block_node),
-            Node::Assignment(assignment_node) => write!(f, "{}", assignment_node),
-            Node::VariableDefinition(node) => write!(f, "{}", node),
-            _ => write!(f, "Unknown"),
-        }
+        write!(f, "Call {} {}", self.name, self.span)
     }
 }
 
-const BOOLEAN_STRINGS: &[&str] = &["true", "false"];
-const TYPE_STRINGS: &[&str] = &["i32", "u32", "f32", "string", "void", "bool"];
-const OPERATOR_STRINGS: &[&str] = &["&", "|", "<", "=", ">", "+", "-", "/", "*", "%", ".", "!"];
-const KEYWORD_STRINGS: &[&str] = &["if", "forever", "return"];
+#[derive(Clone, PartialEq)]
+pub struct IfNode {
+    pub condition: Box<Node>,
+    pub block: BlockNode,
+    pub else_node: Option<Box<Node>>,
+    pub span: Span,
+}
 
-pub fn build_multisymbol_operators(nodes: &mut Vec<Node>) {
-    for index in 0..nodes.len() {
-        if index >= nodes.len() {
-            break;
-        }
+impl IfNode {
+    pub fn display(&self, depth: usize) -> String {
+        let mut display = format!(
+            "{}If\n{}\n{}",
+            " | ".repeat(depth),
+            self.condition.display(depth + 1),
+            self.block.display(depth + 1)
+        );
 
-        if nodes.len() >= 2 && index <= nodes.len() - 2 {
-            let first_symbol = &nodes[index];
-            let second_symbol = &nodes[index + 1];
+        if let Some(else_node) = &self.else_node {
+            display += &format!(
+                "\n{}Else\n{}",
+                " | ".repeat(depth + 1),
+                else_node.display(depth + 2)
+            );
+        }
 
-            if let Node::Operator(first_symbol_node) = first_symbol
-                && let Node::Operator(second_symbol_node) = second_symbol
-            {
-                if first_symbol_node.lines.1 != second_symbol_node.lines.0 {
-                    continue;
-                }
+        return display;
+    }
+}
 
-                if first_symbol_node.characters.1 + 1 != second_symbol_node.characters.0 {
-                    continue;
-                }
+impl fmt::Display for IfNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "If {} {}  {}", self.condition, self.block, self.span)
+    }
+}
 
-                let lines = (first_symbol.get_lines().0, second_symbol.get_lines().1);
-                let characters = (
-                    first_symbol.get_characters().0,
-                    second_symbol.get_characters().1,
-                );
+#[derive(Clone, PartialEq)]
+pub struct ForeverNode {
+    pub block: BlockNode,
+    pub span: Span,
+}
 
-                if let Operator::BitwiseAnd = first_symbol_node.operator
-                    && let Operator::BitwiseAnd = second_symbol_node.operator
-                {
-                    nodes.remove(index);
-                    nodes.remove(index);
-                    nodes.insert(
-                        index,
-                        Node::Operator(OperatorNode {
-                            operator: Operator::And,
-                            lines,
-                            characters,
-                        }),
-                    );
-                } else if let Operator::BitwiseOr = first_symbol_node.operator
-                    && let Operator::BitwiseOr = second_symbol_node.operator
-                {
-                    nodes.remove(index);
-                    nodes.remove(index);
-                    nodes.insert(
-                        index,
-                        Node::Operator(OperatorNode {
-                            operator: Operator::Or,
-                            lines,
-                            characters,
-                        }),
-                    );
-                } else if let Operator::LessThan = first_symbol_node.operator
-                    && let Operator::Assign = second_symbol_node.operator
-                {
-                    nodes.remove(index);
-                    nodes.remove(index);
-                    nodes.insert(
-                        index,
-                        Node::Operator(OperatorNode {
-                            operator: Operator::LessThanOrEqual,
-                            lines,
-                            characters,
-                        }),
-                    );
-                } else if let Operator::GreaterThan = first_symbol_node.operator
-                    && let Operator::Assign = second_symbol_node.operator
-                {
-                    nodes.remove(index);
-                    nodes.remove(index);
-                    nodes.insert(
-                        index,
-                        Node::Operator(OperatorNode {
-                            operator: Operator::GreaterThanOrEqual,
-                            lines,
-                            characters,
-                        }),
-                    );
-                } else if let Operator::Assign = first_symbol_node.operator
-                    && let Operator::Assign = second_symbol_node.operator
-                {
-                    nodes.remove(index);
-                    nodes.remove(index);
-                    nodes.insert(
-                        index,
-                        Node::Operator(OperatorNode {
-                            operator: Operator::Equal,
-                            lines,
-                            characters,
-                        }),
-                    );
-                }
-            }
-        }
+impl ForeverNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Forever\n{}",
+            " | ".repeat(depth),
+            self.block.display(depth + 1)
+        );
     }
 }
 
-pub fn build_blocks(nodes: &mut Vec<Node>) {
-    let mut index_stack: Vec<usize> = Vec::new();
-
-    for index in 0..nodes.len() {
-        if index >= nodes.len() {
-            break;
-        }
+impl fmt::Display for ForeverNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Forever {}  {}", self.block, self.span)
+    }
+}
 
-        let token = &nodes[index];
+#[derive(Clone, PartialEq)]
+pub struct ReturnNode {
+    pub value: Option<Box<Node>>,
+    pub span: Span,
+}
 
-        if let Node::Symbol(symbol_node) = token
-            && let Symbol::OpenCurlyBracket = symbol_node.symbol
-        {
-            index_stack.push(index);
+impl ReturnNode {
+    pub fn display(&self, depth: usize) -> String {
+        if let Some(value) = &self.value {
+            return format!(
+                "{}Return\n{}",
+                " | ".repeat(depth),
+                value.display(depth + 1)
+            );
         }
 
-        if let Node::Symbol(symbol_node) = token
-            && let Symbol::ClosedCurlyBracket = symbol_node.symbol
-        {
-            if index_stack.len() == 0 {
-                continue;
-            }
-
-            let start_index = index_stack.pop().unwrap();
-
-            let start_character = nodes[start_index].get_characters().0;
-            let end_character = nodes[index].get_characters().1;
-
-            let start_line = nodes[start_index].get_lines().0;
-            let end_line = nodes[index].get_lines().1;
-
-            let content: Vec<Node> = nodes.drain(start_index + 1..index).collect();
+        return format!("{}Return", " | ".repeat(depth));
+    }
+}
 
-            nodes.splice(
-                start_index..start_index + 2,
-                std::iter::once(Node::Block(BlockNode {
-                    content: content,
-                    characters: (start_character, end_character),
-                    lines: (start_line, end_line),
-                })),
-            );
-        }
+impl fmt::Display for ReturnNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Return {}", self.span)
     }
 }
 
-pub fn build_assignements(nodes: &mut Vec<Node>) {
+#[derive(Clone, PartialEq)]
+pub struct OperationNode {
+    pub operator: OperatorNode,
+    pub values: Vec<Node>,
+    pub span: Span,
+}
+
+impl OperationNode {
+    pub fn display(&self, depth: usize) -> String {
+        let mut display = format!(
+            "{}Operation\n{}",
+            " | ".repeat(depth),
+            self.operator.display(depth + 1)
+        );
+
+        for node in &self.values {
+            display += &format!("\n{}", node.display(depth + 1));
+        }
+
+        return display;
+    }
+}
+
+impl fmt::Display for OperationNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Operation")
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct GroupNode {
+    pub content: Vec<Node>,
+    pub span: Span,
+}
+
+impl GroupNode {
+    pub fn display(&self, depth: usize) -> String {
+        let mut sub_display = String::from("");
+
+        for node in &self.content {
+            sub_display += &format!("\n{}", node.display(depth + 1));
+        }
+
+        return format!("{}Group{}", " | ".repeat(depth), sub_display);
+    }
+}
+
+impl fmt::Display for GroupNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Group {}", self.span)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ArrayNode {
+    pub values: Vec<Node>,
+    pub span: Span,
+}
+
+impl ArrayNode {
+    pub fn display(&self, depth: usize) -> String {
+        let mut sub_display = String::from("");
+
+        for node in &self.values {
+            sub_display += &format!("\n{}", node.display(depth + 1));
+        }
+
+        return format!("{}Array{}", " | ".repeat(depth), sub_display);
+    }
+}
+
+impl fmt::Display for ArrayNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Array {}", self.span)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ComptimeNode {
+    pub value: Box<Node>,
+    pub span: Span,
+}
+
+impl ComptimeNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Comptime\n{}",
+            " | ".repeat(depth),
+            self.value.display(depth + 1)
+        );
+    }
+}
+
+impl fmt::Display for ComptimeNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Comptime {} {}", self.value, self.span)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ImportNode {
+    pub names: Vec<NameNode>,
+    pub module: NameNode,
+    pub span: Span,
+}
+
+impl ImportNode {
+    pub fn display(&self, depth: usize) -> String {
+        let mut sub_display = String::from("");
+
+        for name in &self.names {
+            sub_display += &format!("\n{}", name.display(depth + 1));
+        }
+
+        return format!(
+            "{}Import from {}{}",
+            " | ".repeat(depth),
+            self.module.value,
+            sub_display
+        );
+    }
+}
+
+impl fmt::Display for ImportNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Import from {} {}", self.module, self.span)
+    }
+}
+
+/// `pointer[index]`, reading a value of an allocation.
+#[derive(Clone, PartialEq)]
+pub struct IndexNode {
+    pub name: NameNode,
+    pub index: Box<Node>,
+    pub span: Span,
+}
+
+impl IndexNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Index\n{}\n{}",
+            " | ".repeat(depth),
+            self.name.display(depth + 1),
+            self.index.display(depth + 1)
+        );
+    }
+}
+
+impl fmt::Display for IndexNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Index {} [{}] {}", self.name, self.index, self.span)
+    }
+}
+
+/// `pointer[index] = value`, writing a value of an allocation.
+#[derive(Clone, PartialEq)]
+pub struct IndexAssignmentNode {
+    pub target: IndexNode,
+    pub value: Box<Node>,
+    pub span: Span,
+}
+
+impl IndexAssignmentNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Index Assignment\n{}\n{}",
+            " | ".repeat(depth),
+            self.target.display(depth + 1),
+            self.value.display(depth + 1)
+        );
+    }
+}
+
+impl fmt::Display for IndexAssignmentNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Index Assignment {} = {}  {}",
+            self.target, self.value, self.span
+        )
+    }
+}
+
+/// `defer statement`, which runs the statement when the enclosing block is left, however it's
+/// left. Deferred statements run in the reverse of the order they were reached.
+#[derive(Clone, PartialEq)]
+pub struct DeferNode {
+    pub statement: Box<Node>,
+    pub span: Span,
+}
+
+impl DeferNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!(
+            "{}Defer\n{}",
+            " | ".repeat(depth),
+            self.statement.display(depth + 1)
+        );
+    }
+}
+
+impl fmt::Display for DeferNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Defer {}  {}", self.statement, self.span)
+    }
+}
+
+/// Code that didn't parse, from where the parser got stuck to the next newline, `}` or type. The
+/// parser keeps going after it, so one build can report every error.
+#[derive(Clone, PartialEq)]
+pub struct ErrorNode {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ErrorNode {
+    pub fn display(&self, depth: usize) -> String {
+        return format!("{}Error {}", " | ".repeat(depth), self.message);
+    }
+}
+
+impl fmt::Display for ErrorNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error {} {}", self.message, self.span)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Node {
+    #[allow(dead_code)]
+    String,
+    Keyword(KeywordNode),
+    Type(TypeNode),
+    Operator(OperatorNode),
+    Symbol(SymbolNode),
+    Number(NumberNode),
+    Boolean(BooleanNode),
+    Name(NameNode),
+    Block(BlockNode),
+    Assignment(AssignmentNode),
+    VariableDefinition(VariableDefinitionNode),
+    FunctionDefinition(FunctionDefinitionNode),
+    ExternFunction(ExternFunctionNode),
+    Operation(OperationNode),
+    Group(GroupNode),
+    Array(ArrayNode),
+    Comptime(ComptimeNode),
+    Call(CallNode),
+    If(IfNode),
+    Forever(ForeverNode),
+    Return(ReturnNode),
+    Import(ImportNode),
+    Index(IndexNode),
+    IndexAssignment(IndexAssignmentNode),
+    Defer(DeferNode),
+    Error(ErrorNode),
+}
+
+impl Node {
+    pub fn get_span(&self) -> Span {
+        match self {
+            Node::String => Span::default(),
+            Node::Keyword(node) => node.span,
+            Node::Type(node) => node.span,
+            Node::Operator(node) => node.span,
+            Node::Symbol(node) => node.span,
+            Node::Number(node) => node.span,
+            Node::Boolean(node) => node.span,
+            Node::Name(node) => node.span,
+            Node::Block(node) => node.span,
+            Node::Assignment(node) => node.span,
+            Node::VariableDefinition(node) => node.span,
+            Node::FunctionDefinition(node) => node.span,
+            Node::ExternFunction(node) => node.span,
+            Node::Operation(node) => node.span,
+            Node::Group(node) => node.span,
+            Node::Array(node) => node.span,
+            Node::Comptime(node) => node.span,
+            Node::Call(node) => node.span,
+            Node::If(node) => node.span,
+            Node::Forever(node) => node.span,
+            Node::Return(node) => node.span,
+            Node::Import(node) => node.span,
+            Node::Index(node) => node.span,
+            Node::IndexAssignment(node) => node.span,
+            Node::Defer(node) => node.span,
+            Node::Error(node) => node.span,
+        }
+    }
+
+    pub fn display(&self, depth: usize) -> String {
+        match self {
+            Node::String => String::from("String"),
+            Node::Keyword(node) => node.display(depth),
+            Node::Type(node) => node.display(depth),
+            Node::Operator(node) => node.display(depth),
+            Node::Symbol(node) => node.display(depth),
+            Node::Number(node) => node.display(depth),
+            Node::Boolean(node) => node.display(depth),
+            Node::Name(node) => node.display(depth),
+            Node::Block(node) => node.display(depth),
+            Node::Assignment(node) => node.display(depth),
+            Node::VariableDefinition(node) => node.display(depth),
+            Node::FunctionDefinition(node) => node.display(depth),
+            Node::ExternFunction(node) => node.display(depth),
+            Node::Operation(node) => node.display(depth),
+            Node::Group(node) => node.display(depth),
+            Node::Array(node) => node.display(depth),
+            Node::Comptime(node) => node.display(depth),
+            Node::Call(node) => node.display(depth),
+            Node::If(node) => node.display(depth),
+            Node::Forever(node) => node.display(depth),
+            Node::Return(node) => node.display(depth),
+            Node::Import(node) => node.display(depth),
+            Node::Index(node) => node.display(depth),
+            Node::IndexAssignment(node) => node.display(depth),
+            Node::Defer(node) => node.display(depth),
+            Node::Error(node) => node.display(depth),
+        }
+    }
+
+    pub fn content_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Node::Block(node) => Some(&mut node.content),
+            Node::Group(node) => Some(&mut node.content),
+            Node::Array(node) => Some(&mut node.values),
+            Node::Operation(node) => Some(&mut node.values),
+            Node::Call(node) => Some(&mut node.arguments),
+            // The index is in brackets until `build_index_values` takes it out.
+            Node::Index(node) => node.index.content_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn is_value(&self) -> bool {
+        matches!(
+            self,
+            Node::String
+                | Node::Number(_)
+                | Node::Boolean(_)
+                | Node::Name(_)
+                | Node::Operation(_)
+                | Node::Group(_)
+                | Node::Array(_)
+                | Node::Comptime(_)
+                | Node::Call(_)
+                | Node::Index(_)
+                // A number too large to parse still takes the place of a value.
+                | Node::Error(_)
+        )
+    }
+
+    /// Whether a node can stand on its own at the top level or in a block. Anything else is left
+    /// over from code that didn't parse.
+    pub fn is_item(&self) -> bool {
+        return self.is_value()
+            || self.is_keyword(Keyword::Break)
+            || matches!(
+                self,
+                Node::Block(_)
+                    | Node::Assignment(_)
+                    | Node::VariableDefinition(_)
+                    | Node::FunctionDefinition(_)
+                    | Node::ExternFunction(_)
+                    | Node::If(_)
+                    | Node::Forever(_)
+                    | Node::Return(_)
+                    | Node::Import(_)
+                    | Node::IndexAssignment(_)
+                    | Node::Defer(_)
+            );
+    }
+
+    /// Whether a node ends a statement, which is a `;` or a line break. Terminators are removed once
+    /// the statements are built.
+    pub fn is_terminator(&self) -> bool {
+        return self.is_symbol(Symbol::Semicolon);
+    }
+
+    /// Whether a node is a terminator for a line break rather than a `;`. Those get an empty span
+    /// at the line break.
+    fn is_line_break(&self) -> bool {
+        let span = self.get_span();
+
+        return self.is_terminator() && span.lo == span.hi;
+    }
+
+    /// Whether a node ends with a block, so the next statement can follow it on the same line.
+    fn ends_with_block(&self) -> bool {
+        return match self {
+            Node::Block(_) | Node::FunctionDefinition(_) | Node::If(_) | Node::Forever(_) => true,
+            Node::Defer(node) => node.statement.ends_with_block(),
+            _ => false,
+        };
+    }
+
+    /// Whether a node starts with a type, so it may be the start of the next definition.
+    fn starts_with_type(&self) -> bool {
+        return match self {
+            Node::Type(_) | Node::FunctionDefinition(_) => true,
+            Node::VariableDefinition(node) => !node.constant,
+            _ => false,
+        };
+    }
+
+    /// Every node directly inside this one, including the contents of its blocks and the sizes of
+    /// its array types.
+    pub fn children(&self) -> Vec<&Node> {
+        let mut children: Vec<&Node> = Vec::new();
+
+        match self {
+            Node::Type(node) => children.extend(node.array_size.as_deref()),
+            Node::Block(node) => children.extend(&node.content),
+            Node::Assignment(node) => children.push(&node.value),
+            Node::VariableDefinition(node) => {
+                children.extend(node.node_type.array_size.as_deref());
+                children.push(&node.assignment.value);
+            }
+            Node::FunctionDefinition(node) => {
+                children.extend(node.node_type.array_size.as_deref());
+
+                for parameter in &node.parameters {
+                    children.extend(parameter.node_type.array_size.as_deref());
+                }
+
+                children.extend(&node.block.content);
+            }
+            Node::ExternFunction(node) => {
+                children.extend(node.node_type.array_size.as_deref());
+
+                for parameter in &node.parameters {
+                    children.extend(parameter.node_type.array_size.as_deref());
+                }
+            }
+            Node::Operation(node) => children.extend(&node.values),
+            Node::Group(node) => children.extend(&node.content),
+            Node::Array(node) => children.extend(&node.values),
+            Node::Comptime(node) => children.push(&node.value),
+            Node::Call(node) => children.extend(&node.arguments),
+            Node::If(node) => {
+                children.push(&node.condition);
+                children.extend(&node.block.content);
+                children.extend(node.else_node.as_deref());
+            }
+            Node::Forever(node) => children.extend(&node.block.content),
+            Node::Return(node) => children.extend(node.value.as_deref()),
+            Node::Index(node) => children.push(&node.index),
+            Node::IndexAssignment(node) => {
+                children.push(&node.target.index);
+                children.push(&node.value);
+            }
+            Node::Defer(node) => children.push(&node.statement),
+            _ => {}
+        }
+
+        return children;
+    }
+
+    /// Like `children`, but for changing them.
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        let mut children: Vec<&mut Node> = Vec::new();
+
+        match self {
+            Node::Type(node) => children.extend(node.array_size.as_deref_mut()),
+            Node::Block(node) => children.extend(&mut node.content),
+            Node::Assignment(node) => children.push(&mut node.value),
+            Node::VariableDefinition(node) => {
+                children.extend(node.node_type.array_size.as_deref_mut());
+                children.push(&mut node.assignment.value);
+            }
+            Node::FunctionDefinition(node) => {
+                children.extend(node.node_type.array_size.as_deref_mut());
+
+                for parameter in &mut node.parameters {
+                    children.extend(parameter.node_type.array_size.as_deref_mut());
+                }
+
+                children.extend(&mut node.block.content);
+            }
+            Node::ExternFunction(node) => {
+                children.extend(node.node_type.array_size.as_deref_mut());
+
+                for parameter in &mut node.parameters {
+                    children.extend(parameter.node_type.array_size.as_deref_mut());
+                }
+            }
+            Node::Operation(node) => children.extend(&mut node.values),
+            Node::Group(node) => children.extend(&mut node.content),
+            Node::Array(node) => children.extend(&mut node.values),
+            Node::Comptime(node) => children.push(&mut node.value),
+            Node::Call(node) => children.extend(&mut node.arguments),
+            Node::If(node) => {
+                children.push(&mut node.condition);
+                children.extend(&mut node.block.content);
+                children.extend(node.else_node.as_deref_mut());
+            }
+            Node::Forever(node) => children.extend(&mut node.block.content),
+            Node::Return(node) => children.extend(node.value.as_deref_mut()),
+            Node::Index(node) => children.push(&mut node.index),
+            Node::IndexAssignment(node) => {
+                children.push(&mut node.target.index);
+                children.push(&mut node.value);
+            }
+            Node::Defer(node) => children.push(&mut node.statement),
+            _ => {}
+        }
+
+        return children;
+    }
+
+    pub fn is_symbol(&self, symbol: Symbol) -> bool {
+        if let Node::Symbol(symbol_node) = self {
+            return symbol_node.symbol == symbol;
+        }
+
+        return false;
+    }
+
+    pub fn is_keyword(&self, keyword: Keyword) -> bool {
+        if let Node::Keyword(keyword_node) = self {
+            return keyword_node.keyword == keyword;
+        }
+
+        return false;
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Keyword(keyword_node) => write!(f, "{}", keyword_node),
+            Node::Type(type_node) => write!(f, "{}", type_node),
+            Node::Name(name_node) => write!(f, "{}", name_node),
+            Node::Operator(operator_node) => write!(f, "{}", operator_node),
+            Node::Number(number_node) => write!(f, "{}", number_node),
+            Node::Boolean(boolean_node) => write!(f, "{}", boolean_node),
+            Node::Symbol(symbol_node) => write!(f, "{}", symbol_node),
+            Node::Block(block_node) => write!(f, "{}", block_node),
+            Node::Assignment(assignment_node) => write!(f, "{}", assignment_node),
+            Node::VariableDefinition(node) => write!(f, "{}", node),
+            Node::Group(node) => write!(f, "{}", node),
+            Node::Array(node) => write!(f, "{}", node),
+            Node::Comptime(node) => write!(f, "{}", node),
+            Node::Call(node) => write!(f, "{}", node),
+            Node::If(node) => write!(f, "{}", node),
+            Node::Forever(node) => write!(f, "{}", node),
+            Node::Return(node) => write!(f, "{}", node),
+            Node::Import(node) => write!(f, "{}", node),
+            Node::Index(node) => write!(f, "{}", node),
+            Node::IndexAssignment(node) => write!(f, "{}", node),
+            Node::Defer(node) => write!(f, "{}", node),
+            Node::ExternFunction(node) => write!(f, "{}", node),
+            Node::Error(node) => write!(f, "{}", node),
+            _ => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// The operator two operators written right next to each other make, like `<` and `=` making `<=`.
+fn multisymbol_operator(first: Operator, second: Operator) -> Option<Operator> {
+    return match (first, second) {
+        (Operator::BitwiseAnd, Operator::BitwiseAnd) => Some(Operator::And),
+        (Operator::BitwiseOr, Operator::BitwiseOr) => Some(Operator::Or),
+        (Operator::LessThan, Operator::Assign) => Some(Operator::LessThanOrEqual),
+        (Operator::GreaterThan, Operator::Assign) => Some(Operator::GreaterThanOrEqual),
+        (Operator::Assign, Operator::Assign) => Some(Operator::Equal),
+        (Operator::Not, Operator::Assign) => Some(Operator::NotEqual),
+        _ => None,
+    };
+}
+
+pub fn build_multisymbol_operators(nodes: &mut Vec<Node>) {
+    let mut built: Vec<Node> = Vec::with_capacity(nodes.len());
+
+    for node in std::mem::take(nodes) {
+        // An operator that was already joined never starts another one, so `===` is `==` and `=`.
+        if let Some(Node::Operator(first)) = built.last_mut()
+            && let Node::Operator(second) = &node
+            && first.span.hi == second.span.lo
+            && let Some(operator) = multisymbol_operator(first.operator, second.operator)
+        {
+            first.operator = operator;
+            first.span = first.span.to(second.span);
+
+            continue;
+        }
+
+        built.push(node);
+    }
+
+    *nodes = built;
+}
+
+type EnclosureBuilder = fn(Vec<Node>, Span) -> Node;
+
+fn build_enclosures(nodes: &mut Vec<Node>, open: Symbol, close: Symbol, build: EnclosureBuilder) {
+    // The nodes before each bracket that is still open, along with the bracket itself.
+    let mut outer: Vec<(Vec<Node>, Node)> = Vec::new();
+    let mut built: Vec<Node> = Vec::with_capacity(nodes.len());
+
+    for mut node in std::mem::take(nodes) {
+        if let Some(content) = node.content_mut() {
+            build_enclosures(content, open, close, build);
+        }
+
+        if node.is_symbol(open) {
+            outer.push((std::mem::take(&mut built), node));
+        } else if node.is_symbol(close)
+            && let Some((before, open_node)) = outer.pop()
+        {
+            let span = open_node.get_span().to(node.get_span());
+            let content = std::mem::replace(&mut built, before);
+
+            built.push(build(content, span));
+        } else {
+            built.push(node);
+        }
+    }
+
+    // A bracket that is never closed stays where it is, along with everything after it.
+    while let Some((mut before, open_node)) = outer.pop() {
+        before.push(open_node);
+        before.append(&mut built);
+        built = before;
+    }
+
+    *nodes = built;
+}
+
+pub fn build_blocks(nodes: &mut Vec<Node>) {
+    build_enclosures(
+        nodes,
+        Symbol::OpenCurlyBracket,
+        Symbol::ClosedCurlyBracket,
+        |content, span| Node::Block(BlockNode { content, span }),
+    );
+}
+
+/// Line breaks inside parentheses and brackets don't end anything, so a call or array can span
+/// lines.
+fn without_line_breaks(mut nodes: Vec<Node>) -> Vec<Node> {
+    nodes.retain(|node| !node.is_line_break());
+
+    return nodes;
+}
+
+pub fn build_groups(nodes: &mut Vec<Node>) {
+    build_enclosures(
+        nodes,
+        Symbol::OpenParen,
+        Symbol::ClosedParen,
+        |content, span| {
+            Node::Group(GroupNode {
+                content: without_line_breaks(content),
+                span,
+            })
+        },
+    );
+}
+
+pub fn build_arrays(nodes: &mut Vec<Node>) {
+    build_enclosures(
+        nodes,
+        Symbol::OpenSquareBracket,
+        Symbol::ClosedSquareBracket,
+        |values, span| {
+            Node::Array(ArrayNode {
+                values: without_line_breaks(values),
+                span,
+            })
+        },
+    );
+}
+
+/// Builds pointer types like `i32*`. A type is never a value, so a `*` after one can't be a
+/// multiplication.
+pub fn build_pointer_types(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_pointer_types(content);
+        }
+    }
+
+    let mut index = 0;
+
+    while index + 1 < nodes.len() {
+        let Node::Type(type_node) = &nodes[index] else {
+            index += 1;
+
+            continue;
+        };
+
+        if !matches!(&nodes[index + 1], Node::Operator(node) if node.operator == Operator::Multiply)
+        {
+            index += 1;
+
+            continue;
+        }
+
+        let span = type_node.span.to(nodes[index + 1].get_span());
+
+        if type_node.pointer {
+            nodes.splice(
+                index..index + 2,
+                [Node::Error(ErrorNode {
+                    message: String::from("Pointers to pointers are not supported"),
+                    span,
+                })],
+            );
+        } else {
+            nodes.remove(index + 1);
+
+            if let Node::Type(type_node) = &mut nodes[index] {
+                type_node.pointer = true;
+                type_node.span = span;
+            }
+
+            // Look at the type again, in case another `*` follows.
+            continue;
+        }
+
+        index += 1;
+    }
+}
+
+/// The names listed between the braces of an import, which are separated by commas and may end
+/// with one.
+fn import_names(content: &[Node]) -> Option<Vec<NameNode>> {
+    let mut names: Vec<NameNode> = Vec::new();
+    let mut expects_name = true;
+
+    for node in content.iter().filter(|node| !node.is_terminator()) {
+        match node {
+            Node::Name(name_node) if expects_name => names.push(NameNode {
+                value: name_node.value.clone(),
+                span: name_node.span,
+            }),
+            Node::Symbol(symbol_node) if !expects_name && symbol_node.symbol == Symbol::Comma => {}
+            _ => return None,
+        }
+
+        expects_name = !expects_name;
+    }
+
+    if names.is_empty() {
+        return None;
+    }
+
+    return Some(names);
+}
+
+/// Builds `import { a, b } from Module` at the top level, before the braces are parsed as a block.
+pub fn build_imports(nodes: &mut Vec<Node>) {
+    let mut index = 0;
+
+    while index + 3 < nodes.len() {
+        if !nodes[index].is_keyword(Keyword::Import) || !nodes[index + 2].is_keyword(Keyword::From)
+        {
+            index += 1;
+
+            continue;
+        }
+
+        let (Node::Block(block_node), Node::Name(module)) = (&nodes[index + 1], &nodes[index + 3])
+        else {
+            index += 1;
+
+            continue;
+        };
+
+        let span = nodes[index].get_span().to(module.span);
+
+        let node = match import_names(&block_node.content) {
+            Some(names) => Node::Import(ImportNode {
+                names,
+                module: NameNode {
+                    value: module.value.clone(),
+                    span: module.span,
+                },
+                span,
+            }),
+            None => Node::Error(ErrorNode {
+                message: String::from("An import lists names separated by ,"),
+                span,
+            }),
+        };
+
+        nodes.splice(index..index + 4, [node]);
+
+        index += 1;
+    }
+}
+
+pub fn build_unary_operations(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_unary_operations(content);
+        }
+    }
+
+    let mut index = nodes.len();
+
+    while index > 0 {
+        index -= 1;
+
+        if index + 1 >= nodes.len() || !nodes[index + 1].is_value() {
+            continue;
+        }
+
+        if index > 0 && nodes[index - 1].is_value() {
+            continue;
+        }
+
+        let operator = if let Node::Operator(operator_node) = &nodes[index] {
+            match operator_node.operator {
+                Operator::Not => Operator::Not,
+                Operator::Subtract => Operator::Negate,
+                _ => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let operator_node = nodes.remove(index);
+        let value_node = nodes.remove(index);
+
+        let span = operator_node.get_span().to(value_node.get_span());
+
+        nodes.insert(
+            index,
+            Node::Operation(OperationNode {
+                operator: OperatorNode {
+                    operator,
+                    span: operator_node.get_span(),
+                },
+                values: vec![value_node],
+                span,
+            }),
+        );
+    }
+}
+
+pub fn build_assignements(nodes: &mut Vec<Node>) {
     for index in 0..nodes.len() {
         if index >= nodes.len() {
             break;
@@ -861,13 +1550,13 @@ pub fn build_assignements(nodes: &mut Vec<Node>) {
             if let Node::Name(_) = name_node
                 && let Node::Operator(operator_node) = operator_node
                 && let Operator::Assign = operator_node.operator
+                && !nodes[index + 2].is_terminator()
             {
                 let name_node = nodes.remove(index);
                 let _operator_node = nodes.remove(index);
                 let value_node = nodes.remove(index);
 
-                let lines = (name_node.get_lines().0, value_node.get_lines().1);
-                let characters = (name_node.get_characters().0, value_node.get_characters().1);
+                let span = name_node.get_span().to(value_node.get_span());
 
                 let inner_name = if let Node::Name(node) = name_node {
                     node
@@ -880,8 +1569,32 @@ pub fn build_assignements(nodes: &mut Vec<Node>) {
                     Node::Assignment(AssignmentNode {
                         name: inner_name,
                         value: Box::new(value_node),
-                        lines,
-                        characters,
+                        span,
+                    }),
+                );
+            } else if let Node::Index(_) = name_node
+                && let Node::Operator(operator_node) = operator_node
+                && let Operator::Assign = operator_node.operator
+                && !nodes[index + 2].is_terminator()
+            {
+                let index_node = nodes.remove(index);
+                let _operator_node = nodes.remove(index);
+                let value_node = nodes.remove(index);
+
+                let span = index_node.get_span().to(value_node.get_span());
+
+                let inner_index = if let Node::Index(node) = index_node {
+                    node
+                } else {
+                    unreachable!()
+                };
+
+                nodes.insert(
+                    index,
+                    Node::IndexAssignment(IndexAssignmentNode {
+                        target: inner_index,
+                        value: Box::new(value_node),
+                        span,
                     }),
                 );
             }
@@ -895,23 +1608,29 @@ pub fn build_variable_definitions(nodes: &mut Vec<Node>) {
             break;
         }
 
+        let constant = nodes[index].is_keyword(Keyword::Const);
+        let offset = if constant { 1 } else { 0 };
+
         if let Node::Block(block_node) = &mut nodes[index] {
             build_variable_definitions(&mut block_node.content);
-        } else if nodes.len() >= 2 && index <= nodes.len() - 2 {
-            let type_node = &nodes[index];
-            let assignment_node = &nodes[index + 1];
+        } else if nodes.len() >= 2 + offset && index <= nodes.len() - 2 - offset {
+            let type_node = &nodes[index + offset];
+            let assignment_node = &nodes[index + offset + 1];
 
             if let Node::Type(_) = type_node
                 && let Node::Assignment(_) = assignment_node
             {
+                let const_node = if constant {
+                    Some(nodes.remove(index))
+                } else {
+                    None
+                };
                 let type_node = nodes.remove(index);
                 let assignment_node = nodes.remove(index);
 
-                let lines = (type_node.get_lines().0, assignment_node.get_lines().1);
-                let characters = (
-                    type_node.get_characters().0,
-                    assignment_node.get_characters().1,
-                );
+                let start_node = const_node.as_ref().unwrap_or(&type_node);
+
+                let span = start_node.get_span().to(assignment_node.get_span());
 
                 let inner_type = if let Node::Type(node) = type_node {
                     node
@@ -927,10 +1646,10 @@ pub fn build_variable_definitions(nodes: &mut Vec<Node>) {
                 nodes.insert(
                     index,
                     Node::VariableDefinition(VariableDefinitionNode {
+                        constant,
                         node_type: inner_type,
                         assignment: inner_assignment,
-                        lines,
-                        characters,
+                        span,
                     }),
                 );
             }
@@ -938,84 +1657,602 @@ pub fn build_variable_definitions(nodes: &mut Vec<Node>) {
     }
 }
 
-pub fn build_function_definitions(nodes: &mut Vec<Node>) {
-    for index in 0..nodes.len() {
-        if index >= nodes.len() {
-            break;
+fn count_parameters(nodes: &[Node], index: usize) -> Option<usize> {
+    if index + 2 >= nodes.len() {
+        return None;
+    }
+
+    if let Node::Block(_) = nodes[index + 2] {
+        return Some(0);
+    }
+
+    if !nodes[index + 2].is_symbol(Symbol::Colon) {
+        return None;
+    }
+
+    let mut parameter_index = index + 3;
+    let mut count = 0;
+
+    while parameter_index + 2 < nodes.len() {
+        if let Node::Type(_) = nodes[parameter_index]
+            && let Node::Name(_) = nodes[parameter_index + 1]
+        {
+            count += 1;
+        } else {
+            return None;
+        }
+
+        if let Node::Block(_) = nodes[parameter_index + 2] {
+            return Some(count);
+        }
+
+        if !nodes[parameter_index + 2].is_symbol(Symbol::Comma) {
+            return None;
+        }
+
+        parameter_index += 3;
+    }
+
+    return None;
+}
+
+/// Takes a function definition with `parameter_count` parameters off the front of `nodes`.
+fn function_definition(nodes: &mut impl Iterator<Item = Node>, parameter_count: usize) -> Node {
+    let mut next = || nodes.next().unwrap();
+
+    let type_node = next();
+    let name_node = next();
+
+    let mut parameters: Vec<ParameterNode> = Vec::new();
+
+    if parameter_count > 0 {
+        let _colon_node = next();
+    }
+
+    for parameter_index in 0..parameter_count {
+        let parameter_type_node = next();
+        let parameter_name_node = next();
+
+        if parameter_index + 1 < parameter_count {
+            let _comma_node = next();
+        }
+
+        let span = parameter_type_node
+            .get_span()
+            .to(parameter_name_node.get_span());
+
+        let inner_type = if let Node::Type(node) = parameter_type_node {
+            node
+        } else {
+            unreachable!()
+        };
+        let inner_name = if let Node::Name(node) = parameter_name_node {
+            node
+        } else {
+            unreachable!()
+        };
+
+        parameters.push(ParameterNode {
+            node_type: inner_type,
+            name: inner_name,
+            span,
+        });
+    }
+
+    let block_node = next();
+
+    let span = type_node.get_span().to(block_node.get_span());
+
+    let inner_type = if let Node::Type(node) = type_node {
+        node
+    } else {
+        unreachable!()
+    };
+    let inner_name = if let Node::Name(node) = name_node {
+        node
+    } else {
+        unreachable!()
+    };
+    let inner_block = if let Node::Block(node) = block_node {
+        node
+    } else {
+        unreachable!()
+    };
+
+    return Node::FunctionDefinition(FunctionDefinitionNode {
+        node_type: inner_type,
+        name: inner_name,
+        parameters,
+        block: inner_block,
+        external: false,
+        span,
+    });
+}
+
+pub fn build_function_definitions(nodes: &mut Vec<Node>) {
+    // Where each function starts and how many parameters it has. They're all found before any are
+    // built, so the nodes only have to be moved once.
+    let mut functions: Vec<(usize, usize)> = Vec::new();
+    let mut index = 0;
+
+    while index < nodes.len() {
+        if let Node::Block(block_node) = &mut nodes[index] {
+            build_function_definitions(&mut block_node.content);
+        } else if let Some(parameter_count) = count_parameters(nodes, index)
+            && let Node::Type(_) = nodes[index]
+            && let Node::Name(_) = nodes[index + 1]
+        {
+            functions.push((index, parameter_count));
+
+            // The type, name and block, plus a colon and a type, name and comma for each
+            // parameter but the last.
+            index += 3 + 3 * parameter_count;
+
+            continue;
+        }
+
+        index += 1;
+    }
+
+    let mut old_nodes = std::mem::take(nodes).into_iter();
+    let mut position = 0;
+
+    for (start, parameter_count) in functions {
+        nodes.extend(old_nodes.by_ref().take(start - position));
+        nodes.push(function_definition(&mut old_nodes, parameter_count));
+
+        position = start + 3 + 3 * parameter_count;
+    }
+
+    nodes.extend(old_nodes);
+}
+
+/// The number of nodes in a function signature without a block, `T name` or
+/// `T name: T a, T b`, if one starts at `index` and ends at a terminator or the end of the nodes.
+fn signature_length(nodes: &[Node], index: usize) -> Option<usize> {
+    let is_parameter = |offset: usize| {
+        matches!(nodes.get(index + offset), Some(Node::Type(_)))
+            && matches!(nodes.get(index + offset + 1), Some(Node::Name(_)))
+    };
+
+    let ends = |offset: usize| nodes.get(index + offset).is_none_or(Node::is_terminator);
+
+    if !is_parameter(0) {
+        return None;
+    }
+
+    if ends(2) {
+        return Some(2);
+    }
+
+    if !nodes[index + 2].is_symbol(Symbol::Colon) {
+        return None;
+    }
+
+    let mut length = 3;
+
+    loop {
+        if !is_parameter(length) {
+            return None;
+        }
+
+        length += 2;
+
+        if ends(length) {
+            return Some(length);
+        }
+
+        if !nodes[index + length].is_symbol(Symbol::Comma) {
+            return None;
+        }
+
+        length += 1;
+    }
+}
+
+/// Builds externs inside blocks too, so the type checker can say they belong at the top level.
+fn build_nested_externs(node: &mut Node) {
+    match node {
+        Node::Block(node) => build_externs(&mut node.content),
+        Node::FunctionDefinition(node) => build_externs(&mut node.block.content),
+        Node::Forever(node) => build_externs(&mut node.block.content),
+        Node::If(node) => {
+            build_externs(&mut node.block.content);
+
+            if let Some(else_node) = &mut node.else_node {
+                build_nested_externs(else_node);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Builds `extern "C"` in front of a function definition, which makes the function callable from
+/// C, or in front of a signature without a block, which declares a C function for Metor to call.
+pub fn build_externs(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        build_nested_externs(node);
+    }
+
+    let mut index = 0;
+
+    while index < nodes.len() {
+        if !nodes[index].is_keyword(Keyword::Extern) {
+            index += 1;
+
+            continue;
+        }
+
+        let abi = match &nodes[index..] {
+            [_, open, Node::Name(abi), close, ..]
+                if open.is_symbol(Symbol::Quote) && close.is_symbol(Symbol::Quote) =>
+            {
+                abi.value.clone()
+            }
+            _ => {
+                nodes[index] = Node::Error(ErrorNode {
+                    message: String::from("extern is followed by an ABI in quotes, like \"C\""),
+                    span: nodes[index].get_span(),
+                });
+                index += 1;
+
+                continue;
+            }
+        };
+
+        let start = nodes[index].get_span();
+        let abi_span = start.to(nodes[index + 3].get_span());
+
+        if abi != "C" {
+            nodes.splice(
+                index..index + 4,
+                [Node::Error(ErrorNode {
+                    message: format!("Unknown ABI {}, only \"C\" is supported", abi),
+                    span: abi_span,
+                })],
+            );
+            index += 1;
+
+            continue;
+        }
+
+        if let Some(Node::FunctionDefinition(_)) = nodes.get(index + 4) {
+            let Node::FunctionDefinition(mut function_node) = nodes.remove(index + 4) else {
+                unreachable!()
+            };
+
+            function_node.external = true;
+            function_node.span = start.to(function_node.span);
+
+            nodes.splice(index..index + 4, [Node::FunctionDefinition(function_node)]);
+            index += 1;
+
+            continue;
+        }
+
+        let Some(length) = signature_length(nodes, index + 4) else {
+            nodes.splice(
+                index..index + 4,
+                [Node::Error(ErrorNode {
+                    message: String::from("Expected a function after extern \"C\""),
+                    span: abi_span,
+                })],
+            );
+            index += 1;
+
+            continue;
+        };
+
+        // Without the `:` and `,` between them, the signature is pairs of a type and a name.
+        let mut signature = nodes
+            .drain(index..index + 4 + length)
+            .skip(4)
+            .filter(|node| !matches!(node, Node::Symbol(_)))
+            .collect::<Vec<Node>>()
+            .into_iter();
+
+        let (Some(Node::Type(node_type)), Some(Node::Name(name))) =
+            (signature.next(), signature.next())
+        else {
+            unreachable!()
+        };
+
+        let mut parameters: Vec<ParameterNode> = Vec::new();
+
+        while let (Some(Node::Type(parameter_type)), Some(Node::Name(parameter_name))) =
+            (signature.next(), signature.next())
+        {
+            parameters.push(ParameterNode {
+                span: parameter_type.span.to(parameter_name.span),
+                node_type: parameter_type,
+                name: parameter_name,
+            });
+        }
+
+        let end = match parameters.last() {
+            Some(parameter) => parameter.span,
+            None => name.span,
+        };
+
+        nodes.insert(
+            index,
+            Node::ExternFunction(ExternFunctionNode {
+                node_type,
+                name,
+                parameters,
+                span: start.to(end),
+            }),
+        );
+        index += 1;
+    }
+}
+
+pub fn build_calls(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_calls(content);
+        }
+    }
+
+    for index in 0..nodes.len() {
+        if index >= nodes.len() {
+            break;
+        }
+
+        if nodes.len() >= 2
+            && index <= nodes.len() - 2
+            && let Node::Name(_) = nodes[index]
+            && let Node::Group(_) = nodes[index + 1]
+        {
+            let name_node = nodes.remove(index);
+            let group_node = nodes.remove(index);
+
+            let span = name_node.get_span().to(group_node.get_span());
+
+            let inner_name = if let Node::Name(node) = name_node {
+                node
+            } else {
+                unreachable!()
+            };
+            let inner_group = if let Node::Group(node) = group_node {
+                node
+            } else {
+                unreachable!()
+            };
+
+            nodes.insert(
+                index,
+                Node::Call(CallNode {
+                    name: inner_name,
+                    arguments: inner_group.content,
+                    span,
+                }),
+            );
+        }
+    }
+}
+
+/// Builds `name[index]` out of a name and the brackets after it. The brackets stay around the index
+/// until the expressions inside them are built.
+pub fn build_indexes(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_indexes(content);
+        }
+    }
+
+    let mut index = 0;
+
+    while index + 1 < nodes.len() {
+        if let Node::Name(_) = nodes[index]
+            && let Node::Array(_) = nodes[index + 1]
+        {
+            let name_node = nodes.remove(index);
+            let array_node = nodes.remove(index);
+
+            let span = name_node.get_span().to(array_node.get_span());
+
+            let inner_name = if let Node::Name(node) = name_node {
+                node
+            } else {
+                unreachable!()
+            };
+
+            nodes.insert(
+                index,
+                Node::Index(IndexNode {
+                    name: inner_name,
+                    index: Box::new(array_node),
+                    span,
+                }),
+            );
+        }
+
+        index += 1;
+    }
+}
+
+/// Takes the index of each `name[index]` out of its brackets, once the expression inside is built.
+pub fn build_index_values(nodes: &mut [Node]) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_index_values(content);
+        }
+
+        if let Node::Index(index_node) = node
+            && let Node::Array(array_node) = index_node.index.as_mut()
+        {
+            *index_node.index = if array_node.values.len() == 1 {
+                array_node.values.pop().unwrap()
+            } else {
+                Node::Error(ErrorNode {
+                    message: String::from("An index is a single value"),
+                    span: array_node.span,
+                })
+            };
+        }
+    }
+}
+
+pub fn build_returns(nodes: &mut Vec<Node>) {
+    for index in 0..nodes.len() {
+        if index >= nodes.len() {
+            break;
+        }
+
+        if let Node::Block(block_node) = &mut nodes[index] {
+            build_returns(&mut block_node.content);
+        } else if nodes[index].is_keyword(Keyword::Return) {
+            let keyword_node = nodes.remove(index);
+
+            let value_node = if index < nodes.len() && nodes[index].is_value() {
+                Some(Box::new(nodes.remove(index)))
+            } else {
+                None
+            };
+
+            let end_node = value_node.as_deref().unwrap_or(&keyword_node);
+
+            let span = keyword_node.get_span().to(end_node.get_span());
+
+            nodes.insert(
+                index,
+                Node::Return(ReturnNode {
+                    value: value_node,
+                    span,
+                }),
+            );
+        }
+    }
+}
+
+pub fn build_control_flow(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_control_flow(content);
         }
+    }
 
-        if let Node::Block(block_node) = &mut nodes[index] {
-            build_function_definitions(&mut block_node.content);
-        } else if nodes.len() >= 3 && index <= nodes.len() - 3 {
-            let type_node = &nodes[index];
-            let name_node = &nodes[index + 1];
-            let block_node = &nodes[index + 2];
+    let mut index = nodes.len();
 
-            if let Node::Type(_) = type_node
-                && let Node::Name(_) = name_node
-                && let Node::Block(_) = block_node
-            {
-                let type_node = nodes.remove(index);
-                let name_node = nodes.remove(index);
-                let block_node = nodes.remove(index);
+    while index > 0 {
+        index -= 1;
 
-                let lines = (type_node.get_lines().0, block_node.get_lines().1);
-                let characters = (type_node.get_characters().0, block_node.get_characters().1);
+        if nodes[index].is_keyword(Keyword::Forever)
+            && index + 1 < nodes.len()
+            && let Node::Block(_) = nodes[index + 1]
+        {
+            let keyword_node = nodes.remove(index);
+            let block_node = nodes.remove(index);
 
-                let inner_type = if let Node::Type(node) = type_node {
-                    node
-                } else {
-                    unreachable!()
-                };
-                let inner_name = if let Node::Name(node) = name_node {
-                    node
-                } else {
-                    unreachable!()
-                };
-                let inner_block = if let Node::Block(node) = block_node {
-                    node
-                } else {
-                    unreachable!()
-                };
+            let span = keyword_node.get_span().to(block_node.get_span());
 
-                nodes.insert(
-                    index,
-                    Node::FunctionDefinition(FunctionDefinitionNode {
-                        node_type: inner_type,
-                        name: inner_name,
-                        block: inner_block,
-                        lines,
-                        characters,
-                    }),
-                );
-            }
+            let inner_block = if let Node::Block(node) = block_node {
+                node
+            } else {
+                unreachable!()
+            };
+
+            nodes.insert(
+                index,
+                Node::Forever(ForeverNode {
+                    block: inner_block,
+                    span,
+                }),
+            );
+        } else if nodes[index].is_keyword(Keyword::If)
+            && index + 2 < nodes.len()
+            && nodes[index + 1].is_value()
+            && let Node::Block(_) = nodes[index + 2]
+        {
+            let has_else = index + 4 < nodes.len()
+                && nodes[index + 3].is_keyword(Keyword::Else)
+                && matches!(nodes[index + 4], Node::Block(_) | Node::If(_));
+
+            let keyword_node = nodes.remove(index);
+            let condition_node = nodes.remove(index);
+            let block_node = nodes.remove(index);
+
+            let else_node = if has_else {
+                let _else_keyword_node = nodes.remove(index);
+
+                Some(Box::new(nodes.remove(index)))
+            } else {
+                None
+            };
+
+            let end_node = else_node.as_deref().unwrap_or(&block_node);
+
+            let span = keyword_node.get_span().to(end_node.get_span());
+
+            let inner_block = if let Node::Block(node) = block_node {
+                node
+            } else {
+                unreachable!()
+            };
+
+            nodes.insert(
+                index,
+                Node::If(IfNode {
+                    condition: Box::new(condition_node),
+                    block: inner_block,
+                    else_node,
+                    span,
+                }),
+            );
+        } else if nodes[index].is_keyword(Keyword::Defer)
+            && index + 1 < nodes.len()
+            && nodes[index + 1].is_item()
+        {
+            let keyword_node = nodes.remove(index);
+            let statement_node = nodes.remove(index);
+
+            let span = keyword_node.get_span().to(statement_node.get_span());
+
+            nodes.insert(
+                index,
+                Node::Defer(DeferNode {
+                    statement: Box::new(statement_node),
+                    span,
+                }),
+            );
         }
     }
 }
 
 pub fn build_operations(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_operations(content);
+        }
+
+        if let Node::Group(group_node) = node
+            && group_node.content.len() == 1
+        {
+            let value_node = group_node.content.pop().unwrap();
+
+            *node = value_node;
+        }
+    }
+
     for priority in 0..Operator::priority_limit() {
         let mut index = 0;
 
         while index < nodes.len() {
-            if let Node::Block(block_node) = &mut nodes[index] {
-                build_operations(&mut block_node.content);
-            } else if nodes.len() >= 3 && index <= nodes.len() - 3 {
+            if nodes.len() >= 3 && index <= nodes.len() - 3 {
                 let operator_node = &nodes[index + 1];
 
                 if let Node::Operator(operator_node) = operator_node
                     && operator_node.operator.two_sided()
                     && operator_node.operator.priority() == priority
+                    && nodes[index].is_value()
+                    && nodes[index + 2].is_value()
                 {
                     let value_a_node = nodes.remove(index);
                     let operator_node = nodes.remove(index);
                     let value_b_node = nodes.remove(index);
 
-                    let lines = (value_a_node.get_lines().0, value_b_node.get_lines().1);
-                    let characters = (
-                        value_a_node.get_characters().0,
-                        value_b_node.get_characters().1,
-                    );
+                    let span = value_a_node.get_span().to(value_b_node.get_span());
 
                     let inner_operator = if let Node::Operator(node) = operator_node {
                         node
@@ -1028,12 +2265,11 @@ pub fn build_operations(nodes: &mut Vec<Node>) {
                         Node::Operation(OperationNode {
                             operator: inner_operator,
                             values: vec![value_a_node, value_b_node],
-                            lines,
-                            characters,
+                            span,
                         }),
                     );
 
-                    index -= 1;
+                    continue;
                 }
             }
 
@@ -1042,41 +2278,620 @@ pub fn build_operations(nodes: &mut Vec<Node>) {
     }
 }
 
-pub fn build_syntax_tree(tokens: &Vec<tokenizer::Token>) -> Vec<Node> {
-    let mut nodes: Vec<Node> = Vec::new();
+pub fn build_lists(nodes: &mut [Node]) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_lists(content);
+        }
 
-    for token in tokens {
-        match token.token_type {
-            TokenType::Symbol => {
-                if OPERATOR_STRINGS.contains(&token.content.as_str()) {
-                    nodes.push(Node::Operator(OperatorNode::from_token(token)));
-                } else {
-                    nodes.push(Node::Symbol(SymbolNode::from_token(token)));
-                }
+        if let Node::Array(array_node) = node {
+            build_list(&mut array_node.values);
+        }
+
+        if let Node::Call(call_node) = node {
+            build_list(&mut call_node.arguments);
+        }
+    }
+}
+
+/// Takes the commas out of a list of values, turning a missing comma like in `add(1 2)` or `[1 2]`,
+/// or an extra one like in `add(1,, 2)`, into an error.
+fn build_list(values: &mut Vec<Node>) {
+    let mut expects_value = true;
+    let mut last_comma = None;
+
+    for value_node in std::mem::take(values) {
+        if value_node.is_symbol(Symbol::Comma) {
+            if expects_value {
+                values.push(Node::Error(ErrorNode {
+                    message: String::from("Unexpected ,"),
+                    span: value_node.get_span(),
+                }));
             }
-            TokenType::Unkown => {
-                if TYPE_STRINGS.contains(&token.content.as_str()) {
-                    nodes.push(Node::Type(TypeNode::from_token(token)));
-                } else if BOOLEAN_STRINGS.contains(&token.content.as_str()) {
-                    nodes.push(Node::Boolean(BooleanNode::from_token(token)));
-                } else if NumberNode::is_number(&token.content) {
-                    nodes.push(Node::Number(NumberNode::from_token(token)));
-                } else if KEYWORD_STRINGS.contains(&token.content.as_str()) {
-                    nodes.push(Node::Keyword(KeywordNode::from_token(token)));
+
+            expects_value = true;
+            last_comma = Some(value_node.get_span());
+
+            continue;
+        }
+
+        if expects_value {
+            values.push(value_node);
+        } else {
+            values.push(Node::Error(ErrorNode {
+                message: String::from("Expected , between values"),
+                span: value_node.get_span(),
+            }));
+        }
+
+        expects_value = false;
+        last_comma = None;
+    }
+
+    if let Some(span) = last_comma {
+        values.push(Node::Error(ErrorNode {
+            message: String::from("Unexpected ,"),
+            span,
+        }));
+    }
+}
+
+/// Joins `comptime` and the value after it into a group, so operations take them as one value, like
+/// the `comptime 2` in `1 + comptime 2`. The passes in between still reach the value inside, and
+/// `build_comptime` turns the group into a comptime value once it's built.
+pub fn build_comptime_groups(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_comptime_groups(content);
+        }
+    }
+
+    let mut index = nodes.len();
+
+    while index > 1 {
+        index -= 1;
+
+        if !nodes[index - 1].is_keyword(Keyword::Comptime) || !nodes[index].is_value() {
+            continue;
+        }
+
+        let span = nodes[index - 1].get_span().to(nodes[index].get_span());
+        let content = nodes.drain(index - 1..=index).collect();
+
+        nodes.insert(index - 1, Node::Group(GroupNode { content, span }));
+    }
+}
+
+pub fn build_comptime(nodes: &mut [Node]) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_comptime(content);
+        }
+
+        if let Node::Group(group_node) = node
+            && let [keyword_node, _] = group_node.content.as_slice()
+            && keyword_node.is_keyword(Keyword::Comptime)
+        {
+            let span = group_node.span;
+            let value_node = group_node.content.pop().unwrap();
+
+            *node = Node::Comptime(ComptimeNode {
+                value: Box::new(value_node),
+                span,
+            });
+        }
+    }
+}
+
+pub fn build_array_types(nodes: &mut Vec<Node>) {
+    for index in 0..nodes.len() {
+        if index >= nodes.len() {
+            break;
+        }
+
+        if let Some(content) = nodes[index].content_mut() {
+            build_array_types(content);
+        } else if nodes.len() >= 2 && index <= nodes.len() - 2 {
+            let type_node = &nodes[index];
+            let array_node = &nodes[index + 1];
+
+            if let Node::Type(_) = type_node
+                && let Node::Array(array_node) = array_node
+                && array_node.values.len() == 1
+            {
+                let array_node = nodes.remove(index + 1);
+
+                let mut inner_array = if let Node::Array(node) = array_node {
+                    node
                 } else {
-                    nodes.push(Node::Name(NameNode::from_token(token)));
+                    unreachable!()
+                };
+
+                if let Node::Type(type_node) = &mut nodes[index] {
+                    type_node.array_size = Some(Box::new(inner_array.values.pop().unwrap()));
+                    type_node.span.hi = inner_array.span.hi;
                 }
             }
-            _ => {}
         }
     }
+}
+
+/// The message for code that didn't parse, going by the first node left over.
+fn error_message(node: &Node) -> String {
+    return match node {
+        Node::Symbol(node) => format!("Unexpected {}", node.symbol),
+        Node::Operator(node) => format!("Unexpected {}", node.operator),
+        Node::Keyword(node) => format!("Unexpected keyword {}", node.keyword),
+        Node::Type(_) => String::from("Expected a variable or function definition"),
+        _ => String::from("Expected a statement"),
+    };
+}
+
+/// Turns the nodes of each block that don't form a statement or definition into error nodes, and
+/// removes the terminators. Each error runs from the first node left over to the end of the
+/// statement, the end of the block or a node starting with a type, and the nodes after it are
+/// parsed as usual.
+pub fn build_errors(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        build_nested_errors(node);
+    }
+
+    let mut index = 0;
+
+    while index < nodes.len() {
+        // Statements need a `;` or a line break between them, like the two in `a = 1 b = 2`. A
+        // block can't tell, since the line break before a line starting with `{` is dropped.
+        if index + 1 < nodes.len()
+            && nodes[index].is_item()
+            && nodes[index + 1].is_item()
+            && !nodes[index].ends_with_block()
+            && !matches!(nodes[index], Node::Error(_))
+            && !matches!(nodes[index + 1], Node::Error(_) | Node::Block(_))
+        {
+            // What's left of a line that already has an error, like the `1` in `a = = 1`, is part
+            // of the same mistake.
+            if !errors(std::slice::from_ref(&nodes[index])).is_empty() {
+                nodes.remove(index + 1);
+
+                continue;
+            }
+
+            let span = nodes[index + 1].get_span();
+
+            nodes[index + 1] = Node::Error(ErrorNode {
+                message: String::from("Expected ; or a line break"),
+                span,
+            });
+        }
+
+        if nodes[index].is_item() || nodes[index].is_terminator() {
+            index += 1;
+
+            continue;
+        }
+
+        let mut end = index + 1;
+
+        while end < nodes.len() && !nodes[end].is_terminator() && !nodes[end].starts_with_type() {
+            end += 1;
+        }
+
+        let span = nodes[index].get_span().to(nodes[end - 1].get_span());
+        let message = error_message(&nodes[index]);
+
+        nodes.splice(index..end, [Node::Error(ErrorNode { message, span })]);
+
+        index += 1;
+    }
+
+    nodes.retain(|node| !node.is_terminator());
+}
+
+fn build_nested_errors(node: &mut Node) {
+    match node {
+        Node::Block(node) => build_errors(&mut node.content),
+        Node::FunctionDefinition(node) => build_errors(&mut node.block.content),
+        Node::Forever(node) => build_errors(&mut node.block.content),
+        Node::If(node) => {
+            build_expression_errors(&mut node.condition);
+            build_errors(&mut node.block.content);
+
+            if let Some(else_node) = &mut node.else_node {
+                build_nested_errors(else_node);
+            }
+        }
+        Node::Assignment(node) => build_expression_errors(&mut node.value),
+        Node::VariableDefinition(node) => build_expression_errors(&mut node.assignment.value),
+        Node::Return(node) => {
+            if let Some(value) = &mut node.value {
+                build_expression_errors(value);
+            }
+        }
+        Node::Comptime(node) => build_expression_errors(&mut node.value),
+        Node::Operation(node) => {
+            for value in &mut node.values {
+                build_expression_errors(value);
+            }
+        }
+        Node::Call(node) => {
+            for argument in &mut node.arguments {
+                build_expression_errors(argument);
+            }
+        }
+        Node::Array(node) => {
+            for value in &mut node.values {
+                build_expression_errors(value);
+            }
+        }
+        Node::Index(node) => build_expression_errors(&mut node.index),
+        Node::IndexAssignment(node) => {
+            build_expression_errors(&mut node.target.index);
+            build_expression_errors(&mut node.value);
+        }
+        Node::Defer(node) => build_nested_errors(&mut node.statement),
+        _ => {}
+    }
+}
+
+/// Turns a symbol, operator or keyword where a value should be, like the `)` in `x = )`, into an
+/// error node.
+fn build_expression_errors(node: &mut Node) {
+    if let Node::Symbol(_) | Node::Operator(_) | Node::Keyword(_) = node {
+        *node = Node::Error(ErrorNode {
+            message: error_message(node),
+            span: node.get_span(),
+        });
+    } else {
+        build_nested_errors(node);
+    }
+}
+
+/// Reports every part of a syntax tree that didn't parse. Passes after parsing assume there are
+/// none, so this has to run before a tree is trusted.
+pub fn errors(nodes: &[Node]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut stack: Vec<&Node> = nodes.iter().rev().collect();
+
+    while let Some(node) = stack.pop() {
+        if let Node::Error(error_node) = node {
+            diagnostics.push(Diagnostic::error(
+                error_node.message.clone(),
+                error_node.span,
+            ));
+        }
+
+        stack.extend(node.children().into_iter().rev());
+    }
+
+    return diagnostics;
+}
+
+/// Whether a line ending with this token goes on to the next line, like one ending with `+` or `,`.
+fn continues_statement(node: &Node) -> bool {
+    return match node {
+        Node::Operator(_) => true,
+        Node::Symbol(node) => matches!(
+            node.symbol,
+            Symbol::Comma
+                | Symbol::Colon
+                | Symbol::Semicolon
+                | Symbol::OpenParen
+                | Symbol::OpenSquareBracket
+                | Symbol::OpenCurlyBracket
+        ),
+        _ => false,
+    };
+}
+
+/// Whether a line starting with this token belongs to the line before, like `{` or `else` after
+/// `}`, or `.` starting a chained access.
+fn continues_line(node: &Node) -> bool {
+    return node.is_symbol(Symbol::OpenCurlyBracket)
+        || node.is_keyword(Keyword::Else)
+        || matches!(node, Node::Operator(node) if node.operator == Operator::Access);
+}
+
+/// The problem with a line break before `next`, when the line before ends with `previous`. A line
+/// can't start with an operator like `+`, and `-` or `(` could just as well continue the line
+/// before, so those need a `;` to say they don't.
+fn line_break_error(previous: &Node, next: &Node) -> Option<String> {
+    if let Node::Operator(operator_node) = next {
+        let operator = operator_node.operator;
+
+        if operator == Operator::Subtract && previous.is_value() {
+            return Some(String::from(
+                "Ambiguous line break before -, end the line before with - to subtract or with ; to \
+                 negate",
+            ));
+        }
+
+        if operator.two_sided() && operator != Operator::Subtract {
+            return Some(format!(
+                "A line can't start with {}, end the line before with it instead",
+                operator
+            ));
+        }
+    }
+
+    if let Node::Group(_) = next
+        && let Node::Name(name_node) = previous
+    {
+        return Some(format!(
+            "Ambiguous line break before (, join the lines to call {} or end the line before with ;",
+            name_node.value
+        ));
+    }
+
+    return None;
+}
+
+/// Turns statements that start after an ambiguous line break into error nodes, up to the next
+/// terminator.
+pub fn build_line_errors(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Some(content) = node.content_mut() {
+            build_line_errors(content);
+        }
+    }
+
+    let mut index = 1;
+
+    while index + 1 < nodes.len() {
+        let message = if nodes[index].is_line_break() {
+            line_break_error(&nodes[index - 1], &nodes[index + 1])
+        } else {
+            None
+        };
+
+        index += 1;
+
+        let Some(message) = message else {
+            continue;
+        };
+
+        let mut end = index + 1;
+
+        while end < nodes.len() && !nodes[end].is_terminator() {
+            end += 1;
+        }
+
+        let span = nodes[index].get_span().to(nodes[end - 1].get_span());
+
+        nodes.splice(index..end, [Node::Error(ErrorNode { message, span })]);
+    }
+}
+
+/// Builds the syntax tree of a stream of tokens, like a `tokenizer::Lexer`. Statements end at a `;`
+/// or a line break, except for line breaks inside parentheses or brackets, after a line ending with
+/// an operator, `,`, `:` or an opening bracket, and before a line starting with `{`, `else` or `.`.
+pub fn build_syntax_tree<'a>(tokens: impl IntoIterator<Item = tokenizer::Token<'a>>) -> Vec<Node> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut can_end = false;
+    let mut line_break: Option<Span> = None;
+
+    for token in tokens {
+        let span = token.span;
+
+        if let TokenType::Seperator = token.token_type
+            && can_end
+            && line_break.is_none()
+        {
+            line_break = Some(Span::new(span.file, span.lo as usize, span.lo as usize));
+        }
+
+        let node = match token.token_type {
+            TokenType::Operator(operator) => Node::Operator(OperatorNode { operator, span }),
+            TokenType::Symbol(symbol) => Node::Symbol(SymbolNode { symbol, span }),
+            TokenType::Type(node_type) => Node::Type(TypeNode {
+                node_type,
+                pointer: false,
+                array_size: None,
+                span,
+            }),
+            TokenType::Keyword(keyword) => Node::Keyword(KeywordNode { keyword, span }),
+            TokenType::Boolean(value) => Node::Boolean(BooleanNode { value, span }),
+            TokenType::Number => match token.content.parse() {
+                Ok(value) => Node::Number(NumberNode { value, span }),
+                Err(_) => Node::Error(ErrorNode {
+                    message: format!("Number {} is too large", token.content),
+                    span,
+                }),
+            },
+            TokenType::Name => Node::Name(NameNode::from_token(&token)),
+            // Stray characters are reported by `tokenizer::errors`.
+            TokenType::WhiteSpace
+            | TokenType::Seperator
+            | TokenType::Comment
+            | TokenType::Error => continue,
+        };
+
+        if let Some(span) = line_break.take()
+            && !continues_line(&node)
+        {
+            nodes.push(Node::Symbol(SymbolNode {
+                symbol: Symbol::Semicolon,
+                span,
+            }));
+        }
+
+        can_end = !continues_statement(&node);
+
+        nodes.push(node);
+    }
 
     build_multisymbol_operators(&mut nodes);
     build_blocks(&mut nodes);
+    build_imports(&mut nodes);
+    build_groups(&mut nodes);
+    build_arrays(&mut nodes);
+    build_pointer_types(&mut nodes);
+    build_line_errors(&mut nodes);
+    build_calls(&mut nodes);
+    build_indexes(&mut nodes);
+    build_comptime_groups(&mut nodes);
+    build_unary_operations(&mut nodes);
     build_operations(&mut nodes);
+    build_lists(&mut nodes);
+    build_index_values(&mut nodes);
+    build_comptime(&mut nodes);
+    build_array_types(&mut nodes);
     build_assignements(&mut nodes);
     build_variable_definitions(&mut nodes);
+    build_returns(&mut nodes);
+    build_control_flow(&mut nodes);
     build_function_definitions(&mut nodes);
+    build_externs(&mut nodes);
+    build_errors(&mut nodes);
 
     return nodes;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_map::FileId;
+
+    fn messages(code: &str) -> Vec<String> {
+        let tree = build_syntax_tree(tokenizer::Lexer::new(code, FileId(0)));
+
+        return errors(&tree)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+    }
+
+    #[test]
+    fn separates_arguments_with_commas() {
+        assert!(messages("println(add(1, 2), 3)").is_empty());
+        assert!(messages("println()").is_empty());
+        assert_eq!(messages("println(add(1 2))"), ["Expected , between values"]);
+        assert_eq!(messages("println(1,, 2)"), ["Unexpected ,"]);
+        assert_eq!(messages("println(, 1)"), ["Unexpected ,"]);
+        assert_eq!(messages("println(1,)"), ["Unexpected ,"]);
+    }
+
+    #[test]
+    fn separates_array_values_with_commas() {
+        assert!(messages("i32[3] a = [1, 2, 3]").is_empty());
+        assert_eq!(
+            messages("i32[3] a = [1 2 3]"),
+            ["Expected , between values"; 2]
+        );
+        assert_eq!(
+            messages("i32[3] a = [1,, 2 3]"),
+            ["Unexpected ,", "Expected , between values"]
+        );
+    }
+
+    fn value(code: &str) -> String {
+        let tree = build_syntax_tree(tokenizer::Lexer::new(code, FileId(0)));
+
+        return match tree.as_slice() {
+            [Node::VariableDefinition(node)] => node.assignment.value.display(0),
+            _ => panic!("Expected one variable definition in {}", code),
+        };
+    }
+
+    #[test]
+    fn binds_comptime_to_the_value_after_it() {
+        assert_eq!(
+            value("i32 a = 1 + comptime 2"),
+            "Operation\n | Operator +\n | Number 1\n | Comptime\n |  | Number 2"
+        );
+        assert_eq!(
+            value("i32 a = comptime 2 + 1"),
+            "Operation\n | Operator +\n | Comptime\n |  | Number 2\n | Number 1"
+        );
+        assert_eq!(
+            value("i32 a = 3 * comptime (2 * 3)"),
+            "Operation\n | Operator *\n | Number 3\n | Comptime\n |  | Operation\n |  |  | Operator *\n |  |  | Number 2\n |  |  | Number 3"
+        );
+        assert_eq!(
+            value("i32 a = -comptime add(1, 2)"),
+            "Operation\n | Operator -\n | Comptime\n |  | Call\n |  |  | Name add\n |  |  | Number 1\n |  |  | Number 2"
+        );
+    }
+
+    #[test]
+    fn ends_statements_at_line_breaks() {
+        assert_eq!(
+            messages("i32 a = 1\n-2"),
+            [
+                "Ambiguous line break before -, end the line before with - to subtract or with ; to negate"
+            ]
+        );
+        assert!(messages("i32 a = 1;\n-2").is_empty());
+        assert_eq!(
+            value("i32 a = 1 -\n2"),
+            "Operation\n | Operator -\n | Number 1\n | Number 2"
+        );
+
+        assert_eq!(
+            messages("a = b\n(c)"),
+            [
+                "Ambiguous line break before (, join the lines to call b or end the line before with ;"
+            ]
+        );
+        assert_eq!(
+            statements("a = b;\n(c)"),
+            ["Assignment\n | Name a\n | Name b", "Name c"]
+        );
+        assert_eq!(
+            statements("a = b(\nc)"),
+            ["Assignment\n | Name a\n | Call\n |  | Name b\n |  | Name c"]
+        );
+
+        assert_eq!(
+            messages("i32 a = 1\n* 2"),
+            ["A line can't start with *, end the line before with it instead"]
+        );
+    }
+
+    #[test]
+    fn separates_statements_on_one_line() {
+        assert_eq!(messages("a = 1 b = 2"), ["Expected ; or a line break"]);
+        assert_eq!(
+            statements("a = 1; b = 2"),
+            [
+                "Assignment\n | Name a\n | Number 1",
+                "Assignment\n | Name b\n | Number 2"
+            ]
+        );
+        assert!(messages("a = 1\nb = 2").is_empty());
+        assert!(messages("void main {\n    a = 1\n} i32 b = 2").is_empty());
+    }
+
+    fn statements(code: &str) -> Vec<String> {
+        return build_syntax_tree(tokenizer::Lexer::new(code, FileId(0)))
+            .iter()
+            .map(|node| node.display(0))
+            .collect();
+    }
+
+    #[test]
+    fn recovers_after_a_bad_line() {
+        let code = "\
+i32 a = = 1
+i32 b = 2
+b = ]
+void main {
+    a = ) 2
+    println(b)
+}
+i32 c = 3";
+
+        assert_eq!(
+            messages(code),
+            ["Unexpected =", "Unexpected ]", "Unexpected )"]
+        );
+        assert_eq!(
+            statements(code)[1..],
+            [
+                "Variable Definition\n | Type I32\n | Assignment\n |  | Name b\n |  | Number 2",
+                "Assignment\n | Name b\n | Error Unexpected ]",
+                "Function Definition\n | Type Void\n | Name main\n | Block\n |  | Assignment\n |  |  | Name a\n |  |  | Error Unexpected )\n |  | Call\n |  |  | Name println\n |  |  | Name b",
+                "Variable Definition\n | Type I32\n | Assignment\n |  | Name c\n |  | Number 3",
+            ]
+        );
+    }
+}
@@ -1,10 +1,27 @@
 use std::fmt;
 
+use crate::diagnostic::Diagnostic;
+use crate::source_map::{FileId, Span};
+use crate::syntax_tree::{Keyword, Operator, Symbol, Type};
+use crate::unicode;
+
+/// What a token is. Words and symbols are classified here, so the parser never compares strings.
+#[derive(Clone, Copy)]
 pub enum TokenType {
     WhiteSpace,
     Seperator,
-    Symbol,
-    Unkown,
+    /// A `//` comment up to, but not including, the end of the line.
+    Comment,
+    /// A single character operator. The parser joins ones like `=` `=` into `==`.
+    Operator(Operator),
+    Symbol(Symbol),
+    Type(Type),
+    Keyword(Keyword),
+    Boolean(bool),
+    Number,
+    Name,
+    /// A character that can't start any token, like `@` or an emoji outside a comment.
+    Error,
 }
 
 impl fmt::Display for TokenType {
@@ -12,51 +29,41 @@ impl fmt::Display for TokenType {
         match self {
             TokenType::WhiteSpace => write!(f, "White Space"),
             TokenType::Seperator => write!(f, "Seperator"),
-            TokenType::Symbol => write!(f, "Symbol"),
-            TokenType::Unkown => write!(f, "Unknown"),
+            TokenType::Comment => write!(f, "Comment"),
+            TokenType::Operator(_) => write!(f, "Operator"),
+            TokenType::Symbol(_) => write!(f, "Symbol"),
+            TokenType::Type(_) => write!(f, "Type"),
+            TokenType::Keyword(_) => write!(f, "Keyword"),
+            TokenType::Boolean(_) => write!(f, "Boolean"),
+            TokenType::Number => write!(f, "Number"),
+            TokenType::Name => write!(f, "Name"),
+            TokenType::Error => write!(f, "Error"),
         }
     }
 }
 
-pub struct Token {
-    pub content: String,
+/// A token borrowing its text from the source, so lexing doesn't allocate.
+#[derive(Clone, Copy)]
+pub struct Token<'a> {
+    pub content: &'a str,
     pub token_type: TokenType,
-    pub lines: (usize, usize),
-    pub characters: (usize, usize),
+    pub span: Span,
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.token_type {
-            TokenType::WhiteSpace => write!(
-                f,
-                "\'{}\' - {} {}, {} -> {}, {}",
-                self.content.escape_default(),
-                self.token_type,
-                self.lines.0,
-                self.characters.0,
-                self.lines.1,
-                self.characters.1
-            ),
-            TokenType::Seperator => write!(
+            TokenType::WhiteSpace | TokenType::Seperator => write!(
                 f,
-                "\'{}\' - {} {}, {} -> {}, {}",
+                "\'{}\' - {} {}",
                 self.content.escape_default(),
                 self.token_type,
-                self.lines.0,
-                self.characters.0,
-                self.lines.1,
-                self.characters.1
+                self.span
             ),
             _ => write!(
                 f,
-                "\'{}\' - {} {}, {} -> {}, {}",
-                self.content,
-                self.token_type,
-                self.lines.0,
-                self.characters.0,
-                self.lines.1,
-                self.characters.1
+                "\'{}\' - {} {}",
+                self.content, self.token_type, self.span
             ),
         }
     }
@@ -64,71 +71,222 @@ impl fmt::Display for Token {
 
 const WHITE_SPACE_CHARACTERS: &[char] = &[' ', '\t', '\r'];
 const SEPERATOR_CHARACTERS: &[char] = &['\n'];
-const SYMBOL_CHARACTERS: &[char] = &[
-    '+', '-', '/', '*', '=', '(', ')', ',', '"', '\'', '<', '>', '{', '}', '&', '|', '.', '%', '!',
-];
-
-pub fn tokenize(code: &str) -> Vec<Token> {
-    let mut tokens: Vec<Token> = Vec::new();
-
-    let mut start_line: usize = 0;
-    let mut start_character: usize = 0;
-
-    let mut current_line: usize = 0;
-    let mut current_character: usize = 0;
-
-    let mut start_character_index: usize = 0;
-
-    for (current_character_index, character) in code.char_indices() {
-        let white_space = WHITE_SPACE_CHARACTERS.contains(&character);
-        let seperator = SEPERATOR_CHARACTERS.contains(&character);
-        let symbol = SYMBOL_CHARACTERS.contains(&character);
-
-        if white_space || seperator || symbol {
-            if start_character_index != current_character_index {
-                tokens.push(Token {
-                    content: String::from(&code[start_character_index..current_character_index]),
-                    lines: (start_line, current_line),
-                    characters: (start_character, current_character - 1),
-                    token_type: TokenType::Unkown,
-                });
-            }
-
-            tokens.push(Token {
-                content: String::from(&code[current_character_index..current_character_index + 1]),
-                lines: (current_line, current_line),
-                characters: (current_character, current_character),
-                token_type: if white_space {
-                    TokenType::WhiteSpace
-                } else if seperator {
-                    TokenType::Seperator
-                } else {
-                    TokenType::Symbol
-                },
-            });
-
-            start_character = current_character + 1;
-            start_line = current_line;
-
-            start_character_index = current_character_index + character.len_utf8();
-        }
+const NUMBER_CHARACTERS: &[char] = &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+fn symbol_type(character: char) -> Option<TokenType> {
+    let token_type = match character {
+        '&' => TokenType::Operator(Operator::BitwiseAnd),
+        '|' => TokenType::Operator(Operator::BitwiseOr),
+        '<' => TokenType::Operator(Operator::LessThan),
+        '=' => TokenType::Operator(Operator::Assign),
+        '>' => TokenType::Operator(Operator::GreaterThan),
+        '+' => TokenType::Operator(Operator::Add),
+        '-' => TokenType::Operator(Operator::Subtract),
+        '*' => TokenType::Operator(Operator::Multiply),
+        '/' => TokenType::Operator(Operator::Divide),
+        '%' => TokenType::Operator(Operator::Modulo),
+        '.' => TokenType::Operator(Operator::Access),
+        '!' => TokenType::Operator(Operator::Not),
+        '(' => TokenType::Symbol(Symbol::OpenParen),
+        ')' => TokenType::Symbol(Symbol::ClosedParen),
+        ',' => TokenType::Symbol(Symbol::Comma),
+        '"' => TokenType::Symbol(Symbol::Quote),
+        '\'' => TokenType::Symbol(Symbol::SingleQuote),
+        '{' => TokenType::Symbol(Symbol::OpenCurlyBracket),
+        '}' => TokenType::Symbol(Symbol::ClosedCurlyBracket),
+        '[' => TokenType::Symbol(Symbol::OpenSquareBracket),
+        ']' => TokenType::Symbol(Symbol::ClosedSquareBracket),
+        ':' => TokenType::Symbol(Symbol::Colon),
+        ';' => TokenType::Symbol(Symbol::Semicolon),
+        _ => return None,
+    };
 
-        current_character += 1;
+    return Some(token_type);
+}
 
-        if character == '\n' {
-            current_line += 1;
-            current_character = 0;
+fn word_type(word: &str) -> TokenType {
+    match word {
+        "i32" => return TokenType::Type(Type::I32),
+        "u32" => return TokenType::Type(Type::U32),
+        "f32" => return TokenType::Type(Type::F32),
+        "string" => return TokenType::Type(Type::String),
+        "void" => return TokenType::Type(Type::Void),
+        "bool" => return TokenType::Type(Type::Boolean),
+        "true" => return TokenType::Boolean(true),
+        "false" => return TokenType::Boolean(false),
+        "if" => return TokenType::Keyword(Keyword::If),
+        "forever" => return TokenType::Keyword(Keyword::Forever),
+        "return" => return TokenType::Keyword(Keyword::Return),
+        "const" => return TokenType::Keyword(Keyword::Const),
+        "comptime" => return TokenType::Keyword(Keyword::Comptime),
+        "else" => return TokenType::Keyword(Keyword::Else),
+        "break" => return TokenType::Keyword(Keyword::Break),
+        "import" => return TokenType::Keyword(Keyword::Import),
+        "from" => return TokenType::Keyword(Keyword::From),
+        "defer" => return TokenType::Keyword(Keyword::Defer),
+        "extern" => return TokenType::Keyword(Keyword::Extern),
+        _ if word
+            .chars()
+            .all(|character| NUMBER_CHARACTERS.contains(&character)) =>
+        {
+            return TokenType::Number;
         }
+        // Only whole numbers may start with a digit, so `1x` can't shadow a name or a number.
+        _ if word.starts_with(|character: char| character.is_ascii_digit()) => {
+            return TokenType::Error;
+        }
+        _ => return TokenType::Name,
+    }
+}
+
+/// Yields the tokens of source code one at a time, so a whole file of tokens never has to be in
+/// memory at once.
+pub struct Lexer<'a> {
+    code: &'a str,
+    file: FileId,
+    offset: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(code: &'a str, file: FileId) -> Lexer<'a> {
+        return Lexer {
+            code,
+            file,
+            offset: 0,
+        };
+    }
+
+    /// Lexes from a byte offset that starts a line, for re-lexing part of an edited file. Tokens
+    /// never span lines, so they come out the same as lexing the whole file.
+    pub fn starting_at(code: &'a str, file: FileId, offset: usize) -> Lexer<'a> {
+        return Lexer { code, file, offset };
     }
 
-    if start_character_index != code.len() {
-        tokens.push(Token {
-            content: String::from(&code[start_character_index..code.len()]),
-            lines: (start_line, current_line),
-            characters: (start_character, current_character - 1),
-            token_type: TokenType::Unkown,
+    pub fn file(&self) -> FileId {
+        return self.file;
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let rest = &self.code[self.offset..];
+        let character = rest.chars().next()?;
+
+        let (length, token_type) = if rest.starts_with("//") {
+            (rest.find('\n').unwrap_or(rest.len()), TokenType::Comment)
+        } else if WHITE_SPACE_CHARACTERS.contains(&character) {
+            (character.len_utf8(), TokenType::WhiteSpace)
+        } else if SEPERATOR_CHARACTERS.contains(&character) {
+            (character.len_utf8(), TokenType::Seperator)
+        } else if let Some(token_type) = symbol_type(character) {
+            (character.len_utf8(), token_type)
+        } else if unicode::is_identifier_start(character) || character.is_ascii_digit() {
+            // Numbers are words too, so that `1st` is one bad word rather than a number and a name.
+            let length = rest
+                .find(|character| !unicode::is_identifier_continue(character))
+                .unwrap_or(rest.len());
+
+            (length, word_type(&rest[..length]))
+        } else {
+            (unicode::first_grapheme(rest).len(), TokenType::Error)
+        };
+
+        let start = self.offset;
+
+        self.offset += length;
+
+        return Some(Token {
+            content: &rest[..length],
+            token_type,
+            span: Span::new(self.file, start, self.offset),
         });
     }
+}
+
+/// Reports every character that can't start a token, and every word that starts with a digit but
+/// isn't a number. The parser skips them, so this has to run before a tree is trusted.
+pub fn errors(code: &str, file: FileId) -> Vec<Diagnostic> {
+    return Lexer::new(code, file)
+        .filter(|token| matches!(token.token_type, TokenType::Error))
+        .map(|token| {
+            let message = if token
+                .content
+                .starts_with(|character: char| character.is_ascii_digit())
+            {
+                format!(
+                    "Invalid number {}, names can't start with a digit",
+                    token.content
+                )
+            } else {
+                format!("Unexpected character {}", token.content.escape_debug())
+            };
+
+            Diagnostic::error(message, token.span)
+        })
+        .collect();
+}
+
+/// Collects every token of source code, for the passes that look at tokens more than once.
+pub fn tokenize(code: &str, file: FileId) -> Vec<Token<'_>> {
+    return Lexer::new(code, file).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(code: &str) -> Vec<String> {
+        return tokenize(code, FileId(0))
+            .into_iter()
+            .filter(|token| !matches!(token.token_type, TokenType::WhiteSpace))
+            .map(|token| format!("{} {}", token.content, token.token_type))
+            .collect();
+    }
+
+    fn messages(code: &str) -> Vec<String> {
+        return errors(code, FileId(0))
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+    }
+
+    #[test]
+    fn lexes_numbers_and_names() {
+        assert_eq!(
+            tokens("i32 x1 = 42"),
+            ["i32 Type", "x1 Name", "= Operator", "42 Number"]
+        );
+        assert!(messages("i32 x1 = 42").is_empty());
+    }
 
-    return tokens;
+    #[test]
+    fn rejects_names_that_start_with_a_digit() {
+        assert_eq!(
+            tokens("i32 1x = 2"),
+            ["i32 Type", "1x Error", "= Operator", "2 Number"]
+        );
+        assert_eq!(
+            messages("i32 1x = 2\nprintln(1x)"),
+            [
+                "Invalid number 1x, names can't start with a digit",
+                "Invalid number 1x, names can't start with a digit",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_digit_separators() {
+        assert_eq!(tokens("1_000"), ["1_000 Error"]);
+        assert_eq!(
+            messages("i32 a = 1_000"),
+            ["Invalid number 1_000, names can't start with a digit"]
+        );
+    }
+
+    #[test]
+    fn reports_stray_characters() {
+        assert_eq!(messages("i32 a = 1 $"), ["Unexpected character $"]);
+    }
 }
@@ -0,0 +1,824 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::syntax_tree::{
+    CallNode, ExternFunctionNode, FunctionDefinitionNode, IndexNode, Keyword, NameNode, Node,
+    OperationNode, Operator, ParameterNode, Type, TypeNode,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CheckedType {
+    Scalar(Type),
+    Array(Type, usize),
+    Pointer(Type),
+}
+
+impl CheckedType {
+    pub fn from_type_node(type_node: &TypeNode) -> CheckedType {
+        if let Some(array_size) = &type_node.array_size
+            && let Node::Number(number_node) = array_size.as_ref()
+        {
+            return CheckedType::Array(type_node.node_type, number_node.value as usize);
+        }
+
+        if type_node.pointer {
+            return CheckedType::Pointer(type_node.node_type);
+        }
+
+        return CheckedType::Scalar(type_node.node_type);
+    }
+
+    /// The type of the values, which for arrays and pointers is the type of what they hold.
+    pub fn element_type(&self) -> Type {
+        match self {
+            CheckedType::Scalar(node_type) => *node_type,
+            CheckedType::Array(node_type, _) => *node_type,
+            CheckedType::Pointer(node_type) => *node_type,
+        }
+    }
+}
+
+impl fmt::Display for CheckedType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckedType::Scalar(node_type) => write!(f, "{}", node_type),
+            CheckedType::Array(node_type, size) => write!(f, "{}[{}]", node_type, size),
+            CheckedType::Pointer(node_type) => write!(f, "{}*", node_type),
+        }
+    }
+}
+
+pub struct FunctionSignature {
+    pub parameters: Vec<CheckedType>,
+    pub return_type: CheckedType,
+}
+
+struct Variable {
+    variable_type: CheckedType,
+    constant: bool,
+}
+
+fn error(message: String, node: &Node) -> Diagnostic {
+    return Diagnostic::error(message, node.get_span());
+}
+
+fn is_integer(node_type: Type) -> bool {
+    matches!(node_type, Type::I32 | Type::U32)
+}
+
+fn is_numeric(node_type: Type) -> bool {
+    matches!(node_type, Type::I32 | Type::U32 | Type::F32)
+}
+
+/// Returns whether every path through the statements ends in a `return`.
+fn always_returns(nodes: &[Node]) -> bool {
+    return nodes.iter().any(|node| match node {
+        Node::Return(_) => true,
+        Node::Block(block_node) => always_returns(&block_node.content),
+        Node::If(if_node) => match &if_node.else_node {
+            Some(else_node) => {
+                always_returns(&if_node.block.content)
+                    && always_returns(std::slice::from_ref(else_node.as_ref()))
+            }
+            None => false,
+        },
+        Node::Forever(forever_node) => !contains_break(&forever_node.block.content),
+        _ => false,
+    });
+}
+
+fn contains_break(nodes: &[Node]) -> bool {
+    return nodes.iter().any(|node| match node {
+        Node::Keyword(keyword_node) => keyword_node.keyword == Keyword::Break,
+        Node::Block(block_node) => contains_break(&block_node.content),
+        Node::If(if_node) => {
+            contains_break(&if_node.block.content)
+                || if_node
+                    .else_node
+                    .as_ref()
+                    .is_some_and(|else_node| contains_break(std::slice::from_ref(else_node)))
+        }
+        _ => false,
+    });
+}
+
+pub struct TypeChecker {
+    pub functions: HashMap<String, FunctionSignature>,
+    scopes: Vec<HashMap<String, Variable>>,
+    return_type: CheckedType,
+    loop_depth: usize,
+    /// Whether the statement being checked is deferred, so it can't leave its block.
+    deferred: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        return TypeChecker {
+            functions: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            return_type: CheckedType::Scalar(Type::Void),
+            loop_depth: 0,
+            deferred: false,
+            diagnostics: Vec::new(),
+        };
+    }
+
+    fn define(&mut self, name: &str, variable_type: CheckedType, constant: bool) {
+        self.scopes.last_mut().unwrap().insert(
+            String::from(name),
+            Variable {
+                variable_type,
+                constant,
+            },
+        );
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(variable) = scope.get(name) {
+                return Some(variable);
+            }
+        }
+
+        return None;
+    }
+
+    /// Reports the types that can be written but not used, which are arrays of pointers and
+    /// pointers to `void`.
+    fn check_type_node(&mut self, type_node: &TypeNode) {
+        let message = match type_node.node_type {
+            _ if !type_node.pointer => return,
+            _ if type_node.array_size.is_some() => "Arrays of pointers are not supported",
+            Type::Void => "Pointers to Void are not supported",
+            _ => return,
+        };
+
+        self.diagnostics
+            .push(Diagnostic::error(String::from(message), type_node.span));
+    }
+
+    fn expect_type(&mut self, found: CheckedType, expected: CheckedType, node: &Node) {
+        if found != expected {
+            self.diagnostics.push(error(
+                format!("Expected a value of type {} but got {}", expected, found),
+                node,
+            ));
+        }
+    }
+
+    /// Returns the type of an expression. Integer literals take on the `expected` type when there
+    /// is one, and `i32` otherwise.
+    pub fn expression_type(
+        &mut self,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        match node {
+            Node::Number(number_node) => {
+                let node_type = match expected.map(|expected| expected.element_type()) {
+                    Some(Type::U32) => Type::U32,
+                    Some(Type::F32) => Type::F32,
+                    _ => Type::I32,
+                };
+
+                let in_range = match node_type {
+                    Type::U32 => u32::try_from(number_node.value).is_ok(),
+                    Type::I32 => i32::try_from(number_node.value).is_ok(),
+                    _ => true,
+                };
+
+                if !in_range {
+                    return Err(error(
+                        format!(
+                            "Integer literal {} does not fit in {}",
+                            number_node.value, node_type
+                        ),
+                        node,
+                    ));
+                }
+
+                return Ok(CheckedType::Scalar(node_type));
+            }
+            Node::Boolean(_) => return Ok(CheckedType::Scalar(Type::Boolean)),
+            Node::Name(name_node) => {
+                return match self.lookup(&name_node.value) {
+                    Some(variable) => Ok(variable.variable_type),
+                    None => Err(error(format!("Unknown variable {}", name_node.value), node)),
+                };
+            }
+            Node::Comptime(comptime_node) => {
+                return self.expression_type(&comptime_node.value, expected);
+            }
+            Node::Group(group_node) if group_node.content.len() == 1 => {
+                return self.expression_type(&group_node.content[0], expected);
+            }
+            Node::Array(array_node) => {
+                let element_expected =
+                    expected.map(|expected| CheckedType::Scalar(expected.element_type()));
+
+                let mut element_type: Option<CheckedType> = element_expected;
+
+                for value in &array_node.values {
+                    let value_type = self.expression_type(value, element_type)?;
+
+                    match element_type {
+                        Some(element_type) => self.expect_type(value_type, element_type, value),
+                        None => element_type = Some(value_type),
+                    }
+                }
+
+                return match element_type {
+                    Some(CheckedType::Scalar(element_type)) => {
+                        Ok(CheckedType::Array(element_type, array_node.values.len()))
+                    }
+                    Some(CheckedType::Array(_, _)) => {
+                        Err(error(String::from("Nested arrays are not supported"), node))
+                    }
+                    Some(CheckedType::Pointer(_)) => Err(error(
+                        String::from("Arrays of pointers are not supported"),
+                        node,
+                    )),
+                    None => Err(error(
+                        String::from("The type of an empty array can't be inferred"),
+                        node,
+                    )),
+                };
+            }
+            Node::Operation(operation_node) => {
+                return self.operation_type(operation_node, node, expected);
+            }
+            Node::Index(index_node) => return self.index_type(index_node),
+            Node::Call(call_node) => {
+                let name = &call_node.name.value;
+
+                if !self.functions.contains_key(name) && (name == "print" || name == "println") {
+                    for argument in &call_node.arguments {
+                        self.expression_type(argument, None)?;
+                    }
+
+                    return Ok(CheckedType::Scalar(Type::Void));
+                }
+
+                if !self.functions.contains_key(name)
+                    && (name == "alloc" || name == "resize" || name == "free")
+                {
+                    return self.memory_call_type(call_node, node, expected);
+                }
+
+                let (parameters, return_type) = match self.functions.get(name) {
+                    Some(signature) => (signature.parameters.clone(), signature.return_type),
+                    None => return Err(error(format!("Unknown function {}", name), node)),
+                };
+
+                if parameters.len() != call_node.arguments.len() {
+                    return Err(error(
+                        format!(
+                            "Function {} expects {} arguments but got {}",
+                            name,
+                            parameters.len(),
+                            call_node.arguments.len()
+                        ),
+                        node,
+                    ));
+                }
+
+                for (parameter, argument) in parameters.iter().zip(&call_node.arguments) {
+                    let argument_type = self.expression_type(argument, Some(*parameter))?;
+
+                    self.expect_type(argument_type, *parameter, argument);
+                }
+
+                return Ok(return_type);
+            }
+            _ => {}
+        }
+
+        return Err(error(String::from("Expected an expression"), node));
+    }
+
+    /// Returns the type a pointer points to, reporting when a name isn't a pointer.
+    fn pointer_type(&mut self, node: &Node) -> Result<Type, Diagnostic> {
+        return match self.expression_type(node, None)? {
+            CheckedType::Pointer(element_type) => Ok(element_type),
+            found => Err(error(format!("Expected a pointer but got {}", found), node)),
+        };
+    }
+
+    fn index_type(&mut self, index_node: &IndexNode) -> Result<CheckedType, Diagnostic> {
+        let element_type = match self.lookup(&index_node.name.value) {
+            Some(Variable {
+                variable_type: CheckedType::Pointer(element_type),
+                ..
+            }) => *element_type,
+            Some(variable) => {
+                return Err(Diagnostic::error(
+                    format!(
+                        "Only pointers can be indexed, but {} is {}",
+                        index_node.name.value, variable.variable_type
+                    ),
+                    index_node.name.span,
+                ));
+            }
+            None => {
+                return Err(Diagnostic::error(
+                    format!("Unknown variable {}", index_node.name.value),
+                    index_node.name.span,
+                ));
+            }
+        };
+
+        match self.expression_type(&index_node.index, None)? {
+            CheckedType::Scalar(Type::I32 | Type::U32) => {}
+            found => {
+                return Err(error(
+                    format!("Expected an integer index but got {}", found),
+                    &index_node.index,
+                ));
+            }
+        }
+
+        return Ok(CheckedType::Scalar(element_type));
+    }
+
+    /// Checks a call to `alloc`, `resize` or `free`. What `alloc` allocates comes from the pointer
+    /// type expected of it, like `i32* p = alloc(n)`.
+    fn memory_call_type(
+        &mut self,
+        call_node: &CallNode,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        let name = call_node.name.value.as_str();
+
+        let parameter_count = match name {
+            "alloc" | "free" => 1,
+            _ => 2,
+        };
+
+        if call_node.arguments.len() != parameter_count {
+            return Err(error(
+                format!(
+                    "Function {} expects {} arguments but got {}",
+                    name,
+                    parameter_count,
+                    call_node.arguments.len()
+                ),
+                node,
+            ));
+        }
+
+        let count = CheckedType::Scalar(Type::I32);
+
+        match name {
+            "alloc" => {
+                let Some(CheckedType::Pointer(element_type)) = expected else {
+                    return Err(error(
+                        String::from("alloc can only be used where a pointer is expected"),
+                        node,
+                    ));
+                };
+
+                self.check_expression(&call_node.arguments[0], Some(count));
+
+                return Ok(CheckedType::Pointer(element_type));
+            }
+            "resize" => {
+                let element_type = self.pointer_type(&call_node.arguments[0])?;
+
+                self.check_expression(&call_node.arguments[1], Some(count));
+
+                return Ok(CheckedType::Pointer(element_type));
+            }
+            _ => {
+                self.pointer_type(&call_node.arguments[0])?;
+
+                return Ok(CheckedType::Scalar(Type::Void));
+            }
+        }
+    }
+
+    fn operation_type(
+        &mut self,
+        operation_node: &OperationNode,
+        node: &Node,
+        expected: Option<CheckedType>,
+    ) -> Result<CheckedType, Diagnostic> {
+        let operator = &operation_node.operator.operator;
+
+        let arithmetic = matches!(
+            operator,
+            Operator::Add
+                | Operator::Subtract
+                | Operator::Multiply
+                | Operator::Divide
+                | Operator::Modulo
+                | Operator::BitwiseAnd
+                | Operator::BitwiseOr
+                | Operator::Negate
+        );
+
+        let operand_expected = if arithmetic { expected } else { None };
+
+        let invalid = |operand: CheckedType| {
+            error(
+                format!(
+                    "Invalid operand of type {} for operator {}",
+                    operand, operator
+                ),
+                node,
+            )
+        };
+
+        if operator.one_sided() {
+            let operand = self.expression_type(&operation_node.values[0], operand_expected)?;
+
+            return match (operator, operand) {
+                (Operator::Not, CheckedType::Scalar(Type::Boolean)) => Ok(operand),
+                (Operator::Negate, CheckedType::Scalar(Type::I32 | Type::F32)) => Ok(operand),
+                _ => Err(invalid(operand)),
+            };
+        }
+
+        let value_a_node = &operation_node.values[0];
+        let value_b_node = &operation_node.values[1];
+
+        let (value_a, value_b) = if let Node::Number(_) = value_a_node
+            && !matches!(value_b_node, Node::Number(_))
+        {
+            let value_b = self.expression_type(value_b_node, operand_expected)?;
+            let value_a = self.expression_type(value_a_node, Some(value_b))?;
+
+            (value_a, value_b)
+        } else {
+            let value_a = self.expression_type(value_a_node, operand_expected)?;
+            let value_b = self.expression_type(value_b_node, Some(value_a))?;
+
+            (value_a, value_b)
+        };
+
+        if value_a != value_b {
+            return Err(error(
+                format!(
+                    "Mismatched operand types {} and {} for operator {}",
+                    value_a, value_b, operator
+                ),
+                node,
+            ));
+        }
+
+        let operand_type = match value_a {
+            CheckedType::Scalar(operand_type) => operand_type,
+            CheckedType::Array(_, _) | CheckedType::Pointer(_) => return Err(invalid(value_a)),
+        };
+
+        let result = match operator {
+            Operator::Add
+            | Operator::Subtract
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Modulo
+                if is_numeric(operand_type) =>
+            {
+                operand_type
+            }
+            Operator::BitwiseAnd | Operator::BitwiseOr
+                if is_integer(operand_type) || operand_type == Type::Boolean =>
+            {
+                operand_type
+            }
+            Operator::And | Operator::Or if operand_type == Type::Boolean => Type::Boolean,
+            Operator::LessThan
+            | Operator::LessThanOrEqual
+            | Operator::GreaterThan
+            | Operator::GreaterThanOrEqual
+                if is_numeric(operand_type) =>
+            {
+                Type::Boolean
+            }
+            Operator::Equal | Operator::NotEqual if operand_type != Type::Void => Type::Boolean,
+            _ => return Err(invalid(value_a)),
+        };
+
+        return Ok(CheckedType::Scalar(result));
+    }
+
+    fn check_expression(&mut self, node: &Node, expected: Option<CheckedType>) {
+        match self.expression_type(node, expected) {
+            Ok(found) => {
+                if let Some(expected) = expected {
+                    self.expect_type(found, expected, node);
+                }
+            }
+            Err(diagnostic) => self.diagnostics.push(diagnostic),
+        }
+    }
+
+    fn check_block(&mut self, nodes: &[Node]) {
+        self.scopes.push(HashMap::new());
+
+        for node in nodes {
+            self.check_statement(node);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn check_statement(&mut self, node: &Node) {
+        match node {
+            Node::Block(block_node) => self.check_block(&block_node.content),
+            Node::VariableDefinition(definition_node) => {
+                let variable_type = CheckedType::from_type_node(&definition_node.node_type);
+
+                self.check_type_node(&definition_node.node_type);
+
+                if variable_type.element_type() == Type::Void && !definition_node.node_type.pointer
+                {
+                    self.diagnostics.push(error(
+                        String::from("Variables can't have the type Void"),
+                        node,
+                    ));
+                }
+
+                self.check_expression(&definition_node.assignment.value, Some(variable_type));
+
+                self.define(
+                    &definition_node.assignment.name.value,
+                    variable_type,
+                    definition_node.constant,
+                );
+            }
+            Node::Assignment(assignment_node) => {
+                let name = &assignment_node.name.value;
+
+                let (variable_type, constant) = match self.lookup(name) {
+                    Some(variable) => (variable.variable_type, variable.constant),
+                    None => {
+                        self.diagnostics
+                            .push(error(format!("Unknown variable {}", name), node));
+
+                        return;
+                    }
+                };
+
+                if constant {
+                    self.diagnostics
+                        .push(error(format!("Cannot assign to constant {}", name), node));
+                }
+
+                self.check_expression(&assignment_node.value, Some(variable_type));
+            }
+            Node::IndexAssignment(assignment_node) => {
+                match self.index_type(&assignment_node.target) {
+                    Ok(element_type) => {
+                        self.check_expression(&assignment_node.value, Some(element_type))
+                    }
+                    Err(diagnostic) => self.diagnostics.push(diagnostic),
+                }
+            }
+            Node::If(if_node) => {
+                self.check_expression(&if_node.condition, Some(CheckedType::Scalar(Type::Boolean)));
+
+                self.check_block(&if_node.block.content);
+
+                if let Some(else_node) = &if_node.else_node {
+                    self.check_statement(else_node);
+                }
+            }
+            Node::Forever(forever_node) => {
+                self.loop_depth += 1;
+                self.check_block(&forever_node.block.content);
+                self.loop_depth -= 1;
+            }
+            Node::Return(_) if self.deferred => self.diagnostics.push(error(
+                String::from("Deferred code can't return from its function"),
+                node,
+            )),
+            Node::Return(return_node) => {
+                let return_type = self.return_type;
+
+                match &return_node.value {
+                    Some(value) => self.check_expression(value, Some(return_type)),
+                    None if return_type != CheckedType::Scalar(Type::Void) => {
+                        self.diagnostics.push(error(
+                            format!("Expected a return value of type {}", return_type),
+                            node,
+                        ))
+                    }
+                    None => {}
+                }
+            }
+            Node::Defer(_) if self.deferred => self.diagnostics.push(error(
+                String::from("Deferred code can't defer more code"),
+                node,
+            )),
+            Node::Defer(defer_node) => {
+                let loop_depth = self.loop_depth;
+
+                self.deferred = true;
+                self.loop_depth = 0;
+                self.check_block(std::slice::from_ref(&defer_node.statement));
+                self.loop_depth = loop_depth;
+                self.deferred = false;
+            }
+            Node::Keyword(keyword_node) if keyword_node.keyword == Keyword::Break => {
+                if self.deferred && self.loop_depth == 0 {
+                    self.diagnostics.push(error(
+                        String::from("Deferred code can't break out of a loop"),
+                        node,
+                    ));
+                } else if self.loop_depth == 0 {
+                    self.diagnostics
+                        .push(error(String::from("Break outside of a loop"), node));
+                }
+            }
+            Node::FunctionDefinition(_) | Node::ExternFunction(_) => self.diagnostics.push(error(
+                String::from("Functions must be defined at the top level"),
+                node,
+            )),
+            Node::Call(_)
+            | Node::Operation(_)
+            | Node::Name(_)
+            | Node::Number(_)
+            | Node::Index(_) => self.check_expression(node, None),
+            _ => self
+                .diagnostics
+                .push(error(String::from("Expected a statement"), node)),
+        }
+    }
+
+    fn check_function(&mut self, function_node: &FunctionDefinitionNode) {
+        if function_node.external {
+            self.check_c_signature(
+                &function_node.name,
+                &function_node.parameters,
+                &function_node.node_type,
+            );
+        }
+
+        self.scopes.push(HashMap::new());
+
+        for parameter in &function_node.parameters {
+            self.check_type_node(&parameter.node_type);
+            self.define(
+                &parameter.name.value,
+                CheckedType::from_type_node(&parameter.node_type),
+                false,
+            );
+        }
+
+        self.check_type_node(&function_node.node_type);
+        self.return_type = CheckedType::from_type_node(&function_node.node_type);
+
+        self.check_block(&function_node.block.content);
+
+        if self.return_type != CheckedType::Scalar(Type::Void)
+            && !always_returns(&function_node.block.content)
+        {
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "Function {} doesn't return a value on every path",
+                    function_node.name.value
+                ),
+                function_node.span,
+            ));
+        }
+
+        self.scopes.pop();
+    }
+
+    /// Checks that a function C code calls, or that calls C, only passes values C has a type for.
+    fn check_c_signature(
+        &mut self,
+        name: &NameNode,
+        parameters: &[ParameterNode],
+        return_type: &TypeNode,
+    ) {
+        if name.value == "main" {
+            self.diagnostics.push(Diagnostic::error(
+                String::from("main can't be extern \"C\", since the compiler defines the C main"),
+                name.span,
+            ));
+        }
+
+        let types = parameters
+            .iter()
+            .map(|parameter| &parameter.node_type)
+            .chain([return_type]);
+
+        for type_node in types {
+            let message = if type_node.array_size.is_some() {
+                "Arrays can't be passed to or from C"
+            } else if type_node.node_type == Type::String {
+                "Strings can't be passed to or from C"
+            } else {
+                continue;
+            };
+
+            self.diagnostics
+                .push(Diagnostic::error(String::from(message), type_node.span));
+        }
+    }
+
+    /// Registers every function signature so calls can appear before the function definition.
+    /// Functions declared with `extern "C"` are called the same way as the ones defined in Metor.
+    pub fn declare_functions(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            let (name, parameters, return_type) = match node {
+                Node::FunctionDefinition(function_node) => (
+                    &function_node.name,
+                    &function_node.parameters,
+                    &function_node.node_type,
+                ),
+                Node::ExternFunction(function_node) => (
+                    &function_node.name,
+                    &function_node.parameters,
+                    &function_node.node_type,
+                ),
+                _ => continue,
+            };
+
+            if self.functions.contains_key(&name.value) {
+                self.diagnostics.push(error(
+                    format!("Function {} is defined more than once", name.value),
+                    node,
+                ));
+            }
+
+            self.functions.insert(
+                name.value.clone(),
+                FunctionSignature {
+                    parameters: parameters
+                        .iter()
+                        .map(|parameter| CheckedType::from_type_node(&parameter.node_type))
+                        .collect(),
+                    return_type: CheckedType::from_type_node(return_type),
+                },
+            );
+        }
+    }
+
+    fn check_extern_function(&mut self, function_node: &ExternFunctionNode) {
+        for parameter in &function_node.parameters {
+            self.check_type_node(&parameter.node_type);
+        }
+
+        self.check_type_node(&function_node.node_type);
+        self.check_c_signature(
+            &function_node.name,
+            &function_node.parameters,
+            &function_node.node_type,
+        );
+    }
+
+    pub fn check_program(&mut self, nodes: &[Node]) {
+        self.declare_functions(nodes);
+
+        for node in nodes {
+            match node {
+                Node::FunctionDefinition(function_node) => self.check_function(function_node),
+                Node::ExternFunction(function_node) => self.check_extern_function(function_node),
+                Node::VariableDefinition(_) => self.check_statement(node),
+                _ => self.diagnostics.push(error(
+                    String::from("Expected a variable or function definition"),
+                    node,
+                )),
+            }
+        }
+    }
+
+    /// Checks one interactive entry. Unlike a program, an entry may be any statement, and a
+    /// function definition replaces an earlier one with the same name.
+    pub fn check_entry(&mut self, node: &Node) {
+        match node {
+            Node::FunctionDefinition(function_node) => {
+                self.functions.remove(&function_node.name.value);
+                self.declare_functions(std::slice::from_ref(node));
+                self.check_function(function_node);
+            }
+            Node::ExternFunction(function_node) => {
+                self.functions.remove(&function_node.name.value);
+                self.declare_functions(std::slice::from_ref(node));
+                self.check_extern_function(function_node);
+            }
+            Node::Defer(_) => self.diagnostics.push(error(
+                String::from("defer can only be used inside a function"),
+                node,
+            )),
+            _ => self.check_statement(node),
+        }
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return std::mem::take(&mut self.diagnostics);
+    }
+}
+
+pub fn check(nodes: &[Node]) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker::new();
+
+    checker.check_program(nodes);
+
+    return checker.take_diagnostics();
+}
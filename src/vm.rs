@@ -0,0 +1,321 @@
+use crate::bytecode::{GLOBALS_FUNCTION, Instruction, Program};
+use crate::diagnostic::Diagnostic;
+use crate::heap::Heap;
+use crate::interpreter::Value;
+use crate::ir::{BinaryOperator, UnaryOperator};
+
+const MAX_CALL_DEPTH: usize = 512;
+
+struct Frame {
+    function: usize,
+    instruction: usize,
+    /// Where the frame's locals start on the stack. Its temporaries sit above them.
+    base: usize,
+}
+
+fn binary(operator: BinaryOperator, value_a: Value, value_b: Value) -> Result<Value, String> {
+    if let BinaryOperator::Equal | BinaryOperator::NotEqual = operator {
+        return Ok(Value::Boolean(
+            (value_a == value_b) == (operator == BinaryOperator::Equal),
+        ));
+    }
+
+    let result = match (value_a, value_b) {
+        (Value::I32(a), Value::I32(b)) => match operator {
+            BinaryOperator::Add => Some(Value::I32(a.wrapping_add(b))),
+            BinaryOperator::Subtract => Some(Value::I32(a.wrapping_sub(b))),
+            BinaryOperator::Multiply => Some(Value::I32(a.wrapping_mul(b))),
+            BinaryOperator::Divide | BinaryOperator::Remainder if b == 0 => {
+                return Err(String::from("Division by zero"));
+            }
+            BinaryOperator::Divide => Some(Value::I32(a.wrapping_div(b))),
+            BinaryOperator::Remainder => Some(Value::I32(a.wrapping_rem(b))),
+            BinaryOperator::And => Some(Value::I32(a & b)),
+            BinaryOperator::Or => Some(Value::I32(a | b)),
+            BinaryOperator::LessThan => Some(Value::Boolean(a < b)),
+            BinaryOperator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+            BinaryOperator::GreaterThan => Some(Value::Boolean(a > b)),
+            BinaryOperator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+            _ => None,
+        },
+        (Value::U32(a), Value::U32(b)) => match operator {
+            BinaryOperator::Add => Some(Value::U32(a.wrapping_add(b))),
+            BinaryOperator::Subtract => Some(Value::U32(a.wrapping_sub(b))),
+            BinaryOperator::Multiply => Some(Value::U32(a.wrapping_mul(b))),
+            BinaryOperator::Divide | BinaryOperator::Remainder if b == 0 => {
+                return Err(String::from("Division by zero"));
+            }
+            BinaryOperator::Divide => Some(Value::U32(a / b)),
+            BinaryOperator::Remainder => Some(Value::U32(a % b)),
+            BinaryOperator::And => Some(Value::U32(a & b)),
+            BinaryOperator::Or => Some(Value::U32(a | b)),
+            BinaryOperator::LessThan => Some(Value::Boolean(a < b)),
+            BinaryOperator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+            BinaryOperator::GreaterThan => Some(Value::Boolean(a > b)),
+            BinaryOperator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+            _ => None,
+        },
+        (Value::F32(a), Value::F32(b)) => match operator {
+            BinaryOperator::Add => Some(Value::F32(a + b)),
+            BinaryOperator::Subtract => Some(Value::F32(a - b)),
+            BinaryOperator::Multiply => Some(Value::F32(a * b)),
+            BinaryOperator::Divide => Some(Value::F32(a / b)),
+            BinaryOperator::Remainder => Some(Value::F32(a % b)),
+            BinaryOperator::LessThan => Some(Value::Boolean(a < b)),
+            BinaryOperator::LessThanOrEqual => Some(Value::Boolean(a <= b)),
+            BinaryOperator::GreaterThan => Some(Value::Boolean(a > b)),
+            BinaryOperator::GreaterThanOrEqual => Some(Value::Boolean(a >= b)),
+            _ => None,
+        },
+        (Value::Boolean(a), Value::Boolean(b)) => match operator {
+            BinaryOperator::And => Some(Value::Boolean(a & b)),
+            BinaryOperator::Or => Some(Value::Boolean(a | b)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    return result.ok_or_else(|| format!("Invalid operands for operator {}", operator));
+}
+
+fn unary(operator: UnaryOperator, value: Value) -> Result<Value, String> {
+    match (operator, value) {
+        (UnaryOperator::Not, Value::Boolean(value)) => return Ok(Value::Boolean(!value)),
+        (UnaryOperator::Negate, Value::I32(value)) => return Ok(Value::I32(value.wrapping_neg())),
+        (UnaryOperator::Negate, Value::F32(value)) => return Ok(Value::F32(-value)),
+        (_, value) => {
+            return Err(format!(
+                "Invalid operand {} for operator {}",
+                value, operator
+            ));
+        }
+    }
+}
+
+/// Runs bytecode on a value stack. Each call frame's locals live on the stack below its
+/// temporaries, so calling a function just marks where its arguments already are.
+pub struct Vm<'a> {
+    program: &'a Program,
+    stack: Vec<Value>,
+    globals: Vec<Value>,
+    frames: Vec<Frame>,
+    heap: Heap,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program) -> Vm<'a> {
+        return Vm {
+            program,
+            stack: Vec::new(),
+            globals: vec![Value::Void; program.globals.len()],
+            frames: Vec::new(),
+            heap: Heap::new(),
+        };
+    }
+
+    fn pop(&mut self) -> Value {
+        return self.stack.pop().expect("Bytecode popped an empty stack");
+    }
+
+    fn error(&self, message: String) -> Diagnostic {
+        let frame = self.frames.last().unwrap();
+        let (lines, characters) =
+            self.program.functions[frame.function].spans[frame.instruction - 1];
+
+        return Diagnostic::resolved(message, lines, characters);
+    }
+
+    fn enter(&mut self, function: usize) -> Result<(), Diagnostic> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(self.error(String::from("Stack overflow")));
+        }
+
+        let function_code = &self.program.functions[function];
+        let base = self.stack.len() - function_code.parameter_count as usize;
+
+        self.stack
+            .resize(base + function_code.local_count as usize, Value::Void);
+        self.frames.push(Frame {
+            function,
+            instruction: 0,
+            base,
+        });
+
+        return Ok(());
+    }
+
+    /// Calls a function with the given arguments and runs until it returns.
+    pub fn call(&mut self, function: usize, arguments: Vec<Value>) -> Result<Value, Diagnostic> {
+        let depth = self.frames.len();
+
+        self.stack.extend(arguments);
+        self.enter(function)?;
+
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instruction = self.program.functions[frame.function].code[frame.instruction];
+
+            frame.instruction += 1;
+
+            let base = frame.base;
+
+            match instruction {
+                Instruction::Constant(index) => {
+                    let constant = self.program.constants[index as usize].clone();
+
+                    self.stack.push(constant);
+                }
+                Instruction::LoadLocal(slot) => {
+                    let value = self.stack[base + slot as usize].clone();
+
+                    self.stack.push(value);
+                }
+                Instruction::StoreLocal(slot) => {
+                    self.stack[base + slot as usize] = self.pop();
+                }
+                Instruction::LoadGlobal(index) => {
+                    let value = self.globals[index as usize].clone();
+
+                    self.stack.push(value);
+                }
+                Instruction::StoreGlobal(index) => {
+                    self.globals[index as usize] = self.pop();
+                }
+                Instruction::Binary(operator) => {
+                    let value_b = self.pop();
+                    let value_a = self.pop();
+
+                    let result = binary(operator, value_a, value_b)
+                        .map_err(|message| self.error(message))?;
+
+                    self.stack.push(result);
+                }
+                Instruction::Unary(operator) => {
+                    let value = self.pop();
+                    let result = unary(operator, value).map_err(|message| self.error(message))?;
+
+                    self.stack.push(result);
+                }
+                Instruction::Array(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count as usize);
+
+                    self.stack.push(Value::Array(values));
+                }
+                Instruction::Print(count) | Instruction::Write(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count as usize);
+                    let values: Vec<String> =
+                        values.iter().map(|value| value.to_string()).collect();
+
+                    if matches!(instruction, Instruction::Print(_)) {
+                        println!("{}", values.join(" "));
+                    } else {
+                        print!("{}", values.join(" "));
+                    }
+
+                    self.stack.push(Value::Void);
+                }
+                Instruction::Swap => {
+                    let length = self.stack.len();
+
+                    self.stack.swap(length - 1, length - 2);
+                }
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Jump(target) => {
+                    self.frames.last_mut().unwrap().instruction = target as usize
+                }
+                Instruction::JumpIfFalse(target) => match self.pop() {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => {
+                        self.frames.last_mut().unwrap().instruction = target as usize
+                    }
+                    value => {
+                        return Err(self.error(format!(
+                            "Expected a condition of type Boolean but got {}",
+                            value
+                        )));
+                    }
+                },
+                Instruction::Call(function) => self.enter(function as usize)?,
+                Instruction::Return | Instruction::ReturnVoid => {
+                    let value = match instruction {
+                        Instruction::Return => self.pop(),
+                        _ => Value::Void,
+                    };
+
+                    self.frames.pop();
+                    self.stack.truncate(base);
+
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+
+                    self.stack.push(value);
+                }
+                Instruction::Alloc(element_type) => {
+                    let count = self.pop();
+                    let result = self.heap.alloc(element_type, &count);
+                    let pointer = result.map_err(|message| self.error(message))?;
+
+                    self.stack.push(pointer);
+                }
+                Instruction::Resize => {
+                    let count = self.pop();
+                    let pointer = self.pop();
+                    let result = self.heap.resize(&pointer, &count);
+                    let pointer = result.map_err(|message| self.error(message))?;
+
+                    self.stack.push(pointer);
+                }
+                Instruction::Free => {
+                    let pointer = self.pop();
+
+                    self.heap
+                        .free(&pointer)
+                        .map_err(|message| self.error(message))?;
+                    self.stack.push(Value::Void);
+                }
+                Instruction::Load => {
+                    let index = self.pop();
+                    let pointer = self.pop();
+                    let result = self.heap.load(&pointer, &index);
+                    let value = result.map_err(|message| self.error(message))?;
+
+                    self.stack.push(value);
+                }
+                Instruction::Store => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let pointer = self.pop();
+                    let result = self.heap.store(&pointer, &index, value);
+
+                    result.map_err(|message| self.error(message))?;
+                }
+                Instruction::MissingReturn => {
+                    let name = &self.program.functions[self.frames.last().unwrap().function].name;
+
+                    return Err(
+                        self.error(format!("Function {} ended without returning a value", name))
+                    );
+                }
+            }
+        }
+    }
+
+    /// Initializes the globals and then runs `main`.
+    pub fn run_main(&mut self) -> Result<Value, Diagnostic> {
+        if let Some(globals) = self.program.function_index(GLOBALS_FUNCTION) {
+            self.call(globals, Vec::new())?;
+        }
+
+        return match self.program.function_index("main") {
+            Some(main) => self.call(main, Vec::new()),
+            None => Err(Diagnostic::resolved(
+                String::from("No main function defined"),
+                (0, 0),
+                (0, 0),
+            )),
+        };
+    }
+}
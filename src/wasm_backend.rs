@@ -0,0 +1,1217 @@
+use std::collections::HashMap;
+
+use crate::ir::{
+    BinaryOperator, BlockCall, BlockId, Constant, Function, Instruction, InstructionKind, IrType,
+    Module, Terminator, UnaryOperator, ValueId,
+};
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+const F32: u8 = 0x7d;
+const EMPTY_BLOCK: u8 = 0x40;
+
+const UNREACHABLE: u8 = 0x00;
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+const BR: u8 = 0x0c;
+const BR_IF: u8 = 0x0d;
+const RETURN: u8 = 0x0f;
+const CALL: u8 = 0x10;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const GLOBAL_GET: u8 = 0x23;
+const GLOBAL_SET: u8 = 0x24;
+const I32_LOAD: u8 = 0x28;
+const F32_LOAD: u8 = 0x2a;
+const I32_LOAD8_U: u8 = 0x2d;
+const I32_STORE: u8 = 0x36;
+const F32_STORE: u8 = 0x38;
+const I32_STORE8: u8 = 0x3a;
+const MEMORY_SIZE: u8 = 0x3f;
+const MEMORY_GROW: u8 = 0x40;
+const I32_CONST: u8 = 0x41;
+const I64_CONST: u8 = 0x42;
+const F32_CONST: u8 = 0x43;
+const I32_EQZ: u8 = 0x45;
+const I32_EQ: u8 = 0x46;
+const I32_GT_U: u8 = 0x4b;
+const I32_LE_U: u8 = 0x4d;
+const I32_GE_U: u8 = 0x4f;
+const I64_GT_U: u8 = 0x56;
+const I32_ADD: u8 = 0x6a;
+const I32_SUB: u8 = 0x6b;
+const I32_MUL: u8 = 0x6c;
+const I32_AND: u8 = 0x71;
+const I32_XOR: u8 = 0x73;
+const I32_SHL: u8 = 0x74;
+const I32_SHR_U: u8 = 0x76;
+const I64_MUL: u8 = 0x7e;
+const I32_WRAP_I64: u8 = 0xa7;
+const I64_EXTEND_I32_S: u8 = 0xac;
+const I64_EXTEND_I32_U: u8 = 0xad;
+/// The prefix of the bulk memory instructions, followed by `MEMORY_COPY` or `MEMORY_FILL`.
+const BULK: u8 = 0xfc;
+const MEMORY_COPY: u8 = 10;
+const MEMORY_FILL: u8 = 11;
+const F32_NEG: u8 = 0x8c;
+const F32_TRUNC: u8 = 0x8f;
+const F32_SUB: u8 = 0x93;
+const F32_MUL: u8 = 0x94;
+const F32_DIV: u8 = 0x95;
+
+/// Host functions the module imports from `env` to implement `print` and `println`, in function
+/// index order. The host formats the values, and should print floats like
+/// `interpreter::f32_text` to match the other backends.
+const IMPORTS: &[(&str, Option<u8>)] = &[
+    ("print_i32", Some(I32)),
+    ("print_u32", Some(I32)),
+    ("print_f32", Some(F32)),
+    ("print_bool", Some(I32)),
+    ("print_space", None),
+    ("print_newline", None),
+];
+
+/// The allocator's functions, defined after the module's own, in function index order. Each block
+/// of memory starts with an 8 byte header holding how many bytes it has room for and how many are
+/// in use, and freed blocks are kept in a list threaded through their first bytes, which `alloc`
+/// reuses the first big enough one from before growing the heap.
+const HEAP_FUNCTIONS: &[&str] = &["alloc", "resize", "free"];
+
+/// The largest allocation in bytes. Anything bigger, or a negative count, traps.
+const MAXIMUM_ALLOCATION: i64 = 0x7fff_0000;
+
+fn value_type(ir_type: IrType) -> Option<u8> {
+    match ir_type {
+        // Addresses are 32 bits wide in wasm32.
+        IrType::I32 | IrType::U32 | IrType::Bool | IrType::Pointer => Some(I32),
+        IrType::F32 => Some(F32),
+        IrType::Void => None,
+    }
+}
+
+fn unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(byte);
+
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+
+        value >>= 7;
+
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+
+        if done {
+            bytes.push(byte);
+
+            return;
+        }
+
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn name(bytes: &mut Vec<u8>, text: &str) {
+    unsigned(bytes, text.len() as u64);
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn section(output: &mut Vec<u8>, id: u8, count: usize, contents: Vec<u8>) {
+    let mut body: Vec<u8> = Vec::new();
+
+    unsigned(&mut body, count as u64);
+    body.extend(contents);
+
+    output.push(id);
+    unsigned(output, body.len() as u64);
+    output.extend(body);
+}
+
+fn constant(bytes: &mut Vec<u8>, constant: &Constant) {
+    match constant {
+        Constant::Integer(value) => {
+            bytes.push(I32_CONST);
+            signed(bytes, *value as u32 as i32 as i64);
+        }
+        Constant::Float(value) => {
+            bytes.push(F32_CONST);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        Constant::Boolean(value) => {
+            bytes.push(I32_CONST);
+            signed(bytes, *value as i64);
+        }
+    }
+}
+
+/// The natural alignment of values of a type on the heap, as a power of two.
+fn alignment(element_type: IrType) -> u8 {
+    return element_type.size().trailing_zeros() as u8;
+}
+
+/// The alignment, as a power of two, and offset that loads and stores take.
+fn memory_argument(bytes: &mut Vec<u8>, alignment: u8, offset: u32) {
+    bytes.push(alignment);
+    unsigned(bytes, offset as u64);
+}
+
+fn integer(bytes: &mut Vec<u8>, value: i32) {
+    bytes.push(I32_CONST);
+    signed(bytes, value as i64);
+}
+
+/// Emits an instruction that takes an index, like `local.get` or `call`.
+fn indexed(bytes: &mut Vec<u8>, opcode: u8, index: u32) {
+    bytes.push(opcode);
+    unsigned(bytes, index as u64);
+}
+
+/// Traps unless the i64 size in bytes in a local fits in an allocation, which negative sizes don't
+/// once they're read as unsigned.
+fn check_size(bytes: &mut Vec<u8>, size_local: u32) {
+    indexed(bytes, LOCAL_GET, size_local);
+    bytes.push(I64_CONST);
+    signed(bytes, MAXIMUM_ALLOCATION);
+    bytes.extend([I64_GT_U, IF, EMPTY_BLOCK, UNREACHABLE, END]);
+}
+
+/// `alloc(bytes: i64) -> i32`, with locals for the size, the rounded up capacity, the previous
+/// and current free block, and the new top of the heap.
+fn heap_alloc(free_list: u32, top: u32) -> Vec<u8> {
+    let (size, capacity, previous, block, new_top) = (1, 2, 3, 4, 5);
+
+    let mut code: Vec<u8> = vec![1, 5, I32];
+
+    check_size(&mut code, 0);
+
+    indexed(&mut code, LOCAL_GET, 0);
+    code.push(I32_WRAP_I64);
+    indexed(&mut code, LOCAL_SET, size);
+
+    indexed(&mut code, LOCAL_GET, size);
+    integer(&mut code, 7);
+    code.push(I32_ADD);
+    integer(&mut code, -8);
+    code.push(I32_AND);
+    indexed(&mut code, LOCAL_SET, capacity);
+
+    indexed(&mut code, GLOBAL_GET, free_list);
+    indexed(&mut code, LOCAL_SET, block);
+
+    // Looks for a free block with room, unlinking it and breaking out of the outer block if
+    // there's one, or breaking out of the inner block to grow the heap if not.
+    code.extend([BLOCK, EMPTY_BLOCK, BLOCK, EMPTY_BLOCK, LOOP, EMPTY_BLOCK]);
+
+    indexed(&mut code, LOCAL_GET, block);
+    code.extend([I32_EQZ, BR_IF, 1]);
+
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 0);
+    indexed(&mut code, LOCAL_GET, capacity);
+    code.extend([I32_GE_U, IF, EMPTY_BLOCK]);
+
+    indexed(&mut code, LOCAL_GET, previous);
+    code.extend([I32_EQZ, IF, EMPTY_BLOCK]);
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 8);
+    indexed(&mut code, GLOBAL_SET, free_list);
+    code.push(ELSE);
+    indexed(&mut code, LOCAL_GET, previous);
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 8);
+    code.push(I32_STORE);
+    memory_argument(&mut code, 2, 8);
+    code.push(END);
+
+    code.extend([BR, 3, END]);
+
+    indexed(&mut code, LOCAL_GET, block);
+    indexed(&mut code, LOCAL_SET, previous);
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 8);
+    indexed(&mut code, LOCAL_SET, block);
+    code.extend([BR, 0, END, END]);
+
+    // Takes a new block from the top of the heap, growing memory by enough pages for it.
+    indexed(&mut code, GLOBAL_GET, top);
+    indexed(&mut code, LOCAL_SET, block);
+
+    indexed(&mut code, LOCAL_GET, block);
+    indexed(&mut code, LOCAL_GET, capacity);
+    code.push(I32_ADD);
+    integer(&mut code, 8);
+    code.push(I32_ADD);
+    indexed(&mut code, LOCAL_SET, new_top);
+
+    indexed(&mut code, LOCAL_GET, new_top);
+    code.extend([MEMORY_SIZE, 0]);
+    integer(&mut code, 16);
+    code.extend([I32_SHL, I32_GT_U, IF, EMPTY_BLOCK]);
+    indexed(&mut code, LOCAL_GET, new_top);
+    code.extend([MEMORY_SIZE, 0]);
+    integer(&mut code, 16);
+    code.extend([I32_SHL, I32_SUB]);
+    integer(&mut code, 0xffff);
+    code.push(I32_ADD);
+    integer(&mut code, 16);
+    code.extend([I32_SHR_U, MEMORY_GROW, 0]);
+    integer(&mut code, -1);
+    code.extend([I32_EQ, IF, EMPTY_BLOCK, UNREACHABLE, END, END]);
+
+    indexed(&mut code, LOCAL_GET, new_top);
+    indexed(&mut code, GLOBAL_SET, top);
+
+    indexed(&mut code, LOCAL_GET, block);
+    indexed(&mut code, LOCAL_GET, capacity);
+    code.push(I32_STORE);
+    memory_argument(&mut code, 2, 0);
+    code.push(END);
+
+    // Records the size in use and zeroes it.
+    indexed(&mut code, LOCAL_GET, block);
+    indexed(&mut code, LOCAL_GET, size);
+    code.push(I32_STORE);
+    memory_argument(&mut code, 2, 4);
+
+    indexed(&mut code, LOCAL_GET, block);
+    integer(&mut code, 8);
+    code.push(I32_ADD);
+    integer(&mut code, 0);
+    indexed(&mut code, LOCAL_GET, size);
+    code.extend([BULK, MEMORY_FILL, 0]);
+
+    indexed(&mut code, LOCAL_GET, block);
+    integer(&mut code, 8);
+    code.extend([I32_ADD, END]);
+
+    return code;
+}
+
+/// `resize(pointer: i32, bytes: i64) -> i32`, with locals for the new size, the block, its old
+/// size and the block it moves to. Blocks with room are resized in place.
+fn heap_resize(alloc: u32, free: u32) -> Vec<u8> {
+    let (pointer, bytes, size, block, old_size, moved) = (0, 1, 2, 3, 4, 5);
+
+    let mut code: Vec<u8> = vec![1, 4, I32];
+
+    check_size(&mut code, bytes);
+
+    indexed(&mut code, LOCAL_GET, bytes);
+    code.push(I32_WRAP_I64);
+    indexed(&mut code, LOCAL_SET, size);
+
+    indexed(&mut code, LOCAL_GET, pointer);
+    integer(&mut code, 8);
+    code.push(I32_SUB);
+    indexed(&mut code, LOCAL_SET, block);
+
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 4);
+    indexed(&mut code, LOCAL_SET, old_size);
+
+    indexed(&mut code, LOCAL_GET, size);
+    indexed(&mut code, LOCAL_GET, block);
+    code.push(I32_LOAD);
+    memory_argument(&mut code, 2, 0);
+    code.extend([I32_LE_U, IF, EMPTY_BLOCK]);
+
+    indexed(&mut code, LOCAL_GET, size);
+    indexed(&mut code, LOCAL_GET, old_size);
+    code.extend([I32_GT_U, IF, EMPTY_BLOCK]);
+    indexed(&mut code, LOCAL_GET, pointer);
+    indexed(&mut code, LOCAL_GET, old_size);
+    code.push(I32_ADD);
+    integer(&mut code, 0);
+    indexed(&mut code, LOCAL_GET, size);
+    indexed(&mut code, LOCAL_GET, old_size);
+    code.extend([I32_SUB, BULK, MEMORY_FILL, 0, END]);
+
+    indexed(&mut code, LOCAL_GET, block);
+    indexed(&mut code, LOCAL_GET, size);
+    code.push(I32_STORE);
+    memory_argument(&mut code, 2, 4);
+
+    indexed(&mut code, LOCAL_GET, pointer);
+    code.extend([RETURN, END]);
+
+    indexed(&mut code, LOCAL_GET, bytes);
+    indexed(&mut code, CALL, alloc);
+    indexed(&mut code, LOCAL_SET, moved);
+
+    indexed(&mut code, LOCAL_GET, moved);
+    indexed(&mut code, LOCAL_GET, pointer);
+    indexed(&mut code, LOCAL_GET, old_size);
+    code.extend([BULK, MEMORY_COPY, 0, 0]);
+
+    indexed(&mut code, LOCAL_GET, pointer);
+    indexed(&mut code, CALL, free);
+
+    indexed(&mut code, LOCAL_GET, moved);
+    code.push(END);
+
+    return code;
+}
+
+/// `free(pointer: i32)`, which puts the block at the front of the free list.
+fn heap_free(free_list: u32) -> Vec<u8> {
+    let mut code: Vec<u8> = vec![0];
+
+    indexed(&mut code, LOCAL_GET, 0);
+    indexed(&mut code, GLOBAL_GET, free_list);
+    code.push(I32_STORE);
+    memory_argument(&mut code, 2, 0);
+
+    indexed(&mut code, LOCAL_GET, 0);
+    integer(&mut code, 8);
+    code.push(I32_SUB);
+    indexed(&mut code, GLOBAL_SET, free_list);
+    code.push(END);
+
+    return code;
+}
+
+/// The constructs enclosing the code being generated, innermost last, used to work out branch
+/// depths.
+#[derive(PartialEq)]
+enum Frame {
+    IfThenElse,
+    LoopHeadedBy(BlockId),
+    BlockFollowedBy(BlockId),
+}
+
+/// Turns a function's control flow graph back into structured wasm control flow, following
+/// Ramsey's "Beyond Relooper". Each block is placed under its immediate dominator: loop headers
+/// open a `loop`, and blocks reached by more than one forward edge are placed after a `block` that
+/// their predecessors break out of.
+struct FunctionGenerator<'a> {
+    function: &'a Function,
+    function_indices: &'a HashMap<String, u32>,
+    global_indices: &'a HashMap<String, u32>,
+    /// The index of the first of `HEAP_FUNCTIONS`.
+    heap_index: u32,
+    locals: Vec<u32>,
+    order_numbers: Vec<usize>,
+    children: Vec<Vec<BlockId>>,
+    loop_headers: Vec<bool>,
+    merge_nodes: Vec<bool>,
+    code: Vec<u8>,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    fn get(&mut self, value: ValueId) {
+        self.code.push(LOCAL_GET);
+        unsigned(&mut self.code, self.locals[value.0] as u64);
+    }
+
+    fn set(&mut self, value: ValueId) {
+        self.code.push(LOCAL_SET);
+        unsigned(&mut self.code, self.locals[value.0] as u64);
+    }
+
+    fn call(&mut self, index: u32) {
+        self.code.push(CALL);
+        unsigned(&mut self.code, index as u64);
+    }
+
+    fn import(&mut self, import: &str) {
+        let index = IMPORTS
+            .iter()
+            .position(|(name, _)| *name == import)
+            .unwrap();
+
+        self.call(index as u32);
+    }
+
+    fn heap(&mut self, function: &str) {
+        let index = HEAP_FUNCTIONS
+            .iter()
+            .position(|name| *name == function)
+            .unwrap();
+
+        self.call(self.heap_index + index as u32);
+    }
+
+    /// Pushes the size in bytes of `count` values of a type, as an i64 so it can't overflow.
+    fn allocation_size(&mut self, element_type: IrType, count: ValueId) {
+        self.get(count);
+
+        if self.function.value_type(count) == IrType::U32 {
+            self.code.push(I64_EXTEND_I32_U);
+        } else {
+            self.code.push(I64_EXTEND_I32_S);
+        }
+
+        self.code.push(I64_CONST);
+        signed(&mut self.code, element_type.size() as i64);
+        self.code.push(I64_MUL);
+    }
+
+    /// Pushes the address of `pointer[index]`.
+    fn element(&mut self, element_type: IrType, pointer: ValueId, index: ValueId) {
+        self.get(pointer);
+        self.get(index);
+        integer(&mut self.code, element_type.size() as i32);
+        self.code.extend([I32_MUL, I32_ADD]);
+    }
+
+    fn binary(&mut self, operator: BinaryOperator, value_a: ValueId, value_b: ValueId) {
+        let operand_type = self.function.value_type(value_a);
+
+        // wasm has no float remainder, so it's worked out as a - trunc(a / b) * b.
+        if operator == BinaryOperator::Remainder && operand_type == IrType::F32 {
+            self.get(value_a);
+            self.get(value_a);
+            self.get(value_b);
+            self.code.extend([F32_DIV, F32_TRUNC]);
+            self.get(value_b);
+            self.code.extend([F32_MUL, F32_SUB]);
+
+            return;
+        }
+
+        self.get(value_a);
+        self.get(value_b);
+
+        let opcode = match (operand_type, operator) {
+            (IrType::F32, BinaryOperator::Add) => 0x92,
+            (IrType::F32, BinaryOperator::Subtract) => F32_SUB,
+            (IrType::F32, BinaryOperator::Multiply) => F32_MUL,
+            (IrType::F32, BinaryOperator::Divide) => F32_DIV,
+            (IrType::F32, BinaryOperator::Equal) => 0x5b,
+            (IrType::F32, BinaryOperator::NotEqual) => 0x5c,
+            (IrType::F32, BinaryOperator::LessThan) => 0x5d,
+            (IrType::F32, BinaryOperator::GreaterThan) => 0x5e,
+            (IrType::F32, BinaryOperator::LessThanOrEqual) => 0x5f,
+            (IrType::F32, BinaryOperator::GreaterThanOrEqual) => 0x60,
+            (_, BinaryOperator::Equal) => 0x46,
+            (_, BinaryOperator::NotEqual) => 0x47,
+            (IrType::U32, BinaryOperator::LessThan) => 0x49,
+            (_, BinaryOperator::LessThan) => 0x48,
+            (IrType::U32, BinaryOperator::GreaterThan) => 0x4b,
+            (_, BinaryOperator::GreaterThan) => 0x4a,
+            (IrType::U32, BinaryOperator::LessThanOrEqual) => 0x4d,
+            (_, BinaryOperator::LessThanOrEqual) => 0x4c,
+            (IrType::U32, BinaryOperator::GreaterThanOrEqual) => 0x4f,
+            (_, BinaryOperator::GreaterThanOrEqual) => 0x4e,
+            (_, BinaryOperator::Add) => 0x6a,
+            (_, BinaryOperator::Subtract) => I32_SUB,
+            (_, BinaryOperator::Multiply) => 0x6c,
+            (IrType::U32, BinaryOperator::Divide) => 0x6e,
+            (_, BinaryOperator::Divide) => 0x6d,
+            (IrType::U32, BinaryOperator::Remainder) => 0x70,
+            (_, BinaryOperator::Remainder) => 0x6f,
+            (_, BinaryOperator::And) => 0x71,
+            (_, BinaryOperator::Or) => 0x72,
+        };
+
+        self.code.push(opcode);
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        match &instruction.kind {
+            InstructionKind::Constant(value) => constant(&mut self.code, value),
+            InstructionKind::Binary(operator, value_a, value_b) => {
+                self.binary(*operator, *value_a, *value_b)
+            }
+            InstructionKind::Unary(operator, value) => {
+                match (operator, self.function.value_type(*value)) {
+                    (UnaryOperator::Negate, IrType::F32) => {
+                        self.get(*value);
+                        self.code.push(F32_NEG);
+                    }
+                    (UnaryOperator::Negate, _) => {
+                        self.code.extend([I32_CONST, 0]);
+                        self.get(*value);
+                        self.code.push(I32_SUB);
+                    }
+                    (UnaryOperator::Not, IrType::Bool) => {
+                        self.get(*value);
+                        self.code.push(I32_EQZ);
+                    }
+                    (UnaryOperator::Not, _) => {
+                        self.get(*value);
+                        self.code.extend([I32_CONST, 0x7f, I32_XOR]);
+                    }
+                }
+            }
+            InstructionKind::Call(name, arguments) if name == "print" || name == "println" => {
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        self.import("print_space");
+                    }
+
+                    self.get(*argument);
+
+                    match self.function.value_type(*argument) {
+                        IrType::U32 => self.import("print_u32"),
+                        IrType::F32 => self.import("print_f32"),
+                        IrType::Bool => self.import("print_bool"),
+                        _ => self.import("print_i32"),
+                    }
+                }
+
+                if name == "println" {
+                    self.import("print_newline");
+                }
+            }
+            InstructionKind::Call(name, arguments) => {
+                for argument in arguments {
+                    self.get(*argument);
+                }
+
+                self.call(self.function_indices[name]);
+            }
+            InstructionKind::LoadGlobal(name) => {
+                self.code.push(GLOBAL_GET);
+                unsigned(&mut self.code, self.global_indices[name] as u64);
+            }
+            InstructionKind::StoreGlobal(name, value) => {
+                self.get(*value);
+                self.code.push(GLOBAL_SET);
+                unsigned(&mut self.code, self.global_indices[name] as u64);
+            }
+            InstructionKind::Alloc(element_type, count) => {
+                self.allocation_size(*element_type, *count);
+                self.heap("alloc");
+            }
+            InstructionKind::Resize(element_type, pointer, count) => {
+                self.get(*pointer);
+                self.allocation_size(*element_type, *count);
+                self.heap("resize");
+            }
+            InstructionKind::Free(pointer) => {
+                self.get(*pointer);
+                self.heap("free");
+            }
+            InstructionKind::Load(pointer, index) => {
+                let element_type = self.function.value_type(instruction.result.unwrap());
+
+                self.element(element_type, *pointer, *index);
+
+                match element_type {
+                    IrType::F32 => self.code.push(F32_LOAD),
+                    IrType::Bool => self.code.push(I32_LOAD8_U),
+                    _ => self.code.push(I32_LOAD),
+                }
+
+                memory_argument(&mut self.code, alignment(element_type), 0);
+            }
+            InstructionKind::Store(pointer, index, value) => {
+                let element_type = self.function.value_type(*value);
+
+                self.element(element_type, *pointer, *index);
+                self.get(*value);
+
+                match element_type {
+                    IrType::F32 => self.code.push(F32_STORE),
+                    IrType::Bool => self.code.push(I32_STORE8),
+                    _ => self.code.push(I32_STORE),
+                }
+
+                memory_argument(&mut self.code, alignment(element_type), 0);
+            }
+        }
+
+        if let Some(result) = instruction.result {
+            self.set(result);
+        }
+    }
+
+    fn branch_depth(&self, context: &[Frame], frame: Frame) -> u64 {
+        return context
+            .iter()
+            .rev()
+            .position(|other| *other == frame)
+            .unwrap() as u64;
+    }
+
+    /// Passes the block arguments through the target's parameter locals, then either branches to
+    /// an enclosing construct or, for a block only reached from here, places its code inline.
+    fn branch(&mut self, source: BlockId, target: &BlockCall, context: &mut Vec<Frame>) {
+        let parameters = &self.function.blocks[target.block.0].parameters;
+
+        for argument in &target.arguments {
+            self.get(*argument);
+        }
+
+        for parameter in parameters.iter().rev() {
+            self.set(*parameter);
+        }
+
+        if self.order_numbers[target.block.0] <= self.order_numbers[source.0] {
+            let depth = self.branch_depth(context, Frame::LoopHeadedBy(target.block));
+
+            self.code.push(BR);
+            unsigned(&mut self.code, depth);
+        } else if self.merge_nodes[target.block.0] {
+            let depth = self.branch_depth(context, Frame::BlockFollowedBy(target.block));
+
+            self.code.push(BR);
+            unsigned(&mut self.code, depth);
+        } else {
+            self.tree(target.block, context);
+        }
+    }
+
+    fn node_within(
+        &mut self,
+        block: BlockId,
+        merge_children: &[BlockId],
+        context: &mut Vec<Frame>,
+    ) {
+        if let Some((child, rest)) = merge_children.split_first() {
+            self.code.extend([BLOCK, EMPTY_BLOCK]);
+
+            context.push(Frame::BlockFollowedBy(*child));
+            self.node_within(block, rest, context);
+            context.pop();
+
+            self.code.push(END);
+            self.tree(*child, context);
+
+            return;
+        }
+
+        let function = self.function;
+
+        for instruction in &function.blocks[block.0].instructions {
+            self.instruction(instruction);
+        }
+
+        match &function.blocks[block.0].terminator {
+            Terminator::Jump(target) => self.branch(block, target, context),
+            Terminator::Branch(condition, then_target, else_target) => {
+                self.get(*condition);
+                self.code.extend([IF, EMPTY_BLOCK]);
+
+                context.push(Frame::IfThenElse);
+                self.branch(block, then_target, context);
+                self.code.push(ELSE);
+                self.branch(block, else_target, context);
+                context.pop();
+
+                self.code.extend([END, UNREACHABLE]);
+            }
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    self.get(*value);
+                }
+
+                self.code.push(RETURN);
+            }
+            Terminator::Unreachable => self.code.push(UNREACHABLE),
+        }
+    }
+
+    fn tree(&mut self, block: BlockId, context: &mut Vec<Frame>) {
+        let mut merge_children: Vec<BlockId> = self.children[block.0]
+            .iter()
+            .filter(|child| self.merge_nodes[child.0])
+            .copied()
+            .collect();
+
+        // The child reached last gets the outermost block, so it's placed last.
+        merge_children.sort_by_key(|child| std::cmp::Reverse(self.order_numbers[child.0]));
+
+        if self.loop_headers[block.0] {
+            self.code.extend([LOOP, EMPTY_BLOCK]);
+
+            context.push(Frame::LoopHeadedBy(block));
+            self.node_within(block, &merge_children, context);
+            context.pop();
+
+            self.code.extend([END, UNREACHABLE]);
+        } else {
+            self.node_within(block, &merge_children, context);
+        }
+    }
+
+    /// Encodes the function body, including its local declarations.
+    fn generate(mut self) -> Vec<u8> {
+        let function = self.function;
+        let parameter_count = function.parameters.len();
+
+        let mut local_types: Vec<u8> = Vec::new();
+
+        for (index, ir_type) in function.value_types.iter().enumerate() {
+            if let Some(position) = function.blocks[0]
+                .parameters
+                .iter()
+                .position(|parameter| parameter.0 == index)
+            {
+                self.locals[index] = position as u32;
+            } else if let Some(local_type) = value_type(*ir_type) {
+                self.locals[index] = (parameter_count + local_types.len()) as u32;
+                local_types.push(local_type);
+            }
+        }
+
+        let mut context: Vec<Frame> = Vec::new();
+
+        self.tree(BlockId(0), &mut context);
+        self.code.push(END);
+
+        let mut groups: Vec<(u32, u8)> = Vec::new();
+
+        for local_type in local_types {
+            match groups.last_mut() {
+                Some((count, group_type)) if *group_type == local_type => *count += 1,
+                _ => groups.push((1, local_type)),
+            }
+        }
+
+        let mut body: Vec<u8> = Vec::new();
+
+        unsigned(&mut body, groups.len() as u64);
+
+        for (count, local_type) in groups {
+            unsigned(&mut body, count as u64);
+            body.push(local_type);
+        }
+
+        body.extend(self.code);
+
+        return body;
+    }
+}
+
+fn function_generator<'a>(
+    function: &'a Function,
+    function_indices: &'a HashMap<String, u32>,
+    global_indices: &'a HashMap<String, u32>,
+    heap_index: u32,
+) -> FunctionGenerator<'a> {
+    let order = function.reverse_postorder();
+    let dominators = function.immediate_dominators();
+
+    let mut order_numbers = vec![usize::MAX; function.blocks.len()];
+
+    for (index, block) in order.iter().enumerate() {
+        order_numbers[block.0] = index;
+    }
+
+    let mut children: Vec<Vec<BlockId>> = vec![Vec::new(); function.blocks.len()];
+    let mut loop_headers = vec![false; function.blocks.len()];
+    let mut forward_edges = vec![0; function.blocks.len()];
+
+    for block in &order {
+        if let Some(dominator) = dominators[block.0]
+            && dominator != *block
+        {
+            children[dominator.0].push(*block);
+        }
+
+        for target in function.blocks[block.0].terminator.successors() {
+            if order_numbers[target.block.0] <= order_numbers[block.0] {
+                loop_headers[target.block.0] = true;
+            } else {
+                forward_edges[target.block.0] += 1;
+            }
+        }
+    }
+
+    return FunctionGenerator {
+        function,
+        function_indices,
+        global_indices,
+        heap_index,
+        locals: vec![0; function.value_types.len()],
+        order_numbers,
+        children,
+        loop_headers,
+        merge_nodes: forward_edges.iter().map(|count| *count > 1).collect(),
+        code: Vec::new(),
+    };
+}
+
+/// Whether a module uses heap memory, so it needs a memory and the allocator.
+fn uses_heap(module: &Module) -> bool {
+    return module.functions.iter().any(|function| {
+        function.blocks.iter().any(|block| {
+            block.instructions.iter().any(|instruction| {
+                matches!(
+                    instruction.kind,
+                    InstructionKind::Alloc(_, _)
+                        | InstructionKind::Resize(_, _, _)
+                        | InstructionKind::Free(_)
+                        | InstructionKind::Load(_, _)
+                        | InstructionKind::Store(_, _, _)
+                )
+            })
+        })
+    });
+}
+
+/// Encodes a verified module in the WebAssembly binary format. `main` and the functions C can call
+/// are exported under their own names, and `print` and `println` are implemented with the functions
+/// in `IMPORTS`. Externs are imported from `env` too, after `IMPORTS`. Modules that use the heap
+/// also define and export a memory, managed by the functions in `HEAP_FUNCTIONS`.
+pub fn generate(module: &Module) -> Vec<u8> {
+    let heap = uses_heap(module);
+
+    let mut signatures: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+
+    let mut signature_index = |parameters: Vec<u8>, results: Vec<u8>| -> u32 {
+        let signature = (parameters, results);
+
+        match signatures.iter().position(|other| *other == signature) {
+            Some(index) => return index as u32,
+            None => {
+                signatures.push(signature);
+
+                return (signatures.len() - 1) as u32;
+            }
+        }
+    };
+
+    let import_signatures: Vec<u32> = IMPORTS
+        .iter()
+        .map(|(_, parameter)| signature_index(parameter.iter().copied().collect(), Vec::new()))
+        .collect();
+
+    let extern_signatures: Vec<u32> = module
+        .externs
+        .iter()
+        .map(|extern_function| {
+            signature_index(
+                extern_function
+                    .parameters
+                    .iter()
+                    .filter_map(|parameter| value_type(*parameter))
+                    .collect(),
+                value_type(extern_function.return_type)
+                    .into_iter()
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let function_signatures: Vec<u32> = module
+        .functions
+        .iter()
+        .map(|function| {
+            signature_index(
+                function
+                    .parameters
+                    .iter()
+                    .filter_map(|parameter| value_type(*parameter))
+                    .collect(),
+                value_type(function.return_type).into_iter().collect(),
+            )
+        })
+        .collect();
+
+    let heap_signatures: Vec<u32> = if heap {
+        vec![
+            signature_index(vec![I64], vec![I32]),
+            signature_index(vec![I32, I64], vec![I32]),
+            signature_index(vec![I32], Vec::new()),
+        ]
+    } else {
+        Vec::new()
+    };
+
+    let mut function_indices: HashMap<String, u32> = HashMap::new();
+
+    for (index, extern_function) in module.externs.iter().enumerate() {
+        function_indices.insert(extern_function.name.clone(), (IMPORTS.len() + index) as u32);
+    }
+
+    let import_count = IMPORTS.len() + module.externs.len();
+
+    for (index, function) in module.functions.iter().enumerate() {
+        function_indices.insert(function.name.clone(), (import_count + index) as u32);
+    }
+
+    let heap_index = (import_count + module.functions.len()) as u32;
+
+    let mut global_indices: HashMap<String, u32> = HashMap::new();
+
+    for (index, global) in module.globals.iter().enumerate() {
+        global_indices.insert(global.name.clone(), index as u32);
+    }
+
+    // The allocator's globals, the free list and the top of the heap, come after the module's.
+    let (free_list, top) = (module.globals.len() as u32, module.globals.len() as u32 + 1);
+
+    let mut output: Vec<u8> = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    let mut types: Vec<u8> = Vec::new();
+
+    for (parameters, results) in &signatures {
+        types.push(0x60);
+        unsigned(&mut types, parameters.len() as u64);
+        types.extend(parameters);
+        unsigned(&mut types, results.len() as u64);
+        types.extend(results);
+    }
+
+    section(&mut output, 1, signatures.len(), types);
+
+    let mut imports: Vec<u8> = Vec::new();
+
+    for ((import, _), signature) in IMPORTS.iter().zip(&import_signatures) {
+        name(&mut imports, "env");
+        name(&mut imports, import);
+        imports.push(0x00);
+        unsigned(&mut imports, *signature as u64);
+    }
+
+    for (extern_function, signature) in module.externs.iter().zip(&extern_signatures) {
+        name(&mut imports, "env");
+        name(&mut imports, &extern_function.name);
+        imports.push(0x00);
+        unsigned(&mut imports, *signature as u64);
+    }
+
+    section(&mut output, 2, import_count, imports);
+
+    let mut functions: Vec<u8> = Vec::new();
+
+    for signature in function_signatures.iter().chain(&heap_signatures) {
+        unsigned(&mut functions, *signature as u64);
+    }
+
+    section(
+        &mut output,
+        3,
+        function_signatures.len() + heap_signatures.len(),
+        functions,
+    );
+
+    // One page to start with, which the allocator grows as it needs.
+    if heap {
+        section(&mut output, 5, 1, vec![0x00, 0x01]);
+    }
+
+    let mut globals: Vec<u8> = Vec::new();
+
+    for global in &module.globals {
+        globals.push(value_type(global.global_type).unwrap_or(I32));
+        globals.push(0x01);
+        constant(&mut globals, &global.value);
+        globals.push(END);
+    }
+
+    // The heap starts past address 0, so no allocation is ever at the null address.
+    if heap {
+        for start in [0, 8] {
+            globals.extend([I32, 0x01]);
+            integer(&mut globals, start);
+            globals.push(END);
+        }
+    }
+
+    let global_count = module.globals.len() + if heap { 2 } else { 0 };
+
+    if global_count > 0 {
+        section(&mut output, 6, global_count, globals);
+    }
+
+    let mut exports: Vec<u8> = Vec::new();
+
+    // Helpers, including the ones imported from modules like `Math`, stay internal.
+    let exported: Vec<&Function> = module
+        .functions
+        .iter()
+        .filter(|function| function.name == "main" || function.external)
+        .collect();
+
+    for function in &exported {
+        name(&mut exports, &function.name);
+        exports.push(0x00);
+        unsigned(&mut exports, function_indices[&function.name] as u64);
+    }
+
+    if heap {
+        name(&mut exports, "memory");
+        exports.extend([0x02, 0x00]);
+    }
+
+    section(&mut output, 7, exported.len() + heap as usize, exports);
+
+    let mut code: Vec<u8> = Vec::new();
+
+    for function in &module.functions {
+        let body =
+            function_generator(function, &function_indices, &global_indices, heap_index).generate();
+
+        unsigned(&mut code, body.len() as u64);
+        code.extend(body);
+    }
+
+    if heap {
+        let bodies = [
+            heap_alloc(free_list, top),
+            heap_resize(heap_index, heap_index + 2),
+            heap_free(free_list),
+        ];
+
+        for body in bodies {
+            unsigned(&mut code, body.len() as u64);
+            code.extend(body);
+        }
+    }
+
+    section(
+        &mut output,
+        10,
+        module.functions.len() + heap_signatures.len(),
+        code,
+    );
+
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_lowering;
+    use crate::source_map::SourceMap;
+    use crate::wasm_validator;
+
+    /// Compiles a program to wasm and checks the module is valid.
+    fn compile(code: &str) -> wasm_validator::Module {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add(String::from("test.mt"), String::from(code));
+
+        let tree = match crate::analyze(&mut source_map, file) {
+            Ok(tree) => tree,
+            Err(diagnostics) => panic!("{}", diagnostics[0].display(&source_map)),
+        };
+
+        let module = match ir_lowering::lower(&tree) {
+            Ok(module) => module,
+            Err(diagnostic) => panic!("{}", diagnostic.display(&source_map)),
+        };
+
+        return match wasm_validator::validate(&generate(&module)) {
+            Ok(module) => module,
+            Err(error) => panic!("{}", error),
+        };
+    }
+
+    #[test]
+    fn compiles_control_flow() {
+        let module = compile(
+            "u32 collatzSteps: u32 n {
+                u32 steps = 0
+
+                forever {
+                    if n == 1 {
+                        break
+                    } else if n % 2 == 0 {
+                        n = n / 2
+                    } else {
+                        n = 3 * n + 1
+                    }
+
+                    steps = steps + 1
+                }
+
+                return steps
+            }
+
+            void main {
+                bool even = collatzSteps(27) % 2 == 0
+                println(collatzSteps(27), even && !false)
+            }",
+        );
+
+        assert_eq!(module.exports, vec!["main"]);
+    }
+
+    #[test]
+    fn compiles_numbers() {
+        compile(
+            "f32 half: f32 x {
+                f32 two = 2
+
+                return x / two - -x % two
+            }
+
+            i32 mixed: i32 a, u32 b {
+                if a <= 0 || b >= 10 {
+                    return -a * 2 % 3
+                }
+
+                return a / 2
+            }
+
+            void main {
+                print(half(3), mixed(4, 2), 7 / 2 > 3 == false)
+            }",
+        );
+    }
+
+    #[test]
+    fn compiles_the_heap() {
+        let module = compile(
+            "i32 total = 0
+
+            void main {
+                i32* values = alloc(4)
+                defer free(values)
+
+                bool* flags = alloc(2)
+                defer free(flags)
+
+                values = resize(values, 8)
+                values[7] = 23
+                flags[1] = values[7] > 0
+                total = total + values[7]
+
+                println(total, flags[1])
+            }",
+        );
+
+        assert_eq!(module.exports, vec!["main", "memory"]);
+    }
+
+    #[test]
+    fn exports_only_entry_points() {
+        let module = compile(
+            "import { max } from Math
+
+            extern \"C\" i32 abs: i32 n
+
+            extern \"C\" i32 twice: i32 n {
+                return n * 2
+            }
+
+            i32 helper: i32 n {
+                return max(n, abs(n))
+            }
+
+            void main {
+                println(twice(helper(-21)))
+            }",
+        );
+
+        assert_eq!(module.exports, vec!["twice", "main"]);
+        assert_eq!(
+            module.imports.last(),
+            Some(&(String::from("env"), String::from("abs")))
+        );
+    }
+}
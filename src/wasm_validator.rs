@@ -0,0 +1,1000 @@
+use std::collections::HashSet;
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+const F32: u8 = 0x7d;
+const F64: u8 = 0x7c;
+const EMPTY_BLOCK: u8 = 0x40;
+
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+
+/// The most 64 KiB pages a 32-bit memory can have.
+const MAXIMUM_PAGES: u64 = 65536;
+
+/// The most locals a function can declare, the same limit browsers have.
+const MAXIMUM_LOCALS: u64 = 50000;
+
+/// What a module that validated imports and exports, for tests to check.
+pub struct Module {
+    /// The module and name of every import, in order.
+    pub imports: Vec<(String, String)>,
+    pub exports: Vec<String>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// Where `bytes` starts in the whole module, so errors point at the right byte.
+    base: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        return Err(format!("{} at byte {}", message, self.base + self.position));
+    }
+
+    fn done(&self) -> bool {
+        return self.position == self.bytes.len();
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < count {
+            return self.error("Unexpected end");
+        }
+
+        let bytes = &self.bytes[self.position..self.position + count];
+
+        self.position += count;
+
+        return Ok(bytes);
+    }
+
+    /// A reader for the next `count` bytes, like the contents of a section.
+    fn sub(&mut self, count: usize) -> Result<Reader<'a>, String> {
+        let base = self.base + self.position;
+
+        return Ok(Reader {
+            bytes: self.take(count)?,
+            position: 0,
+            base,
+        });
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    /// Reads an unsigned LEB128 integer that has to fit in `bits` bits.
+    fn unsigned(&mut self, bits: u32) -> Result<u64, String> {
+        let mut value = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.byte()?;
+            let low = (byte & 0x7f) as u64;
+
+            if shift + 7 > bits && low >> (bits - shift) != 0 {
+                return self.error("Integer too large");
+            }
+
+            value |= low << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+
+            shift += 7;
+
+            if shift >= bits {
+                return self.error("Integer representation too long");
+            }
+        }
+    }
+
+    /// Reads a signed LEB128 integer that has to fit in `bits` bits.
+    fn signed(&mut self, bits: u32) -> Result<i64, String> {
+        let mut value = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.byte()?;
+            let low = (byte & 0x7f) as i64;
+
+            // The bits past the last one that fits all have to be copies of the sign bit.
+            if shift + 7 > bits {
+                let used = bits - shift;
+                let extra = low >> (used - 1);
+
+                if extra != 0 && extra != (1 << (8 - used)) - 1 {
+                    return self.error("Integer too large");
+                }
+            }
+
+            value |= low << shift;
+
+            if byte & 0x80 == 0 {
+                if shift + 7 < 64 && byte & 0x40 != 0 {
+                    value |= -1 << (shift + 7);
+                }
+
+                return Ok(value);
+            }
+
+            shift += 7;
+
+            if shift >= bits {
+                return self.error("Integer representation too long");
+            }
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        return Ok(self.unsigned(32)? as u32);
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+
+        return match std::str::from_utf8(bytes) {
+            Ok(name) => Ok(String::from(name)),
+            Err(_) => self.error("Name is not valid UTF-8"),
+        };
+    }
+
+    fn value_type(&mut self) -> Result<u8, String> {
+        let value_type = self.byte()?;
+
+        if ![I32, I64, F32, F64].contains(&value_type) {
+            return self.error(&format!("Unknown value type 0x{:02x}", value_type));
+        }
+
+        return Ok(value_type);
+    }
+
+    fn value_types(&mut self) -> Result<Vec<u8>, String> {
+        let count = self.u32()?;
+
+        return (0..count).map(|_| self.value_type()).collect();
+    }
+
+    fn index(&mut self, count: usize, kind: &str) -> Result<u32, String> {
+        let index = self.u32()?;
+
+        if index as usize >= count {
+            return self.error(&format!("Unknown {} {}", kind, index));
+        }
+
+        return Ok(index);
+    }
+
+    fn limits(&mut self) -> Result<(), String> {
+        let (minimum, maximum) = match self.byte()? {
+            0x00 => (self.unsigned(32)?, None),
+            0x01 => (self.unsigned(32)?, Some(self.unsigned(32)?)),
+            _ => return self.error("Unknown limits flag"),
+        };
+
+        if minimum > MAXIMUM_PAGES || maximum.is_some_and(|maximum| maximum > MAXIMUM_PAGES) {
+            return self.error("A memory can't be bigger than 4 GiB");
+        }
+
+        if maximum.is_some_and(|maximum| maximum < minimum) {
+            return self.error("A memory's maximum size is smaller than its minimum");
+        }
+
+        return Ok(());
+    }
+}
+
+/// Everything in the module that function bodies can refer to.
+#[derive(Default)]
+struct Context {
+    types: Vec<(Vec<u8>, Vec<u8>)>,
+    /// The signature of every function, imported ones first.
+    functions: Vec<u32>,
+    /// The type of every global and whether it's mutable, imported ones first.
+    globals: Vec<(u8, bool)>,
+    imported_globals: usize,
+    memories: usize,
+}
+
+impl Context {
+    fn signature(&self, function: u32) -> &(Vec<u8>, Vec<u8>) {
+        return &self.types[self.functions[function as usize] as usize];
+    }
+}
+
+/// The operand types and result type of a numeric instruction.
+fn numeric(opcode: u8) -> Option<(&'static [u8], u8)> {
+    return Some(match opcode {
+        0x45 => (&[I32], I32),
+        0x46..=0x4f => (&[I32, I32], I32),
+        0x50 => (&[I64], I32),
+        0x51..=0x5a => (&[I64, I64], I32),
+        0x5b..=0x60 => (&[F32, F32], I32),
+        0x61..=0x66 => (&[F64, F64], I32),
+        0x67..=0x69 => (&[I32], I32),
+        0x6a..=0x78 => (&[I32, I32], I32),
+        0x79..=0x7b => (&[I64], I64),
+        0x7c..=0x8a => (&[I64, I64], I64),
+        0x8b..=0x91 => (&[F32], F32),
+        0x92..=0x98 => (&[F32, F32], F32),
+        0x99..=0x9f => (&[F64], F64),
+        0xa0..=0xa6 => (&[F64, F64], F64),
+        0xa7 => (&[I64], I32),
+        0xa8 | 0xa9 | 0xbc => (&[F32], I32),
+        0xaa | 0xab => (&[F64], I32),
+        0xac | 0xad => (&[I32], I64),
+        0xae | 0xaf => (&[F32], I64),
+        0xb0 | 0xb1 | 0xbd => (&[F64], I64),
+        0xb2 | 0xb3 | 0xbe => (&[I32], F32),
+        0xb4 | 0xb5 => (&[I64], F32),
+        0xb6 => (&[F64], F32),
+        0xb7 | 0xb8 => (&[I32], F64),
+        0xb9 | 0xba | 0xbf => (&[I64], F64),
+        0xbb => (&[F32], F64),
+        _ => return None,
+    });
+}
+
+/// The value type of a load or store, and its natural alignment as a power of two.
+fn memory_access(opcode: u8) -> (u8, u64) {
+    match opcode {
+        0x28 | 0x36 => return (I32, 2),
+        0x29 | 0x37 => return (I64, 3),
+        0x2a | 0x38 => return (F32, 2),
+        0x2b | 0x39 => return (F64, 3),
+        0x2c | 0x2d | 0x3a => return (I32, 0),
+        0x2e | 0x2f | 0x3b => return (I32, 1),
+        0x30 | 0x31 | 0x3c => return (I64, 0),
+        0x32 | 0x33 | 0x3d => return (I64, 1),
+        _ => return (I64, 2),
+    }
+}
+
+/// A block, loop or if being validated, or the function body itself.
+struct Frame {
+    opcode: u8,
+    results: Vec<u8>,
+    /// How many values were on the stack when it started.
+    height: usize,
+    /// Whether the rest of it can't be reached, after which any value can be popped.
+    unreachable: bool,
+}
+
+/// Checks a function body with the algorithm in the appendix of the WebAssembly spec. `None` on
+/// the stack is a value of unknown type, which unreachable code can pop.
+struct FunctionValidator<'a> {
+    reader: Reader<'a>,
+    context: &'a Context,
+    locals: Vec<u8>,
+    values: Vec<Option<u8>>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> FunctionValidator<'a> {
+    fn pop(&mut self) -> Result<Option<u8>, String> {
+        let frame = self.frames.last().unwrap();
+
+        if self.values.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+
+            return self.reader.error("Popped from an empty stack");
+        }
+
+        return Ok(self.values.pop().unwrap());
+    }
+
+    fn pop_expected(&mut self, expected: u8) -> Result<(), String> {
+        let actual = self.pop()?;
+
+        if actual.is_some_and(|actual| actual != expected) {
+            return self.reader.error(&format!(
+                "Expected a value of type 0x{:02x} but found 0x{:02x}",
+                expected,
+                actual.unwrap()
+            ));
+        }
+
+        return Ok(());
+    }
+
+    fn pop_all(&mut self, types: &[u8]) -> Result<(), String> {
+        for value_type in types.iter().rev() {
+            self.pop_expected(*value_type)?;
+        }
+
+        return Ok(());
+    }
+
+    fn push_all(&mut self, types: &[u8]) {
+        self.values
+            .extend(types.iter().map(|value_type| Some(*value_type)));
+    }
+
+    fn push_frame(&mut self, opcode: u8, results: Vec<u8>) {
+        self.frames.push(Frame {
+            opcode,
+            results,
+            height: self.values.len(),
+            unreachable: false,
+        });
+    }
+
+    fn pop_frame(&mut self) -> Result<Frame, String> {
+        let results = self.frames.last().unwrap().results.clone();
+
+        self.pop_all(&results)?;
+
+        if self.values.len() != self.frames.last().unwrap().height {
+            return self
+                .reader
+                .error("Values left on the stack at the end of a block");
+        }
+
+        return Ok(self.frames.pop().unwrap());
+    }
+
+    /// The types a branch to the frame `depth` levels out has to pass, nothing for a loop since
+    /// branching to one starts it again.
+    fn label_types(&mut self) -> Result<Vec<u8>, String> {
+        let depth = self.reader.u32()? as usize;
+
+        if depth >= self.frames.len() {
+            return self.reader.error(&format!("Unknown label {}", depth));
+        }
+
+        let frame = &self.frames[self.frames.len() - 1 - depth];
+
+        return match frame.opcode {
+            LOOP => Ok(Vec::new()),
+            _ => Ok(frame.results.clone()),
+        };
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+
+        self.values.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn block_type(&mut self) -> Result<Vec<u8>, String> {
+        let block_type = self.reader.byte()?;
+
+        return match block_type {
+            EMPTY_BLOCK => Ok(Vec::new()),
+            I32 | I64 | F32 | F64 => Ok(vec![block_type]),
+            _ => self.reader.error("Unsupported block type"),
+        };
+    }
+
+    fn local(&mut self) -> Result<u8, String> {
+        let index = self.reader.index(self.locals.len(), "local")?;
+
+        return Ok(self.locals[index as usize]);
+    }
+
+    fn memory(&mut self) -> Result<(), String> {
+        if self.context.memories == 0 {
+            return self.reader.error("Memory used without a memory");
+        }
+
+        return Ok(());
+    }
+
+    fn memory_argument(&mut self, opcode: u8) -> Result<u8, String> {
+        self.memory()?;
+
+        let (value_type, natural) = memory_access(opcode);
+
+        if self.reader.unsigned(32)? > natural {
+            return self.reader.error("Alignment larger than natural");
+        }
+
+        self.reader.u32()?;
+
+        return Ok(value_type);
+    }
+
+    fn reserved(&mut self) -> Result<(), String> {
+        if self.reader.byte()? != 0 {
+            return self.reader.error("Expected a zero byte");
+        }
+
+        return Ok(());
+    }
+
+    fn validate(mut self, results: Vec<u8>) -> Result<(), String> {
+        self.push_frame(0, results);
+
+        loop {
+            let opcode = self.reader.byte()?;
+
+            match opcode {
+                0x00 => self.set_unreachable(),
+                0x01 => {}
+                BLOCK | LOOP => {
+                    let results = self.block_type()?;
+
+                    self.push_frame(opcode, results);
+                }
+                IF => {
+                    let results = self.block_type()?;
+
+                    self.pop_expected(I32)?;
+                    self.push_frame(IF, results);
+                }
+                ELSE => {
+                    let frame = self.pop_frame()?;
+
+                    if frame.opcode != IF {
+                        return self.reader.error("Else without an if");
+                    }
+
+                    self.push_frame(ELSE, frame.results);
+                }
+                END => {
+                    let frame = self.pop_frame()?;
+
+                    // Without an else, the missing branch passes nothing along.
+                    if frame.opcode == IF && !frame.results.is_empty() {
+                        return self.reader.error("An if with results needs an else");
+                    }
+
+                    self.push_all(&frame.results);
+
+                    if self.frames.is_empty() {
+                        if !self.reader.done() {
+                            return self.reader.error("Code after the end of the function");
+                        }
+
+                        return Ok(());
+                    }
+                }
+                0x0c => {
+                    let types = self.label_types()?;
+
+                    self.pop_all(&types)?;
+                    self.set_unreachable();
+                }
+                0x0d => {
+                    let types = self.label_types()?;
+
+                    self.pop_expected(I32)?;
+                    self.pop_all(&types)?;
+                    self.push_all(&types);
+                }
+                0x0e => {
+                    let count = self.reader.u32()?;
+                    let mut targets = Vec::new();
+
+                    for _ in 0..=count {
+                        targets.push(self.label_types()?);
+                    }
+
+                    let types = targets.pop().unwrap();
+
+                    if targets.iter().any(|target| *target != types) {
+                        return self
+                            .reader
+                            .error("Branch table targets take different types");
+                    }
+
+                    self.pop_expected(I32)?;
+                    self.pop_all(&types)?;
+                    self.set_unreachable();
+                }
+                0x0f => {
+                    let results = self.frames[0].results.clone();
+
+                    self.pop_all(&results)?;
+                    self.set_unreachable();
+                }
+                0x10 => {
+                    let function = self
+                        .reader
+                        .index(self.context.functions.len(), "function")?;
+                    let (parameters, results) = self.context.signature(function);
+
+                    self.pop_all(parameters)?;
+                    self.push_all(results);
+                }
+                0x1a => {
+                    self.pop()?;
+                }
+                0x1b => {
+                    self.pop_expected(I32)?;
+
+                    let first = self.pop()?;
+                    let second = self.pop()?;
+
+                    if first.is_some() && second.is_some() && first != second {
+                        return self
+                            .reader
+                            .error("Select between values of different types");
+                    }
+
+                    self.values.push(first.or(second));
+                }
+                0x20 => {
+                    let local_type = self.local()?;
+
+                    self.values.push(Some(local_type));
+                }
+                0x21 => {
+                    let local_type = self.local()?;
+
+                    self.pop_expected(local_type)?;
+                }
+                0x22 => {
+                    let local_type = self.local()?;
+
+                    self.pop_expected(local_type)?;
+                    self.values.push(Some(local_type));
+                }
+                0x23 => {
+                    let global = self.reader.index(self.context.globals.len(), "global")?;
+
+                    self.values
+                        .push(Some(self.context.globals[global as usize].0));
+                }
+                0x24 => {
+                    let global = self.reader.index(self.context.globals.len(), "global")?;
+                    let (global_type, mutable) = self.context.globals[global as usize];
+
+                    if !mutable {
+                        return self.reader.error("Set an immutable global");
+                    }
+
+                    self.pop_expected(global_type)?;
+                }
+                0x28..=0x35 => {
+                    let value_type = self.memory_argument(opcode)?;
+
+                    self.pop_expected(I32)?;
+                    self.values.push(Some(value_type));
+                }
+                0x36..=0x3e => {
+                    let value_type = self.memory_argument(opcode)?;
+
+                    self.pop_expected(value_type)?;
+                    self.pop_expected(I32)?;
+                }
+                0x3f => {
+                    self.reserved()?;
+                    self.memory()?;
+                    self.values.push(Some(I32));
+                }
+                0x40 => {
+                    self.reserved()?;
+                    self.memory()?;
+                    self.pop_expected(I32)?;
+                    self.values.push(Some(I32));
+                }
+                0x41 => {
+                    self.reader.signed(32)?;
+                    self.values.push(Some(I32));
+                }
+                0x42 => {
+                    self.reader.signed(64)?;
+                    self.values.push(Some(I64));
+                }
+                0x43 => {
+                    self.reader.take(4)?;
+                    self.values.push(Some(F32));
+                }
+                0x44 => {
+                    self.reader.take(8)?;
+                    self.values.push(Some(F64));
+                }
+                // memory.copy and memory.fill, from bulk memory.
+                0xfc => {
+                    match self.reader.u32()? {
+                        10 => {
+                            self.reserved()?;
+                            self.reserved()?;
+                        }
+                        11 => self.reserved()?,
+                        _ => return self.reader.error("Unknown 0xfc instruction"),
+                    }
+
+                    self.memory()?;
+                    self.pop_all(&[I32, I32, I32])?;
+                }
+                _ => {
+                    let Some((operands, result)) = numeric(opcode) else {
+                        return self
+                            .reader
+                            .error(&format!("Unknown opcode 0x{:02x}", opcode));
+                    };
+
+                    self.pop_all(operands)?;
+                    self.values.push(Some(result));
+                }
+            }
+        }
+    }
+}
+
+/// Reads the constant expression that initializes a global, which has to be a constant or an
+/// imported immutable global of the right type.
+fn constant_expression(reader: &mut Reader, context: &Context, expected: u8) -> Result<(), String> {
+    let value_type = match reader.byte()? {
+        0x41 => {
+            reader.signed(32)?;
+            I32
+        }
+        0x42 => {
+            reader.signed(64)?;
+            I64
+        }
+        0x43 => {
+            reader.take(4)?;
+            F32
+        }
+        0x44 => {
+            reader.take(8)?;
+            F64
+        }
+        0x23 => {
+            let global = reader.index(context.imported_globals, "imported global")?;
+            let (global_type, mutable) = context.globals[global as usize];
+
+            if mutable {
+                return reader.error("A constant expression read a mutable global");
+            }
+
+            global_type
+        }
+        _ => return reader.error("Not a constant expression"),
+    };
+
+    if value_type != expected {
+        return reader.error("A global's initial value has the wrong type");
+    }
+
+    if reader.byte()? != END {
+        return reader.error("Expected the end of a constant expression");
+    }
+
+    return Ok(());
+}
+
+/// Decodes a module in the WebAssembly binary format and checks that it's valid. This covers
+/// every section and instruction of the first version of WebAssembly except tables and data, plus
+/// `memory.copy` and `memory.fill`, which is everything the wasm backend writes.
+pub fn validate(bytes: &[u8]) -> Result<Module, String> {
+    let mut reader = Reader {
+        bytes,
+        position: 0,
+        base: 0,
+    };
+
+    if reader.take(4)? != b"\0asm" {
+        return reader.error("Missing the \\0asm header");
+    }
+
+    if reader.take(4)? != [1, 0, 0, 0] {
+        return reader.error("Only version 1 is supported");
+    }
+
+    let mut context = Context::default();
+    let mut module = Module {
+        imports: Vec::new(),
+        exports: Vec::new(),
+    };
+
+    let mut imported_functions = 0;
+    let mut previous_section = 0;
+    let mut code_count = 0;
+
+    while !reader.done() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let mut section = reader.sub(size)?;
+
+        // Custom sections can go anywhere and don't change what the module means.
+        if id == 0 {
+            section.name()?;
+
+            continue;
+        }
+
+        if id <= previous_section {
+            return section.error(&format!("Section {} is out of order", id));
+        }
+
+        previous_section = id;
+
+        match id {
+            1 => {
+                for _ in 0..section.u32()? {
+                    if section.byte()? != 0x60 {
+                        return section.error("Expected a function type");
+                    }
+
+                    let parameters = section.value_types()?;
+                    let results = section.value_types()?;
+
+                    context.types.push((parameters, results));
+                }
+            }
+            2 => {
+                for _ in 0..section.u32()? {
+                    let import = (section.name()?, section.name()?);
+
+                    match section.byte()? {
+                        0x00 => {
+                            let signature = section.index(context.types.len(), "type")?;
+
+                            context.functions.push(signature);
+                            imported_functions += 1;
+                        }
+                        0x02 => {
+                            section.limits()?;
+                            context.memories += 1;
+                        }
+                        0x03 => {
+                            let global_type = section.value_type()?;
+                            let mutable = section.byte()? == 0x01;
+
+                            context.globals.push((global_type, mutable));
+                            context.imported_globals += 1;
+                        }
+                        _ => return section.error("Unsupported import kind"),
+                    }
+
+                    module.imports.push(import);
+                }
+            }
+            3 => {
+                for _ in 0..section.u32()? {
+                    let signature = section.index(context.types.len(), "type")?;
+
+                    context.functions.push(signature);
+                }
+            }
+            5 => {
+                for _ in 0..section.u32()? {
+                    section.limits()?;
+                    context.memories += 1;
+                }
+            }
+            6 => {
+                for _ in 0..section.u32()? {
+                    let global_type = section.value_type()?;
+
+                    let mutable = match section.byte()? {
+                        0x00 => false,
+                        0x01 => true,
+                        _ => return section.error("Unknown mutability"),
+                    };
+
+                    constant_expression(&mut section, &context, global_type)?;
+                    context.globals.push((global_type, mutable));
+                }
+            }
+            7 => {
+                let mut names = HashSet::new();
+
+                for _ in 0..section.u32()? {
+                    let name = section.name()?;
+
+                    if !names.insert(name.clone()) {
+                        return section.error(&format!("{} is exported twice", name));
+                    }
+
+                    match section.byte()? {
+                        0x00 => section.index(context.functions.len(), "function")?,
+                        0x02 => section.index(context.memories, "memory")?,
+                        0x03 => section.index(context.globals.len(), "global")?,
+                        _ => return section.error("Unsupported export kind"),
+                    };
+
+                    module.exports.push(name);
+                }
+            }
+            8 => {
+                let function = section.index(context.functions.len(), "function")?;
+
+                if *context.signature(function) != (Vec::new(), Vec::new()) {
+                    return section.error("The start function takes or returns values");
+                }
+            }
+            10 => {
+                code_count = section.u32()? as usize;
+
+                if code_count != context.functions.len() - imported_functions {
+                    return section.error("The code and function sections have different counts");
+                }
+
+                for function in imported_functions..context.functions.len() {
+                    let size = section.u32()? as usize;
+                    let mut body = section.sub(size)?;
+
+                    let (parameters, results) = context.signature(function as u32).clone();
+                    let mut locals = parameters;
+
+                    for _ in 0..body.u32()? {
+                        let count = body.u32()? as u64;
+                        let local_type = body.value_type()?;
+
+                        if locals.len() as u64 + count > MAXIMUM_LOCALS {
+                            return body.error("Too many locals");
+                        }
+
+                        locals.extend(std::iter::repeat_n(local_type, count as usize));
+                    }
+
+                    let validator = FunctionValidator {
+                        reader: body,
+                        context: &context,
+                        locals,
+                        values: Vec::new(),
+                        frames: Vec::new(),
+                    };
+
+                    validator.validate(results)?;
+                }
+            }
+            _ => return section.error(&format!("Unsupported section {}", id)),
+        }
+
+        if !section.done() {
+            return section.error(&format!("Section {} is longer than its contents", id));
+        }
+    }
+
+    if code_count != context.functions.len() - imported_functions {
+        return reader.error("Functions without code");
+    }
+
+    if context.memories > 1 {
+        return reader.error("More than one memory");
+    }
+
+    return Ok(module);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: u8 = 1;
+    const FUNCTIONS: u8 = 3;
+    const EXPORTS: u8 = 7;
+    const CODE: u8 = 10;
+
+    fn encode(sections: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+
+        for (id, contents) in sections {
+            bytes.extend([*id, contents.len() as u8]);
+            bytes.extend(contents);
+        }
+
+        return bytes;
+    }
+
+    /// The sections of a module that exports one function of type `[i32] -> [i32]` as `f`, with
+    /// the given body, which starts with its local declarations.
+    fn sections(body: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut code = vec![0x01, body.len() as u8];
+
+        code.extend(body);
+
+        return vec![
+            (TYPES, vec![0x01, 0x60, 0x01, I32, 0x01, I32]),
+            (FUNCTIONS, vec![0x01, 0x00]),
+            (EXPORTS, vec![0x01, 0x01, b'f', 0x00, 0x00]),
+            (CODE, code),
+        ];
+    }
+
+    fn assert_rejected(sections: &[(u8, Vec<u8>)], message: &str) {
+        match validate(&encode(sections)) {
+            Ok(_) => panic!("expected an error containing {:?}", message),
+            Err(error) => assert!(error.contains(message), "{:?}", error),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_module() {
+        // Doubles its parameter until it's over 100, then returns it.
+        let module = validate(&encode(&sections(&[
+            0x00,
+            BLOCK,
+            EMPTY_BLOCK,
+            LOOP,
+            EMPTY_BLOCK,
+            0x20,
+            0x00,
+            0x41,
+            0xe4,
+            0x00,
+            0x4a,
+            0x0d,
+            0x01,
+            0x20,
+            0x00,
+            0x41,
+            0x02,
+            0x6c,
+            0x21,
+            0x00,
+            0x0c,
+            0x00,
+            END,
+            END,
+            0x20,
+            0x00,
+            END,
+        ])))
+        .unwrap();
+
+        assert!(module.imports.is_empty());
+        assert_eq!(module.exports, vec!["f"]);
+    }
+
+    #[test]
+    fn accepts_unreachable_code() {
+        validate(&encode(&sections(&[0x00, 0x00, 0x6a, END]))).unwrap();
+        validate(&encode(&sections(&[0x00, 0x20, 0x00, 0x0f, 0x1a, END]))).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_bodies() {
+        let assert_body_rejected = |body: &[u8], message| assert_rejected(&sections(body), message);
+
+        assert_body_rejected(
+            &[0x00, 0x43, 0, 0, 0, 0, END],
+            "Expected a value of type 0x7f",
+        );
+        assert_body_rejected(
+            &[0x00, 0x20, 0x00, 0x20, 0x00, END],
+            "Values left on the stack",
+        );
+        assert_body_rejected(&[0x00, 0x6a, END], "Popped from an empty stack");
+        assert_body_rejected(&[0x00, 0x20, 0x01, END], "Unknown local 1");
+        assert_body_rejected(&[0x00, 0x20, 0x00, 0x0c, 0x01, END], "Unknown label 1");
+        assert_body_rejected(&[0x00, 0x10, 0x01, END], "Unknown function 1");
+        assert_body_rejected(
+            &[0x00, 0x41, 0x00, 0x28, 0x02, 0x00, END],
+            "without a memory",
+        );
+        assert_body_rejected(&[0x00, 0x20, 0x00], "Unexpected end");
+        assert_body_rejected(&[0x00, 0x20, 0x00, END, END], "Code after the end");
+        assert_body_rejected(
+            &[0x00, 0x20, 0x00, IF, I32, 0x20, 0x00, END, END],
+            "needs an else",
+        );
+        assert_body_rejected(&[0x00, 0x20, 0x00, 0xff, END], "Unknown opcode 0xff");
+    }
+
+    #[test]
+    fn rejects_invalid_modules() {
+        assert!(validate(b"\0asm\x02\0\0\0").is_err());
+
+        let mut module = sections(&[0x00, 0x20, 0x00, END]);
+
+        module.swap(2, 3);
+        assert_rejected(&module, "Section 7 is out of order");
+
+        module.remove(2);
+        assert_rejected(&module, "Functions without code");
+
+        let mut module = sections(&[0x00, 0x20, 0x00, END]);
+
+        module[2].1.extend([0x01, b'f', 0x00, 0x00]);
+        module[2].1[0] = 0x02;
+        assert_rejected(&module, "f is exported twice");
+    }
+}
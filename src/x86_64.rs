@@ -0,0 +1,845 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Register {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R12 = 12,
+    R13,
+    R14,
+    R15,
+}
+
+const REGISTER_NAMES: &[[&str; 3]] = &[
+    ["al", "eax", "rax"],
+    ["cl", "ecx", "rcx"],
+    ["dl", "edx", "rdx"],
+    ["bl", "ebx", "rbx"],
+    ["spl", "esp", "rsp"],
+    ["bpl", "ebp", "rbp"],
+    ["sil", "esi", "rsi"],
+    ["dil", "edi", "rdi"],
+    ["r8b", "r8d", "r8"],
+    ["r9b", "r9d", "r9"],
+    ["r10b", "r10d", "r10"],
+    ["r11b", "r11d", "r11"],
+    ["r12b", "r12d", "r12"],
+    ["r13b", "r13d", "r13"],
+    ["r14b", "r14d", "r14"],
+    ["r15b", "r15d", "r15"],
+];
+
+impl Register {
+    /// The register's number in instruction encodings.
+    pub fn number(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn name(&self, size: Size) -> &'static str {
+        return REGISTER_NAMES[*self as usize][size as usize];
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Size {
+    Byte,
+    Dword,
+    Qword,
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Size::Byte => write!(f, "BYTE PTR"),
+            Size::Dword => write!(f, "DWORD PTR"),
+            Size::Qword => write!(f, "QWORD PTR"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Operand {
+    Register(Register, Size),
+    Xmm(u8),
+    /// `[base + offset]`.
+    Memory(Register, i32, Size),
+    /// `[rip + symbol]`, for globals and constant data.
+    Symbol(String, Size),
+    Immediate(i64),
+}
+
+impl Operand {
+    pub fn byte(register: Register) -> Operand {
+        return Operand::Register(register, Size::Byte);
+    }
+
+    pub fn dword(register: Register) -> Operand {
+        return Operand::Register(register, Size::Dword);
+    }
+
+    pub fn qword(register: Register) -> Operand {
+        return Operand::Register(register, Size::Qword);
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(register, size) => write!(f, "{}", register.name(*size)),
+            Operand::Xmm(number) => write!(f, "xmm{}", number),
+            Operand::Memory(base, offset, size) if *offset < 0 => {
+                write!(f, "{} [{}-{}]", size, base.name(Size::Qword), -offset)
+            }
+            Operand::Memory(base, offset, size) => {
+                write!(f, "{} [{}+{}]", size, base.name(Size::Qword), offset)
+            }
+            Operand::Symbol(symbol, size) => write!(f, "{} [rip+{}]", size, symbol),
+            Operand::Immediate(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Condition {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Below,
+    BelowOrEqual,
+    Above,
+    AboveOrEqual,
+    Parity,
+    NoParity,
+}
+
+impl Condition {
+    /// The low nibble of the `jcc`, `setcc` and `cmovcc` opcodes.
+    fn code(&self) -> u8 {
+        match self {
+            Condition::Below => 0x2,
+            Condition::AboveOrEqual => 0x3,
+            Condition::Equal => 0x4,
+            Condition::NotEqual => 0x5,
+            Condition::BelowOrEqual => 0x6,
+            Condition::Above => 0x7,
+            Condition::Parity => 0xA,
+            Condition::NoParity => 0xB,
+            Condition::Less => 0xC,
+            Condition::GreaterOrEqual => 0xD,
+            Condition::LessOrEqual => 0xE,
+            Condition::Greater => 0xF,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Equal => write!(f, "e"),
+            Condition::NotEqual => write!(f, "ne"),
+            Condition::Less => write!(f, "l"),
+            Condition::LessOrEqual => write!(f, "le"),
+            Condition::Greater => write!(f, "g"),
+            Condition::GreaterOrEqual => write!(f, "ge"),
+            Condition::Below => write!(f, "b"),
+            Condition::BelowOrEqual => write!(f, "be"),
+            Condition::Above => write!(f, "a"),
+            Condition::AboveOrEqual => write!(f, "ae"),
+            Condition::Parity => write!(f, "p"),
+            Condition::NoParity => write!(f, "np"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArithmeticOperation {
+    Add,
+    Or,
+    And,
+    Subtract,
+    Xor,
+    Compare,
+}
+
+impl ArithmeticOperation {
+    /// The operation's number, used both in the opcode and in the ModRM reg field.
+    fn code(&self) -> u8 {
+        match self {
+            ArithmeticOperation::Add => 0,
+            ArithmeticOperation::Or => 1,
+            ArithmeticOperation::And => 4,
+            ArithmeticOperation::Subtract => 5,
+            ArithmeticOperation::Xor => 6,
+            ArithmeticOperation::Compare => 7,
+        }
+    }
+}
+
+impl fmt::Display for ArithmeticOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArithmeticOperation::Add => write!(f, "add"),
+            ArithmeticOperation::Or => write!(f, "or"),
+            ArithmeticOperation::And => write!(f, "and"),
+            ArithmeticOperation::Subtract => write!(f, "sub"),
+            ArithmeticOperation::Xor => write!(f, "xor"),
+            ArithmeticOperation::Compare => write!(f, "cmp"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScalarOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl ScalarOperation {
+    /// The second opcode byte after `F3 0F`.
+    fn code(&self) -> u8 {
+        match self {
+            ScalarOperation::Add => 0x58,
+            ScalarOperation::Subtract => 0x5C,
+            ScalarOperation::Multiply => 0x59,
+            ScalarOperation::Divide => 0x5E,
+        }
+    }
+}
+
+impl fmt::Display for ScalarOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScalarOperation::Add => write!(f, "addss"),
+            ScalarOperation::Subtract => write!(f, "subss"),
+            ScalarOperation::Multiply => write!(f, "mulss"),
+            ScalarOperation::Divide => write!(f, "divss"),
+        }
+    }
+}
+
+/// The subset of x86-64 the backend generates. Each variant maps to one assembler instruction, so
+/// the same list can be printed as text or encoded directly.
+#[derive(Clone, PartialEq)]
+pub enum Instruction {
+    Label(String),
+    /// Marks the source file, numbered from 1, and the line and column, both counted from zero, of
+    /// the instructions that follow.
+    Location(usize, usize, usize),
+    Mov(Operand, Operand),
+    /// Zero extends a byte, from a register or memory, into a 32 bit register.
+    Movzx(Register, Operand),
+    /// Sign extends a 32 bit register into a 64 bit one.
+    Movsxd(Register, Register),
+    Lea(Register, Operand),
+    Arithmetic(ArithmeticOperation, Operand, Operand),
+    /// Multiplies a register by the operand, at the operand's size.
+    Imul(Register, Operand),
+    Test(Register, Register),
+    Neg(Register),
+    Not(Register),
+    Cdq,
+    Idiv(Register),
+    Div(Register),
+    Set(Condition, Register),
+    Cmov(Condition, Register, Register),
+    Jmp(String),
+    Jcc(Condition, String),
+    /// Calls a function, through the procedure linkage table if it's defined outside the module.
+    Call(String, bool),
+    Ret,
+    Push(Register),
+    Pop(Register),
+    Movss(Operand, Operand),
+    Scalar(ScalarOperation, u8, Operand),
+    Ucomiss(u8, Operand),
+    Cvtss2sd(u8, u8),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Label(label) => write!(f, "{}:", label),
+            Instruction::Location(file, line, column) => {
+                write!(f, "    .loc {} {} {}", file, line + 1, column + 1)
+            }
+            Instruction::Mov(destination, source) => {
+                write!(f, "    mov {}, {}", destination, source)
+            }
+            Instruction::Movzx(destination, source) => {
+                write!(f, "    movzx {}, {}", destination.name(Size::Dword), source)
+            }
+            Instruction::Movsxd(destination, source) => write!(
+                f,
+                "    movsxd {}, {}",
+                destination.name(Size::Qword),
+                source.name(Size::Dword)
+            ),
+            Instruction::Lea(destination, Operand::Symbol(symbol, _)) => {
+                write!(
+                    f,
+                    "    lea {}, [rip+{}]",
+                    destination.name(Size::Qword),
+                    symbol
+                )
+            }
+            Instruction::Lea(destination, source) => {
+                write!(f, "    lea {}, {}", destination.name(Size::Qword), source)
+            }
+            Instruction::Arithmetic(operation, destination, source) => {
+                write!(f, "    {} {}, {}", operation, destination, source)
+            }
+            Instruction::Imul(destination, source) => write!(
+                f,
+                "    imul {}, {}",
+                destination.name(operand_size(source)),
+                source
+            ),
+            Instruction::Test(a, b) => write!(
+                f,
+                "    test {}, {}",
+                a.name(Size::Dword),
+                b.name(Size::Dword)
+            ),
+            Instruction::Neg(register) => write!(f, "    neg {}", register.name(Size::Dword)),
+            Instruction::Not(register) => write!(f, "    not {}", register.name(Size::Dword)),
+            Instruction::Cdq => write!(f, "    cdq"),
+            Instruction::Idiv(register) => write!(f, "    idiv {}", register.name(Size::Dword)),
+            Instruction::Div(register) => write!(f, "    div {}", register.name(Size::Dword)),
+            Instruction::Set(condition, register) => {
+                write!(f, "    set{} {}", condition, register.name(Size::Byte))
+            }
+            Instruction::Cmov(condition, destination, source) => write!(
+                f,
+                "    cmov{} {}, {}",
+                condition,
+                destination.name(Size::Qword),
+                source.name(Size::Qword)
+            ),
+            Instruction::Jmp(label) => write!(f, "    jmp {}", label),
+            Instruction::Jcc(condition, label) => write!(f, "    j{} {}", condition, label),
+            Instruction::Call(symbol, true) => write!(f, "    call {}@PLT", symbol),
+            Instruction::Call(symbol, false) => write!(f, "    call {}", symbol),
+            Instruction::Ret => write!(f, "    ret"),
+            Instruction::Push(register) => write!(f, "    push {}", register.name(Size::Qword)),
+            Instruction::Pop(register) => write!(f, "    pop {}", register.name(Size::Qword)),
+            Instruction::Movss(destination, source) => {
+                write!(f, "    movss {}, {}", destination, source)
+            }
+            Instruction::Scalar(operation, destination, source) => {
+                write!(f, "    {} xmm{}, {}", operation, destination, source)
+            }
+            Instruction::Ucomiss(a, b) => write!(f, "    ucomiss xmm{}, {}", a, b),
+            Instruction::Cvtss2sd(destination, source) => {
+                write!(f, "    cvtss2sd xmm{}, xmm{}", destination, source)
+            }
+        }
+    }
+}
+
+pub struct AsmFunction {
+    pub name: String,
+    pub global: bool,
+    pub instructions: Vec<Instruction>,
+}
+
+pub struct AsmModule {
+    /// The source files that location markers refer to by number, counting from 1. The first is
+    /// the module's own source, and the rest are modules it imported from.
+    pub files: Vec<String>,
+    pub functions: Vec<AsmFunction>,
+    /// Mutable 32 bit values, placed in `.data`.
+    pub data: Vec<(String, u32)>,
+    /// Null terminated strings, placed in `.rodata`.
+    pub strings: Vec<(String, String)>,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for character in text.chars() {
+        match character {
+            '\n' => escaped.push_str("\\n"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            _ => escaped.push(character),
+        }
+    }
+
+    return escaped;
+}
+
+impl fmt::Display for AsmModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "    .intel_syntax noprefix")?;
+        for (index, file) in self.files.iter().enumerate() {
+            writeln!(f, "    .file {} \"{}\"", index + 1, escape(file))?;
+        }
+
+        if !self.strings.is_empty() {
+            writeln!(f, "\n    .section .rodata")?;
+
+            for (label, text) in &self.strings {
+                writeln!(f, "{}:\n    .asciz \"{}\"", label, escape(text))?;
+            }
+        }
+
+        if !self.data.is_empty() {
+            writeln!(f, "\n    .data")?;
+
+            for (label, value) in &self.data {
+                writeln!(f, "    .p2align 2\n{}:\n    .long {}", label, value)?;
+            }
+        }
+
+        writeln!(f, "\n    .text")?;
+
+        for function in &self.functions {
+            writeln!(f)?;
+
+            if function.global {
+                writeln!(f, "    .globl {}", function.name)?;
+            }
+
+            writeln!(f, "    .type {}, @function", function.name)?;
+            writeln!(f, "{}:", function.name)?;
+
+            for instruction in &function.instructions {
+                writeln!(f, "{}", instruction)?;
+            }
+
+            writeln!(f, "    .size {}, .-{}", function.name, function.name)?;
+        }
+
+        writeln!(f, "\n    .section .note.GNU-stack,\"\",@progbits")?;
+
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RelocationKind {
+    /// A 32 bit offset from the next instruction to a symbol, for data accessed through `rip`.
+    Pc32,
+    /// A 32 bit offset to a function, through the procedure linkage table when it's in a shared
+    /// library.
+    Plt32,
+}
+
+pub struct Relocation {
+    pub offset: usize,
+    pub symbol: String,
+    pub kind: RelocationKind,
+    pub addend: i64,
+}
+
+pub struct FunctionCode {
+    pub name: String,
+    pub global: bool,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// A module's `.text` section as bytes, along with everything a linker needs to place it.
+pub struct MachineCode {
+    pub text: Vec<u8>,
+    pub functions: Vec<FunctionCode>,
+    pub relocations: Vec<Relocation>,
+    /// The offset, file, line and column of every location marker, in order.
+    pub lines: Vec<(usize, usize, usize, usize)>,
+}
+
+/// The size of a register or memory operand, taking immediates and `xmm` registers as 32 bit.
+fn operand_size(operand: &Operand) -> Size {
+    match operand {
+        Operand::Register(_, size) | Operand::Memory(_, _, size) | Operand::Symbol(_, size) => {
+            *size
+        }
+        Operand::Xmm(_) | Operand::Immediate(_) => Size::Dword,
+    }
+}
+
+fn fits_byte(value: i64) -> bool {
+    return value >= i8::MIN as i64 && value <= i8::MAX as i64;
+}
+
+struct Encoder {
+    code: Vec<u8>,
+    labels: HashMap<String, usize>,
+    /// Positions of 32 bit jump offsets, patched once every label is known.
+    jumps: Vec<(usize, String)>,
+    relocations: Vec<Relocation>,
+    lines: Vec<(usize, usize, usize, usize)>,
+}
+
+impl Encoder {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn register_operand(&mut self, opcode: u8, register: Register) {
+        if register.number() >= 8 {
+            self.bytes(&[0x41]);
+        }
+
+        self.bytes(&[opcode + (register.number() & 7)]);
+    }
+
+    /// Emits an instruction taking a ModRM byte: the mandatory prefix, a REX prefix when one is
+    /// needed, the opcode, the addressing of `operand` with `field` in the reg field, and finally
+    /// the immediate. Byte registers four through seven need an empty REX prefix to mean `spl`
+    /// through `dil` rather than `ah` through `bh`.
+    fn modrm(
+        &mut self,
+        prefix: Option<u8>,
+        wide: bool,
+        opcode: &[u8],
+        field: u8,
+        operand: &Operand,
+        immediate: &[u8],
+    ) {
+        if let Some(prefix) = prefix {
+            self.bytes(&[prefix]);
+        }
+
+        let base = match operand {
+            Operand::Register(register, _) | Operand::Memory(register, _, _) => register.number(),
+            Operand::Xmm(number) => *number,
+            Operand::Symbol(_, _) | Operand::Immediate(_) => 0,
+        };
+
+        let byte_register = |number: u8| (4..8).contains(&number);
+
+        let force = match operand {
+            Operand::Register(_, Size::Byte) => byte_register(base) || byte_register(field),
+            _ => false,
+        };
+
+        let rex = 0x40 | (wide as u8) << 3 | (field >> 3) << 2 | (base >> 3);
+
+        if rex != 0x40 || force {
+            self.bytes(&[rex]);
+        }
+
+        self.bytes(opcode);
+
+        let field = (field & 7) << 3;
+
+        match operand {
+            Operand::Register(_, _) | Operand::Xmm(_) => self.bytes(&[0xC0 | field | (base & 7)]),
+            Operand::Memory(_, offset, _) => {
+                let mode = if fits_byte(*offset as i64) {
+                    0x40
+                } else {
+                    0x80
+                };
+
+                self.bytes(&[mode | field | (base & 7)]);
+
+                if base & 7 == 4 {
+                    self.bytes(&[0x24]);
+                }
+
+                if mode == 0x40 {
+                    self.bytes(&[*offset as i8 as u8]);
+                } else {
+                    self.bytes(&offset.to_le_bytes());
+                }
+            }
+            Operand::Symbol(symbol, _) => {
+                self.bytes(&[field | 0x05]);
+
+                // The processor adds the offset to the address after the whole instruction,
+                // including any immediate that follows it.
+                self.relocations.push(Relocation {
+                    offset: self.code.len(),
+                    symbol: symbol.clone(),
+                    kind: RelocationKind::Pc32,
+                    addend: -4 - immediate.len() as i64,
+                });
+
+                self.bytes(&[0; 4]);
+            }
+            Operand::Immediate(_) => panic!("Immediate used as a memory operand"),
+        }
+
+        self.bytes(immediate);
+    }
+
+    fn jump(&mut self, opcode: &[u8], label: &str) {
+        self.bytes(opcode);
+        self.jumps.push((self.code.len(), String::from(label)));
+        self.bytes(&[0; 4]);
+    }
+
+    fn arithmetic(
+        &mut self,
+        operation: ArithmeticOperation,
+        destination: &Operand,
+        source: &Operand,
+    ) {
+        let size = match destination {
+            Operand::Register(_, size) | Operand::Memory(_, _, size) | Operand::Symbol(_, size) => {
+                *size
+            }
+            _ => panic!("Invalid arithmetic destination {}", destination),
+        };
+
+        let wide = size == Size::Qword;
+        let code = operation.code();
+
+        match source {
+            Operand::Immediate(value) if fits_byte(*value) => self.modrm(
+                None,
+                wide,
+                &[0x83],
+                code,
+                destination,
+                &[*value as i8 as u8],
+            ),
+            Operand::Immediate(value) => self.modrm(
+                None,
+                wide,
+                &[0x81],
+                code,
+                destination,
+                &(*value as i32).to_le_bytes(),
+            ),
+            Operand::Register(register, Size::Byte) => self.modrm(
+                None,
+                false,
+                &[code << 3],
+                register.number(),
+                destination,
+                &[],
+            ),
+            Operand::Register(register, _) => self.modrm(
+                None,
+                wide,
+                &[code << 3 | 1],
+                register.number(),
+                destination,
+                &[],
+            ),
+            _ => match destination {
+                Operand::Register(register, _) => {
+                    self.modrm(None, wide, &[code << 3 | 3], register.number(), source, &[])
+                }
+                _ => panic!("Invalid arithmetic operands {}, {}", destination, source),
+            },
+        }
+    }
+
+    fn mov(&mut self, destination: &Operand, source: &Operand) {
+        match (destination, source) {
+            (Operand::Register(register, Size::Dword), Operand::Immediate(value)) => {
+                self.register_operand(0xB8, *register);
+                self.bytes(&(*value as i32).to_le_bytes());
+            }
+            (_, Operand::Immediate(value)) => {
+                let wide = matches!(destination, Operand::Register(_, Size::Qword));
+
+                self.modrm(
+                    None,
+                    wide,
+                    &[0xC7],
+                    0,
+                    destination,
+                    &(*value as i32).to_le_bytes(),
+                );
+            }
+            (_, Operand::Register(register, size)) => self.modrm(
+                None,
+                *size == Size::Qword,
+                &[if *size == Size::Byte { 0x88 } else { 0x89 }],
+                register.number(),
+                destination,
+                &[],
+            ),
+            (Operand::Register(register, size), _) => self.modrm(
+                None,
+                *size == Size::Qword,
+                &[if *size == Size::Byte { 0x8A } else { 0x8B }],
+                register.number(),
+                source,
+                &[],
+            ),
+            _ => panic!("Invalid mov operands {}, {}", destination, source),
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Label(label) => {
+                self.labels.insert(label.clone(), self.code.len());
+            }
+            Instruction::Location(file, line, column) => {
+                self.lines.push((self.code.len(), *file, *line, *column));
+            }
+            Instruction::Mov(destination, source) => self.mov(destination, source),
+            Instruction::Movzx(destination, source) => self.modrm(
+                None,
+                false,
+                &[0x0F, 0xB6],
+                destination.number(),
+                source,
+                &[],
+            ),
+            Instruction::Movsxd(destination, source) => self.modrm(
+                None,
+                true,
+                &[0x63],
+                destination.number(),
+                &Operand::dword(*source),
+                &[],
+            ),
+            Instruction::Lea(destination, source) => {
+                self.modrm(None, true, &[0x8D], destination.number(), source, &[])
+            }
+            Instruction::Arithmetic(operation, destination, source) => {
+                self.arithmetic(*operation, destination, source)
+            }
+            Instruction::Imul(destination, source) => self.modrm(
+                None,
+                operand_size(source) == Size::Qword,
+                &[0x0F, 0xAF],
+                destination.number(),
+                source,
+                &[],
+            ),
+            Instruction::Test(a, b) => {
+                self.modrm(None, false, &[0x85], b.number(), &Operand::dword(*a), &[])
+            }
+            Instruction::Neg(register) => {
+                self.modrm(None, false, &[0xF7], 3, &Operand::dword(*register), &[])
+            }
+            Instruction::Not(register) => {
+                self.modrm(None, false, &[0xF7], 2, &Operand::dword(*register), &[])
+            }
+            Instruction::Cdq => self.bytes(&[0x99]),
+            Instruction::Idiv(register) => {
+                self.modrm(None, false, &[0xF7], 7, &Operand::dword(*register), &[])
+            }
+            Instruction::Div(register) => {
+                self.modrm(None, false, &[0xF7], 6, &Operand::dword(*register), &[])
+            }
+            Instruction::Set(condition, register) => self.modrm(
+                None,
+                false,
+                &[0x0F, 0x90 | condition.code()],
+                0,
+                &Operand::Register(*register, Size::Byte),
+                &[],
+            ),
+            Instruction::Cmov(condition, destination, source) => self.modrm(
+                None,
+                true,
+                &[0x0F, 0x40 | condition.code()],
+                destination.number(),
+                &Operand::qword(*source),
+                &[],
+            ),
+            Instruction::Jmp(label) => self.jump(&[0xE9], label),
+            Instruction::Jcc(condition, label) => {
+                self.jump(&[0x0F, 0x80 | condition.code()], label)
+            }
+            Instruction::Call(symbol, _) => {
+                self.bytes(&[0xE8]);
+                self.relocations.push(Relocation {
+                    offset: self.code.len(),
+                    symbol: symbol.clone(),
+                    kind: RelocationKind::Plt32,
+                    addend: -4,
+                });
+                self.bytes(&[0; 4]);
+            }
+            Instruction::Ret => self.bytes(&[0xC3]),
+            Instruction::Push(register) => self.register_operand(0x50, *register),
+            Instruction::Pop(register) => self.register_operand(0x58, *register),
+            Instruction::Movss(Operand::Xmm(destination), source) => {
+                self.modrm(Some(0xF3), false, &[0x0F, 0x10], *destination, source, &[])
+            }
+            Instruction::Movss(destination, Operand::Xmm(source)) => {
+                self.modrm(Some(0xF3), false, &[0x0F, 0x11], *source, destination, &[])
+            }
+            Instruction::Movss(destination, source) => {
+                panic!("Invalid movss operands {}, {}", destination, source)
+            }
+            Instruction::Scalar(operation, destination, source) => self.modrm(
+                Some(0xF3),
+                false,
+                &[0x0F, operation.code()],
+                *destination,
+                source,
+                &[],
+            ),
+            Instruction::Ucomiss(a, b) => self.modrm(None, false, &[0x0F, 0x2E], *a, b, &[]),
+            Instruction::Cvtss2sd(destination, source) => self.modrm(
+                Some(0xF3),
+                false,
+                &[0x0F, 0x5A],
+                *destination,
+                &Operand::Xmm(*source),
+                &[],
+            ),
+        }
+    }
+}
+
+impl AsmModule {
+    /// Encodes every function into one `.text` section. Jumps to labels are resolved here, while
+    /// calls and `rip` relative data accesses are left as relocations for the linker.
+    pub fn assemble(&self) -> MachineCode {
+        let mut encoder = Encoder {
+            code: Vec::new(),
+            labels: HashMap::new(),
+            jumps: Vec::new(),
+            relocations: Vec::new(),
+            lines: Vec::new(),
+        };
+
+        let mut functions: Vec<FunctionCode> = Vec::new();
+
+        for function in &self.functions {
+            let offset = encoder.code.len();
+
+            for instruction in &function.instructions {
+                encoder.instruction(instruction);
+            }
+
+            functions.push(FunctionCode {
+                name: function.name.clone(),
+                global: function.global,
+                offset,
+                size: encoder.code.len() - offset,
+            });
+        }
+
+        for (position, label) in &encoder.jumps {
+            let target = encoder.labels[label] as i64;
+            let offset = (target - (*position as i64 + 4)) as i32;
+
+            encoder.code[*position..*position + 4].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        return MachineCode {
+            text: encoder.code,
+            functions,
+            relocations: encoder.relocations,
+            lines: encoder.lines,
+        };
+    }
+}
@@ -0,0 +1,1453 @@
+use crate::ir::{
+    BinaryOperator, BlockCall, BlockId, Constant, Function, Instruction, InstructionKind, IrType,
+    Module, Terminator, UnaryOperator, ValueId,
+};
+use crate::source_map::SourceMap;
+use crate::x86_64::{
+    ArithmeticOperation, AsmFunction, AsmModule, Condition, Instruction as Asm, Operand, Register,
+    ScalarOperation, Size,
+};
+
+/// Registers values can live in. They're all callee saved, so values survive calls without being
+/// spilled, and every caller saved register stays free as scratch space.
+const ALLOCATABLE_REGISTERS: &[Register] = &[
+    Register::Rbx,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+const INTEGER_ARGUMENT_REGISTERS: &[Register] = &[
+    Register::Rdi,
+    Register::Rsi,
+    Register::Rdx,
+    Register::Rcx,
+    Register::R8,
+    Register::R9,
+];
+
+const FLOAT_ARGUMENT_REGISTERS: u8 = 8;
+
+const STRINGS: &[(&str, &str)] = &[
+    (".Lmt_format_i32", "%d"),
+    (".Lmt_format_u32", "%u"),
+    (".Lmt_format_f32", "%.*g"),
+    (".Lmt_format_bool", "%s"),
+    (".Lmt_format_text", "%s"),
+    (".Lmt_nan", "nan"),
+    (".Lmt_true", "true"),
+    (".Lmt_false", "false"),
+    (
+        ".Lmt_division_by_zero",
+        "Error: Division by zero %d, %d -> %d, %d\n",
+    ),
+    (".Lmt_negative_count", "Error: Can't allocate %lld values\n"),
+    (".Lmt_out_of_memory", "Error: Out of memory\n"),
+];
+
+/// Externs and functions exported to C go by their C name, and everything else gets a prefix so
+/// it can't clash with C.
+fn function_name(module: &Module, name: &str) -> String {
+    if module.is_c_symbol(name) {
+        return String::from(name);
+    }
+
+    return format!("mt_{}", name);
+}
+
+fn global_name(name: &str) -> String {
+    return format!("mt_global_{}", name);
+}
+
+fn symbol(name: &str) -> Operand {
+    return Operand::Symbol(String::from(name), Size::Dword);
+}
+
+fn constant_bits(constant: &Constant) -> i64 {
+    match constant {
+        Constant::Integer(value) => *value as u32 as i32 as i64,
+        Constant::Float(value) => value.to_bits() as i32 as i64,
+        Constant::Boolean(value) => *value as i64,
+    }
+}
+
+/// Where each value's live range starts and ends, counting block starts, instructions and
+/// terminators in block order. Values live into or out of a block are stretched over all of it,
+/// so a range covers every point the value has to be kept.
+fn live_intervals(function: &Function) -> Vec<Option<(usize, usize)>> {
+    let value_count = function.value_types.len();
+    let block_count = function.blocks.len();
+
+    let mut block_starts: Vec<usize> = Vec::new();
+    let mut block_ends: Vec<usize> = Vec::new();
+    let mut position = 0;
+
+    for block in &function.blocks {
+        block_starts.push(position);
+        position += block.instructions.len() + 1;
+        block_ends.push(position);
+        position += 1;
+    }
+
+    let mut intervals: Vec<Option<(usize, usize)>> = vec![None; value_count];
+
+    let extend = |intervals: &mut Vec<Option<(usize, usize)>>, value: ValueId, at: usize| {
+        intervals[value.0] = match intervals[value.0] {
+            None => Some((at, at)),
+            Some((start, end)) => Some((start.min(at), end.max(at))),
+        };
+    };
+
+    let mut uses: Vec<Vec<bool>> = vec![vec![false; value_count]; block_count];
+    let mut definitions: Vec<Vec<bool>> = vec![vec![false; value_count]; block_count];
+
+    for (index, block) in function.blocks.iter().enumerate() {
+        for parameter in &block.parameters {
+            definitions[index][parameter.0] = true;
+            extend(&mut intervals, *parameter, block_starts[index]);
+        }
+
+        for (offset, instruction) in block.instructions.iter().enumerate() {
+            for operand in instruction.operands() {
+                if !definitions[index][operand.0] {
+                    uses[index][operand.0] = true;
+                }
+
+                extend(&mut intervals, operand, block_starts[index] + offset + 1);
+            }
+
+            if let Some(result) = instruction.result {
+                definitions[index][result.0] = true;
+                extend(&mut intervals, result, block_starts[index] + offset + 1);
+            }
+        }
+
+        for operand in block.terminator.operands() {
+            if !definitions[index][operand.0] {
+                uses[index][operand.0] = true;
+            }
+
+            extend(&mut intervals, operand, block_ends[index]);
+        }
+    }
+
+    let mut live_in = uses.clone();
+    let mut live_out: Vec<Vec<bool>> = vec![vec![false; value_count]; block_count];
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for index in (0..block_count).rev() {
+            for target in function.blocks[index].terminator.successors() {
+                for value in 0..value_count {
+                    if live_in[target.block.0][value] && !live_out[index][value] {
+                        live_out[index][value] = true;
+                        changed = true;
+                    }
+                }
+            }
+
+            for value in 0..value_count {
+                if live_out[index][value] && !definitions[index][value] && !live_in[index][value] {
+                    live_in[index][value] = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for index in 0..block_count {
+        for value in 0..value_count {
+            if live_in[index][value] {
+                extend(&mut intervals, ValueId(value), block_starts[index]);
+            }
+
+            if live_out[index][value] {
+                extend(&mut intervals, ValueId(value), block_ends[index]);
+            }
+        }
+    }
+
+    return intervals;
+}
+
+/// Linear scan register allocation over the integer and boolean values. When every register is
+/// taken, whichever of the competing values lives longest goes to the stack instead. Floats are
+/// always kept on the stack.
+fn allocate_registers(function: &Function) -> Vec<Option<Register>> {
+    let intervals = live_intervals(function);
+
+    let mut order: Vec<(usize, (usize, usize))> = intervals
+        .iter()
+        .enumerate()
+        .filter(|(value, _)| {
+            matches!(
+                function.value_types[*value],
+                IrType::I32 | IrType::U32 | IrType::Bool | IrType::Pointer
+            )
+        })
+        .filter_map(|(value, interval)| interval.map(|interval| (value, interval)))
+        .collect();
+
+    order.sort_by_key(|(value, (start, _))| (*start, *value));
+
+    let mut registers: Vec<Option<Register>> = vec![None; function.value_types.len()];
+    let mut active: Vec<(usize, usize)> = Vec::new();
+    let mut free: Vec<Register> = ALLOCATABLE_REGISTERS.iter().rev().copied().collect();
+
+    for (value, (start, end)) in order {
+        active.retain(|(active_value, active_end)| {
+            if *active_end < start {
+                free.push(registers[*active_value].unwrap());
+
+                return false;
+            }
+
+            return true;
+        });
+
+        if let Some(register) = free.pop() {
+            registers[value] = Some(register);
+            active.push((value, end));
+
+            continue;
+        }
+
+        let furthest = (0..active.len())
+            .max_by_key(|index| active[*index].1)
+            .unwrap();
+
+        if active[furthest].1 > end {
+            let (spilled, _) = active[furthest];
+
+            registers[value] = registers[spilled].take();
+            active[furthest] = (value, end);
+        }
+    }
+
+    return registers;
+}
+
+struct FunctionGenerator<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    source_map: &'a SourceMap,
+    /// The number of the source file the function is from, for location markers.
+    file: usize,
+    homes: Vec<Operand>,
+    saved_registers: Vec<Register>,
+    frame_size: i32,
+    instructions: Vec<Asm>,
+    label_count: usize,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    fn emit(&mut self, instruction: Asm) {
+        self.instructions.push(instruction);
+    }
+
+    fn label(&mut self) -> String {
+        self.label_count += 1;
+
+        return format!(".Lmt_{}_{}", self.function.name, self.label_count);
+    }
+
+    fn block_label(&self, block: BlockId) -> String {
+        return format!(".Lmt_{}_{}", self.function.name, block);
+    }
+
+    fn return_label(&self) -> String {
+        return format!(".Lmt_{}_return", self.function.name);
+    }
+
+    fn home(&self, value: ValueId) -> Operand {
+        return self.homes[value.0].clone();
+    }
+
+    /// The part of `register` that holds a value, which is all of it for pointers.
+    fn register(&self, register: Register, value: ValueId) -> Operand {
+        if self.function.value_type(value) == IrType::Pointer {
+            return Operand::qword(register);
+        }
+
+        return Operand::dword(register);
+    }
+
+    fn load(&mut self, register: Register, value: ValueId) {
+        let home = self.home(value);
+        let register = self.register(register, value);
+
+        if home != register {
+            self.emit(Asm::Mov(register, home));
+        }
+    }
+
+    fn store(&mut self, value: ValueId, register: Register) {
+        let home = self.home(value);
+        let register = self.register(register, value);
+
+        if home != register {
+            self.emit(Asm::Mov(home, register));
+        }
+    }
+
+    fn load_float(&mut self, register: u8, value: ValueId) {
+        let home = self.home(value);
+
+        self.emit(Asm::Movss(Operand::Xmm(register), home));
+    }
+
+    fn store_float(&mut self, value: ValueId, register: u8) {
+        let home = self.home(value);
+
+        self.emit(Asm::Movss(home, Operand::Xmm(register)));
+    }
+
+    fn call_external(&mut self, name: &str) {
+        self.emit(Asm::Call(String::from(name), true));
+    }
+
+    /// Leaves `a / b` or `a % b` in eax, reporting the instruction's span if `b` is zero. Dividing
+    /// by -1 is done without `idiv`, which traps when the quotient overflows.
+    fn divide(
+        &mut self,
+        remainder: bool,
+        signed: bool,
+        value_a: ValueId,
+        value_b: ValueId,
+        instruction: &Instruction,
+    ) {
+        let checked = self.label();
+
+        self.load(Register::Rax, value_a);
+        self.load(Register::Rcx, value_b);
+        self.emit(Asm::Test(Register::Rcx, Register::Rcx));
+        self.emit(Asm::Jcc(Condition::NotEqual, checked.clone()));
+
+        let (lines, characters) = self.source_map.lines_and_characters(instruction.span);
+        let span = [lines.0, characters.0, lines.1, characters.1];
+
+        for (register, value) in INTEGER_ARGUMENT_REGISTERS.iter().zip(span) {
+            self.emit(Asm::Mov(
+                Operand::dword(*register),
+                Operand::Immediate(value as i64),
+            ));
+        }
+
+        self.emit(Asm::Call(String::from("mt_division_by_zero"), false));
+        self.emit(Asm::Label(checked));
+
+        if !signed {
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rdx),
+                Operand::dword(Register::Rdx),
+            ));
+            self.emit(Asm::Div(Register::Rcx));
+
+            if remainder {
+                self.emit(Asm::Mov(
+                    Operand::dword(Register::Rax),
+                    Operand::dword(Register::Rdx),
+                ));
+            }
+
+            return;
+        }
+
+        let divide = self.label();
+        let done = self.label();
+
+        self.emit(Asm::Arithmetic(
+            ArithmeticOperation::Compare,
+            Operand::dword(Register::Rcx),
+            Operand::Immediate(-1),
+        ));
+        self.emit(Asm::Jcc(Condition::NotEqual, divide.clone()));
+
+        if remainder {
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::dword(Register::Rax),
+            ));
+        } else {
+            self.emit(Asm::Neg(Register::Rax));
+        }
+
+        self.emit(Asm::Jmp(done.clone()));
+        self.emit(Asm::Label(divide));
+        self.emit(Asm::Cdq);
+        self.emit(Asm::Idiv(Register::Rcx));
+
+        if remainder {
+            self.emit(Asm::Mov(
+                Operand::dword(Register::Rax),
+                Operand::dword(Register::Rdx),
+            ));
+        }
+
+        self.emit(Asm::Label(done));
+    }
+
+    fn float_binary(
+        &mut self,
+        operator: BinaryOperator,
+        value_a: ValueId,
+        value_b: ValueId,
+        result: ValueId,
+    ) {
+        let operation = match operator {
+            BinaryOperator::Add => ScalarOperation::Add,
+            BinaryOperator::Subtract => ScalarOperation::Subtract,
+            BinaryOperator::Multiply => ScalarOperation::Multiply,
+            BinaryOperator::Divide => ScalarOperation::Divide,
+            BinaryOperator::Remainder => {
+                self.load_float(0, value_a);
+                self.load_float(1, value_b);
+                self.call_external("fmodf");
+                self.store_float(result, 0);
+
+                return;
+            }
+            _ => {
+                // ucomiss only sets the unsigned flags, so less than is greater than swapped.
+                let (first, second, condition) = match operator {
+                    BinaryOperator::LessThan => (value_b, value_a, Condition::Above),
+                    BinaryOperator::LessThanOrEqual => (value_b, value_a, Condition::AboveOrEqual),
+                    BinaryOperator::GreaterThan => (value_a, value_b, Condition::Above),
+                    BinaryOperator::GreaterThanOrEqual => {
+                        (value_a, value_b, Condition::AboveOrEqual)
+                    }
+                    BinaryOperator::Equal => (value_a, value_b, Condition::Equal),
+                    _ => (value_a, value_b, Condition::NotEqual),
+                };
+
+                self.load_float(0, first);
+
+                let second = self.home(second);
+
+                self.emit(Asm::Ucomiss(0, second));
+                self.emit(Asm::Set(condition, Register::Rax));
+
+                // Comparisons with NaN are unordered and set the parity flag.
+                let (parity, combine) = match operator {
+                    BinaryOperator::Equal => (Condition::NoParity, ArithmeticOperation::And),
+                    BinaryOperator::NotEqual => (Condition::Parity, ArithmeticOperation::Or),
+                    _ => {
+                        self.emit(Asm::Movzx(Register::Rax, Operand::byte(Register::Rax)));
+                        self.store(result, Register::Rax);
+
+                        return;
+                    }
+                };
+
+                self.emit(Asm::Set(parity, Register::Rcx));
+                self.emit(Asm::Arithmetic(
+                    combine,
+                    Operand::Register(Register::Rax, Size::Byte),
+                    Operand::Register(Register::Rcx, Size::Byte),
+                ));
+                self.emit(Asm::Movzx(Register::Rax, Operand::byte(Register::Rax)));
+                self.store(result, Register::Rax);
+
+                return;
+            }
+        };
+
+        self.load_float(0, value_a);
+
+        let second = self.home(value_b);
+
+        self.emit(Asm::Scalar(operation, 0, second));
+        self.store_float(result, 0);
+    }
+
+    fn binary(
+        &mut self,
+        operator: BinaryOperator,
+        value_a: ValueId,
+        value_b: ValueId,
+        instruction: &Instruction,
+    ) {
+        let result = instruction.result.unwrap();
+        let value_type = self.function.value_type(value_a);
+
+        if value_type == IrType::F32 {
+            self.float_binary(operator, value_a, value_b, result);
+
+            return;
+        }
+
+        if matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder) {
+            self.divide(
+                operator == BinaryOperator::Remainder,
+                value_type == IrType::I32,
+                value_a,
+                value_b,
+                instruction,
+            );
+            self.store(result, Register::Rax);
+
+            return;
+        }
+
+        self.load(Register::Rax, value_a);
+
+        let second = self.home(value_b);
+        let signed = value_type == IrType::I32;
+
+        let condition = match operator {
+            BinaryOperator::Add => {
+                self.emit(Asm::Arithmetic(
+                    ArithmeticOperation::Add,
+                    Operand::dword(Register::Rax),
+                    second,
+                ));
+                None
+            }
+            BinaryOperator::Subtract => {
+                self.emit(Asm::Arithmetic(
+                    ArithmeticOperation::Subtract,
+                    Operand::dword(Register::Rax),
+                    second,
+                ));
+                None
+            }
+            BinaryOperator::Multiply => {
+                self.emit(Asm::Imul(Register::Rax, second));
+                None
+            }
+            BinaryOperator::And => {
+                self.emit(Asm::Arithmetic(
+                    ArithmeticOperation::And,
+                    Operand::dword(Register::Rax),
+                    second,
+                ));
+                None
+            }
+            BinaryOperator::Or => {
+                self.emit(Asm::Arithmetic(
+                    ArithmeticOperation::Or,
+                    Operand::dword(Register::Rax),
+                    second,
+                ));
+                None
+            }
+            BinaryOperator::Equal => Some(Condition::Equal),
+            BinaryOperator::NotEqual => Some(Condition::NotEqual),
+            BinaryOperator::LessThan if signed => Some(Condition::Less),
+            BinaryOperator::LessThan => Some(Condition::Below),
+            BinaryOperator::LessThanOrEqual if signed => Some(Condition::LessOrEqual),
+            BinaryOperator::LessThanOrEqual => Some(Condition::BelowOrEqual),
+            BinaryOperator::GreaterThan if signed => Some(Condition::Greater),
+            BinaryOperator::GreaterThan => Some(Condition::Above),
+            BinaryOperator::GreaterThanOrEqual if signed => Some(Condition::GreaterOrEqual),
+            BinaryOperator::GreaterThanOrEqual => Some(Condition::AboveOrEqual),
+            BinaryOperator::Divide | BinaryOperator::Remainder => unreachable!(),
+        };
+
+        if let Some(condition) = condition {
+            let first = self.register(Register::Rax, value_a);
+            let second = self.home(value_b);
+
+            self.emit(Asm::Arithmetic(ArithmeticOperation::Compare, first, second));
+            self.emit(Asm::Set(condition, Register::Rax));
+            self.emit(Asm::Movzx(Register::Rax, Operand::byte(Register::Rax)));
+        }
+
+        self.store(result, Register::Rax);
+    }
+
+    fn unary(&mut self, operator: UnaryOperator, value: ValueId, result: ValueId) {
+        self.load(Register::Rax, value);
+
+        match (operator, self.function.value_type(value)) {
+            (UnaryOperator::Negate, IrType::F32) => self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::Immediate(i32::MIN as i64),
+            )),
+            (UnaryOperator::Negate, _) => self.emit(Asm::Neg(Register::Rax)),
+            (UnaryOperator::Not, IrType::Bool) => self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::Immediate(1),
+            )),
+            (UnaryOperator::Not, _) => self.emit(Asm::Not(Register::Rax)),
+        }
+
+        self.store(result, Register::Rax);
+    }
+
+    /// Prints the arguments separated by spaces, followed by a newline for `println`.
+    fn print(&mut self, arguments: &[ValueId], newline: bool) {
+        for (index, argument) in arguments.iter().enumerate() {
+            if index > 0 {
+                self.emit(Asm::Mov(
+                    Operand::dword(Register::Rdi),
+                    Operand::Immediate(' ' as i64),
+                ));
+                self.call_external("putchar");
+            }
+
+            let value_type = self.function.value_type(*argument);
+
+            match value_type {
+                IrType::I32 | IrType::U32 => self.load(Register::Rsi, *argument),
+                IrType::F32 => {
+                    self.load_float(0, *argument);
+                    self.emit(Asm::Call(String::from("mt_print_f32"), false));
+
+                    continue;
+                }
+                IrType::Bool => {
+                    self.load(Register::Rax, *argument);
+                    self.emit(Asm::Test(Register::Rax, Register::Rax));
+                    self.emit(Asm::Lea(Register::Rsi, symbol(".Lmt_true")));
+                    self.emit(Asm::Lea(Register::Rcx, symbol(".Lmt_false")));
+                    self.emit(Asm::Cmov(Condition::Equal, Register::Rsi, Register::Rcx));
+                }
+                IrType::Void | IrType::Pointer => continue,
+            }
+
+            let format = format!(".Lmt_format_{}", value_type);
+
+            self.emit(Asm::Lea(Register::Rdi, symbol(&format)));
+
+            // Variadic calls pass the number of vector registers used in al.
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::dword(Register::Rax),
+            ));
+            self.call_external("printf");
+        }
+
+        if newline {
+            self.emit(Asm::Mov(
+                Operand::dword(Register::Rdi),
+                Operand::Immediate('\n' as i64),
+            ));
+            self.call_external("putchar");
+        }
+    }
+
+    /// Loads an integer into all of `register`, sign extended if it's an i32.
+    fn widen(&mut self, register: Register, value: ValueId) {
+        self.load(register, value);
+
+        if self.function.value_type(value) == IrType::I32 {
+            self.emit(Asm::Movsxd(register, register));
+        }
+    }
+
+    /// Leaves the address of `pointer[index]` in rax, using rcx and rdx as scratch.
+    fn element(&mut self, element_type: IrType, pointer: ValueId, index: ValueId) {
+        self.widen(Register::Rcx, index);
+        self.emit(Asm::Mov(
+            Operand::dword(Register::Rdx),
+            Operand::Immediate(element_type.size() as i64),
+        ));
+        self.emit(Asm::Imul(Register::Rcx, Operand::qword(Register::Rdx)));
+        self.load(Register::Rax, pointer);
+        self.emit(Asm::Arithmetic(
+            ArithmeticOperation::Add,
+            Operand::qword(Register::Rax),
+            Operand::qword(Register::Rcx),
+        ));
+    }
+
+    /// Calls another Metor function, or an extern, with the System V calling convention. Arguments
+    /// that don't fit in registers are pushed right to left, with padding first if needed to keep
+    /// the stack 16 byte aligned at the call.
+    fn call(&mut self, name: &str, arguments: &[ValueId], result: Option<ValueId>) {
+        let mut integer_arguments: Vec<ValueId> = Vec::new();
+        let mut float_arguments: Vec<ValueId> = Vec::new();
+        let mut stack_arguments: Vec<ValueId> = Vec::new();
+
+        for argument in arguments {
+            if self.function.value_type(*argument) == IrType::F32 {
+                if float_arguments.len() < FLOAT_ARGUMENT_REGISTERS as usize {
+                    float_arguments.push(*argument);
+                } else {
+                    stack_arguments.push(*argument);
+                }
+            } else if integer_arguments.len() < INTEGER_ARGUMENT_REGISTERS.len() {
+                integer_arguments.push(*argument);
+            } else {
+                stack_arguments.push(*argument);
+            }
+        }
+
+        let padding = stack_arguments.len() % 2 == 1;
+
+        if padding {
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Subtract,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(8),
+            ));
+        }
+
+        for argument in stack_arguments.iter().rev() {
+            self.load(Register::Rax, *argument);
+            self.emit(Asm::Push(Register::Rax));
+        }
+
+        for (register, argument) in INTEGER_ARGUMENT_REGISTERS.iter().zip(&integer_arguments) {
+            self.load(*register, *argument);
+        }
+
+        for (index, argument) in float_arguments.iter().enumerate() {
+            self.load_float(index as u8, *argument);
+        }
+
+        let is_extern = self.module.is_extern(name);
+
+        self.emit(Asm::Call(function_name(self.module, name), is_extern));
+
+        // C only sets the low byte of a returned bool.
+        if is_extern
+            && result.is_some_and(|result| self.function.value_type(result) == IrType::Bool)
+        {
+            self.emit(Asm::Movzx(Register::Rax, Operand::byte(Register::Rax)));
+        }
+
+        let stack_size = (stack_arguments.len() + padding as usize) * 8;
+
+        if stack_size > 0 {
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(stack_size as i64),
+            ));
+        }
+
+        if let Some(result) = result {
+            if self.function.value_type(result) == IrType::F32 {
+                self.store_float(result, 0);
+            } else {
+                self.store(result, Register::Rax);
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let (lines, characters) = self.source_map.lines_and_characters(instruction.span);
+
+        self.emit(Asm::Location(self.file, lines.0, characters.0));
+
+        match &instruction.kind {
+            InstructionKind::Constant(constant) => {
+                let home = self.home(instruction.result.unwrap());
+
+                self.emit(Asm::Mov(home, Operand::Immediate(constant_bits(constant))));
+            }
+            InstructionKind::Binary(operator, value_a, value_b) => {
+                self.binary(*operator, *value_a, *value_b, instruction)
+            }
+            InstructionKind::Unary(operator, value) => {
+                self.unary(*operator, *value, instruction.result.unwrap())
+            }
+            InstructionKind::Call(name, arguments) if name == "print" || name == "println" => {
+                self.print(arguments, name == "println")
+            }
+            InstructionKind::Call(name, arguments) => {
+                self.call(name, arguments, instruction.result)
+            }
+            InstructionKind::LoadGlobal(name) => {
+                self.emit(Asm::Mov(
+                    Operand::dword(Register::Rax),
+                    symbol(&global_name(name)),
+                ));
+                self.store(instruction.result.unwrap(), Register::Rax);
+            }
+            InstructionKind::StoreGlobal(name, value) => {
+                self.load(Register::Rax, *value);
+                self.emit(Asm::Mov(
+                    symbol(&global_name(name)),
+                    Operand::dword(Register::Rax),
+                ));
+            }
+            InstructionKind::Alloc(element_type, count) => {
+                self.widen(Register::Rdi, *count);
+                self.emit(Asm::Mov(
+                    Operand::dword(Register::Rsi),
+                    Operand::Immediate(element_type.size() as i64),
+                ));
+                self.emit(Asm::Call(String::from("mt_heap_alloc"), false));
+                self.store(instruction.result.unwrap(), Register::Rax);
+            }
+            InstructionKind::Resize(element_type, pointer, count) => {
+                self.load(Register::Rdi, *pointer);
+                self.widen(Register::Rsi, *count);
+                self.emit(Asm::Mov(
+                    Operand::dword(Register::Rdx),
+                    Operand::Immediate(element_type.size() as i64),
+                ));
+                self.emit(Asm::Call(String::from("mt_heap_resize"), false));
+                self.store(instruction.result.unwrap(), Register::Rax);
+            }
+            InstructionKind::Free(pointer) => {
+                self.load(Register::Rdi, *pointer);
+                self.emit(Asm::Call(String::from("mt_heap_free"), false));
+            }
+            InstructionKind::Load(pointer, index) => {
+                let result = instruction.result.unwrap();
+                let element_type = self.function.value_type(result);
+
+                self.element(element_type, *pointer, *index);
+
+                if element_type == IrType::Bool {
+                    self.emit(Asm::Movzx(
+                        Register::Rax,
+                        Operand::Memory(Register::Rax, 0, Size::Byte),
+                    ));
+                } else {
+                    self.emit(Asm::Mov(
+                        Operand::dword(Register::Rax),
+                        Operand::Memory(Register::Rax, 0, Size::Dword),
+                    ));
+                }
+
+                self.store(result, Register::Rax);
+            }
+            InstructionKind::Store(pointer, index, value) => {
+                let element_type = self.function.value_type(*value);
+
+                self.element(element_type, *pointer, *index);
+                self.load(Register::Rcx, *value);
+
+                let size = if element_type == IrType::Bool {
+                    Size::Byte
+                } else {
+                    Size::Dword
+                };
+
+                self.emit(Asm::Mov(
+                    Operand::Memory(Register::Rax, 0, size),
+                    Operand::Register(Register::Rcx, size),
+                ));
+            }
+        }
+    }
+
+    /// Copies block arguments into the target's parameters and jumps there. The copies go through
+    /// the stack, since a parameter being assigned can also be one of the arguments.
+    fn jump(&mut self, target: &BlockCall) {
+        let parameters = self.function.blocks[target.block.0].parameters.clone();
+
+        for argument in &target.arguments {
+            self.load(Register::Rax, *argument);
+            self.emit(Asm::Push(Register::Rax));
+        }
+
+        for parameter in parameters.iter().rev() {
+            self.emit(Asm::Pop(Register::Rax));
+            self.store(*parameter, Register::Rax);
+        }
+
+        let label = self.block_label(target.block);
+
+        self.emit(Asm::Jmp(label));
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match terminator {
+            Terminator::Jump(target) => self.jump(target),
+            Terminator::Branch(condition, then_target, else_target) => {
+                self.load(Register::Rax, *condition);
+                self.emit(Asm::Test(Register::Rax, Register::Rax));
+
+                if else_target.arguments.is_empty() {
+                    let label = self.block_label(else_target.block);
+
+                    self.emit(Asm::Jcc(Condition::Equal, label));
+                    self.jump(then_target);
+
+                    return;
+                }
+
+                let else_edge = self.label();
+
+                self.emit(Asm::Jcc(Condition::Equal, else_edge.clone()));
+                self.jump(then_target);
+                self.emit(Asm::Label(else_edge));
+                self.jump(else_target);
+            }
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    if self.function.value_type(*value) == IrType::F32 {
+                        self.load_float(0, *value);
+                    } else {
+                        self.load(Register::Rax, *value);
+                    }
+                }
+
+                let label = self.return_label();
+
+                self.emit(Asm::Jmp(label));
+            }
+            Terminator::Unreachable => self.call_external("abort"),
+        }
+    }
+
+    fn prologue(&mut self) {
+        self.emit(Asm::Push(Register::Rbp));
+        self.emit(Asm::Mov(
+            Operand::qword(Register::Rbp),
+            Operand::qword(Register::Rsp),
+        ));
+
+        for register in self.saved_registers.clone() {
+            self.emit(Asm::Push(register));
+        }
+
+        if self.frame_size > 0 {
+            self.emit(Asm::Arithmetic(
+                ArithmeticOperation::Subtract,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(self.frame_size as i64),
+            ));
+        }
+
+        let mut integer_count = 0;
+        let mut float_count = 0;
+        let mut stack_offset = 16;
+
+        for parameter in self.function.blocks[0].parameters.clone() {
+            if self.function.value_type(parameter) == IrType::F32 {
+                if float_count < FLOAT_ARGUMENT_REGISTERS {
+                    self.store_float(parameter, float_count);
+                    float_count += 1;
+
+                    continue;
+                }
+            } else if integer_count < INTEGER_ARGUMENT_REGISTERS.len() {
+                self.store(parameter, INTEGER_ARGUMENT_REGISTERS[integer_count]);
+                integer_count += 1;
+
+                continue;
+            }
+
+            let size = match self.function.value_type(parameter) {
+                IrType::Pointer => Size::Qword,
+                _ => Size::Dword,
+            };
+
+            self.emit(Asm::Mov(
+                self.register(Register::Rax, parameter),
+                Operand::Memory(Register::Rbp, stack_offset, size),
+            ));
+            self.store(parameter, Register::Rax);
+            stack_offset += 8;
+        }
+    }
+
+    fn epilogue(&mut self) {
+        let label = self.return_label();
+
+        self.emit(Asm::Label(label));
+
+        if self.saved_registers.is_empty() {
+            self.emit(Asm::Mov(
+                Operand::qword(Register::Rsp),
+                Operand::qword(Register::Rbp),
+            ));
+        } else {
+            let offset = -8 * self.saved_registers.len() as i32;
+
+            self.emit(Asm::Lea(
+                Register::Rsp,
+                Operand::Memory(Register::Rbp, offset, Size::Qword),
+            ));
+        }
+
+        for register in self.saved_registers.clone().iter().rev() {
+            self.emit(Asm::Pop(*register));
+        }
+
+        self.emit(Asm::Pop(Register::Rbp));
+        self.emit(Asm::Ret);
+    }
+
+    fn generate(mut self) -> AsmFunction {
+        let function = self.function;
+        let (lines, characters) = self.source_map.lines_and_characters(function.span);
+
+        self.emit(Asm::Location(self.file, lines.0, characters.0));
+        self.prologue();
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            if index > 0 {
+                let label = self.block_label(BlockId(index));
+
+                self.emit(Asm::Label(label));
+            }
+
+            for instruction in &block.instructions {
+                self.instruction(instruction);
+            }
+
+            self.terminator(&block.terminator);
+        }
+
+        self.epilogue();
+
+        return AsmFunction {
+            name: function_name(self.module, &function.name),
+            global: function.external,
+            instructions: self.instructions,
+        };
+    }
+}
+
+/// Gives every value a home: its allocated register, or otherwise a stack slot below the saved
+/// registers. The frame is padded so calls happen with a 16 byte aligned stack.
+fn function_generator<'a>(
+    module: &'a Module,
+    function: &'a Function,
+    source_map: &'a SourceMap,
+    file: usize,
+) -> FunctionGenerator<'a> {
+    let registers = allocate_registers(function);
+
+    let saved_registers: Vec<Register> = ALLOCATABLE_REGISTERS
+        .iter()
+        .filter(|register| registers.contains(&Some(**register)))
+        .copied()
+        .collect();
+
+    let mut homes: Vec<Operand> = Vec::new();
+    let mut slot_count = 0;
+
+    for (register, value_type) in registers.iter().zip(&function.value_types) {
+        let size = match value_type {
+            IrType::Pointer => Size::Qword,
+            _ => Size::Dword,
+        };
+
+        match register {
+            Some(register) => homes.push(Operand::Register(*register, size)),
+            None => {
+                slot_count += 1;
+
+                let offset = -8 * (saved_registers.len() + slot_count) as i32;
+
+                homes.push(Operand::Memory(Register::Rbp, offset, size));
+            }
+        }
+    }
+
+    let mut frame_size = 8 * slot_count as i32;
+
+    if (8 * saved_registers.len() as i32 + frame_size) % 16 != 0 {
+        frame_size += 8;
+    }
+
+    return FunctionGenerator {
+        module,
+        function,
+        source_map,
+        file,
+        homes,
+        saved_registers,
+        frame_size,
+        instructions: Vec::new(),
+        label_count: 0,
+    };
+}
+
+/// Prints the span of a failed division to stderr and exits, like the interpreter's diagnostic.
+fn division_by_zero() -> AsmFunction {
+    return AsmFunction {
+        name: String::from("mt_division_by_zero"),
+        global: false,
+        instructions: vec![
+            Asm::Push(Register::Rbp),
+            Asm::Mov(Operand::dword(Register::R9), Operand::dword(Register::Rcx)),
+            Asm::Mov(Operand::dword(Register::R8), Operand::dword(Register::Rdx)),
+            Asm::Mov(Operand::dword(Register::Rcx), Operand::dword(Register::Rsi)),
+            Asm::Mov(Operand::dword(Register::Rdx), Operand::dword(Register::Rdi)),
+            Asm::Mov(Operand::dword(Register::Rdi), Operand::Immediate(2)),
+            Asm::Lea(Register::Rsi, symbol(".Lmt_division_by_zero")),
+            Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::dword(Register::Rax),
+            ),
+            Asm::Call(String::from("dprintf"), true),
+            Asm::Mov(Operand::dword(Register::Rdi), Operand::Immediate(1)),
+            Asm::Call(String::from("exit"), true),
+        ],
+    };
+}
+
+/// Reports a failed allocation of the count in rdi and exits, which is out of memory unless the
+/// count is negative.
+fn allocation_failed() -> AsmFunction {
+    let mut instructions = vec![
+        Asm::Push(Register::Rbp),
+        Asm::Arithmetic(
+            ArithmeticOperation::Compare,
+            Operand::qword(Register::Rdi),
+            Operand::Immediate(0),
+        ),
+        Asm::Lea(Register::Rsi, symbol(".Lmt_out_of_memory")),
+        Asm::Jcc(
+            Condition::GreaterOrEqual,
+            String::from(".Lmt.report_allocation"),
+        ),
+        Asm::Lea(Register::Rsi, symbol(".Lmt_negative_count")),
+        Asm::Label(String::from(".Lmt.report_allocation")),
+        Asm::Mov(Operand::qword(Register::Rdx), Operand::qword(Register::Rdi)),
+        Asm::Mov(Operand::dword(Register::Rdi), Operand::Immediate(2)),
+    ];
+
+    instructions.extend([
+        Asm::Arithmetic(
+            ArithmeticOperation::Xor,
+            Operand::dword(Register::Rax),
+            Operand::dword(Register::Rax),
+        ),
+        Asm::Call(String::from("dprintf"), true),
+        Asm::Mov(Operand::dword(Register::Rdi), Operand::Immediate(1)),
+        Asm::Call(String::from("exit"), true),
+    ]);
+
+    return AsmFunction {
+        name: String::from("mt_allocation_failed"),
+        global: false,
+        instructions,
+    };
+}
+
+/// Allocates rdi values of rsi bytes each, all zero. Allocations keep their size in bytes just
+/// before their values, so resizing knows what to zero.
+fn heap_alloc() -> AsmFunction {
+    return AsmFunction {
+        name: String::from("mt_heap_alloc"),
+        global: false,
+        instructions: vec![
+            Asm::Push(Register::Rbp),
+            Asm::Push(Register::Rbx),
+            Asm::Push(Register::R12),
+            Asm::Mov(Operand::qword(Register::R12), Operand::qword(Register::Rdi)),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::qword(Register::R12),
+                Operand::Immediate(0),
+            ),
+            Asm::Jcc(Condition::Less, String::from(".Lmt.heap_alloc_failed")),
+            Asm::Mov(Operand::dword(Register::Rbx), Operand::dword(Register::Rsi)),
+            Asm::Imul(Register::Rbx, Operand::qword(Register::R12)),
+            Asm::Lea(
+                Register::Rsi,
+                Operand::Memory(Register::Rbx, 8, Size::Qword),
+            ),
+            Asm::Mov(Operand::dword(Register::Rdi), Operand::Immediate(1)),
+            Asm::Call(String::from("calloc"), true),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::qword(Register::Rax),
+                Operand::Immediate(0),
+            ),
+            Asm::Jcc(Condition::Equal, String::from(".Lmt.heap_alloc_failed")),
+            Asm::Mov(
+                Operand::Memory(Register::Rax, 0, Size::Qword),
+                Operand::qword(Register::Rbx),
+            ),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rax),
+                Operand::Immediate(8),
+            ),
+            Asm::Pop(Register::R12),
+            Asm::Pop(Register::Rbx),
+            Asm::Pop(Register::Rbp),
+            Asm::Ret,
+            Asm::Label(String::from(".Lmt.heap_alloc_failed")),
+            Asm::Mov(Operand::qword(Register::Rdi), Operand::qword(Register::R12)),
+            Asm::Call(String::from("mt_allocation_failed"), false),
+        ],
+    };
+}
+
+/// Resizes the allocation at rdi to rsi values of rdx bytes each, zeroing any past the old end.
+fn heap_resize() -> AsmFunction {
+    return AsmFunction {
+        name: String::from("mt_heap_resize"),
+        global: false,
+        instructions: vec![
+            Asm::Push(Register::Rbp),
+            Asm::Push(Register::Rbx),
+            Asm::Push(Register::R12),
+            Asm::Push(Register::R13),
+            Asm::Arithmetic(
+                ArithmeticOperation::Subtract,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(8),
+            ),
+            Asm::Mov(Operand::qword(Register::R12), Operand::qword(Register::Rsi)),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::qword(Register::R12),
+                Operand::Immediate(0),
+            ),
+            Asm::Jcc(Condition::Less, String::from(".Lmt.heap_resize_failed")),
+            Asm::Mov(Operand::dword(Register::Rbx), Operand::dword(Register::Rdx)),
+            Asm::Imul(Register::Rbx, Operand::qword(Register::R12)),
+            Asm::Lea(
+                Register::Rdi,
+                Operand::Memory(Register::Rdi, -8, Size::Qword),
+            ),
+            Asm::Mov(
+                Operand::qword(Register::R13),
+                Operand::Memory(Register::Rdi, 0, Size::Qword),
+            ),
+            Asm::Lea(
+                Register::Rsi,
+                Operand::Memory(Register::Rbx, 8, Size::Qword),
+            ),
+            Asm::Call(String::from("realloc"), true),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::qword(Register::Rax),
+                Operand::Immediate(0),
+            ),
+            Asm::Jcc(Condition::Equal, String::from(".Lmt.heap_resize_failed")),
+            Asm::Mov(
+                Operand::Memory(Register::Rax, 0, Size::Qword),
+                Operand::qword(Register::Rbx),
+            ),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rax),
+                Operand::Immediate(8),
+            ),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::qword(Register::Rbx),
+                Operand::qword(Register::R13),
+            ),
+            Asm::Jcc(
+                Condition::LessOrEqual,
+                String::from(".Lmt.heap_resize_done"),
+            ),
+            Asm::Mov(Operand::qword(Register::Rdi), Operand::qword(Register::Rax)),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rdi),
+                Operand::qword(Register::R13),
+            ),
+            Asm::Mov(Operand::qword(Register::Rdx), Operand::qword(Register::Rbx)),
+            Asm::Arithmetic(
+                ArithmeticOperation::Subtract,
+                Operand::qword(Register::Rdx),
+                Operand::qword(Register::R13),
+            ),
+            Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rsi),
+                Operand::dword(Register::Rsi),
+            ),
+            Asm::Mov(Operand::qword(Register::Rbx), Operand::qword(Register::Rax)),
+            Asm::Call(String::from("memset"), true),
+            Asm::Mov(Operand::qword(Register::Rax), Operand::qword(Register::Rbx)),
+            Asm::Label(String::from(".Lmt.heap_resize_done")),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(8),
+            ),
+            Asm::Pop(Register::R13),
+            Asm::Pop(Register::R12),
+            Asm::Pop(Register::Rbx),
+            Asm::Pop(Register::Rbp),
+            Asm::Ret,
+            Asm::Label(String::from(".Lmt.heap_resize_failed")),
+            Asm::Mov(Operand::qword(Register::Rdi), Operand::qword(Register::R12)),
+            Asm::Call(String::from("mt_allocation_failed"), false),
+        ],
+    };
+}
+
+fn heap_free() -> AsmFunction {
+    return AsmFunction {
+        name: String::from("mt_heap_free"),
+        global: false,
+        instructions: vec![
+            Asm::Push(Register::Rbp),
+            Asm::Lea(
+                Register::Rdi,
+                Operand::Memory(Register::Rdi, -8, Size::Qword),
+            ),
+            Asm::Call(String::from("free"), true),
+            Asm::Pop(Register::Rbp),
+            Asm::Ret,
+        ],
+    };
+}
+
+/// Prints the float in xmm0 as `%g` with the fewest significant digits that read back as the same
+/// float, or as `nan`, trying each precision in a buffer on the stack.
+fn print_f32() -> AsmFunction {
+    let text = Operand::Memory(Register::Rsp, 0, Size::Qword);
+    let value = Operand::Memory(Register::Rsp, 32, Size::Dword);
+
+    return AsmFunction {
+        name: String::from("mt_print_f32"),
+        global: false,
+        instructions: vec![
+            Asm::Push(Register::Rbp),
+            Asm::Push(Register::Rbx),
+            Asm::Arithmetic(
+                ArithmeticOperation::Subtract,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(40),
+            ),
+            Asm::Movss(value.clone(), Operand::Xmm(0)),
+            Asm::Lea(Register::Rsi, symbol(".Lmt_nan")),
+            Asm::Ucomiss(0, Operand::Xmm(0)),
+            Asm::Jcc(Condition::Parity, String::from(".Lmt.print_f32_text")),
+            Asm::Mov(Operand::dword(Register::Rbx), Operand::Immediate(1)),
+            Asm::Label(String::from(".Lmt.print_f32_precision")),
+            Asm::Lea(Register::Rdi, text.clone()),
+            Asm::Mov(Operand::dword(Register::Rsi), Operand::Immediate(32)),
+            Asm::Lea(Register::Rdx, symbol(".Lmt_format_f32")),
+            Asm::Mov(Operand::dword(Register::Rcx), Operand::dword(Register::Rbx)),
+            Asm::Movss(Operand::Xmm(0), value.clone()),
+            Asm::Cvtss2sd(0, 0),
+            Asm::Mov(Operand::dword(Register::Rax), Operand::Immediate(1)),
+            Asm::Call(String::from("snprintf"), true),
+            Asm::Lea(Register::Rdi, text.clone()),
+            Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rsi),
+                Operand::dword(Register::Rsi),
+            ),
+            Asm::Call(String::from("strtof"), true),
+            Asm::Lea(Register::Rsi, text),
+            Asm::Ucomiss(0, value),
+            Asm::Jcc(Condition::Equal, String::from(".Lmt.print_f32_text")),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::dword(Register::Rbx),
+                Operand::Immediate(1),
+            ),
+            Asm::Arithmetic(
+                ArithmeticOperation::Compare,
+                Operand::dword(Register::Rbx),
+                Operand::Immediate(9),
+            ),
+            Asm::Jcc(
+                Condition::LessOrEqual,
+                String::from(".Lmt.print_f32_precision"),
+            ),
+            Asm::Label(String::from(".Lmt.print_f32_text")),
+            Asm::Lea(Register::Rdi, symbol(".Lmt_format_text")),
+            Asm::Arithmetic(
+                ArithmeticOperation::Xor,
+                Operand::dword(Register::Rax),
+                Operand::dword(Register::Rax),
+            ),
+            Asm::Call(String::from("printf"), true),
+            Asm::Arithmetic(
+                ArithmeticOperation::Add,
+                Operand::qword(Register::Rsp),
+                Operand::Immediate(40),
+            ),
+            Asm::Pop(Register::Rbx),
+            Asm::Pop(Register::Rbp),
+            Asm::Ret,
+        ],
+    };
+}
+
+/// The C entry point, which calls the module's main function and returns its result as the exit
+/// code when it has one.
+fn entry_point(module: &Module, main: &Function) -> AsmFunction {
+    let mut instructions = vec![
+        Asm::Push(Register::Rbp),
+        Asm::Call(function_name(module, &main.name), false),
+    ];
+
+    if main.return_type != IrType::I32 {
+        instructions.push(Asm::Arithmetic(
+            ArithmeticOperation::Xor,
+            Operand::dword(Register::Rax),
+            Operand::dword(Register::Rax),
+        ));
+    }
+
+    instructions.push(Asm::Pop(Register::Rbp));
+    instructions.push(Asm::Ret);
+
+    return AsmFunction {
+        name: String::from("main"),
+        global: true,
+        instructions,
+    };
+}
+
+/// Translates a verified module to x86-64 assembly for the System V ABI. Integers live in
+/// registers where the allocator can find room and on the stack otherwise. The output links
+/// against libc and libm, and marks each instruction with its line in `source_path`, or in the
+/// module it was imported from.
+pub fn generate(module: &Module, source_map: &SourceMap, source_path: &str) -> AsmModule {
+    let mut functions = vec![
+        division_by_zero(),
+        allocation_failed(),
+        heap_alloc(),
+        heap_resize(),
+        heap_free(),
+        print_f32(),
+    ];
+    let mut files = vec![String::from(source_path)];
+
+    for function in &module.functions {
+        let name = source_map.name(function.span.file);
+
+        let file = match files.iter().position(|file| file == name) {
+            Some(index) => index + 1,
+            None => {
+                files.push(String::from(name));
+
+                files.len()
+            }
+        };
+
+        functions.push(function_generator(module, function, source_map, file).generate());
+    }
+
+    let main = module
+        .functions
+        .iter()
+        .find(|function| function.name == "main" && function.parameters.is_empty());
+
+    if let Some(main) = main {
+        functions.push(entry_point(module, main));
+    }
+
+    return AsmModule {
+        files,
+        functions,
+        data: module
+            .globals
+            .iter()
+            .map(|global| {
+                (
+                    global_name(&global.name),
+                    constant_bits(&global.value) as u32,
+                )
+            })
+            .collect(),
+        strings: STRINGS
+            .iter()
+            .map(|(label, text)| (String::from(*label), String::from(*text)))
+            .collect(),
+    };
+}
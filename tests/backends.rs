@@ -0,0 +1,142 @@
+//! Runs one program through every backend, and checks they all print exactly the same output.
+
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+const PROGRAM: &str = "\
+i32 main {
+    f32 zero = 0
+    f32 one = 1
+    f32 three = 3
+    f32 ten = 10
+    f32 big = 16777216
+    u32 most = 4294967295
+    println(one / three, one / ten, ten * ten * ten * ten * ten * ten * ten * ten * ten * ten)
+    println(one / (ten * ten * ten * ten * ten), big, big * ten, one / zero, zero - one / zero)
+    println(zero / zero, zero * (zero - one), three / 2, one + one / three)
+    print(1, most, 0 - 7, true)
+    println(false, three)
+    return 0
+}
+";
+
+const OUTPUT: &str = "\
+0.33333334 0.1 1e+10
+1e-05 16777216 1.6777216e+08 inf -inf
+nan -0 1.5 1.3333334
+1 4294967295 -7 truefalse 3
+";
+
+/// A path in the temporary directory that no other test uses.
+fn temporary(name: &str) -> PathBuf {
+    return env::temp_dir().join(format!("metor-backends-{}-{}", process::id(), name));
+}
+
+fn run(command: &mut Command) -> String {
+    let output = command.output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "{:?} failed:\n{}",
+        command,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    return String::from_utf8(output.stdout).unwrap();
+}
+
+fn metor(arguments: &[&str]) -> String {
+    return run(Command::new(env!("CARGO_BIN_EXE_metor")).args(arguments));
+}
+
+/// Whether a tool is installed, so backends that need one are skipped rather than failed without it.
+fn installed(tool: &str) -> bool {
+    return Command::new(tool).arg("--version").output().is_ok();
+}
+
+fn program(name: &str) -> String {
+    let path = temporary(&format!("{}.mt", name));
+
+    fs::write(&path, PROGRAM).unwrap();
+
+    return path.to_string_lossy().into_owned();
+}
+
+#[test]
+fn interpreter() {
+    assert_eq!(metor(&["run", &program("interpreter")]), OUTPUT);
+}
+
+#[test]
+fn vm() {
+    assert_eq!(metor(&["vm", &program("vm")]), OUTPUT);
+}
+
+#[test]
+fn c() {
+    if !installed("cc") {
+        return;
+    }
+
+    let executable = temporary("c");
+
+    metor(&["build", &program("c"), "-o", &executable.to_string_lossy()]);
+
+    assert_eq!(run(&mut Command::new(&executable)), OUTPUT);
+
+    let _ = fs::remove_file(executable);
+}
+
+#[test]
+fn llvm() {
+    if !installed("lli") {
+        return;
+    }
+
+    let ir = temporary("llvm.ll");
+
+    fs::write(&ir, metor(&["llvm", &program("llvm")])).unwrap();
+
+    // LLVM before 15 only reads `ptr` with opaque pointers turned on.
+    let version = run(Command::new("lli").arg("--version"));
+    let mut lli = Command::new("lli");
+
+    if version.contains("version 14") {
+        lli.arg("-opaque-pointers");
+    }
+
+    assert_eq!(run(lli.arg(&ir)), OUTPUT);
+
+    let _ = fs::remove_file(ir);
+}
+
+#[test]
+fn x86_64() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) || !installed("cc") {
+        return;
+    }
+
+    let object = temporary("x86_64.o");
+    let executable = temporary("x86_64");
+
+    metor(&[
+        "object",
+        &program("x86_64"),
+        "-o",
+        &object.to_string_lossy(),
+    ]);
+    run(Command::new("cc")
+        .arg("-o")
+        .arg(&executable)
+        .arg(&object)
+        .arg("-lm"));
+
+    assert_eq!(run(&mut Command::new(&executable)), OUTPUT);
+
+    let _ = fs::remove_file(object);
+    let _ = fs::remove_file(executable);
+}
@@ -0,0 +1,227 @@
+; ModuleID = 'externs.mt'
+source_filename = "externs.mt"
+
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32)
+declare ptr @calloc(i64, i64)
+declare ptr @realloc(ptr, i64)
+declare ptr @memset(ptr, i32, i64)
+declare void @free(ptr)
+declare i32 @snprintf(ptr, i64, ptr, ...)
+declare float @strtof(ptr, ptr)
+
+@.true = private unnamed_addr constant [5 x i8] c"true\00"
+@.false = private unnamed_addr constant [6 x i8] c"false\00"
+@.division_by_zero = private unnamed_addr constant [42 x i8] c"Error: Division by zero %d, %d -> %d, %d\0A\00"
+@.negative_count = private unnamed_addr constant [35 x i8] c"Error: Can't allocate %lld values\0A\00"
+@.out_of_memory = private unnamed_addr constant [22 x i8] c"Error: Out of memory\0A\00"
+@.f32_format = private unnamed_addr constant [5 x i8] c"%.*g\00"
+@.text = private unnamed_addr constant [3 x i8] c"%s\00"
+@.nan = private unnamed_addr constant [4 x i8] c"nan\00"
+
+define internal void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.division_by_zero, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i32 @mt_div_i32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %check
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+check:
+  %minimum = icmp eq i32 %a, -2147483648
+  %negative_one = icmp eq i32 %b, -1
+  %overflow = and i1 %minimum, %negative_one
+  br i1 %overflow, label %wrap, label %divide
+wrap:
+  ret i32 -2147483648
+divide:
+  %result = sdiv i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_rem_i32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %check
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+check:
+  %negative_one = icmp eq i32 %b, -1
+  br i1 %negative_one, label %wrap, label %divide
+wrap:
+  ret i32 0
+divide:
+  %result = srem i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_div_u32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %divide
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+divide:
+  %result = udiv i32 %a, %b
+  ret i32 %result
+}
+
+define internal i32 @mt_rem_u32(i32 %a, i32 %b, i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %error, label %divide
+error:
+  call void @mt_division_by_zero(i32 %line_start, i32 %character_start, i32 %line_end, i32 %character_end)
+  unreachable
+divide:
+  %result = urem i32 %a, %b
+  ret i32 %result
+}
+
+define internal void @mt_allocation_failed(i64 %count) {
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %negative_count, label %out_of_memory
+negative_count:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.negative_count, i64 %count)
+  call void @exit(i32 1)
+  unreachable
+out_of_memory:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.out_of_memory)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal ptr @mt_heap_alloc(i64 %count, i64 %size) {
+  %bytes = mul i64 %count, %size
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %error, label %allocate
+allocate:
+  %total = add i64 %bytes, 8
+  %allocation = call ptr @calloc(i64 1, i64 %total)
+  %failed = icmp eq ptr %allocation, null
+  br i1 %failed, label %error, label %allocated
+error:
+  call void @mt_allocation_failed(i64 %count)
+  unreachable
+allocated:
+  store i64 %bytes, ptr %allocation
+  %values = getelementptr i8, ptr %allocation, i64 8
+  ret ptr %values
+}
+
+define internal ptr @mt_heap_resize(ptr %pointer, i64 %count, i64 %size) {
+  %bytes = mul i64 %count, %size
+  %negative = icmp slt i64 %count, 0
+  br i1 %negative, label %error, label %reallocate
+reallocate:
+  %old_allocation = getelementptr i8, ptr %pointer, i64 -8
+  %old_bytes = load i64, ptr %old_allocation
+  %total = add i64 %bytes, 8
+  %allocation = call ptr @realloc(ptr %old_allocation, i64 %total)
+  %failed = icmp eq ptr %allocation, null
+  br i1 %failed, label %error, label %reallocated
+error:
+  call void @mt_allocation_failed(i64 %count)
+  unreachable
+reallocated:
+  store i64 %bytes, ptr %allocation
+  %values = getelementptr i8, ptr %allocation, i64 8
+  %grown = icmp sgt i64 %bytes, %old_bytes
+  br i1 %grown, label %zero, label %done
+zero:
+  %tail = getelementptr i8, ptr %values, i64 %old_bytes
+  %tail_bytes = sub i64 %bytes, %old_bytes
+  call ptr @memset(ptr %tail, i32 0, i64 %tail_bytes)
+  br label %done
+done:
+  ret ptr %values
+}
+
+define internal void @mt_heap_free(ptr %pointer) {
+  %allocation = getelementptr i8, ptr %pointer, i64 -8
+  call void @free(ptr %allocation)
+  ret void
+}
+
+define internal void @mt_print_f32(float %value) {
+entry:
+  %text = alloca [32 x i8]
+  %nan = fcmp uno float %value, %value
+  br i1 %nan, label %print_nan, label %format
+print_nan:
+  call i32 (ptr, ...) @printf(ptr @.nan)
+  ret void
+format:
+  %precision = phi i32 [ 1, %entry ], [ %next_precision, %retry ]
+  %extended = fpext float %value to double
+  call i32 (ptr, i64, ptr, ...) @snprintf(ptr %text, i64 32, ptr @.f32_format, i32 %precision, double %extended)
+  %parsed = call float @strtof(ptr %text, ptr null)
+  %same = fcmp oeq float %parsed, %value
+  %last = icmp eq i32 %precision, 9
+  %done = or i1 %same, %last
+  br i1 %done, label %print, label %retry
+retry:
+  %next_precision = add i32 %precision, 1
+  br label %format
+print:
+  call i32 (ptr, ...) @printf(ptr @.text, ptr %text)
+  ret void
+}
+
+
+define i32 @twice(i32 %v0) !dbg !6 {
+bb0:
+  %v2 = mul i32 %v0, 2, !dbg !8
+  ret i32 %v2
+}
+
+define internal void @mt_main() !dbg !9 {
+bb0:
+  %v2 = call ptr @calloc(i32 4, i32 4), !dbg !12
+  %v5 = call i32 @twice(i32 21), !dbg !15
+  %t0 = sext i32 2 to i64
+  %t1 = getelementptr i32, ptr %v2, i64 %t0
+  store i32 %v5, ptr %t1, !dbg !16
+  %t2 = sext i32 2 to i64
+  %t3 = getelementptr i32, ptr %v2, i64 %t2
+  %v7 = load i32, ptr %t3, !dbg !18
+  call i32 (ptr, ...) @printf(ptr @.string.0, i32 %v7), !dbg !19
+  call void @free(ptr %v2), !dbg !20
+  ret void
+}
+
+define i32 @main() {
+  call void @mt_main()
+  ret i32 0
+}
+
+@.string.0 = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!4, !5}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: "metor", isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)
+!1 = !DIFile(filename: "externs.mt", directory: ".")
+!2 = !{null}
+!3 = !DISubroutineType(types: !2)
+!4 = !{i32 2, !"Debug Info Version", i32 3}
+!5 = !{i32 7, !"Dwarf Version", i32 4}
+!6 = distinct !DISubprogram(name: "twice", linkageName: "twice", scope: !1, file: !1, line: 4, type: !3, scopeLine: 4, spFlags: DISPFlagDefinition, unit: !0)
+!7 = !DILocation(line: 5, column: 16, scope: !6)
+!8 = !DILocation(line: 5, column: 12, scope: !6)
+!9 = distinct !DISubprogram(name: "main", linkageName: "mt_main", scope: !1, file: !1, line: 8, type: !3, scopeLine: 8, spFlags: DISPFlagDefinition, unit: !0)
+!10 = !DILocation(line: 9, column: 26, scope: !9)
+!11 = !DILocation(line: 9, column: 29, scope: !9)
+!12 = !DILocation(line: 9, column: 19, scope: !9)
+!13 = !DILocation(line: 12, column: 12, scope: !9)
+!14 = !DILocation(line: 12, column: 23, scope: !9)
+!15 = !DILocation(line: 12, column: 17, scope: !9)
+!16 = !DILocation(line: 12, column: 5, scope: !9)
+!17 = !DILocation(line: 13, column: 20, scope: !9)
+!18 = !DILocation(line: 13, column: 13, scope: !9)
+!19 = !DILocation(line: 13, column: 5, scope: !9)
+!20 = !DILocation(line: 10, column: 11, scope: !9)